@@ -286,7 +286,7 @@ export class Benchmark {
                 // Show BVH stats if available
                 if (raycaster?.get_stats) {
                     const stats = raycaster.get_stats();
-                    console.log(`  BVH Stats: nodes=${stats[0]}, leaves=${stats[1]}, internal=${stats[2]}, depth=${stats[3]}`);
+                    console.log(`  BVH Stats: nodes=${stats[0]}, leaves=${stats[1]}, internal=${stats[2]}, depth=${stats[3]}, nodeBytes=${stats[5]}`);
                 }
 
                 // Warmup
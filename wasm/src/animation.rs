@@ -0,0 +1,370 @@
+use glam::{Mat3, Quat, Vec3};
+use wasm_bindgen::prelude::*;
+
+/// Floats per solve_two_bone_ik result:
+/// [upper rotation (x, y, z, w), lower rotation (x, y, z, w), end (x, y, z)]
+const IK_RESULT_STRIDE: usize = 11;
+/// Floats per solve_two_bone_ik_batch chain: root, mid, end, target, pole
+const IK_CHAIN_STRIDE: usize = 15;
+/// Floats per solve_look_at_batch entry: rotation (x, y, z, w), eye, target
+const LOOK_AT_STRIDE: usize = 10;
+
+/// Pose solvers for rigged characters (two-bone IK, look-at)
+///
+/// Pure math on flat arrays, so a rig can be posed without allocating
+/// Three.js objects per frame. Every solver has a batched variant for crowds.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct AnimationEngine {}
+
+#[wasm_bindgen]
+impl AnimationEngine {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Two-bone IK: bend a root-mid-end chain (shoulder, elbow, wrist) so its
+    /// end reaches `target`, the elbow bending towards `pole`
+    ///
+    /// A target out of reach straightens the chain towards it; one closer
+    /// than the bones allow folds it as far as it goes.
+    ///
+    /// # Arguments
+    /// * `root_pos`, `mid_pos`, `end_pos` - Current joint positions [x, y, z]
+    /// * `target` - Position the end should reach [x, y, z]
+    /// * `pole` - Point the elbow bends towards [x, y, z]
+    /// * `lengths` - [upper, lower] bone lengths, or empty to measure the pose
+    ///
+    /// # Returns
+    /// [upper rotation (x, y, z, w), lower rotation (x, y, z, w), reached end
+    /// (x, y, z)]. Rotations are world-space deltas taking each bone from its
+    /// current direction to the solved one; for a child joint, the local
+    /// rotation is inverse(upper) * lower.
+    #[wasm_bindgen]
+    pub fn solve_two_bone_ik(
+        root_pos: &[f32],
+        mid_pos: &[f32],
+        end_pos: &[f32],
+        target: &[f32],
+        pole: &[f32],
+        lengths: &[f32],
+    ) -> Result<Vec<f32>, String> {
+        let lengths = match lengths {
+            [] => None,
+            &[upper, lower] => Some((upper, lower)),
+            _ => {
+                return Err(format!(
+                    "expected 0 or 2 bone lengths, got {}",
+                    lengths.len()
+                ))
+            }
+        };
+        let chain = [root_pos, mid_pos, end_pos, target, pole]
+            .map(Self::parse_point)
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::two_bone_ik(&chain, lengths)?.to_vec())
+    }
+
+    /// solve_two_bone_ik for many chains, e.g. a crowd
+    ///
+    /// # Arguments
+    /// * `chains` - IK_CHAIN_STRIDE floats per chain: root, mid, end, target, pole
+    /// * `lengths` - [upper, lower] per chain, or empty to measure each pose
+    ///
+    /// # Returns
+    /// IK_RESULT_STRIDE floats per chain, as solve_two_bone_ik
+    #[wasm_bindgen]
+    pub fn solve_two_bone_ik_batch(chains: &[f32], lengths: &[f32]) -> Result<Vec<f32>, String> {
+        if !chains.len().is_multiple_of(IK_CHAIN_STRIDE) {
+            return Err(format!("chains need {IK_CHAIN_STRIDE} floats each"));
+        }
+        let count = chains.len() / IK_CHAIN_STRIDE;
+        if !lengths.is_empty() && lengths.len() != count * 2 {
+            return Err(format!(
+                "expected {} bone lengths, got {}",
+                count * 2,
+                lengths.len()
+            ));
+        }
+        let mut out = Vec::with_capacity(count * IK_RESULT_STRIDE);
+        for (i, chain) in chains.chunks_exact(IK_CHAIN_STRIDE).enumerate() {
+            let points: Vec<Vec3> = chain.chunks_exact(3).map(Vec3::from_slice).collect();
+            let lengths = lengths.get(i * 2..i * 2 + 2).map(|l| (l[0], l[1]));
+            out.extend(Self::two_bone_ik(&points, lengths)?);
+        }
+        Ok(out)
+    }
+
+    /// Turn a rotation towards looking from `eye_pos` at `target_pos`, eased
+    /// and clamped, e.g. for a head tracking the cursor
+    ///
+    /// The looking direction is +Z (as Object3D.lookAt for meshes), with +Y
+    /// kept as close to `up` as possible.
+    ///
+    /// # Arguments
+    /// * `current_rot_quat` - Current world rotation [x, y, z, w]
+    /// * `max_angle_radians` - Largest turn one call may apply
+    /// * `t` - Easing factor (0 = stay, 1 = turn fully), clamped to 0..1
+    ///
+    /// # Returns
+    /// The new rotation [x, y, z, w]; the current one if the target is at the eye
+    #[wasm_bindgen]
+    pub fn solve_look_at(
+        current_rot_quat: &[f32],
+        eye_pos: &[f32],
+        target_pos: &[f32],
+        up: &[f32],
+        max_angle_radians: f32,
+        t: f32,
+    ) -> Result<Vec<f32>, String> {
+        let current = Self::parse_quat(current_rot_quat)?;
+        let rotation = Self::look_at(
+            current,
+            Self::parse_point(eye_pos)?,
+            Self::parse_point(target_pos)?,
+            Self::parse_point(up)?,
+            max_angle_radians,
+            t,
+        );
+        Ok(rotation.to_array().to_vec())
+    }
+
+    /// solve_look_at for many heads sharing `up`, the clamp and the easing
+    ///
+    /// # Arguments
+    /// * `entries` - LOOK_AT_STRIDE floats per head: rotation (x, y, z, w), eye, target
+    ///
+    /// # Returns
+    /// 4 floats per head, the new rotation
+    #[wasm_bindgen]
+    pub fn solve_look_at_batch(
+        entries: &[f32],
+        up: &[f32],
+        max_angle_radians: f32,
+        t: f32,
+    ) -> Result<Vec<f32>, String> {
+        if !entries.len().is_multiple_of(LOOK_AT_STRIDE) {
+            return Err(format!("look-at entries need {LOOK_AT_STRIDE} floats each"));
+        }
+        let up = Self::parse_point(up)?;
+        let mut out = Vec::with_capacity(entries.len() / LOOK_AT_STRIDE * 4);
+        for entry in entries.chunks_exact(LOOK_AT_STRIDE) {
+            let current = Self::parse_quat(&entry[..4])?;
+            let (eye, target) = (
+                Vec3::from_slice(&entry[4..7]),
+                Vec3::from_slice(&entry[7..]),
+            );
+            out.extend(Self::look_at(current, eye, target, up, max_angle_radians, t).to_array());
+        }
+        Ok(out)
+    }
+}
+
+impl AnimationEngine {
+    fn parse_point(p: &[f32]) -> Result<Vec3, String> {
+        match p {
+            &[x, y, z] => Ok(Vec3::new(x, y, z)),
+            _ => Err(format!("expected 3 floats for a position, got {}", p.len())),
+        }
+    }
+
+    /// Unit quaternion from [x, y, z, w], checking the length
+    fn parse_quat(q: &[f32]) -> Result<Quat, String> {
+        let &[x, y, z, w] = q else {
+            return Err(format!("expected 4 floats for a rotation, got {}", q.len()));
+        };
+        let q = Quat::from_xyzw(x, y, z, w);
+        if !q.is_finite() || q.length_squared() == 0.0 {
+            return Err("rotation must be a non-zero quaternion".into());
+        }
+        Ok(q.normalize())
+    }
+
+    /// Solve one chain: `points` is [root, mid, end, target, pole]
+    fn two_bone_ik(
+        points: &[Vec3],
+        lengths: Option<(f32, f32)>,
+    ) -> Result<[f32; IK_RESULT_STRIDE], String> {
+        let &[root, mid, end, target, pole] = points else {
+            return Err("IK chain needs root, mid, end, target and pole".into());
+        };
+        let (upper, lower) = lengths.unwrap_or((root.distance(mid), mid.distance(end)));
+        if !(upper > 0.0 && lower > 0.0 && upper.is_finite() && lower.is_finite()) {
+            return Err("IK bone lengths must be positive".into());
+        }
+
+        // Reach along root -> target, clamped to what the bones can span
+        let reach_dir = (target - root)
+            .try_normalize()
+            .or_else(|| (end - root).try_normalize())
+            .unwrap_or(Vec3::Y);
+        let reach = root
+            .distance(target)
+            .clamp((upper - lower).abs(), upper + lower);
+
+        // Bend in the plane of the pole (falling back to the current elbow)
+        let off_axis = |p: Vec3| {
+            let v = p - root;
+            (v - reach_dir * v.dot(reach_dir)).try_normalize()
+        };
+        let bend_dir = off_axis(pole)
+            .or_else(|| off_axis(mid))
+            .unwrap_or_else(|| reach_dir.any_orthonormal_vector());
+
+        // Law of cosines for the angle between the upper bone and the reach
+        let cos_root = ((upper * upper + reach * reach - lower * lower) / (2.0 * upper * reach))
+            .clamp(-1.0, 1.0);
+        let sin_root = (1.0 - cos_root * cos_root).sqrt();
+        let new_mid = root + (reach_dir * cos_root + bend_dir * sin_root) * upper;
+        let new_end = root + reach_dir * reach;
+
+        let arc = |from: Vec3, to: Vec3| match (from.try_normalize(), to.try_normalize()) {
+            (Some(from), Some(to)) => Quat::from_rotation_arc(from, to),
+            _ => Quat::IDENTITY,
+        };
+        let upper_rotation = arc(mid - root, new_mid - root);
+        let lower_rotation = arc(end - mid, new_end - new_mid);
+
+        let mut out = [0.0; IK_RESULT_STRIDE];
+        out[..4].copy_from_slice(&upper_rotation.to_array());
+        out[4..8].copy_from_slice(&lower_rotation.to_array());
+        out[8..].copy_from_slice(&new_end.to_array());
+        Ok(out)
+    }
+
+    fn look_at(current: Quat, eye: Vec3, target: Vec3, up: Vec3, max_angle: f32, t: f32) -> Quat {
+        let Some(forward) = (target - eye).try_normalize() else {
+            return current;
+        };
+        let side = up
+            .cross(forward)
+            .try_normalize()
+            .unwrap_or_else(|| forward.any_orthonormal_vector());
+        let desired = Quat::from_mat3(&Mat3::from_cols(side, forward.cross(side), forward));
+        let eased = current.slerp(desired, t.clamp(0.0, 1.0));
+
+        // Cap the turn this call makes
+        let max_angle = max_angle.max(0.0);
+        let turn = current.angle_between(eased);
+        if turn > max_angle {
+            current.slerp(eased, max_angle / turn)
+        } else {
+            eased
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_vec3(actual: Vec3, expected: Vec3) {
+        assert!(
+            (actual - expected).abs().max_element() < 1e-4,
+            "{actual} vs {expected}"
+        );
+    }
+
+    /// Solved joint positions from a solve_two_bone_ik result
+    fn solved_chain(root: Vec3, mid: Vec3, end: Vec3, solved: &[f32]) -> (Vec3, Vec3) {
+        let upper = Quat::from_slice(&solved[..4]);
+        let lower = Quat::from_slice(&solved[4..8]);
+        let new_mid = root + upper * (mid - root);
+        (new_mid, new_mid + lower * (end - mid))
+    }
+
+    #[test]
+    fn test_two_bone_ik() {
+        // Unit bones straight along +x, pole above
+        let (root, mid, end) = (Vec3::ZERO, Vec3::X, Vec3::X * 2.0);
+        let pole = [0.5, 5.0, 0.0];
+        let solve = |target: Vec3, lengths: &[f32]| {
+            AnimationEngine::solve_two_bone_ik(
+                &root.to_array(),
+                &mid.to_array(),
+                &end.to_array(),
+                &target.to_array(),
+                &pole,
+                lengths,
+            )
+            .unwrap()
+        };
+
+        // A target one bone length away: an equilateral triangle, so the
+        // elbow bends by 180 - 60 degrees, towards the pole
+        let target = Vec3::new(0.0, 0.0, 1.0);
+        let solved = solve(target, &[]);
+        assert_eq!(solved.len(), IK_RESULT_STRIDE);
+        let (new_mid, new_end) = solved_chain(root, mid, end, &solved);
+        assert_vec3(new_end, target);
+        assert_vec3(Vec3::from_slice(&solved[8..]), target);
+        let bend = (new_mid - root).angle_between(new_end - new_mid);
+        assert!((bend - 120f32.to_radians()).abs() < 1e-4, "{bend}");
+        assert!(new_mid.y > 0.0);
+        assert!((new_mid.length() - 1.0).abs() < 1e-5);
+
+        // Out of reach: a straight chain pointing at the target
+        let far = Vec3::new(3.0, 4.0, 0.0);
+        let solved = solve(far, &[1.0, 1.0]);
+        let (new_mid, new_end) = solved_chain(root, mid, end, &solved);
+        assert_vec3(new_mid, far.normalize());
+        assert_vec3(new_end, far.normalize() * 2.0);
+        assert_vec3(Vec3::from_slice(&solved[8..]), far.normalize() * 2.0);
+
+        // The batch matches single calls
+        let chains: Vec<f32> = [target, far]
+            .iter()
+            .flat_map(|t| [root, mid, end, *t, Vec3::from_array(pole)])
+            .flat_map(|p| p.to_array())
+            .collect();
+        let batch = AnimationEngine::solve_two_bone_ik_batch(&chains, &[]).unwrap();
+        assert_eq!(batch[..IK_RESULT_STRIDE], solve(target, &[])[..]);
+        assert_eq!(batch[IK_RESULT_STRIDE..], solve(far, &[])[..]);
+
+        assert!(AnimationEngine::solve_two_bone_ik(
+            &[0.0; 2],
+            &[0.0; 3],
+            &[0.0; 3],
+            &[0.0; 3],
+            &[0.0; 3],
+            &[]
+        )
+        .is_err());
+        assert!(AnimationEngine::solve_two_bone_ik_batch(&chains, &[1.0]).is_err());
+        assert!(AnimationEngine::solve_two_bone_ik_batch(&chains[1..], &[]).is_err());
+    }
+
+    #[test]
+    fn test_look_at() {
+        let identity = Quat::IDENTITY.to_array();
+        let (eye, up) = ([0.0; 3], [0.0, 1.0, 0.0]);
+        let look = |target: [f32; 3], max_angle: f32, t: f32| {
+            let q = AnimationEngine::solve_look_at(&identity, &eye, &target, &up, max_angle, t)
+                .unwrap();
+            Quat::from_slice(&q)
+        };
+
+        // Unclamped and fully eased: +Z ends up on the target, +Y stays up
+        let q = look([1.0, 0.0, 0.0], std::f32::consts::PI, 1.0);
+        assert_vec3(q * Vec3::Z, Vec3::X);
+        assert_vec3(q * Vec3::Y, Vec3::Y);
+
+        // Clamped to 30 degrees per call, and eased halfway
+        let q = look([1.0, 0.0, 0.0], 30f32.to_radians(), 1.0);
+        assert!((Quat::IDENTITY.angle_between(q) - 30f32.to_radians()).abs() < 1e-4);
+        let q = look([1.0, 0.0, 0.0], std::f32::consts::PI, 0.5);
+        assert!((Quat::IDENTITY.angle_between(q) - 45f32.to_radians()).abs() < 1e-4);
+
+        // Target at the eye: no change
+        assert_eq!(look([0.0; 3], 1.0, 1.0), Quat::IDENTITY);
+
+        let entries: Vec<f32> = [identity.as_slice(), &eye, &[1.0, 0.0, 0.0]].concat();
+        let batch =
+            AnimationEngine::solve_look_at_batch(&entries.repeat(2), &up, 0.5, 1.0).unwrap();
+        assert_eq!(batch.len(), 8);
+        assert_eq!(batch[..4], look([1.0, 0.0, 0.0], 0.5, 1.0).to_array());
+        assert!(AnimationEngine::solve_look_at(&[0.0; 4], &eye, &eye, &up, 1.0, 1.0).is_err());
+    }
+}
@@ -0,0 +1,1041 @@
+use glam::{Mat3, Mat4, Quat, Vec3};
+use wasm_bindgen::prelude::*;
+
+use crate::texture_gen::SeededRng;
+
+/// Segment count for the hit marker disc and arrow
+const MARKER_SEGMENTS: u32 = 12;
+/// Floats per scattered instance: position (3) + quaternion (4) + uniform scale (1)
+const SCATTER_STRIDE: usize = 8;
+/// Candidates generated per requested instance when min_spacing is enforced
+const SCATTER_CANDIDATES: u32 = 8;
+/// Debug color ramp (blue -> cyan -> green -> yellow -> red), shallow to deep
+const DEPTH_RAMP: [[f32; 3]; 5] = [
+    [0.1, 0.3, 1.0],
+    [0.0, 0.9, 0.9],
+    [0.1, 0.9, 0.2],
+    [1.0, 0.9, 0.1],
+    [1.0, 0.2, 0.1],
+];
+
+/// Generated mesh buffers shared by the geometry helpers
+///
+/// Buffers are reused when the same object is regenerated, so JS can keep
+/// views from the pointer accessors (re-create them if the length changes).
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct GeometryData {
+    positions: Vec<f32>,
+    normals: Vec<f32>,
+    /// Optional per-vertex RGB colors (empty if unused)
+    colors: Vec<f32>,
+    indices: Vec<u32>,
+    /// Set when a generator hit its output cap and dropped geometry
+    truncated: bool,
+}
+
+#[wasm_bindgen]
+impl GeometryData {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // --- Zero-copy pointers for direct memory access ---
+
+    /// Get pointer to positions (vertex_count * 3 floats)
+    #[wasm_bindgen]
+    pub fn positions_ptr(&self) -> *const f32 {
+        self.positions.as_ptr()
+    }
+
+    /// Get pointer to normals (vertex_count * 3 floats)
+    #[wasm_bindgen]
+    pub fn normals_ptr(&self) -> *const f32 {
+        self.normals.as_ptr()
+    }
+
+    /// Get pointer to colors (vertex_count * 3 floats, or empty)
+    #[wasm_bindgen]
+    pub fn colors_ptr(&self) -> *const f32 {
+        self.colors.as_ptr()
+    }
+
+    /// Get pointer to indices (index_count u32s)
+    #[wasm_bindgen]
+    pub fn indices_ptr(&self) -> *const u32 {
+        self.indices.as_ptr()
+    }
+
+    #[wasm_bindgen]
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len() / 3
+    }
+
+    #[wasm_bindgen]
+    pub fn index_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn has_colors(&self) -> bool {
+        !self.colors.is_empty()
+    }
+
+    /// True if the generator stopped at its size cap (output is incomplete)
+    #[wasm_bindgen]
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    // --- Copying accessors (allocate, for convenience) ---
+
+    #[wasm_bindgen]
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn normals(&self) -> Vec<f32> {
+        self.normals.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn colors(&self) -> Vec<f32> {
+        self.colors.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn indices(&self) -> Vec<u32> {
+        self.indices.clone()
+    }
+
+    // --- In-place regeneration (reuses buffers) ---
+
+    /// Rebuild as an AABB outline, see `make_aabb_outline`
+    #[wasm_bindgen]
+    pub fn set_aabb_outline(&mut self, min: &[f32], max: &[f32], thickness: f32) {
+        self.clear();
+        let min = Vec3::from_slice(min);
+        let max = Vec3::from_slice(max);
+        let corners = aabb_corners(min, max);
+
+        if thickness <= 0.0 {
+            // Line-friendly output: vertex pairs for LineSegments
+            for (a, b) in AABB_EDGES {
+                for corner in [corners[a], corners[b]] {
+                    let normal = (corner - (min + max) * 0.5).normalize_or_zero();
+                    self.push_vertex(corner, normal);
+                }
+            }
+            self.indices.extend(0..24);
+            return;
+        }
+
+        // Each edge is a thin axis-aligned box, so zero-extent axes stay visible
+        let half = Vec3::splat(thickness * 0.5);
+        for (a, b) in AABB_EDGES {
+            let edge_min = corners[a].min(corners[b]) - half;
+            let edge_max = corners[a].max(corners[b]) + half;
+            self.push_box(edge_min, edge_max);
+        }
+    }
+
+    /// Rebuild as the skinned pose of a mesh, see `apply_skinning`
+    ///
+    /// Meant for per-frame use: positions/normals are rewritten in place and
+    /// can be read through the pointer accessors (indices and colors are cleared).
+    #[wasm_bindgen]
+    pub fn set_skinned(
+        &mut self,
+        positions: &[f32],
+        normals: &[f32],
+        joints: &[u16],
+        weights: &[f32],
+        palette: &[f32],
+    ) -> Result<(), String> {
+        self.clear();
+        self.positions.resize(positions.len(), 0.0);
+        self.normals.resize(normals.len(), 0.0);
+        apply_skinning(
+            positions,
+            normals,
+            joints,
+            weights,
+            palette,
+            &mut self.positions,
+            &mut self.normals,
+        )
+    }
+
+    /// Rebuild as a hit marker, see `make_hit_marker`
+    #[wasm_bindgen]
+    pub fn set_hit_marker(&mut self, point: &[f32], normal: &[f32], size: f32) {
+        self.clear();
+        let point = Vec3::from_slice(point);
+        let normal = Vec3::from_slice(normal).try_normalize().unwrap_or(Vec3::Y);
+        let (tangent, bitangent) = normal.any_orthonormal_pair();
+
+        let ring = |center: Vec3, radius: f32, i: u32| -> Vec3 {
+            let angle = i as f32 / MARKER_SEGMENTS as f32 * std::f32::consts::TAU;
+            center + (tangent * angle.cos() + bitangent * angle.sin()) * radius
+        };
+
+        // Disc, lifted slightly off the surface to avoid z-fighting
+        let disc_center = point + normal * size * 0.01;
+        let base = self.vertex_count() as u32;
+        self.push_vertex(disc_center, normal);
+        for i in 0..MARKER_SEGMENTS {
+            self.push_vertex(ring(disc_center, size * 0.5, i), normal);
+        }
+        for i in 0..MARKER_SEGMENTS {
+            let next = (i + 1) % MARKER_SEGMENTS;
+            self.indices
+                .extend_from_slice(&[base, base + 1 + i, base + 1 + next]);
+        }
+
+        // Arrow: shaft (open cylinder) then cone head along the normal
+        let shaft_radius = size * 0.04;
+        let shaft_length = size * 0.7;
+        let head_radius = size * 0.1;
+        let tip = point + normal * size;
+        let shaft_top = point + normal * shaft_length;
+
+        let base = self.vertex_count() as u32;
+        for i in 0..MARKER_SEGMENTS {
+            let side = (ring(Vec3::ZERO, 1.0, i)).normalize_or_zero();
+            self.push_vertex(ring(point, shaft_radius, i), side);
+            self.push_vertex(ring(shaft_top, shaft_radius, i), side);
+        }
+        for i in 0..MARKER_SEGMENTS {
+            let next = (i + 1) % MARKER_SEGMENTS;
+            let (a, b) = (base + i * 2, base + i * 2 + 1);
+            let (c, d) = (base + next * 2, base + next * 2 + 1);
+            self.indices.extend_from_slice(&[a, c, b, b, c, d]);
+        }
+
+        let base = self.vertex_count() as u32;
+        self.push_vertex(tip, normal);
+        for i in 0..MARKER_SEGMENTS {
+            let side = ring(Vec3::ZERO, 1.0, i);
+            let slant = (side * (tip - shaft_top).length() + normal * head_radius).normalize();
+            self.push_vertex(ring(shaft_top, head_radius, i), slant);
+        }
+        for i in 0..MARKER_SEGMENTS {
+            let next = (i + 1) % MARKER_SEGMENTS;
+            self.indices
+                .extend_from_slice(&[base + 1 + i, base + 1 + next, base]);
+        }
+    }
+}
+
+/// Outline geometry for an axis-aligned box
+///
+/// # Arguments
+/// * `min` / `max` - Box corners (same format as the BVHRaycaster bounds)
+/// * `thickness` - Edge thickness; <= 0 emits 24 vertices as line segment pairs instead
+#[wasm_bindgen]
+pub fn make_aabb_outline(min: &[f32], max: &[f32], thickness: f32) -> GeometryData {
+    let mut geometry = GeometryData::new();
+    geometry.set_aabb_outline(min, max, thickness);
+    geometry
+}
+
+/// Hover marker: a disc on the surface plus an arrow along the normal
+#[wasm_bindgen]
+pub fn make_hit_marker(point: &[f32], normal: &[f32], size: f32) -> GeometryData {
+    let mut geometry = GeometryData::new();
+    geometry.set_hit_marker(point, normal, size);
+    geometry
+}
+
+/// Scatter instance transforms uniformly over a triangle mesh surface
+///
+/// Triangles are picked proportionally to their area and points are uniform
+/// within each triangle. With `min_spacing` > 0, an oversampled candidate set
+/// is thinned so no two instances are closer than `min_spacing`; dense limits
+/// can then return fewer than `count` instances.
+///
+/// # Arguments
+/// * `positions` - Flat vertex positions [x0,y0,z0, ...]
+/// * `indices` - Triangle indices
+/// * `count` - Number of instances wanted
+/// * `seed` - Same seed and inputs give the same output
+/// * `align_to_normal` - Rotate each instance's +Y onto the interpolated vertex normal
+/// * `min_spacing` - Minimum distance between instances, 0 disables
+///
+/// # Returns
+/// Per instance: [px, py, pz, qx, qy, qz, qw, scale]. Every instance gets a
+/// random yaw around its up axis; scale is always 1.
+#[wasm_bindgen]
+pub fn scatter_on_surface(
+    positions: &[f32],
+    indices: &[u32],
+    count: u32,
+    seed: u32,
+    align_to_normal: bool,
+    min_spacing: f32,
+) -> Vec<f32> {
+    let vertex_count = positions.len() / 3;
+    let vertex = |i: u32| Vec3::from_slice(&positions[i as usize * 3..i as usize * 3 + 3]);
+    let triangles: Vec<[u32; 3]> = indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .filter(|t| t.iter().all(|&i| (i as usize) < vertex_count))
+        .collect();
+
+    // Area CDF and area-weighted vertex normals
+    let mut cumulative_area = Vec::with_capacity(triangles.len());
+    let mut vertex_normals = vec![Vec3::ZERO; vertex_count];
+    let mut total_area = 0.0;
+    for t in &triangles {
+        let [a, b, c] = t.map(vertex);
+        let cross = (b - a).cross(c - a);
+        total_area += cross.length() * 0.5;
+        cumulative_area.push(total_area);
+        for &i in t {
+            vertex_normals[i as usize] += cross;
+        }
+    }
+    if count == 0 || total_area <= 0.0 {
+        return Vec::new();
+    }
+
+    let spacing = min_spacing.max(0.0);
+    let candidate_count = if spacing > 0.0 {
+        count.saturating_mul(SCATTER_CANDIDATES)
+    } else {
+        count
+    };
+
+    let mut rng = SeededRng::new(seed);
+    let mut candidates = Vec::with_capacity(candidate_count as usize * SCATTER_STRIDE);
+    for _ in 0..candidate_count {
+        let target = rng.next_f32() * total_area;
+        let tri = cumulative_area
+            .partition_point(|&a| a <= target)
+            .min(triangles.len() - 1);
+        let t = triangles[tri];
+
+        // Uniform barycentrics: fold the unit square onto the triangle
+        let (mut u, mut v) = (rng.next_f32(), rng.next_f32());
+        if u + v > 1.0 {
+            (u, v) = (1.0 - u, 1.0 - v);
+        }
+        let w = 1.0 - u - v;
+        let [a, b, c] = t.map(vertex);
+        let point = a * w + b * u + c * v;
+
+        let [na, nb, nc] = t.map(|i| vertex_normals[i as usize]);
+        let normal = (na * w + nb * u + nc * v)
+            .try_normalize()
+            .or_else(|| (b - a).cross(c - a).try_normalize())
+            .unwrap_or(Vec3::Y);
+
+        let yaw = Quat::from_rotation_y(rng.range(0.0, std::f32::consts::TAU));
+        let rotation = if align_to_normal {
+            Quat::from_rotation_arc(Vec3::Y, normal) * yaw
+        } else {
+            yaw
+        };
+
+        candidates.extend_from_slice(&point.to_array());
+        candidates.extend_from_slice(&rotation.to_array());
+        candidates.push(1.0);
+    }
+
+    if spacing <= 0.0 {
+        return candidates;
+    }
+
+    // Greedy thinning in generation order: accept, then reject everything nearby
+    let points: Vec<f32> = candidates
+        .chunks_exact(SCATTER_STRIDE)
+        .flat_map(|c| [c[0], c[1], c[2]])
+        .collect();
+    let hash = SpatialHash::build(&points, spacing);
+    let mut rejected = vec![false; candidate_count as usize];
+    let mut output = Vec::with_capacity(count as usize * SCATTER_STRIDE);
+
+    for (i, instance) in candidates.chunks_exact(SCATTER_STRIDE).enumerate() {
+        if rejected[i] {
+            continue;
+        }
+        output.extend_from_slice(instance);
+        if output.len() / SCATTER_STRIDE == count as usize {
+            break;
+        }
+        hash.for_each_in_radius(Vec3::from_slice(instance), spacing, |j, d_sq| {
+            // Strictly closer than min_spacing
+            if d_sq < spacing * spacing {
+                rejected[j as usize] = true;
+            }
+        });
+    }
+    output
+}
+
+/// Same as scatter_on_surface, composed into column-major 4x4 matrices (16 floats each)
+///
+/// Ready to copy into an InstancedMesh instanceMatrix.
+#[wasm_bindgen]
+pub fn scatter_on_surface_matrices(
+    positions: &[f32],
+    indices: &[u32],
+    count: u32,
+    seed: u32,
+    align_to_normal: bool,
+    min_spacing: f32,
+) -> Vec<f32> {
+    scatter_on_surface(
+        positions,
+        indices,
+        count,
+        seed,
+        align_to_normal,
+        min_spacing,
+    )
+    .chunks_exact(SCATTER_STRIDE)
+    .flat_map(|c| {
+        Mat4::from_scale_rotation_translation(
+            Vec3::splat(c[7]),
+            Quat::from_slice(&c[3..7]),
+            Vec3::from_slice(c),
+        )
+        .to_cols_array()
+    })
+    .collect()
+}
+
+/// Linear blend skinning with up to 4 joint influences per vertex
+///
+/// # Arguments
+/// * `positions` / `normals` - Bind pose, 3 floats per vertex (normals may be empty)
+/// * `joints` / `weights` - 4 joint indices and 4 weights per vertex; weights are
+///   normalized, an all-zero weight set keeps the bind pose
+/// * `palette` - One column-major 4x4 per joint (joint world * inverse bind)
+/// * `out_positions` / `out_normals` - Same sizes as the inputs
+///
+/// Normals use the inverse-transpose of the blended matrix and are renormalized.
+#[wasm_bindgen]
+pub fn apply_skinning(
+    positions: &[f32],
+    normals: &[f32],
+    joints: &[u16],
+    weights: &[f32],
+    palette: &[f32],
+    out_positions: &mut [f32],
+    out_normals: &mut [f32],
+) -> Result<(), String> {
+    let vertex_count = positions.len() / 3;
+    if !positions.len().is_multiple_of(3)
+        || (!normals.is_empty() && normals.len() != positions.len())
+    {
+        return Err("positions/normals must be matching xyz triples".into());
+    }
+    if joints.len() != vertex_count * 4 || weights.len() != vertex_count * 4 {
+        return Err("joints and weights need 4 entries per vertex".into());
+    }
+    if out_positions.len() != positions.len() || out_normals.len() != normals.len() {
+        return Err("output buffers must match the input sizes".into());
+    }
+    if !palette.len().is_multiple_of(16) {
+        return Err("palette must be 4x4 matrices (16 floats each)".into());
+    }
+    let joint_count = palette.len() / 16;
+    if let Some(&bad) = joints.iter().find(|&&j| j as usize >= joint_count) {
+        return Err(format!(
+            "joint index {bad} out of range ({joint_count} joints)"
+        ));
+    }
+
+    let matrices: Vec<Mat4> = palette
+        .chunks_exact(16)
+        .map(Mat4::from_cols_slice)
+        .collect();
+
+    for v in 0..vertex_count {
+        let w = &weights[v * 4..v * 4 + 4];
+        let total: f32 = w.iter().sum();
+        let skin = if total > 0.0 {
+            let mut blended = Mat4::ZERO;
+            for k in 0..4 {
+                blended += matrices[joints[v * 4 + k] as usize] * (w[k] / total);
+            }
+            blended
+        } else {
+            Mat4::IDENTITY
+        };
+
+        let p = skin.transform_point3(Vec3::from_slice(&positions[v * 3..v * 3 + 3]));
+        out_positions[v * 3..v * 3 + 3].copy_from_slice(&p.to_array());
+
+        if !normals.is_empty() {
+            let linear = Mat3::from_mat4(skin);
+            let normal_matrix = if linear.determinant().abs() > f32::EPSILON {
+                linear.inverse().transpose()
+            } else {
+                linear
+            };
+            let n =
+                (normal_matrix * Vec3::from_slice(&normals[v * 3..v * 3 + 3])).normalize_or_zero();
+            out_normals[v * 3..v * 3 + 3].copy_from_slice(&n.to_array());
+        }
+    }
+
+    Ok(())
+}
+
+/// Uniform hash grid over a point set for radius and k-nearest queries
+///
+/// Points are bucketed by cell into a flat, counting-sorted table, so
+/// `rebuild` on a deformed point set reuses every allocation.
+#[wasm_bindgen]
+pub struct SpatialHash {
+    points: Vec<Vec3>,
+    cell_size: f32,
+    /// Derive cell_size from point spacing on every rebuild
+    auto_cell_size: bool,
+    /// Bucket b holds entries[bucket_start[b]..bucket_start[b + 1]]
+    bucket_start: Vec<u32>,
+    entries: Vec<u32>,
+    bounds_min: Vec3,
+    bounds_max: Vec3,
+}
+
+#[wasm_bindgen]
+impl SpatialHash {
+    /// Build the grid from flat [x0,y0,z0, x1,y1,z1, ...] positions
+    ///
+    /// A `cell_size` of 0 (or less) derives it from the average point spacing.
+    #[wasm_bindgen(constructor)]
+    pub fn build(positions: &[f32], cell_size: f32) -> SpatialHash {
+        let mut hash = SpatialHash {
+            points: Vec::new(),
+            cell_size,
+            auto_cell_size: cell_size <= 0.0,
+            bucket_start: Vec::new(),
+            entries: Vec::new(),
+            bounds_min: Vec3::ZERO,
+            bounds_max: Vec3::ZERO,
+        };
+        hash.rebuild(positions);
+        hash
+    }
+
+    /// Re-bucket a (possibly deformed) point set, reusing allocations
+    #[wasm_bindgen]
+    pub fn rebuild(&mut self, positions: &[f32]) {
+        self.points.clear();
+        self.points
+            .extend(positions.chunks_exact(3).map(Vec3::from_slice));
+
+        self.bounds_min = self.points.iter().fold(Vec3::MAX, |a, &p| a.min(p));
+        self.bounds_max = self.points.iter().fold(Vec3::MIN, |a, &p| a.max(p));
+        if self.points.is_empty() {
+            self.bounds_min = Vec3::ZERO;
+            self.bounds_max = Vec3::ZERO;
+        }
+        if self.auto_cell_size {
+            self.cell_size = self.average_spacing();
+        }
+
+        // Counting sort of point indices by bucket
+        let buckets = self.points.len().next_power_of_two().max(1);
+        self.bucket_start.clear();
+        self.bucket_start.resize(buckets + 1, 0);
+        for i in 0..self.points.len() {
+            let b = self.bucket_of(self.cell_of(self.points[i]));
+            self.bucket_start[b + 1] += 1;
+        }
+        for b in 0..buckets {
+            self.bucket_start[b + 1] += self.bucket_start[b];
+        }
+
+        self.entries.clear();
+        self.entries.resize(self.points.len(), 0);
+        let mut fill: Vec<u32> = self.bucket_start[..buckets].to_vec();
+        for (i, &p) in self.points.iter().enumerate() {
+            let b = self.bucket_of(self.cell_of(p));
+            self.entries[fill[b] as usize] = i as u32;
+            fill[b] += 1;
+        }
+    }
+
+    /// Cell edge length in use (useful when it was auto-derived)
+    #[wasm_bindgen]
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Indices of all points within distance `r` of (x, y, z), in no particular order
+    #[wasm_bindgen]
+    pub fn query_radius(&self, x: f32, y: f32, z: f32, r: f32) -> Vec<u32> {
+        let mut out = Vec::new();
+        self.for_each_in_radius(Vec3::new(x, y, z), r, |i, _| out.push(i));
+        out
+    }
+
+    /// Indices of the `k` nearest points to (x, y, z), closest first
+    #[wasm_bindgen]
+    pub fn query_nearest(&self, x: f32, y: f32, z: f32, k: u32) -> Vec<u32> {
+        self.nearest(Vec3::new(x, y, z), k as usize)
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+// --- Shared Rust API for other modules ---
+
+impl SpatialHash {
+    /// Number of indexed points
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Call `visit(index, distance_squared)` for every point within `r` of `center`
+    pub fn for_each_in_radius(&self, center: Vec3, r: f32, mut visit: impl FnMut(u32, f32)) {
+        if self.points.is_empty() || r < 0.0 {
+            return;
+        }
+
+        // Only visit cells that overlap both the query sphere and the point bounds
+        let r_sq = r * r;
+        let lo = self.cell_of((center - Vec3::splat(r)).max(self.bounds_min));
+        let hi = self.cell_of((center + Vec3::splat(r)).min(self.bounds_max));
+
+        for cz in lo[2]..=hi[2] {
+            for cy in lo[1]..=hi[1] {
+                for cx in lo[0]..=hi[0] {
+                    let cell = [cx, cy, cz];
+                    let b = self.bucket_of(cell);
+                    let range = self.bucket_start[b] as usize..self.bucket_start[b + 1] as usize;
+                    for &i in &self.entries[range] {
+                        let p = self.points[i as usize];
+                        // Buckets are shared by colliding cells, only take this cell's points
+                        if self.cell_of(p) != cell {
+                            continue;
+                        }
+                        let d_sq = p.distance_squared(center);
+                        if d_sq <= r_sq {
+                            visit(i, d_sq);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The `k` nearest points as (index, distance_squared), closest first
+    pub fn nearest(&self, center: Vec3, k: usize) -> Vec<(u32, f32)> {
+        if self.points.is_empty() || k == 0 {
+            return Vec::new();
+        }
+
+        // Any radius reaching the far corner of the bounds covers every point
+        let far = (center - self.bounds_min)
+            .abs()
+            .max((center - self.bounds_max).abs())
+            .length();
+
+        let mut radius = self.cell_size;
+        let mut found = Vec::new();
+        loop {
+            found.clear();
+            self.for_each_in_radius(center, radius, |i, d| found.push((i, d)));
+            // With k hits inside r, the k nearest are all inside r
+            if found.len() >= k || radius >= far {
+                break;
+            }
+            radius = (radius * 2.0).min(far.max(self.cell_size));
+        }
+
+        found.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap().then(a.0.cmp(&b.0)));
+        found.truncate(k);
+        found
+    }
+
+    fn cell_of(&self, p: Vec3) -> [i32; 3] {
+        let c = (p / self.cell_size).floor();
+        [c.x as i32, c.y as i32, c.z as i32]
+    }
+
+    fn bucket_of(&self, cell: [i32; 3]) -> usize {
+        let h = (cell[0] as u32).wrapping_mul(0x8da6_b343)
+            ^ (cell[1] as u32).wrapping_mul(0xd816_3841)
+            ^ (cell[2] as u32).wrapping_mul(0xcb1a_b31f);
+        h as usize & (self.bucket_start.len() - 2)
+    }
+
+    /// Average point spacing: (extent volume / n)^(1/d) over the non-flat axes
+    fn average_spacing(&self) -> f32 {
+        let extent = self.bounds_max - self.bounds_min;
+        let max_extent = extent.max_element();
+        if self.points.len() < 2 || max_extent <= 0.0 {
+            return 1.0;
+        }
+
+        let (mut volume, mut dims) = (1.0f32, 0);
+        for e in extent.to_array() {
+            if e > max_extent * 1e-4 {
+                volume *= e;
+                dims += 1;
+            }
+        }
+        (volume / self.points.len() as f32).powf(1.0 / dims as f32)
+    }
+}
+
+/// Corner pairs forming the 12 box edges (corner bit 0 = x, 1 = y, 2 = z)
+const AABB_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+/// Color for `depth` on the debug ramp, `max_depth` mapping to the last entry
+pub(crate) fn depth_color(depth: u32, max_depth: u32) -> [f32; 3] {
+    let t = if max_depth == 0 {
+        0.0
+    } else {
+        (depth as f32 / max_depth as f32).min(1.0)
+    };
+    let scaled = t * (DEPTH_RAMP.len() - 1) as f32;
+    let i = (scaled as usize).min(DEPTH_RAMP.len() - 2);
+    let f = scaled - i as f32;
+    std::array::from_fn(|c| DEPTH_RAMP[i][c] + (DEPTH_RAMP[i + 1][c] - DEPTH_RAMP[i][c]) * f)
+}
+
+fn aabb_corners(min: Vec3, max: Vec3) -> [Vec3; 8] {
+    std::array::from_fn(|i| {
+        Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        )
+    })
+}
+
+impl GeometryData {
+    pub(crate) fn clear(&mut self) {
+        self.positions.clear();
+        self.normals.clear();
+        self.colors.clear();
+        self.indices.clear();
+        self.truncated = false;
+    }
+
+    pub(crate) fn set_truncated(&mut self) {
+        self.truncated = true;
+    }
+
+    pub(crate) fn push_vertex(&mut self, position: Vec3, normal: Vec3) {
+        self.positions.extend_from_slice(&position.to_array());
+        self.normals.extend_from_slice(&normal.to_array());
+    }
+
+    /// Append a colored AABB wireframe as 12 line segments (24 vertices)
+    pub(crate) fn push_line_box(&mut self, min: Vec3, max: Vec3, color: [f32; 3]) {
+        let corners = aabb_corners(min, max);
+        let center = (min + max) * 0.5;
+        for (a, b) in AABB_EDGES {
+            for corner in [corners[a], corners[b]] {
+                let base = self.vertex_count() as u32;
+                self.push_vertex(corner, (corner - center).normalize_or_zero());
+                self.colors.extend_from_slice(&color);
+                self.indices.push(base);
+            }
+        }
+    }
+
+    /// Append a box with per-face normals (24 vertices, 36 indices)
+    pub(crate) fn push_box(&mut self, min: Vec3, max: Vec3) {
+        let corners = aabb_corners(min, max);
+        // (normal, four corners counter-clockwise seen from outside)
+        let faces: [(Vec3, [usize; 4]); 6] = [
+            (Vec3::X, [1, 3, 7, 5]),
+            (Vec3::NEG_X, [0, 4, 6, 2]),
+            (Vec3::Y, [2, 6, 7, 3]),
+            (Vec3::NEG_Y, [0, 1, 5, 4]),
+            (Vec3::Z, [4, 5, 7, 6]),
+            (Vec3::NEG_Z, [0, 2, 3, 1]),
+        ];
+
+        for (normal, quad) in faces {
+            let base = self.vertex_count() as u32;
+            for corner in quad {
+                self.push_vertex(corners[corner], normal);
+            }
+            self.indices
+                .extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle_normal(geometry: &GeometryData, tri: usize) -> Vec3 {
+        let p = |i: usize| Vec3::from_slice(&geometry.positions[i * 3..i * 3 + 3]);
+        let idx = &geometry.indices[tri * 3..tri * 3 + 3];
+        let (a, b, c) = (p(idx[0] as usize), p(idx[1] as usize), p(idx[2] as usize));
+        (b - a).cross(c - a).normalize()
+    }
+
+    #[test]
+    fn test_aabb_outline() {
+        let outline = make_aabb_outline(&[-1.0, -1.0, -1.0], &[1.0, 1.0, 1.0], 0.02);
+        assert_eq!(outline.vertex_count(), 12 * 24);
+        assert_eq!(outline.index_count(), 12 * 36);
+
+        // Winding matches the stored face normals
+        for tri in 0..outline.index_count() / 3 {
+            let vertex = outline.indices[tri * 3] as usize;
+            let normal = Vec3::from_slice(&outline.normals[vertex * 3..vertex * 3 + 3]);
+            assert!(triangle_normal(&outline, tri).dot(normal) > 0.99);
+        }
+
+        let lines = make_aabb_outline(&[0.0, 0.0, 0.0], &[1.0, 2.0, 3.0], 0.0);
+        assert_eq!(lines.vertex_count(), 24);
+    }
+
+    #[test]
+    fn test_degenerate_aabb_outline() {
+        // Flat box (zero extent on Y) still produces finite, visible geometry
+        let outline = make_aabb_outline(&[-1.0, 0.0, -1.0], &[1.0, 0.0, 1.0], 0.05);
+        assert!(outline.normals.iter().all(|n| n.is_finite()));
+        let ys: Vec<f32> = outline.positions.chunks(3).map(|p| p[1]).collect();
+        assert!(ys.iter().cloned().fold(f32::MIN, f32::max) > 0.0);
+
+        let mut lines = make_aabb_outline(&[0.0; 3], &[0.0; 3], 0.0);
+        assert!(lines.normals.iter().all(|n| n.is_finite()));
+
+        // Regeneration reuses the same object
+        lines.set_aabb_outline(&[0.0; 3], &[1.0; 3], 0.1);
+        assert_eq!(lines.vertex_count(), 12 * 24);
+    }
+
+    #[test]
+    fn test_hit_marker() {
+        let marker = make_hit_marker(&[1.0, 2.0, 3.0], &[0.0, 0.0, 2.0], 0.5);
+        assert!(marker.positions.iter().all(|p| p.is_finite()));
+        // Disc faces along the normal
+        assert!(triangle_normal(&marker, 0).dot(Vec3::Z) > 0.99);
+        // Arrow tip sits `size` along the normal
+        let tip_z = marker
+            .positions
+            .chunks(3)
+            .map(|p| p[2])
+            .fold(f32::MIN, f32::max);
+        assert!((tip_z - 3.5).abs() < 1e-5);
+
+        let fallback = make_hit_marker(&[0.0; 3], &[0.0; 3], 1.0);
+        assert!(fallback.normals.iter().all(|n| n.is_finite()));
+    }
+
+    /// Deterministic pseudo-random point cloud
+    fn point_cloud(count: usize, seed: u32) -> Vec<f32> {
+        let mut state = seed.max(1);
+        (0..count * 3)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state >> 8) as f32 / (1u32 << 24) as f32 * 10.0 - 5.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_spatial_hash_radius_matches_brute_force() {
+        let positions = point_cloud(500, 7);
+        let hash = SpatialHash::build(&positions, 0.0);
+        assert!(hash.cell_size() > 0.0);
+
+        for (q, r) in [
+            ([0.0, 0.0, 0.0], 1.5),
+            ([4.0, -3.0, 2.0], 2.5),
+            ([9.0, 9.0, 9.0], 1.0),
+        ] {
+            let mut found = hash.query_radius(q[0], q[1], q[2], r);
+            found.sort_unstable();
+
+            let expected: Vec<u32> = positions
+                .chunks(3)
+                .enumerate()
+                .filter(|(_, p)| Vec3::from_slice(p).distance(Vec3::from(q)) <= r)
+                .map(|(i, _)| i as u32)
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_spatial_hash_nearest_and_rebuild() {
+        let positions = point_cloud(300, 11);
+        let mut hash = SpatialHash::build(&positions, 0.5);
+
+        let nearest = hash.query_nearest(1.0, 1.0, 1.0, 5);
+        let mut brute: Vec<(u32, f32)> = positions
+            .chunks(3)
+            .enumerate()
+            .map(|(i, p)| (i as u32, Vec3::from_slice(p).distance(Vec3::ONE)))
+            .collect();
+        brute.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        let expected: Vec<u32> = brute.iter().take(5).map(|b| b.0).collect();
+        assert_eq!(nearest, expected);
+
+        // Asking for more than exist returns everything
+        assert_eq!(hash.query_nearest(50.0, 0.0, 0.0, 1000).len(), 300);
+
+        let moved: Vec<f32> = positions.iter().map(|v| v + 100.0).collect();
+        hash.rebuild(&moved);
+        assert!(hash.query_radius(1.0, 1.0, 1.0, 2.0).is_empty());
+        assert_eq!(hash.query_nearest(101.0, 101.0, 101.0, 5), expected);
+    }
+
+    /// Unit square in the XZ plane (facing +Y), split into a big and a small triangle
+    fn uneven_plane() -> (Vec<f32>, Vec<u32>) {
+        let positions = vec![
+            0.0, 0.0, 0.0, //
+            0.0, 0.0, 1.0, //
+            0.9, 0.0, 1.0, //
+            1.0, 0.0, 1.0, //
+            1.0, 0.0, 0.0, //
+        ];
+        // Quad split at x = 0.9 on the far edge: triangle areas 0.45 / 0.05 / 0.5
+        let indices = vec![0, 1, 2, 0, 2, 3, 0, 3, 4];
+        (positions, indices)
+    }
+
+    #[test]
+    fn test_scatter_on_surface() {
+        let (positions, indices) = uneven_plane();
+        let instances = scatter_on_surface(&positions, &indices, 2000, 3, true, 0.0);
+        assert_eq!(instances.len(), 2000 * SCATTER_STRIDE);
+        assert_eq!(
+            instances,
+            scatter_on_surface(&positions, &indices, 2000, 3, true, 0.0)
+        );
+
+        // Uniform density: each half of the square gets about half the points
+        let left = instances
+            .chunks_exact(SCATTER_STRIDE)
+            .filter(|c| c[0] < 0.5)
+            .count();
+        assert!((left as f32 / 2000.0 - 0.5).abs() < 0.05, "{left}");
+
+        for c in instances.chunks_exact(SCATTER_STRIDE) {
+            assert_eq!(c[1], 0.0);
+            // Up axis follows the +Y surface normal
+            let up = Quat::from_slice(&c[3..7]) * Vec3::Y;
+            assert!(up.distance(Vec3::Y) < 1e-4);
+            assert_eq!(c[7], 1.0);
+        }
+
+        let matrices = scatter_on_surface_matrices(&positions, &indices, 10, 3, true, 0.0);
+        assert_eq!(matrices.len(), 10 * 16);
+        assert_eq!(matrices[12..15], instances[0..3]);
+    }
+
+    #[test]
+    fn test_scatter_min_spacing() {
+        let (positions, indices) = uneven_plane();
+        let instances = scatter_on_surface(&positions, &indices, 20, 9, false, 0.15);
+        assert_eq!(instances.len(), 20 * SCATTER_STRIDE);
+
+        let points: Vec<Vec3> = instances
+            .chunks_exact(SCATTER_STRIDE)
+            .map(Vec3::from_slice)
+            .collect();
+        for (i, a) in points.iter().enumerate() {
+            for b in &points[i + 1..] {
+                assert!(a.distance(*b) >= 0.15);
+            }
+        }
+
+        // Asking for more than fit returns fewer instances instead of violating spacing
+        let crowded = scatter_on_surface(&positions, &indices, 500, 9, false, 0.3);
+        assert!(crowded.len() < 500 * SCATTER_STRIDE);
+        assert!(scatter_on_surface(&positions, &[], 10, 9, false, 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_skinning_two_bone_bend() {
+        // Cylinder spine along +Y: bottom ring on bone 0, top on bone 1, middle shared
+        let positions = [
+            1.0, 0.0, 0.0, //
+            1.0, 1.0, 0.0, //
+            1.0, 2.0, 0.0, //
+        ];
+        let normals = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+        let joints = [0u16, 0, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0];
+        let weights = [
+            1.0, 0.0, 0.0, 0.0, //
+            0.25, 0.75, 0.0, 0.0, //
+            1.0, 0.0, 0.0, 0.0, //
+        ];
+
+        // Bone 1 bends 90 degrees around the knee at y = 1
+        let knee = Vec3::new(0.0, 1.0, 0.0);
+        let bend = Mat4::from_translation(knee)
+            * Mat4::from_rotation_z(std::f32::consts::FRAC_PI_2)
+            * Mat4::from_translation(-knee);
+        let palette: Vec<f32> = [Mat4::IDENTITY, bend]
+            .iter()
+            .flat_map(|m| m.to_cols_array())
+            .collect();
+
+        let mut out_positions = [0.0; 9];
+        let mut out_normals = [0.0; 9];
+        apply_skinning(
+            &positions,
+            &normals,
+            &joints,
+            &weights,
+            &palette,
+            &mut out_positions,
+            &mut out_normals,
+        )
+        .unwrap();
+
+        // Fully weighted vertices follow their bone exactly
+        assert_eq!(out_positions[0..3], positions[0..3]);
+        let top = bend.transform_point3(Vec3::from_slice(&positions[6..9]));
+        assert!(Vec3::from_slice(&out_positions[6..9]).distance(top) < 1e-5);
+
+        // The shared vertex lands 75% of the way from bone 0's to bone 1's transform
+        let mid = Vec3::from_slice(&positions[3..6]);
+        let expected = mid.lerp(bend.transform_point3(mid), 0.75);
+        assert!(Vec3::from_slice(&out_positions[3..6]).distance(expected) < 1e-5);
+        for n in out_normals.chunks(3) {
+            assert!((Vec3::from_slice(n).length() - 1.0).abs() < 1e-5);
+        }
+
+        // Persistent-buffer variant gives the same result
+        let mut skinned = GeometryData::new();
+        skinned
+            .set_skinned(&positions, &normals, &joints, &weights, &palette)
+            .unwrap();
+        assert_eq!(skinned.positions(), out_positions.to_vec());
+
+        assert!(skinned
+            .set_skinned(&positions, &normals, &[0, 0, 0, 9], &weights[..4], &palette)
+            .is_err());
+    }
+}
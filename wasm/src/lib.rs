@@ -1,15 +1,31 @@
 use wasm_bindgen::prelude::*;
 
 // Modules actively used by JS
+pub mod animation;
+pub mod geometry;
 pub mod matrix_ops;
+pub mod motion;
+pub mod options;
+pub mod pool;
+pub mod presets;
 pub mod raycast;
+pub mod registry;
 pub mod ripple_physics;
+pub mod scheduler;
+pub mod sdf_atlas;
 pub mod texture_gen;
 
+pub use animation::AnimationEngine;
+pub use geometry::{GeometryData, SpatialHash};
 pub use matrix_ops::MatrixComputer;
-pub use raycast::BVHRaycaster;
-pub use ripple_physics::RippleSimulator;
-pub use texture_gen::TextureGenerator;
+pub use pool::ObjectPool;
+pub use presets::Preset;
+pub use raycast::{BVHRaycaster, GridRaycaster, SceneRaycaster};
+pub use registry::Registry;
+pub use ripple_physics::{RippleSimulator, RippleSonifier, SurfaceParticles};
+pub use scheduler::FrameScheduler;
+pub use sdf_atlas::SdfAtlas;
+pub use texture_gen::{LutOptions, TextureGenerator};
 
 #[wasm_bindgen(start)]
 pub fn init() {
@@ -1,4 +1,4 @@
-use glam::Mat4;
+use glam::{DMat4, DVec3, Mat4, Vec3};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -15,6 +15,22 @@ pub struct MatrixComputer {
     // Additional input buffers for MVP (view, projection)
     view_buffer: [f32; 16],
     projection_buffer: [f32; 16],
+    // Previous-frame snapshot (explicit, see snapshot_previous)
+    prev_model_buffer: [f32; 16],
+    prev_view_buffer: [f32; 16],
+    prev_projection_buffer: [f32; 16],
+    prev_mvp_buffer: [f32; 16],
+    reprojection_buffer: [f32; 16],
+    // Sub-pixel projection jitter in NDC units, current and previous frame
+    jitter: [f32; 2],
+    prev_jitter: [f32; 2],
+    // Double-precision shared buffers for huge-coordinate scenes
+    input_buffer_f64: [f64; 16],
+    view_buffer_f64: [f64; 16],
+    projection_buffer_f64: [f64; 16],
+    inverse_buffer_f64: [f64; 16],
+    /// Floating origin the vertex data is relative to (see BVHRaycaster::get_origin)
+    origin_f64: DVec3,
 }
 
 impl Default for MatrixComputer {
@@ -37,6 +53,18 @@ impl MatrixComputer {
             input_buffer: [0.0; 16],
             view_buffer: [0.0; 16],
             projection_buffer: [0.0; 16],
+            prev_model_buffer: [0.0; 16],
+            prev_view_buffer: [0.0; 16],
+            prev_projection_buffer: [0.0; 16],
+            prev_mvp_buffer: [0.0; 16],
+            reprojection_buffer: Mat4::IDENTITY.to_cols_array(),
+            jitter: [0.0; 2],
+            prev_jitter: [0.0; 2],
+            input_buffer_f64: [0.0; 16],
+            view_buffer_f64: [0.0; 16],
+            projection_buffer_f64: [0.0; 16],
+            inverse_buffer_f64: [0.0; 16],
+            origin_f64: DVec3::ZERO,
         }
     }
 
@@ -78,6 +106,36 @@ impl MatrixComputer {
         self.projection_buffer.as_mut_ptr()
     }
 
+    /// Get pointer to previous-frame model buffer (16 floats)
+    #[wasm_bindgen]
+    pub fn get_prev_model_ptr(&self) -> *const f32 {
+        self.prev_model_buffer.as_ptr()
+    }
+
+    /// Get pointer to previous-frame view buffer (16 floats)
+    #[wasm_bindgen]
+    pub fn get_prev_view_ptr(&self) -> *const f32 {
+        self.prev_view_buffer.as_ptr()
+    }
+
+    /// Get pointer to previous-frame projection buffer (16 floats)
+    #[wasm_bindgen]
+    pub fn get_prev_projection_ptr(&self) -> *const f32 {
+        self.prev_projection_buffer.as_ptr()
+    }
+
+    /// Get pointer to previous-frame MVP buffer (16 floats)
+    #[wasm_bindgen]
+    pub fn get_prev_mvp_ptr(&self) -> *const f32 {
+        self.prev_mvp_buffer.as_ptr()
+    }
+
+    /// Get pointer to reprojection matrix buffer (16 floats)
+    #[wasm_bindgen]
+    pub fn get_reprojection_ptr(&self) -> *const f32 {
+        self.reprojection_buffer.as_ptr()
+    }
+
     // --- Zero-copy compute (write to internal buffers) ---
 
     /// Compute inverse matrix in-place (zero allocation)
@@ -140,6 +198,100 @@ impl MatrixComputer {
         self.mvp_buffer.copy_from_slice(&mvp.to_cols_array());
     }
 
+    // --- Double precision (huge-coordinate scenes) ---
+
+    /// Get pointer to f64 model input buffer (16 doubles) - for SharedArrayBuffer
+    #[wasm_bindgen]
+    pub fn get_input_f64_ptr(&mut self) -> *mut f64 {
+        self.input_buffer_f64.as_mut_ptr()
+    }
+
+    /// Get pointer to f64 view input buffer (16 doubles)
+    #[wasm_bindgen]
+    pub fn get_view_f64_ptr(&mut self) -> *mut f64 {
+        self.view_buffer_f64.as_mut_ptr()
+    }
+
+    /// Get pointer to f64 projection input buffer (16 doubles)
+    #[wasm_bindgen]
+    pub fn get_projection_f64_ptr(&mut self) -> *mut f64 {
+        self.projection_buffer_f64.as_mut_ptr()
+    }
+
+    /// Get pointer to f64 inverse matrix buffer (16 doubles)
+    #[wasm_bindgen]
+    pub fn get_inverse_f64_ptr(&self) -> *const f64 {
+        self.inverse_buffer_f64.as_ptr()
+    }
+
+    /// Set the floating origin the mesh vertices are stored relative to
+    ///
+    /// Use BVHRaycaster::get_origin() so rendering and picking agree.
+    #[wasm_bindgen]
+    pub fn set_origin_f64(&mut self, x: f64, y: f64, z: f64) {
+        self.origin_f64 = DVec3::new(x, y, z);
+    }
+
+    /// Invert the f64 model input buffer in f64
+    /// Result is written to the f64 inverse buffer - read via get_inverse_f64_ptr()
+    #[wasm_bindgen]
+    pub fn invert_shared_f64(&mut self) {
+        let mat = DMat4::from_cols_array(&self.input_buffer_f64);
+        self.inverse_buffer_f64 = mat.inverse().to_cols_array();
+    }
+
+    /// Compute MVP from the f64 buffers for origin-relative vertices
+    ///
+    /// P * V * M * translate(origin) is composed in f64 so the large
+    /// translations cancel before rounding; the f32 result goes to the MVP
+    /// buffer (get_mvp_ptr) for the shader.
+    #[wasm_bindgen]
+    pub fn mvp_shared_f64(&mut self) {
+        let m = DMat4::from_cols_array(&self.input_buffer_f64);
+        let v = DMat4::from_cols_array(&self.view_buffer_f64);
+        let p = DMat4::from_cols_array(&self.projection_buffer_f64);
+        let mvp = p * v * m * DMat4::from_translation(self.origin_f64);
+        self.mvp_buffer = mvp.as_mat4().to_cols_array();
+    }
+
+    // --- Temporal reprojection ---
+
+    /// Set this frame's projection jitter in NDC units
+    ///
+    /// The projection/MVP buffers are expected to already contain the jitter;
+    /// it is removed again before building the reprojection matrix.
+    #[wasm_bindgen]
+    pub fn set_jitter(&mut self, x: f32, y: f32) {
+        self.jitter = [x, y];
+    }
+
+    /// Copy the current model/view/projection/MVP and jitter into the previous-frame buffers
+    ///
+    /// Call once at the end of the frame. This is never done implicitly so
+    /// rendering several views per frame does not overwrite the history.
+    /// Model/view/projection are taken from the shared input buffers.
+    #[wasm_bindgen]
+    pub fn snapshot_previous(&mut self) {
+        self.prev_model_buffer = self.input_buffer;
+        self.prev_view_buffer = self.view_buffer;
+        self.prev_projection_buffer = self.projection_buffer;
+        self.prev_mvp_buffer = self.mvp_buffer;
+        self.prev_jitter = self.jitter;
+    }
+
+    /// Compute prevMVP * inverse(currMVP) in-place, with jitter excluded
+    ///
+    /// Maps current clip space to previous clip space for motion vectors.
+    /// Result is written to internal buffer - read via get_reprojection_ptr()
+    #[wasm_bindgen]
+    pub fn compute_reprojection_inplace(&mut self) {
+        let prev = Self::unjitter(&self.prev_mvp_buffer, self.prev_jitter);
+        let curr = Self::unjitter(&self.mvp_buffer, self.jitter);
+        let reprojection = prev * curr.inverse();
+        self.reprojection_buffer
+            .copy_from_slice(&reprojection.to_cols_array());
+    }
+
     // --- Legacy API (allocates, kept for compatibility) ---
 
     /// Update model matrix and compute all derived matrices
@@ -172,3 +324,100 @@ impl MatrixComputer {
         self.normal_matrix.to_cols_array().to_vec()
     }
 }
+
+impl MatrixComputer {
+    /// Remove a clip-space jitter offset (x += jx * w) from an MVP matrix
+    fn unjitter(mvp: &[f32; 16], jitter: [f32; 2]) -> Mat4 {
+        let offset = Mat4::from_translation(Vec3::new(-jitter[0], -jitter[1], 0.0));
+        offset * Mat4::from_cols_array(mvp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn load_camera(computer: &mut MatrixComputer, eye: Vec3, jitter: [f32; 2]) {
+        let model = Mat4::from_rotation_y(0.3);
+        let view = Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::Y);
+        let jitter_matrix = Mat4::from_translation(Vec3::new(jitter[0], jitter[1], 0.0));
+        let projection = jitter_matrix * Mat4::perspective_rh_gl(1.0, 1.5, 0.1, 100.0);
+        computer.input_buffer = model.to_cols_array();
+        computer.view_buffer = view.to_cols_array();
+        computer.projection_buffer = projection.to_cols_array();
+        computer.set_jitter(jitter[0], jitter[1]);
+        computer.mvp_shared();
+    }
+
+    fn assert_identity(buffer: &[f32; 16]) {
+        let identity = Mat4::IDENTITY.to_cols_array();
+        for (a, b) in buffer.iter().zip(identity.iter()) {
+            assert!((a - b).abs() < EPSILON, "{buffer:?}");
+        }
+    }
+
+    #[test]
+    fn test_static_camera_reprojection_is_identity() {
+        let mut computer = MatrixComputer::new();
+        let eye = Vec3::new(0.0, 1.0, 5.0);
+
+        load_camera(&mut computer, eye, [0.0, 0.0]);
+        computer.snapshot_previous();
+        load_camera(&mut computer, eye, [0.0, 0.0]);
+        computer.compute_reprojection_inplace();
+        assert_identity(&computer.reprojection_buffer);
+
+        // Different jitter each frame must not leak into the reprojection
+        load_camera(&mut computer, eye, [0.002, -0.001]);
+        computer.snapshot_previous();
+        load_camera(&mut computer, eye, [-0.003, 0.004]);
+        computer.compute_reprojection_inplace();
+        assert_identity(&computer.reprojection_buffer);
+    }
+
+    #[test]
+    fn test_moving_camera_reprojection() {
+        let mut computer = MatrixComputer::new();
+        load_camera(&mut computer, Vec3::new(0.0, 1.0, 5.0), [0.0, 0.0]);
+        computer.snapshot_previous();
+        let prev_mvp = Mat4::from_cols_array(&computer.prev_mvp_buffer);
+
+        load_camera(&mut computer, Vec3::new(1.0, 1.0, 5.0), [0.0, 0.0]);
+        computer.compute_reprojection_inplace();
+        let curr_mvp = Mat4::from_cols_array(&computer.mvp_buffer);
+        let reprojection = Mat4::from_cols_array(&computer.reprojection_buffer);
+
+        // A point's current clip position reprojects to its previous clip position
+        let point = glam::Vec4::new(0.2, 0.4, -0.3, 1.0);
+        let expected = prev_mvp * point;
+        let actual = reprojection * (curr_mvp * point);
+        assert!((expected - actual).abs().max_element() < 1e-4);
+    }
+
+    #[test]
+    fn test_f64_mvp_with_origin_rebase() {
+        let mut computer = MatrixComputer::new();
+        let origin = DVec3::new(6_378_137.0, 1.0e7, -3_000_000.0);
+        let eye = origin + DVec3::new(0.0, 2.0, 10.0);
+
+        computer.input_buffer_f64 = DMat4::IDENTITY.to_cols_array();
+        computer.view_buffer_f64 = DMat4::look_at_rh(eye, origin, DVec3::Y).to_cols_array();
+        computer.projection_buffer_f64 =
+            DMat4::perspective_rh_gl(1.0, 1.5, 0.1, 100.0).to_cols_array();
+        computer.set_origin_f64(origin.x, origin.y, origin.z);
+        computer.mvp_shared_f64();
+
+        // An origin-relative vertex lands where the full f64 transform puts it
+        let local = DVec3::new(0.25, -0.5, 0.125);
+        let reference = DMat4::from_cols_array(&computer.projection_buffer_f64)
+            * DMat4::from_cols_array(&computer.view_buffer_f64)
+            * (origin + local).extend(1.0);
+        let clip = Mat4::from_cols_array(&computer.mvp_buffer) * local.as_vec3().extend(1.0);
+        assert!((clip.as_dvec4() - reference).abs().max_element() < 1e-4);
+
+        computer.invert_shared_f64();
+        assert_eq!(computer.inverse_buffer_f64, DMat4::IDENTITY.to_cols_array());
+    }
+}
@@ -0,0 +1,86 @@
+use std::cell::Cell;
+
+use wasm_bindgen::prelude::*;
+
+/// set_reduced_motion levels
+const MOTION_FULL: u8 = 0;
+const MOTION_REDUCED: u8 = 1;
+const MOTION_NONE: u8 = 2;
+/// Default "reduced" scaling of ripple amplitude and lifetime
+const DEFAULT_REDUCED_AMPLITUDE: f32 = 0.35;
+const DEFAULT_REDUCED_LIFETIME: f32 = 0.5;
+
+#[derive(Clone, Copy)]
+struct MotionSettings {
+    level: u8,
+    /// Ripple amplitude multiplier in "reduced"
+    amplitude_scale: f32,
+    /// Ripple lifetime multiplier in "reduced" (decay runs 1/scale times faster)
+    lifetime_scale: f32,
+}
+
+// Crate-wide switch. WASM is single threaded; thread-local keeps native tests isolated.
+thread_local! {
+    static MOTION: Cell<MotionSettings> = const {
+        Cell::new(MotionSettings {
+            level: MOTION_FULL,
+            amplitude_scale: DEFAULT_REDUCED_AMPLITUDE,
+            lifetime_scale: DEFAULT_REDUCED_LIFETIME,
+        })
+    };
+}
+
+/// Honor `prefers-reduced-motion` across the crate
+///
+/// # Arguments
+/// * `level` - 0 = full motion, 1 = reduced (ripples weaker and shorter lived,
+///   see set_reduced_motion_params), 2 = none (ripples still advance and expire
+///   but displace nothing)
+#[wasm_bindgen]
+pub fn set_reduced_motion(level: u8) -> Result<(), String> {
+    if level > MOTION_NONE {
+        return Err(format!("unknown reduced motion level {level}"));
+    }
+    MOTION.with(|m| m.set(MotionSettings { level, ..m.get() }));
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn get_reduced_motion() -> u8 {
+    MOTION.with(|m| m.get().level)
+}
+
+/// Tune the "reduced" level
+///
+/// # Arguments
+/// * `amplitude_scale` - Ripple amplitude multiplier (0..1)
+/// * `lifetime_scale` - Ripple lifetime multiplier (0..1, > 0)
+#[wasm_bindgen]
+pub fn set_reduced_motion_params(amplitude_scale: f32, lifetime_scale: f32) {
+    MOTION.with(|m| {
+        m.set(MotionSettings {
+            amplitude_scale: amplitude_scale.clamp(0.0, 1.0),
+            lifetime_scale: lifetime_scale.clamp(0.01, 1.0),
+            ..m.get()
+        })
+    });
+}
+
+/// Multiplier for displayed ripple displacement at the current level
+pub(crate) fn amplitude_scale() -> f32 {
+    let settings = MOTION.with(Cell::get);
+    match settings.level {
+        MOTION_REDUCED => settings.amplitude_scale,
+        MOTION_NONE => 0.0,
+        _ => 1.0,
+    }
+}
+
+/// Multiplier for ripple lifetimes at the current level
+pub(crate) fn lifetime_scale() -> f32 {
+    let settings = MOTION.with(Cell::get);
+    match settings.level {
+        MOTION_REDUCED => settings.lifetime_scale,
+        _ => 1.0,
+    }
+}
@@ -0,0 +1,149 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+use wasm_bindgen::prelude::*;
+
+/// Option structs accepted from JS as plain objects with camelCase keys
+///
+/// Every field is optional and falls back to the struct's Default.
+pub(crate) trait JsOptions: DeserializeOwned + Serialize + Default {
+    /// Keys accepted in the JS object
+    const FIELDS: &'static [&'static str];
+
+    /// Range checks; errors name the offending field
+    fn validate(&self) -> Result<(), String>;
+}
+
+/// Parse an options object
+///
+/// Null fields count as absent. Unknown keys are an error when `strict`,
+/// otherwise they are skipped and reported as warnings. Type errors name the
+/// offending field.
+pub(crate) fn parse_options<T: JsOptions>(
+    value: Value,
+    strict: bool,
+) -> Result<(T, Vec<String>), String> {
+    let Value::Object(map) = value else {
+        return Err("options must be an object".into());
+    };
+
+    let mut warnings = Vec::new();
+    let mut known = Map::new();
+    for (key, field) in map {
+        if !T::FIELDS.contains(&key.as_str()) {
+            let message = format!("unknown option \"{key}\"");
+            if strict {
+                return Err(message);
+            }
+            warnings.push(message);
+            continue;
+        }
+        if field.is_null() {
+            continue;
+        }
+
+        // Deserialize the field on its own so errors can name it
+        let field = integral_numbers(field);
+        let single = Map::from_iter([(key.clone(), field.clone())]);
+        serde_json::from_value::<T>(Value::Object(single)).map_err(|e| format!("{key}: {e}"))?;
+        known.insert(key, field);
+    }
+
+    let options: T = serde_json::from_value(Value::Object(known)).map_err(|e| e.to_string())?;
+    options.validate()?;
+    Ok((options, warnings))
+}
+
+/// Parse an options object from JS, logging unknown-key warnings to the console
+pub(crate) fn from_js<T: JsOptions>(value: JsValue, strict: bool) -> Result<T, String> {
+    let value: Value = serde_wasm_bindgen::from_value(value).map_err(|e| e.to_string())?;
+    let (options, warnings) = parse_options(value, strict)?;
+    for warning in warnings {
+        web_sys::console::warn_1(&warning.into());
+    }
+    Ok(options)
+}
+
+/// Convert options to a plain JS object
+pub(crate) fn to_js<T: JsOptions>(options: &T) -> Result<JsValue, String> {
+    options
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| e.to_string())
+}
+
+/// JS numbers arrive as f64; turn whole ones into integers so integer fields accept them
+fn integral_numbers(value: Value) -> Value {
+    match value {
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if !n.is_i64() && !n.is_u64() && f.fract() == 0.0 && f.abs() < 9.0e15 => {
+                Value::Number(Number::from(f as i64))
+            }
+            _ => Value::Number(n),
+        },
+        Value::Array(items) => Value::Array(items.into_iter().map(integral_numbers).collect()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ripple_physics::RippleParams;
+    use crate::texture_gen::LutOptions;
+    use serde_json::json;
+
+    #[test]
+    fn test_options_round_trip() {
+        let mut lut = LutOptions::new();
+        lut.size = 17;
+        lut.tone_curve = 3;
+        lut.linearize_input = true;
+        let value = serde_json::to_value(lut).unwrap();
+        assert_eq!(value["toneCurve"], json!(3));
+        let (parsed, warnings) = parse_options::<LutOptions>(value, true).unwrap();
+        assert_eq!(parsed, lut);
+        assert!(warnings.is_empty());
+
+        let ripple = RippleParams {
+            axis_mask: [0.0, 1.0, 0.0],
+            displacement_space: 1,
+            blocker_leakage: 0.5,
+        };
+        let value = serde_json::to_value(ripple).unwrap();
+        assert_eq!(
+            parse_options::<RippleParams>(value, true).unwrap().0,
+            ripple
+        );
+    }
+
+    #[test]
+    fn test_options_defaults_and_errors() {
+        // Missing / null fields use defaults, JS-style floats fill integer fields
+        let (lut, _) =
+            parse_options::<LutOptions>(json!({"size": 16.0, "contrast": null}), false).unwrap();
+        assert_eq!(
+            lut,
+            LutOptions {
+                size: 16,
+                ..LutOptions::new()
+            }
+        );
+
+        // Unknown keys warn, or fail in strict mode
+        let typo = json!({"satuation": 0.5});
+        let (lut, warnings) = parse_options::<LutOptions>(typo.clone(), false).unwrap();
+        assert_eq!(lut, LutOptions::new());
+        assert_eq!(warnings, vec!["unknown option \"satuation\"".to_string()]);
+        let err = parse_options::<LutOptions>(typo, true).unwrap_err();
+        assert!(err.contains("satuation"), "{err}");
+
+        // Type and range errors name the field
+        let err = parse_options::<LutOptions>(json!({"exposureEv": "bright"}), false).unwrap_err();
+        assert!(err.starts_with("exposureEv:"), "{err}");
+        let err = parse_options::<LutOptions>(json!({"size": 1}), false).unwrap_err();
+        assert!(err.contains("size"), "{err}");
+        let err = parse_options::<RippleParams>(json!({"blockerLeakage": 2}), false).unwrap_err();
+        assert!(err.contains("blockerLeakage"), "{err}");
+        assert!(parse_options::<RippleParams>(json!([1, 2]), false).is_err());
+    }
+}
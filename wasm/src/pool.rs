@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::raycast::BVHRaycaster;
+
+/// Default number of built trees kept alive by an ObjectPool
+const DEFAULT_POOL_CAPACITY: usize = 8;
+
+/// Keeps built BVHRaycasters alive across view mounts, keyed by a mesh hash
+///
+/// The hash is computed by the caller (e.g. from the mesh URL or buffers);
+/// the pool only compares keys. Oldest entries are evicted (and freed) once
+/// the pool is full.
+#[wasm_bindgen]
+pub struct ObjectPool {
+    /// (mesh_hash, raycaster), oldest first
+    raycasters: VecDeque<(u32, BVHRaycaster)>,
+    capacity: usize,
+}
+
+impl Default for ObjectPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl ObjectPool {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_POOL_CAPACITY)
+    }
+
+    /// Pool holding at most `capacity` raycasters (0 disables pooling)
+    #[wasm_bindgen]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            raycasters: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Take the pooled raycaster built for `mesh_hash`, if any
+    ///
+    /// Ownership moves to the caller; hand it back with release() on unmount.
+    #[wasm_bindgen]
+    pub fn acquire(&mut self, mesh_hash: u32) -> Option<BVHRaycaster> {
+        let slot = self.raycasters.iter().position(|(h, _)| *h == mesh_hash)?;
+        self.raycasters.remove(slot).map(|(_, raycaster)| raycaster)
+    }
+
+    /// Return a raycaster to the pool under `mesh_hash`
+    ///
+    /// Replaces an existing entry with the same hash. Disposed raycasters are
+    /// dropped instead of pooled. Profiling is switched off so a reused tree
+    /// starts clean.
+    #[wasm_bindgen]
+    pub fn release(&mut self, mesh_hash: u32, mut raycaster: BVHRaycaster) {
+        if raycaster.is_disposed() || self.capacity == 0 {
+            return;
+        }
+        raycaster.enable_profiling(false);
+
+        if let Some(slot) = self.raycasters.iter().position(|(h, _)| *h == mesh_hash) {
+            self.raycasters.remove(slot);
+        }
+        while self.raycasters.len() >= self.capacity {
+            self.raycasters.pop_front();
+        }
+        self.raycasters.push_back((mesh_hash, raycaster));
+    }
+
+    /// Number of pooled raycasters
+    #[wasm_bindgen]
+    pub fn len(&self) -> usize {
+        self.raycasters.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn is_empty(&self) -> bool {
+        self.raycasters.is_empty()
+    }
+
+    /// Drop every pooled raycaster
+    #[wasm_bindgen]
+    pub fn clear(&mut self) {
+        self.raycasters.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(offset: f32) -> BVHRaycaster {
+        let positions = [
+            offset,
+            0.0,
+            0.0, //
+            offset + 1.0,
+            0.0,
+            0.0, //
+            offset + 1.0,
+            1.0,
+            0.0, //
+            offset,
+            1.0,
+            0.0, //
+        ];
+        BVHRaycaster::from_geometry(&positions, &[0, 1, 2, 0, 2, 3])
+    }
+
+    #[test]
+    fn test_mount_unmount_reuses_tree() {
+        let mut pool = ObjectPool::new();
+        let mut builds = 0;
+
+        // Repeated mount/unmount of the same mesh builds the tree once
+        for _ in 0..50 {
+            let raycaster = pool.acquire(7).unwrap_or_else(|| {
+                builds += 1;
+                quad(0.0)
+            });
+            assert_eq!(raycaster.get_stats()[4], 2);
+            pool.release(7, raycaster);
+            assert_eq!(pool.len(), 1);
+        }
+        assert_eq!(builds, 1);
+        assert!(pool.acquire(8).is_none());
+    }
+
+    #[test]
+    fn test_pool_capacity_and_disposed() {
+        let mut pool = ObjectPool::with_capacity(2);
+        for hash in 0..5 {
+            pool.release(hash, quad(hash as f32));
+        }
+        // Oldest entries were evicted
+        assert_eq!(pool.len(), 2);
+        assert!(pool.acquire(0).is_none());
+        assert!(pool.acquire(4).is_some());
+
+        let mut disposed = quad(0.0);
+        disposed.dispose();
+        pool.release(9, disposed);
+        assert!(pool.acquire(9).is_none());
+
+        pool.clear();
+        assert!(pool.is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+use serde_json::{json, Map, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::ripple_physics::RippleSimulator;
+use crate::texture_gen::LutOptions;
+
+/// Binary preset magic bytes
+const PRESET_MAGIC: &[u8; 4] = b"CJPS";
+/// Version of the container layout (not of the sections)
+const PRESET_FORMAT_VERSION: u8 = 1;
+
+/// Section id: RippleSimulator::get_params
+const SECTION_RIPPLE: u8 = 0;
+/// Section id: LutOptions::get_params
+const SECTION_LUT: u8 = 1;
+/// Current schema version of each section
+const RIPPLE_SECTION_VERSION: u8 = 1;
+const LUT_SECTION_VERSION: u8 = 1;
+
+/// One captured parameter block
+struct Section {
+    id: u8,
+    version: u8,
+    params: Vec<f32>,
+}
+
+/// Named parameter bundles captured from (and applied to) the tunable types
+///
+/// Every section stores its parameter list with its own version and length.
+/// Applying a preset captured before a parameter existed leaves that
+/// parameter at its current value; unknown sections and extra trailing
+/// parameters from newer presets are ignored.
+///
+/// Binary layout (little endian):
+/// `"CJPS"`, format version (u8), section count (u8), then per section:
+/// id (u8), version (u8), param count (u16), params (f32 each).
+#[wasm_bindgen]
+pub struct Preset;
+
+#[wasm_bindgen]
+impl Preset {
+    /// Capture ripple and LUT settings into the compact binary format
+    #[wasm_bindgen]
+    pub fn capture(ripple: &RippleSimulator, lut: &LutOptions) -> Vec<u8> {
+        encode(&capture_sections(ripple, lut))
+    }
+
+    /// Apply a binary preset; sections not in the preset are left untouched
+    #[wasm_bindgen]
+    pub fn apply(
+        data: &[u8],
+        ripple: &mut RippleSimulator,
+        lut: &mut LutOptions,
+    ) -> Result<(), String> {
+        apply_sections(&decode(data)?, ripple, lut);
+        Ok(())
+    }
+
+    /// Capture as a JSON string: {"format": 1, "ripple": {"version": 1, "params": [...]}, ...}
+    #[wasm_bindgen]
+    pub fn capture_json(ripple: &RippleSimulator, lut: &LutOptions) -> String {
+        let mut root = Map::new();
+        root.insert("format".into(), json!(PRESET_FORMAT_VERSION));
+        for section in capture_sections(ripple, lut) {
+            root.insert(
+                section_name(section.id).into(),
+                json!({ "version": section.version, "params": section.params }),
+            );
+        }
+        Value::Object(root).to_string()
+    }
+
+    /// Apply a JSON preset from capture_json
+    #[wasm_bindgen]
+    pub fn apply_json(
+        text: &str,
+        ripple: &mut RippleSimulator,
+        lut: &mut LutOptions,
+    ) -> Result<(), String> {
+        let root: Value = serde_json::from_str(text).map_err(|e| format!("invalid preset: {e}"))?;
+        let root = root.as_object().ok_or("preset must be a JSON object")?;
+
+        let mut sections = Vec::new();
+        for id in [SECTION_RIPPLE, SECTION_LUT] {
+            let Some(entry) = root.get(section_name(id)) else {
+                continue;
+            };
+            let version = entry.get("version").and_then(Value::as_u64).unwrap_or(0);
+            let params = entry
+                .get("params")
+                .and_then(Value::as_array)
+                .ok_or_else(|| format!("section \"{}\" has no params array", section_name(id)))?
+                .iter()
+                .map(|v| {
+                    v.as_f64()
+                        .map(|v| v as f32)
+                        .ok_or_else(|| format!("non-numeric param in \"{}\"", section_name(id)))
+                })
+                .collect::<Result<Vec<f32>, String>>()?;
+            sections.push(Section {
+                id,
+                version: version.min(u8::MAX as u64) as u8,
+                params,
+            });
+        }
+
+        apply_sections(&sections, ripple, lut);
+        Ok(())
+    }
+}
+
+fn section_name(id: u8) -> &'static str {
+    match id {
+        SECTION_RIPPLE => "ripple",
+        SECTION_LUT => "lut",
+        _ => "unknown",
+    }
+}
+
+fn capture_sections(ripple: &RippleSimulator, lut: &LutOptions) -> Vec<Section> {
+    vec![
+        Section {
+            id: SECTION_RIPPLE,
+            version: RIPPLE_SECTION_VERSION,
+            params: ripple.get_params(),
+        },
+        Section {
+            id: SECTION_LUT,
+            version: LUT_SECTION_VERSION,
+            params: lut.get_params(),
+        },
+    ]
+}
+
+fn apply_sections(sections: &[Section], ripple: &mut RippleSimulator, lut: &mut LutOptions) {
+    // Parameters are append-only across section versions, so older (shorter)
+    // lists apply as a prefix and set_params keeps the rest
+    for section in sections {
+        match section.id {
+            SECTION_RIPPLE => ripple.set_params(&section.params),
+            SECTION_LUT => lut.set_params(&section.params),
+            _ => {}
+        }
+    }
+}
+
+fn encode(sections: &[Section]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(
+        6 + sections
+            .iter()
+            .map(|s| 4 + s.params.len() * 4)
+            .sum::<usize>(),
+    );
+    out.extend_from_slice(PRESET_MAGIC);
+    out.push(PRESET_FORMAT_VERSION);
+    out.push(sections.len() as u8);
+    for section in sections {
+        out.push(section.id);
+        out.push(section.version);
+        out.extend_from_slice(&(section.params.len() as u16).to_le_bytes());
+        for p in &section.params {
+            out.extend_from_slice(&p.to_le_bytes());
+        }
+    }
+    out
+}
+
+fn decode(data: &[u8]) -> Result<Vec<Section>, String> {
+    if data.len() < 6 || &data[..4] != PRESET_MAGIC {
+        return Err("not a preset (bad magic)".into());
+    }
+    if data[4] > PRESET_FORMAT_VERSION {
+        return Err(format!("unsupported preset format version {}", data[4]));
+    }
+
+    let mut sections = Vec::with_capacity(data[5] as usize);
+    let mut pos = 6;
+    for _ in 0..data[5] {
+        let header = data.get(pos..pos + 4).ok_or("truncated section header")?;
+        let count = u16::from_le_bytes([header[2], header[3]]) as usize;
+        let body = data
+            .get(pos + 4..pos + 4 + count * 4)
+            .ok_or("truncated section params")?;
+        sections.push(Section {
+            id: header[0],
+            version: header[1],
+            params: body
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        });
+        pos += 4 + count * 4;
+    }
+    Ok(sections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tuned() -> (RippleSimulator, LutOptions) {
+        let mut ripple = RippleSimulator::new();
+        ripple.set_displacement_axis_mask(0.0, 1.0, 0.5);
+        ripple.set_displacement_space(2);
+        ripple.set_blocker_leakage(0.4);
+
+        let mut lut = LutOptions::new();
+        lut.size = 16;
+        lut.contrast = 1.2;
+        lut.tone_curve = 2;
+        lut.exposure_ev = 1.5;
+        lut.linearize_input = true;
+        (ripple, lut)
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let (ripple, lut) = tuned();
+        let data = Preset::capture(&ripple, &lut);
+
+        let mut ripple_out = RippleSimulator::new();
+        let mut lut_out = LutOptions::new();
+        Preset::apply(&data, &mut ripple_out, &mut lut_out).unwrap();
+        assert_eq!(ripple_out.get_params(), ripple.get_params());
+        assert_eq!(lut_out, lut);
+
+        assert!(Preset::apply(&data[..data.len() - 1], &mut ripple_out, &mut lut_out).is_err());
+        assert!(Preset::apply(b"nope", &mut ripple_out, &mut lut_out).is_err());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let (ripple, lut) = tuned();
+        let text = Preset::capture_json(&ripple, &lut);
+
+        let mut ripple_out = RippleSimulator::new();
+        let mut lut_out = LutOptions::new();
+        Preset::apply_json(&text, &mut ripple_out, &mut lut_out).unwrap();
+        assert_eq!(ripple_out.get_params(), ripple.get_params());
+        assert_eq!(lut_out, lut);
+
+        let bad = r#"{"format": 1, "lut": {"version": 1, "params": ["x"]}}"#;
+        assert!(Preset::apply_json(bad, &mut ripple_out, &mut lut_out).is_err());
+    }
+
+    #[test]
+    fn test_old_preset_on_newer_schema() {
+        // Version 0 ripple section (axis mask only) plus a section id from the future
+        let old = encode(&[
+            Section {
+                id: SECTION_RIPPLE,
+                version: 0,
+                params: vec![1.0, 0.0, 0.0],
+            },
+            Section {
+                id: 42,
+                version: 3,
+                params: vec![9.0; 4],
+            },
+        ]);
+
+        let (mut ripple, mut lut) = tuned();
+        let lut_before = lut;
+        Preset::apply(&old, &mut ripple, &mut lut).unwrap();
+
+        // Mask applied, newer fields keep their values, LUT untouched
+        assert_eq!(ripple.get_params(), vec![1.0, 0.0, 0.0, 2.0, 0.4]);
+        assert_eq!(lut, lut_before);
+    }
+}
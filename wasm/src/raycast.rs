@@ -1,34 +1,194 @@
-use glam::{Mat4, Vec3};
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet, VecDeque};
+
+use glam::{DMat4, DVec3, Mat4, Vec2, Vec3, Vec4};
 use wasm_bindgen::prelude::*;
 
+use crate::geometry::{depth_color, GeometryData};
+
 /// Maximum triangles per leaf node. Smaller = deeper tree, more nodes.
 /// 4-8 is typical; we use 4 for better ray culling.
 const MAX_LEAF_TRIANGLES: usize = 4;
 
+/// Triangle determinant epsilon, relative to epsilon_scale^2 (determinant is length^2)
+const RELATIVE_DET_EPSILON: f32 = 1e-7;
+/// Minimum hit distance, relative to epsilon_scale
+const RELATIVE_T_EPSILON: f32 = 1e-6;
+
+/// set_cull_mode modes
+const CULL_BACK: u8 = 0;
+const CULL_NONE: u8 = 1;
+const CULL_FRONT: u8 = 2;
+
+/// Floats per hit returned by intersect_all, and per ray by intersect_batch
+const ALL_HITS_STRIDE: usize = 8;
+const BATCH_STRIDE: usize = 8;
+/// Hits closer together than this along the ray (relative to epsilon_scale)
+/// are one crossing of a shared edge or vertex; intersect_all keeps the first
+const RELATIVE_DUPLICATE_HIT_EPSILON: f32 = 1e-5;
+
+/// Rays per intersect_packet4 call and floats per packet hit record
+const PACKET_RAYS: usize = 4;
+const PACKET_HIT_STRIDE: usize = 7;
+/// Floats in the intersect_into buffer
+const HIT_BUFFER_LEN: usize = 12;
+/// Box growth for the f32 copy of a high-precision ray, relative to the
+/// largest coordinate involved (covers its rounding with room to spare)
+const RELATIVE_PRECISE_BOX_MARGIN: f32 = 4.0 * f32::EPSILON;
+
+/// Cap on boxes emitted by export_debug_geometry (24 vertices each)
+const MAX_DEBUG_BOXES: usize = 100_000;
+
+/// Most appended triangles kept in the linearly-tested set; append_geometry
+/// compacts first when a chunk would push it past this
+const MAX_PENDING_TRIANGLES: usize = 4096;
+/// compact() rebuilds the whole tree when the appended set is at least this
+/// fraction of the tree; smaller sets become a new subtree beside the old root
+const FULL_REBUILD_FRACTION: f32 = 0.25;
+
+/// from_geometry_with_cleanup flags
+const CLEANUP_DEGENERATE: u8 = 1;
+const CLEANUP_DUPLICATES: u8 = 2;
+/// Triangles with less area than this, relative to the mesh diagonal^2, are dropped
+const RELATIVE_AREA_EPSILON: f32 = 1e-10;
+
+/// Range of bins per axis accepted by from_geometry_sah
+const MIN_SAH_BINS: u32 = 2;
+const MAX_SAH_BINS: u32 = 64;
+/// Past this depth nodes are split by median only; SAH can peel off a few
+/// triangles per level on skewed meshes, median splits always halve
+const MAX_SAH_DEPTH: usize = 64;
+/// Surface area heuristic costs of visiting a node and testing a triangle
+const SAH_TRAVERSAL_COST: f32 = 1.0;
+const SAH_INTERSECTION_COST: f32 = 1.0;
+
+/// Floats per hit returned by SceneRaycaster::intersect
+const SCENE_HIT_STRIDE: usize = 9;
+
+/// GridRaycaster target triangles per cell when picking the resolution
+const GRID_TRIANGLES_PER_CELL: f32 = 2.0;
+/// Upper bound on GridRaycaster cells along either axis
+const MAX_GRID_RESOLUTION: u32 = 1024;
+
+/// Ray directions contains_point tries before settling for an ambiguous count
+const CONTAINMENT_ATTEMPTS: usize = 8;
+/// Barycentric margin within which a crossing counts as on an edge or vertex
+const EDGE_BARYCENTRIC_EPSILON: f32 = 1e-5;
+
+/// Floats per nearest_triangles entry
+const NEAREST_STRIDE: usize = 5;
+
+/// Floats per query_frustum plane (nx, ny, nz, d) and planes per frustum
+const PLANE_STRIDE: usize = 4;
+const FRUSTUM_PLANES: usize = 6;
+/// Frustums thinner than this, relative to their size, count as zero-volume
+const RELATIVE_FRUSTUM_EPSILON: f32 = 1e-5;
+/// query_frustum leaf ID standing for the appended triangles not in the tree yet
+const PENDING_LEAF: u32 = u32::MAX;
+
+/// Serialized tree magic bytes
+const BVH_MAGIC: &[u8; 4] = b"CJBV";
+/// Version of the to_bytes layout; bump on any change
+const BVH_FORMAT_VERSION: u8 = 5;
+/// to_bytes flag: triangles carry vertex normals
+const BVH_HAS_VERTEX_NORMALS: u8 = 1;
+
 #[wasm_bindgen]
 pub struct BVHRaycaster {
     nodes: Vec<BVHNode>,
     triangles: Vec<Triangle>,
     /// Triangle indices, reordered during BVH build
     triangle_indices: Vec<usize>,
+    /// User ID ranges (start_triangle, count, user_id), sorted by start
+    id_ranges: Vec<(u32, u32, u32)>,
+    /// Group ID per input triangle (set_groups); appended triangles have none
+    groups: Vec<u32>,
+    /// Per input triangle, 0 = skipped by ray queries (set_triangle_mask);
+    /// empty, or past its end for appended triangles, = enabled
+    triangle_mask: Vec<u8>,
+    /// Number of triangles in the input index buffer (including skipped ones)
+    input_triangle_count: usize,
+    /// Vertices in the position buffer the triangles index (refit input size)
+    vertex_count: usize,
+    /// Texture coordinates per vertex (set_uvs); appended vertices have none
+    uvs: Vec<Vec2>,
+    /// Traversal profiling (counters are empty while disabled)
+    profiling: bool,
+    node_visit_counts: Vec<Cell<u32>>,
+    triangle_test_counts: Vec<Cell<u32>>,
+    /// Root AABB diagonal, the automatic epsilon scale
+    scene_scale: f32,
+    /// Length scale the intersection epsilons are derived from
+    epsilon_scale: f32,
+    /// Which triangle sides rays can hit (CULL_*)
+    cull_mode: u8,
+    /// Closest-hit queries run the ray and triangle math in f64 (set_precision_high)
+    precision_high: bool,
+    /// Traversal stack kept between queries so walks don't allocate
+    traversal_stack: RefCell<Vec<usize>>,
+    /// Model matrix for intersect_cached and its inverse (set_model_matrix)
+    model: Mat4,
+    inverse_model: Mat4,
+    /// Last intersect_into result, read from JS through get_hit_ptr
+    hit_buffer: [f32; HIT_BUFFER_LEN],
+    /// Set by dispose(); all buffers are empty
+    disposed: bool,
+    /// Bins per axis for SAH splits, 0 = median split (also used by compact)
+    sah_bins: u32,
+    /// Object-space offset subtracted from the vertices at build time (floating origin)
+    origin: DVec3,
+    /// Appended triangles (indices into `triangles`) not yet in the tree, tested linearly
+    pending: Vec<usize>,
+    /// Bounds of `pending`, as a leaf-shaped node for ray_aabb_intersect
+    pending_bounds: BVHNode,
+    /// Cleanup flags given at build time, also applied to appended chunks
+    cleanup: u8,
+    /// Triangles removed by cleanup: zero-area, and duplicates of a kept triangle
+    dropped_degenerate: usize,
+    merged_duplicates: usize,
+    /// Input triangles that were never built: collapsed (repeated index or
+    /// zero/non-finite cross product) and referencing missing vertices
+    skipped_degenerate: usize,
+    skipped_out_of_range: usize,
 }
 
-/// BVH tree node
+/// BVH tree node, 32 bytes
 ///
-/// Internal nodes: triangle_count = 0, left/right are Some
-/// Leaf nodes: triangle_count > 0, left/right are None
-#[derive(Clone)]
+/// Nodes are stored depth-first: an internal node's left child is the next
+/// node, so the near side of a traversal stays in the same cache lines.
+/// Internal nodes: triangle_count = 0, offset = right child index
+/// Leaf nodes: triangle_count > 0, offset = start index into triangle_indices
+/// Empty tree: a root with both 0 (the root is never a child)
+#[derive(Clone, Copy)]
 struct BVHNode {
     bounds_min: Vec3,
     bounds_max: Vec3,
-    /// Index of left child node (None for leaf)
-    left: Option<usize>,
-    /// Index of right child node (None for leaf)
-    right: Option<usize>,
-    /// Start index into triangle_indices array
-    triangle_start: usize,
-    /// Number of triangles (0 for internal nodes)
-    triangle_count: usize,
+    offset: u32,
+    triangle_count: u32,
+}
+
+const _: () = assert!(std::mem::size_of::<BVHNode>() == 32);
+
+impl BVHNode {
+    fn is_leaf(&self) -> bool {
+        self.triangle_count > 0
+    }
+
+    /// (left, right) children of the internal node stored at `index`
+    fn children(&self, index: usize) -> Option<(usize, usize)> {
+        (self.triangle_count == 0 && self.offset != 0).then_some((index + 1, self.offset as usize))
+    }
+
+    /// Leaf range in triangle_indices (empty for internal nodes)
+    fn triangle_range(&self) -> std::ops::Range<usize> {
+        if self.is_leaf() {
+            let start = self.offset as usize;
+            start..start + self.triangle_count as usize
+        } else {
+            0..0
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -39,280 +199,3714 @@ struct Triangle {
     normal: Vec3,
     /// Centroid for sorting during BVH build
     centroid: Vec3,
+    /// Original triangle index in the input index buffer
+    index: usize,
+    /// Vertex indices of v0, v1, v2 in the full position buffer
+    vertices: [u32; 3],
+    /// Shading normals at v0, v1, v2 (see set_vertex_normals), None = flat
+    vertex_normals: Option<[Vec3; 3]>,
 }
 
 struct RayHit {
     point: Vec3,
     normal: Vec3,
     distance: f32,
+    /// Index into `triangles` of the hit triangle
+    triangle: usize,
+    /// Barycentric weights of v1 and v2 (v0 weighs 1 - u - v)
+    u: f32,
+    v: f32,
 }
 
-#[wasm_bindgen]
-impl BVHRaycaster {
-    /// Build BVH from mesh geometry (call once on load)
+impl Triangle {
+    /// Möller–Trumbore ray-triangle intersection, shared by every accelerator
     ///
     /// # Arguments
-    /// * `positions` - Flat vertex array: [x0,y0,z0, x1,y1,z1, ...]
-    /// * `indices` - Triangle indices: [i0,i1,i2, i3,i4,i5, ...]
-    #[wasm_bindgen(constructor)]
-    pub fn from_geometry(positions: &[f32], indices: &[u32]) -> Self {
-        let triangles = Self::build_triangles(positions, indices);
-        let num_triangles = triangles.len();
-
-        // Initial indices: 0, 1, 2, ..., n-1
-        let mut triangle_indices: Vec<usize> = (0..num_triangles).collect();
+    /// * `tri_idx` - Index of this triangle, reported in the hit
+    /// * `cull_mode` - Which sides can be hit (CULL_*)
+    /// * `epsilon_scale` - Length scale the determinant and distance epsilons
+    ///   are relative to
+    #[inline]
+    fn intersect_ray(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        tri_idx: usize,
+        cull_mode: u8,
+        epsilon_scale: f32,
+    ) -> Option<RayHit> {
+        let det_epsilon = RELATIVE_DET_EPSILON * epsilon_scale * epsilon_scale;
+        let t_epsilon = RELATIVE_T_EPSILON * epsilon_scale;
 
-        // Build BVH recursively
-        let mut nodes = Vec::with_capacity(num_triangles * 2); // Approximate size
+        let edge1 = self.v1 - self.v0;
+        let edge2 = self.v2 - self.v0;
+        let h = dir.cross(edge2);
+        let a = edge1.dot(h);
 
-        if !triangles.is_empty() {
-            Self::build_bvh_recursive(
-                &triangles,
-                &mut triangle_indices,
-                0,
-                num_triangles,
-                &mut nodes,
-            );
-        } else {
-            // Empty geometry - create dummy root
-            nodes.push(BVHNode {
-                bounds_min: Vec3::ZERO,
-                bounds_max: Vec3::ZERO,
-                left: None,
-                right: None,
-                triangle_start: 0,
-                triangle_count: 0,
-            });
+        // a > 0 = front face, a < 0 = back face; parallel rays (a ≈ 0) always miss
+        let culled = match cull_mode {
+            CULL_NONE => a.abs() < det_epsilon,
+            CULL_FRONT => a > -det_epsilon,
+            _ => a < det_epsilon,
+        };
+        if culled {
+            return None;
         }
 
-        Self {
-            nodes,
-            triangles,
-            triangle_indices,
+        let f = 1.0 / a;
+        let s = origin - self.v0;
+        let u = f * s.dot(h);
+
+        // Outside triangle (u coordinate)
+        if !(0.0..=1.0).contains(&u) {
+            return None;
         }
-    }
 
-    /// Fast ray-mesh intersection using BVH traversal
-    ///
-    /// # Returns
-    /// `Some([px, py, pz, nx, ny, nz, distance])` or `None`
-    #[wasm_bindgen]
-    pub fn intersect(
-        &self,
-        ray_origin: &[f32],
-        ray_direction: &[f32],
-        model_matrix: &[f32],
-    ) -> Option<Vec<f32>> {
-        let origin = Vec3::from_slice(ray_origin);
-        let direction = Vec3::from_slice(ray_direction).normalize();
-        let model = Mat4::from_cols_array(model_matrix.try_into().unwrap());
-        let inverse_model = model.inverse();
+        let q = s.cross(edge1);
+        let v = f * dir.dot(q);
 
-        // Transform ray to object space
-        let local_origin = inverse_model.transform_point3(origin);
-        let local_dir = inverse_model.transform_vector3(direction).normalize();
+        // Outside triangle (v coordinate or u+v > 1)
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
 
-        // Pre-compute inverse direction for AABB tests
-        let inv_dir = Vec3::new(
-            if local_dir.x.abs() > f32::EPSILON {
-                1.0 / local_dir.x
-            } else {
-                f32::MAX
-            },
-            if local_dir.y.abs() > f32::EPSILON {
-                1.0 / local_dir.y
-            } else {
-                f32::MAX
-            },
-            if local_dir.z.abs() > f32::EPSILON {
-                1.0 / local_dir.z
-            } else {
-                f32::MAX
-            },
-        );
+        let t = f * edge2.dot(q);
 
-        // BVH traversal
-        self.traverse_bvh(local_origin, local_dir, inv_dir)
-            .map(|hit| {
-                // Transform hit back to world space
-                let world_point = model.transform_point3(hit.point);
-                let world_normal = model.transform_vector3(hit.normal).normalize();
-                vec![
-                    world_point.x,
-                    world_point.y,
-                    world_point.z,
-                    world_normal.x,
-                    world_normal.y,
-                    world_normal.z,
-                    hit.distance,
-                ]
+        // Intersection in front of ray origin
+        if t > t_epsilon {
+            let normal = BVHRaycaster::shading_normal(self, u, v);
+            Some(RayHit {
+                point: origin + dir * t,
+                normal: if a < 0.0 { -normal } else { normal },
+                distance: t,
+                triangle: tri_idx,
+                u,
+                v,
             })
-    }
-
-    /// Get BVH statistics for debugging/benchmarking
-    #[wasm_bindgen]
-    pub fn get_stats(&self) -> Vec<u32> {
-        let total_nodes = self.nodes.len();
-        let leaf_nodes = self.nodes.iter().filter(|n| n.triangle_count > 0).count();
-        let internal_nodes = total_nodes - leaf_nodes;
-        let max_depth = self.compute_max_depth(0, 0);
-        let total_triangles = self.triangles.len();
-
-        vec![
-            total_nodes as u32,
-            leaf_nodes as u32,
-            internal_nodes as u32,
-            max_depth as u32,
-            total_triangles as u32,
-        ]
+        } else {
+            None
+        }
     }
 }
 
-// --- Private implementation ---
+#[cfg(all(
+    feature = "simd",
+    target_arch = "wasm32",
+    not(target_feature = "simd128")
+))]
+compile_error!("the simd feature needs RUSTFLAGS=\"-C target-feature=+simd128\"");
 
-impl BVHRaycaster {
-    /// Build triangles from flat position and index arrays
-    fn build_triangles(positions: &[f32], indices: &[u32]) -> Vec<Triangle> {
-        let mut triangles = Vec::with_capacity(indices.len() / 3);
+/// Four object-space rays, laid out per axis for the lane-wise box test
+struct RayPacket4 {
+    /// origin[axis][lane]
+    origin: [[f32; PACKET_RAYS]; 3],
+    inv_dir: [[f32; PACKET_RAYS]; 3],
+}
 
-        for chunk in indices.chunks(3) {
-            if chunk.len() < 3 {
-                continue;
-            }
+/// Frustum in object space, with what the separating axis test needs
+struct Frustum {
+    /// Normalized planes (n, d), inside where n.p + d >= 0
+    planes: [Vec4; FRUSTUM_PLANES],
+    /// Corners: the plane-triple intersections inside every plane
+    corners: Vec<Vec3>,
+    /// Directions of plane-pair intersection lines (covers every edge)
+    edges: Vec<Vec3>,
+}
 
-            let i0 = chunk[0] as usize * 3;
-            let i1 = chunk[1] as usize * 3;
-            let i2 = chunk[2] as usize * 3;
+/// How a containment ray meets one triangle
+#[derive(PartialEq)]
+enum Crossing {
+    Miss,
+    Hit,
+    /// Through an edge or vertex, or along the plane: the count is unreliable
+    Ambiguous,
+}
 
-            // Bounds check
-            if i0 + 2 >= positions.len() || i1 + 2 >= positions.len() || i2 + 2 >= positions.len() {
-                continue;
-            }
+/// Vertex positions in a float buffer: vertex i is [x, y, z] at
+/// buffer[i * stride + offset..]
+#[derive(Clone, Copy)]
+struct VertexPositions<'a> {
+    buffer: &'a [f32],
+    stride: usize,
+    offset: usize,
+}
 
-            let v0 = Vec3::new(positions[i0], positions[i0 + 1], positions[i0 + 2]);
-            let v1 = Vec3::new(positions[i1], positions[i1 + 1], positions[i1 + 2]);
-            let v2 = Vec3::new(positions[i2], positions[i2 + 1], positions[i2 + 2]);
+impl<'a> VertexPositions<'a> {
+    /// Tightly packed [x, y, z, ...]
+    fn flat(buffer: &'a [f32]) -> Self {
+        Self {
+            buffer,
+            stride: 3,
+            offset: 0,
+        }
+    }
 
-            // Calculate face normal
-            let edge1 = v1 - v0;
-            let edge2 = v2 - v0;
-            let normal = edge1.cross(edge2).normalize_or_zero();
+    /// Number of whole vertices in the buffer (stride >= offset + 3)
+    fn len(&self) -> usize {
+        (self.buffer.len() + self.stride - self.offset - 3) / self.stride
+    }
 
-            // Calculate centroid for sorting
-            let centroid = (v0 + v1 + v2) / 3.0;
+    fn get(&self, vertex: usize) -> Vec3 {
+        Vec3::from_slice(&self.buffer[vertex * self.stride + self.offset..])
+    }
+}
 
-            triangles.push(Triangle {
-                v0,
-                v1,
-                v2,
-                normal,
-                centroid,
-            });
-        }
+/// Ray transformed into object space, with pre-computed inverse direction
+struct LocalRay {
+    model: Mat4,
+    /// Inverse transpose of `model`, keeps normals perpendicular under
+    /// non-uniform scale
+    normal_matrix: Mat4,
+    origin: Vec3,
+    direction: Vec3,
+    inv_dir: Vec3,
+}
 
-        triangles
+#[wasm_bindgen]
+impl BVHRaycaster {
+    /// Build BVH from mesh geometry (call once on load)
+    ///
+    /// # Arguments
+    /// * `positions` - Flat vertex array: [x0,y0,z0, x1,y1,z1, ...]
+    /// * `indices` - Triangle indices: [i0,i1,i2, i3,i4,i5, ...]
+    #[wasm_bindgen(constructor)]
+    pub fn from_geometry(positions: &[f32], indices: &[u32]) -> Self {
+        Self::from_geometry_with_cleanup(positions, indices, 0)
     }
 
-    /// Recursively build BVH using median-split on longest axis
+    /// Build BVH, removing triangles that only bloat the tree (e.g. CAD exports)
+    ///
+    /// Removed triangles can never be hit; hits, ID ranges and profiling
+    /// counts keep using the original triangle indices.
     ///
     /// # Arguments
-    /// * `triangles` - All triangles (immutable reference)
-    /// * `indices` - Mutable triangle index array (reordered in place)
-    /// * `start` - Start index in `indices` for this node
-    /// * `end` - End index (exclusive) in `indices` for this node
-    /// * `nodes` - Output node array
+    /// * `cleanup` - Bit flags: 1 = drop triangles whose area is negligible
+    ///   relative to the mesh size, 2 = drop exact duplicates (same three
+    ///   vertex indices in any rotation, so opposite windings are kept)
     ///
-    /// # Returns
-    /// Index of the created node in `nodes`
-    fn build_bvh_recursive(
-        triangles: &[Triangle],
-        indices: &mut [usize],
-        start: usize,
-        end: usize,
-        nodes: &mut Vec<BVHNode>,
-    ) -> usize {
-        let count = end - start;
+    /// See get_cleanup_stats for what was removed.
+    #[wasm_bindgen]
+    pub fn from_geometry_with_cleanup(positions: &[f32], indices: &[u32], cleanup: u8) -> Self {
+        Self::build(
+            VertexPositions::flat(positions),
+            Self::faces(indices),
+            cleanup,
+            0,
+            false,
+        )
+    }
 
-        // Compute bounds for this subset
-        let (bounds_min, bounds_max) = Self::compute_bounds(triangles, &indices[start..end]);
+    /// Build BVH with surface-area-heuristic splits (slower build, faster queries)
+    ///
+    /// Each node takes the cheapest of the binned split candidates on all
+    /// three axes, and falls back to the median split when no candidate beats
+    /// keeping the triangles together. Worth it for meshes with uneven detail.
+    ///
+    /// # Arguments
+    /// * `bins` - Candidates per axis, clamped to 2..=64 (8-16 is typical)
+    ///
+    /// Compare trees with get_sah_cost.
+    #[wasm_bindgen]
+    pub fn from_geometry_sah(positions: &[f32], indices: &[u32], bins: u32) -> Self {
+        Self::build(
+            VertexPositions::flat(positions),
+            Self::faces(indices),
+            0,
+            bins.clamp(MIN_SAH_BINS, MAX_SAH_BINS),
+            false,
+        )
+    }
 
-        // Create leaf node if few enough triangles
-        if count <= MAX_LEAF_TRIANGLES {
-            let node_idx = nodes.len();
-            nodes.push(BVHNode {
-                bounds_min,
-                bounds_max,
-                left: None,
-                right: None,
-                triangle_start: start,
-                triangle_count: count,
-            });
-            return node_idx;
-        }
+    /// Build a linear BVH from Morton codes (fast build, slower queries)
+    ///
+    /// Triangles are sorted along a Z-order curve through the centroid bounds
+    /// (radix sort, no per-node sorting) and split where the codes' leading
+    /// bit changes. For meshes built at runtime, where build time matters more
+    /// than the last bit of query speed. compact() and update_geometry fall
+    /// back to median splits.
+    #[wasm_bindgen]
+    pub fn from_geometry_fast(positions: &[f32], indices: &[u32]) -> Self {
+        Self::build(
+            VertexPositions::flat(positions),
+            Self::faces(indices),
+            0,
+            0,
+            true,
+        )
+    }
 
-        // Find longest axis
-        let extent = bounds_max - bounds_min;
-        let axis = if extent.x >= extent.y && extent.x >= extent.z {
-            0 // X
-        } else if extent.y >= extent.z {
-            1 // Y
-        } else {
-            2 // Z
-        };
+    /// Build BVH from a 16-bit index buffer (Three.js Uint16Array index)
+    ///
+    /// Same as from_geometry, without widening the indices on the JS side.
+    #[wasm_bindgen]
+    pub fn from_geometry_u16(positions: &[f32], indices: &[u16]) -> Self {
+        Self::build(
+            VertexPositions::flat(positions),
+            Self::faces(indices),
+            0,
+            0,
+            false,
+        )
+    }
 
-        // Sort indices by triangle centroid along chosen axis
-        let tri_ref = triangles;
-        indices[start..end].sort_by(|&a, &b| {
-            let ca = match axis {
-                0 => tri_ref[a].centroid.x,
-                1 => tri_ref[a].centroid.y,
-                _ => tri_ref[a].centroid.z,
-            };
-            let cb = match axis {
-                0 => tri_ref[b].centroid.x,
-                1 => tri_ref[b].centroid.y,
-                _ => tri_ref[b].centroid.z,
-            };
-            ca.partial_cmp(&cb).unwrap_or(std::cmp::Ordering::Equal)
-        });
+    /// Build BVH from non-indexed geometry
+    ///
+    /// Every 3 consecutive vertices form a triangle; trailing vertices that
+    /// don't make a whole triangle are ignored.
+    #[wasm_bindgen]
+    pub fn from_soup(positions: &[f32]) -> Self {
+        Self::build(
+            VertexPositions::flat(positions),
+            Self::soup_faces(positions.len() / 3),
+            0,
+            0,
+            false,
+        )
+    }
 
-        // Split at median
-        let mid = start + count / 2;
+    /// Build BVH from an interleaved vertex buffer (position, normal, uv, ...)
+    ///
+    /// Positions are read in place, so the buffer needn't be de-interleaved
+    /// on the JS side.
+    ///
+    /// # Arguments
+    /// * `buffer` - Whole vertex buffer, `stride_floats` floats per vertex
+    /// * `position_offset_floats` - Floats before x within each vertex
+    /// * `indices` - Triangle indices, as from_geometry
+    #[wasm_bindgen]
+    pub fn from_interleaved(
+        buffer: &[f32],
+        stride_floats: u32,
+        position_offset_floats: u32,
+        indices: &[u32],
+    ) -> Result<BVHRaycaster, String> {
+        let (stride, offset) = (stride_floats as usize, position_offset_floats as usize);
+        if stride < offset + 3 {
+            return Err(format!(
+                "stride {stride} leaves no room for a position at offset {offset}"
+            ));
+        }
+        if !buffer.len().is_multiple_of(stride) {
+            return Err(format!(
+                "buffer length {} is not a multiple of the stride {stride}",
+                buffer.len()
+            ));
+        }
+        let positions = VertexPositions {
+            buffer,
+            stride,
+            offset,
+        };
+        Ok(Self::build(positions, Self::faces(indices), 0, 0, false))
+    }
 
-        // Reserve slot for this internal node
-        let node_idx = nodes.len();
-        nodes.push(BVHNode {
-            bounds_min,
-            bounds_max,
-            left: None,  // Will be filled in
-            right: None, // Will be filled in
-            triangle_start: 0,
-            triangle_count: 0, // 0 indicates internal node
-        });
+    /// Build BVH whose hits report smooth (interpolated) vertex normals
+    ///
+    /// Same as from_geometry followed by set_vertex_normals.
+    #[wasm_bindgen]
+    pub fn from_geometry_with_normals(
+        positions: &[f32],
+        indices: &[u32],
+        normals: &[f32],
+    ) -> Result<BVHRaycaster, String> {
+        let mut raycaster = Self::from_geometry(positions, indices);
+        raycaster.set_vertex_normals(normals)?;
+        Ok(raycaster)
+    }
 
-        // Recursively build children
-        let left_idx = Self::build_bvh_recursive(triangles, indices, start, mid, nodes);
-        let right_idx = Self::build_bvh_recursive(triangles, indices, mid, end, nodes);
+    /// Build BVH from double-precision positions (huge-coordinate scenes)
+    ///
+    /// Vertices are stored relative to the center of their bounds (see
+    /// get_origin), so the tree keeps full f32 precision near the mesh.
+    #[wasm_bindgen]
+    pub fn from_geometry_f64(positions: &[f64], indices: &[u32]) -> Self {
+        let (min, max) = positions.chunks_exact(3).fold(
+            (DVec3::splat(f64::INFINITY), DVec3::splat(f64::NEG_INFINITY)),
+            |(min, max), p| {
+                let p = DVec3::from_slice(p);
+                (min.min(p), max.max(p))
+            },
+        );
+        let origin = if min.is_finite() && max.is_finite() {
+            (min + max) * 0.5
+        } else {
+            DVec3::ZERO
+        };
 
-        // Update this node with child indices
-        nodes[node_idx].left = Some(left_idx);
-        nodes[node_idx].right = Some(right_idx);
+        let rebased: Vec<f32> = positions
+            .chunks_exact(3)
+            .flat_map(|p| (DVec3::from_slice(p) - origin).as_vec3().to_array())
+            .collect();
+        let mut raycaster = Self::from_geometry(&rebased, indices);
+        raycaster.origin = origin;
+        raycaster
+    }
 
-        node_idx
+    /// Object-space floating origin of the tree: [x, y, z] (zero unless built from f64)
+    ///
+    /// Pass to MatrixComputer::set_origin_f64 so rendering uses the same rebase.
+    #[wasm_bindgen]
+    pub fn get_origin(&self) -> Vec<f64> {
+        self.origin.to_array().to_vec()
     }
 
-    /// Compute bounding box for a subset of triangles
-    fn compute_bounds(triangles: &[Triangle], indices: &[usize]) -> (Vec3, Vec3) {
-        let mut bounds_min = Vec3::splat(f32::MAX);
-        let mut bounds_max = Vec3::splat(f32::MIN);
+    /// Double-precision ray-mesh intersection
+    ///
+    /// The ray is transformed and rebased in f64 and moved up to the mesh
+    /// bounds before the f32 traversal, so hits stay precise far from (0, 0, 0).
+    ///
+    /// # Returns
+    /// `Some([px, py, pz, nx, ny, nz, distance])` or `None`; also `None` if
+    /// the ray is not 3 + 3 floats or the model matrix is not 16
+    #[wasm_bindgen]
+    pub fn intersect_f64(
+        &self,
+        ray_origin: &[f64],
+        ray_direction: &[f64],
+        model_matrix: &[f64],
+    ) -> Option<Vec<f64>> {
+        let root = self.nodes.first()?;
+        let model = DMat4::from_cols_array(model_matrix.try_into().ok()?);
+        let ray_origin = DVec3::from_array(ray_origin.try_into().ok()?);
+        let ray_direction = DVec3::from_array(ray_direction.try_into().ok()?);
+        let inverse_model = model.inverse();
+        let origin = inverse_model.transform_point3(ray_origin) - self.origin;
+        let direction = inverse_model
+            .transform_vector3(ray_direction.normalize())
+            .normalize();
 
-        for &idx in indices {
-            let tri = &triangles[idx];
-            bounds_min = bounds_min.min(tri.v0).min(tri.v1).min(tri.v2);
-            bounds_max = bounds_max.max(tri.v0).max(tri.v1).max(tri.v2);
-        }
+        // Skip ahead (in f64) to the bounding sphere so the f32 ray starts near the mesh
+        let center = ((root.bounds_min + root.bounds_max) * 0.5).as_dvec3();
+        let radius = ((root.bounds_max - root.bounds_min) * 0.5).length() as f64;
+        let skip = ((center - origin).dot(direction) - radius).max(0.0);
+        let start = origin + direction * skip;
 
-        (bounds_min, bounds_max)
+        let (start_f32, local_dir) = (start.as_vec3(), direction.as_vec3());
+        let hit = self.traverse_bvh(start_f32, local_dir, local_dir.recip())?;
+
+        // Only the short offset from the start went through f32
+        let local_point = start + (hit.point - start_f32).as_dvec3() + self.origin;
+        let world_point = model.transform_point3(local_point);
+        let world_normal = inverse_model
+            .transpose()
+            .transform_vector3(hit.normal.as_dvec3())
+            .normalize();
+        Some(vec![
+            world_point.x,
+            world_point.y,
+            world_point.z,
+            world_normal.x,
+            world_normal.y,
+            world_normal.z,
+            // World distance, so scaled model matrices report it like intersect
+            (world_point - ray_origin).length(),
+        ])
+    }
+
+    /// Fast ray-mesh intersection using BVH traversal
+    ///
+    /// Inverts the model matrix on every call; for a transform that changes
+    /// less often than it is queried use set_model_matrix + intersect_cached.
+    ///
+    /// # Returns
+    /// `Some([px, py, pz, nx, ny, nz, distance])`, or `None` on a miss or if
+    /// the model matrix is not 16 floats
+    #[wasm_bindgen]
+    pub fn intersect(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+    ) -> Option<Vec<f32>> {
+        let model = Self::parse_matrix(model_matrix).ok()?;
+        self.closest_hit(model, model.inverse(), ray_origin, ray_direction)
+            .map(|(_, world)| world.to_vec())
+    }
+
+    /// Store the model matrix (and its inverse) for intersect_cached
+    ///
+    /// The matrix is kept until the next call; rebuilding the geometry does
+    /// not reset it. Identity until set.
+    ///
+    /// # Arguments
+    /// * `elements` - Column-major 4x4 matrix, 16 floats
+    #[wasm_bindgen]
+    pub fn set_model_matrix(&mut self, elements: &[f32]) -> Result<(), String> {
+        let model = Self::parse_matrix(elements)?;
+        self.model = model;
+        self.inverse_model = model.inverse();
+        Ok(())
+    }
+
+    /// intersect with the matrix stored by set_model_matrix (no inversion)
+    ///
+    /// # Returns
+    /// Same as intersect
+    #[wasm_bindgen]
+    pub fn intersect_cached(&self, ray_origin: &[f32], ray_direction: &[f32]) -> Option<Vec<f32>> {
+        self.closest_hit(self.model, self.inverse_model, ray_origin, ray_direction)
+            .map(|(_, world)| world.to_vec())
+    }
+
+    /// Pointer to the intersect_into result (12 floats)
+    ///
+    /// Layout, stable across versions:
+    /// [px, py, pz, nx, ny, nz, distance, triangle_index, u, v, w, reserved]
+    /// with u, v, w the barycentric weights of the triangle's second, third
+    /// and first vertex and the triangle index in original input order.
+    /// After a miss distance and triangle_index are -1. The pointer is valid
+    /// for the raycaster's lifetime, but a view on wasm memory must be
+    /// recreated if memory grows.
+    #[wasm_bindgen]
+    pub fn get_hit_ptr(&self) -> *const f32 {
+        self.hit_buffer.as_ptr()
+    }
+
+    /// intersect without allocating: the hit goes to the get_hit_ptr buffer
+    ///
+    /// # Returns
+    /// Whether anything was hit (false for a model matrix that is not 16
+    /// floats)
+    #[wasm_bindgen]
+    pub fn intersect_into(
+        &mut self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+    ) -> bool {
+        self.hit_buffer = Self::MISS_BUFFER;
+        let Ok(model) = Self::parse_matrix(model_matrix) else {
+            return false;
+        };
+        let Some((hit, world)) =
+            self.closest_hit(model, model.inverse(), ray_origin, ray_direction)
+        else {
+            return false;
+        };
+
+        self.hit_buffer[..7].copy_from_slice(&world);
+        self.hit_buffer[7] = self.triangles[hit.triangle].index as f32;
+        self.hit_buffer[8..11].copy_from_slice(&[hit.u, hit.v, 1.0 - hit.u - hit.v]);
+        true
+    }
+
+    /// intersect, ignoring anything farther than `max_distance`
+    ///
+    /// The search starts with the limit as its closest distance, so boxes and
+    /// triangles beyond it are skipped.
+    ///
+    /// # Arguments
+    /// * `max_distance` - World units along the ray; a hit exactly at the
+    ///   limit counts
+    ///
+    /// # Returns
+    /// Same as intersect
+    #[wasm_bindgen]
+    pub fn intersect_within(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+        max_distance: f32,
+    ) -> Option<Vec<f32>> {
+        let (model, inverse_model) = Self::model_transforms(model_matrix).ok()?;
+        let ray = self.transform_ray(model, inverse_model, ray_origin, ray_direction);
+        // Object units per world unit along the ray
+        let stretch = inverse_model
+            .transform_vector3(Vec3::from_slice(ray_direction).normalize())
+            .length();
+        // The walk keeps only strictly nearer hits
+        let max_t = (max_distance * stretch).next_up();
+
+        self.traverse_bvh_within(ray.origin, ray.direction, ray.inv_dir, max_t)
+            .map(|hit| self.hit_to_world(&ray, &hit))
+    }
+
+    /// First hit on the segment between two world-space points, e.g. camera
+    /// to a UI anchor
+    ///
+    /// Sides are culled per set_cull_mode, so with the default back-face
+    /// culling a segment starting inside a closed mesh does not hit its way
+    /// out. A hit exactly at `end` counts.
+    ///
+    /// # Arguments
+    /// * `start` - Segment start, world space [x, y, z]
+    /// * `end` - Segment end, world space [x, y, z]
+    ///
+    /// # Returns
+    /// `Some([px, py, pz, nx, ny, nz, distance, fraction])` with the world
+    /// distance from `start` and the fraction of the segment (0..1) at the
+    /// hit, or `None` (also for a zero-length segment or a model matrix that
+    /// is not 16 floats)
+    #[wasm_bindgen]
+    pub fn intersect_segment(
+        &self,
+        start: &[f32],
+        end: &[f32],
+        model_matrix: &[f32],
+    ) -> Option<Vec<f32>> {
+        let model = Self::parse_matrix(model_matrix).ok()?;
+        let inverse_model = model.inverse();
+        let world = Vec3::from_slice(end) - Vec3::from_slice(start);
+        let length = world.length();
+        if !(length > 0.0 && length.is_finite()) {
+            return None;
+        }
+        let ray = self.transform_ray(model, inverse_model, start, &world.to_array());
+        // Segment length in object units
+        let local_length = inverse_model.transform_vector3(world).length();
+
+        self.traverse_bvh_within(
+            ray.origin,
+            ray.direction,
+            ray.inv_dir,
+            local_length.next_up(),
+        )
+        .map(|hit| {
+            let fraction = (hit.distance / local_length).min(1.0);
+            let mut out = self.hit_to_world(&ray, &hit);
+            out[6] = fraction * length;
+            out.push(fraction);
+            out
+        })
+    }
+
+    /// intersect against the mesh with every vertex moved, e.g. by the
+    /// ripple displacement applied in the vertex shader
+    ///
+    /// The tree is not rebuilt: nodes are grown by the largest offset on
+    /// each axis and the triangles moved as they are tested. Hit normals are
+    /// the displaced triangles' face normals.
+    ///
+    /// # Arguments
+    /// * `displacements` - Object-space offset per vertex [dx, dy, dz, ...],
+    ///   indexed like the positions the mesh was built from
+    ///
+    /// # Returns
+    /// Same as intersect, or an error if `displacements` is not 3 floats per
+    /// vertex or the model matrix is not 16 floats
+    #[wasm_bindgen]
+    pub fn intersect_displaced(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+        displacements: &[f32],
+    ) -> Result<Option<Vec<f32>>, String> {
+        if displacements.len() != self.vertex_count * 3 {
+            return Err(format!(
+                "displacements need {} floats (3 per vertex), got {}",
+                self.vertex_count * 3,
+                displacements.len()
+            ));
+        }
+        let model = Self::parse_matrix(model_matrix)?;
+        let ray = self.transform_ray(model, model.inverse(), ray_origin, ray_direction);
+
+        let offsets: Vec<Vec3> = displacements
+            .chunks_exact(3)
+            .map(Vec3::from_slice)
+            .collect();
+        let reach = offsets
+            .iter()
+            .fold(Vec3::ZERO, |reach, offset| reach.max(offset.abs()));
+
+        let mut closest: Option<RayHit> = None;
+        self.walk(
+            ray.origin,
+            ray.inv_dir,
+            f32::MAX,
+            reach,
+            |tri_idx, max_t| {
+                if !self.is_enabled(tri_idx) {
+                    return Some(max_t);
+                }
+                let tri = &self.triangles[tri_idx];
+                let [d0, d1, d2] = tri.vertices.map(|vertex| offsets[vertex as usize]);
+                let (v0, v1, v2) = (tri.v0 + d0, tri.v1 + d1, tri.v2 + d2);
+                let moved = Triangle {
+                    v0,
+                    v1,
+                    v2,
+                    normal: (v1 - v0).cross(v2 - v0).normalize_or_zero(),
+                    centroid: (v0 + v1 + v2) / 3.0,
+                    index: tri.index,
+                    vertices: tri.vertices,
+                    vertex_normals: None,
+                };
+                match self.ray_triangle_intersect(ray.origin, ray.direction, &moved, tri_idx) {
+                    Some(hit) if hit.distance < max_t => {
+                        let t = hit.distance;
+                        closest = Some(hit);
+                        Some(t)
+                    }
+                    _ => Some(max_t),
+                }
+            },
+        );
+        Ok(closest.map(|hit| self.hit_to_world(&ray, &hit)))
+    }
+
+    /// Closest hit with the data needed to look up vertex attributes
+    ///
+    /// # Returns
+    /// `Some([px, py, pz, nx, ny, nz, distance, triangle_index, u, v])` or
+    /// `None`. The triangle index is in original input order; u and v weigh
+    /// its second and third vertex (the first weighs 1 - u - v).
+    #[wasm_bindgen]
+    pub fn intersect_detailed(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+    ) -> Option<Vec<f32>> {
+        let ray = self
+            .local_ray(ray_origin, ray_direction, model_matrix)
+            .ok()?;
+
+        self.traverse_bvh(ray.origin, ray.direction, ray.inv_dir)
+            .map(|hit| {
+                let mut out = self.hit_to_world(&ray, &hit);
+                out.extend([self.triangles[hit.triangle].index as f32, hit.u, hit.v]);
+                out
+            })
+    }
+
+    /// Closest hit with the texture coordinate under it, e.g. to pick a texel
+    ///
+    /// # Returns
+    /// `Some([px, py, pz, nx, ny, nz, distance, u, v])` with (u, v)
+    /// interpolated from the triangle's vertex UVs, or the plain intersect
+    /// result if the hit triangle has no UVs (see set_uvs), or `None`
+    #[wasm_bindgen]
+    pub fn intersect_uv(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+    ) -> Option<Vec<f32>> {
+        let ray = self
+            .local_ray(ray_origin, ray_direction, model_matrix)
+            .ok()?;
+
+        self.traverse_bvh(ray.origin, ray.direction, ray.inv_dir)
+            .map(|hit| {
+                let mut out = self.hit_to_world(&ray, &hit);
+                let vertices = self.triangles[hit.triangle].vertices;
+                if let [Some(uv0), Some(uv1), Some(uv2)] =
+                    vertices.map(|v| self.uvs.get(v as usize))
+                {
+                    let uv = *uv0 * (1.0 - hit.u - hit.v) + *uv1 * hit.u + *uv2 * hit.v;
+                    out.extend([uv.x, uv.y]);
+                }
+                out
+            })
+    }
+
+    /// Closest hits for many rays sharing one model matrix (one call per frame)
+    ///
+    /// The matrix is inverted once and no memory is allocated per ray.
+    ///
+    /// # Arguments
+    /// * `origins`, `directions` - Flat [x, y, z, ...] per ray, world space
+    /// * `out` - At least 8 floats per ray, receives
+    ///   [px, py, pz, nx, ny, nz, distance, triangle_index] per ray.
+    ///   Misses have distance = -1 (and triangle_index = -1).
+    ///
+    /// # Returns
+    /// Number of rays that hit
+    #[wasm_bindgen]
+    pub fn intersect_batch(
+        &self,
+        origins: &[f32],
+        directions: &[f32],
+        model_matrix: &[f32],
+        out: &mut [f32],
+    ) -> Result<usize, String> {
+        if origins.len() != directions.len() || !origins.len().is_multiple_of(3) {
+            return Err("origins and directions must hold the same number of xyz triples".into());
+        }
+        let rays = origins.len() / 3;
+        if out.len() < rays * BATCH_STRIDE {
+            return Err(format!(
+                "output holds {} floats, {rays} rays need {}",
+                out.len(),
+                rays * BATCH_STRIDE
+            ));
+        }
+
+        let (model, inverse_model) = Self::model_transforms(model_matrix)?;
+        let mut hits = 0;
+        for ((origin, direction), slot) in origins
+            .chunks_exact(3)
+            .zip(directions.chunks_exact(3))
+            .zip(out.chunks_exact_mut(BATCH_STRIDE))
+        {
+            let ray = self.transform_ray(model, inverse_model, origin, direction);
+            match self.traverse_bvh(ray.origin, ray.direction, ray.inv_dir) {
+                Some(hit) => {
+                    slot[..7].copy_from_slice(&self.hit_to_world_array(&ray, &hit));
+                    slot[7] = self.triangles[hit.triangle].index as f32;
+                    hits += 1;
+                }
+                None => {
+                    slot.fill(0.0);
+                    slot[6] = -1.0;
+                    slot[7] = -1.0;
+                }
+            }
+        }
+        Ok(hits)
+    }
+
+    /// Closest hits for a coherent bundle of 4 rays (e.g. 2x2 pixels)
+    ///
+    /// Boxes are tested for all rays at once (wasm SIMD128 lanes with the
+    /// `simd` feature), triangles one ray at a time. Results are identical to
+    /// four intersect calls.
+    ///
+    /// # Arguments
+    /// * `origins`, `directions` - 12 floats each, [x, y, z] per ray, world space
+    ///
+    /// # Returns
+    /// 4 records of [px, py, pz, nx, ny, nz, distance] back to back; misses
+    /// are zeros with distance = -1
+    #[wasm_bindgen]
+    pub fn intersect_packet4(
+        &self,
+        origins: &[f32],
+        directions: &[f32],
+        model_matrix: &[f32],
+    ) -> Result<Vec<f32>, String> {
+        if origins.len() != PACKET_RAYS * 3 || directions.len() != PACKET_RAYS * 3 {
+            return Err(format!(
+                "a packet needs {} origin and direction floats",
+                PACKET_RAYS * 3
+            ));
+        }
+
+        let (model, inverse_model) = Self::model_transforms(model_matrix)?;
+        let rays: [LocalRay; PACKET_RAYS] = std::array::from_fn(|lane| {
+            let range = lane * 3..lane * 3 + 3;
+            self.transform_ray(
+                model,
+                inverse_model,
+                &origins[range.clone()],
+                &directions[range],
+            )
+        });
+
+        let mut out = vec![0.0; PACKET_RAYS * PACKET_HIT_STRIDE];
+        for ((ray, hit), slot) in rays
+            .iter()
+            .zip(self.traverse_packet4(&rays))
+            .zip(out.chunks_exact_mut(PACKET_HIT_STRIDE))
+        {
+            match hit {
+                Some(hit) => slot.copy_from_slice(&self.hit_to_world_array(ray, &hit)),
+                None => slot[6] = -1.0,
+            }
+        }
+        Ok(out)
+    }
+
+    /// Sweep a sphere along a ray and return its first contact with the mesh
+    ///
+    /// Catches thin geometry a plain ray slips past. Both triangle sides
+    /// count, and a sphere already touching the mesh at the origin reports
+    /// distance 0. Uniform scale in the model matrix is handled; non-uniform
+    /// scale is not supported (the radius is scaled by the ray direction's
+    /// stretch only).
+    ///
+    /// # Arguments
+    /// * `radius` - Sphere radius, world units
+    ///
+    /// # Returns
+    /// `Some([px, py, pz, nx, ny, nz, distance])` with the contact point on the
+    /// triangle, the normal pointing from it to the sphere centre and the
+    /// world distance travelled, or `None`
+    #[wasm_bindgen]
+    pub fn sphere_cast(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        radius: f32,
+        model_matrix: &[f32],
+    ) -> Option<Vec<f32>> {
+        let (model, inverse_model) = Self::model_transforms(model_matrix).ok()?;
+        let ray = self.transform_ray(model, inverse_model, ray_origin, ray_direction);
+        // Object units per world unit along the ray (1 / uniform scale)
+        let stretch = inverse_model
+            .transform_vector3(Vec3::from_slice(ray_direction).normalize())
+            .length();
+        let local_radius = radius.max(0.0) * stretch;
+
+        let mut closest: Option<(f32, Vec3, usize)> = None;
+        self.walk(
+            ray.origin,
+            ray.inv_dir,
+            f32::MAX,
+            Vec3::splat(local_radius),
+            |tri_idx, max_t| {
+                if !self.is_enabled(tri_idx) {
+                    return Some(max_t);
+                }
+                match Self::sweep_sphere_triangle(
+                    ray.origin,
+                    ray.direction,
+                    local_radius,
+                    &self.triangles[tri_idx],
+                ) {
+                    Some((t, contact)) if t < max_t => {
+                        closest = Some((t, contact, tri_idx));
+                        Some(t)
+                    }
+                    _ => Some(max_t),
+                }
+            },
+        );
+
+        let (t, contact, tri_idx) = closest?;
+        let centre = ray.origin + ray.direction * t;
+        let normal = (centre - contact)
+            .try_normalize()
+            .unwrap_or(self.triangles[tri_idx].normal);
+        let world_point = model.transform_point3(contact + self.origin.as_vec3());
+        let world_normal = inverse_model
+            .transpose()
+            .transform_vector3(normal)
+            .normalize_or_zero();
+        Some(vec![
+            world_point.x,
+            world_point.y,
+            world_point.z,
+            world_normal.x,
+            world_normal.y,
+            world_normal.z,
+            t / stretch,
+        ])
+    }
+
+    /// Sweep a capsule along a direction and return its first contact with the
+    /// mesh, e.g. for a rounded cursor volume
+    ///
+    /// BVH nodes are pruned against the swept bounds of the capsule. Both
+    /// triangle sides count. A capsule already overlapping the mesh reports
+    /// distance 0 at its deepest overlap, with the normal pushing it out.
+    /// Scale is handled as in sphere_cast (uniform only for the radius).
+    ///
+    /// # Arguments
+    /// * `p0` - First end of the capsule axis, world space [x, y, z]
+    /// * `p1` - Second end of the capsule axis, world space [x, y, z]
+    /// * `radius` - Capsule radius, world units
+    /// * `direction` - Sweep direction (need not be normalized)
+    /// * `max_distance` - World units of travel; a contact exactly at the
+    ///   limit counts
+    ///
+    /// # Returns
+    /// `Some([px, py, pz, nx, ny, nz, distance])` with the contact point on the
+    /// triangle, the normal pointing from it to the capsule axis and the world
+    /// distance travelled, or `None`
+    #[wasm_bindgen]
+    pub fn capsule_cast(
+        &self,
+        p0: &[f32],
+        p1: &[f32],
+        radius: f32,
+        direction: &[f32],
+        max_distance: f32,
+        model_matrix: &[f32],
+    ) -> Option<Vec<f32>> {
+        let (model, inverse_model) = Self::model_transforms(model_matrix).ok()?;
+        let ray = self.transform_ray(model, inverse_model, p0, direction);
+        let (a, dir) = (ray.origin, ray.direction);
+        let b = inverse_model.transform_point3(Vec3::from_slice(p1)) - self.origin.as_vec3();
+        // Object units per world unit along the sweep (1 / uniform scale)
+        let stretch = inverse_model
+            .transform_vector3(Vec3::from_slice(direction).normalize())
+            .length();
+        let local_radius = radius.max(0.0) * stretch;
+        let max_t = (max_distance * stretch).next_up();
+
+        // The capsule's box swept along the ray, as a ray against grown nodes
+        let centre = (a + b) * 0.5;
+        let half = (b - a).abs() * 0.5 + local_radius;
+
+        // (t, contact, axis point, triangle); at t = 0 the deepest overlap wins
+        let mut closest: Option<(f32, Vec3, Vec3, usize)> = None;
+        self.walk(centre, ray.inv_dir, max_t, half, |tri_idx, max_t| {
+            if !self.is_enabled(tri_idx) {
+                return Some(max_t);
+            }
+            let Some((t, contact, axis)) =
+                Self::sweep_capsule_triangle(a, b, dir, local_radius, &self.triangles[tri_idx])
+            else {
+                return Some(max_t);
+            };
+            let deeper = t == 0.0
+                && closest.is_some_and(|(best_t, best_contact, best_axis, _)| {
+                    best_t == 0.0
+                        && axis.distance_squared(contact) < best_axis.distance_squared(best_contact)
+                });
+            if t < max_t || deeper {
+                closest = Some((t, contact, axis, tri_idx));
+                return Some(t);
+            }
+            Some(max_t)
+        });
+
+        let (t, contact, axis, tri_idx) = closest?;
+        let normal = (axis - contact).try_normalize().unwrap_or_else(|| {
+            // Axis through the face: push towards the side the capsule's
+            // middle is on, or back against the sweep
+            let n = self.triangles[tri_idx].normal;
+            let side = (centre + dir * t - self.triangles[tri_idx].v0).dot(n);
+            if side < 0.0 || (side == 0.0 && dir.dot(n) > 0.0) {
+                -n
+            } else {
+                n
+            }
+        });
+        let world_point = model.transform_point3(contact + self.origin.as_vec3());
+        let world_normal = inverse_model
+            .transpose()
+            .transform_vector3(normal)
+            .normalize_or_zero();
+        Some(vec![
+            world_point.x,
+            world_point.y,
+            world_point.z,
+            world_normal.x,
+            world_normal.y,
+            world_normal.z,
+            t / stretch,
+        ])
+    }
+
+    /// Triangles overlapping a world-space box, e.g. for a paint brush
+    ///
+    /// The box is carried into object space by its 8 corners, so under
+    /// rotation it grows a little; the triangle test against that box is exact
+    /// (separating axes).
+    ///
+    /// # Arguments
+    /// * `min` - World-space box minimum [x, y, z]
+    /// * `max` - World-space box maximum [x, y, z]
+    ///
+    /// # Returns
+    /// Original triangle indices, ascending, without duplicates (none if the
+    /// model matrix is not 16 floats)
+    #[wasm_bindgen]
+    pub fn query_aabb(&self, min: &[f32], max: &[f32], model_matrix: &[f32]) -> Vec<u32> {
+        let Ok((_, inverse_model)) = Self::model_transforms(model_matrix) else {
+            return Vec::new();
+        };
+        let (box_min, box_max) =
+            Self::transform_bounds(inverse_model, Vec3::from_slice(min), Vec3::from_slice(max));
+        let offset = self.origin.as_vec3();
+        let (box_min, box_max) = (box_min - offset, box_max - offset);
+        let (center, half) = ((box_min + box_max) * 0.5, (box_max - box_min) * 0.5);
+
+        let mut found = Vec::new();
+        self.walk_overlapping(
+            |node| node.bounds_min.cmple(box_max).all() && node.bounds_max.cmpge(box_min).all(),
+            |_, triangles| {
+                for &tri_idx in triangles {
+                    let tri = &self.triangles[tri_idx];
+                    if Self::triangle_overlaps_box(tri, center, half) {
+                        found.push(tri.index as u32);
+                    }
+                }
+            },
+        );
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    /// Triangles inside or crossing a frustum, e.g. to limit an effect to
+    /// what the camera sees
+    ///
+    /// Subtrees outside any plane are skipped. Triangles inside every plane
+    /// are accepted directly; ones crossing a plane get an exact separating
+    /// axis test against the frustum.
+    ///
+    /// # Arguments
+    /// * `planes` - 6 world-space planes, 24 floats: [nx, ny, nz, d, ...];
+    ///   inside where n.p + d >= 0 (the three.js Frustum convention)
+    /// * `leaves_only` - Return the IDs of leaves whose bounds touch the
+    ///   frustum instead of testing each triangle (see get_leaf_triangles)
+    ///
+    /// # Returns
+    /// Original triangle indices (or leaf IDs, with u32::MAX for appended
+    /// triangles not compacted yet), ascending. A zero-volume frustum
+    /// returns nothing.
+    #[wasm_bindgen]
+    pub fn query_frustum(
+        &self,
+        planes: &[f32],
+        model_matrix: &[f32],
+        leaves_only: bool,
+    ) -> Result<Vec<u32>, String> {
+        if planes.len() != FRUSTUM_PLANES * PLANE_STRIDE {
+            return Err(format!(
+                "frustum needs {} floats, got {}",
+                FRUSTUM_PLANES * PLANE_STRIDE,
+                planes.len()
+            ));
+        }
+        let (model, _) = Self::model_transforms(model_matrix)?;
+        let Some(frustum) = self.local_frustum(planes, model) else {
+            return Ok(Vec::new());
+        };
+
+        let box_outside = |node: &BVHNode| {
+            let center = (node.bounds_min + node.bounds_max) * 0.5;
+            let half = (node.bounds_max - node.bounds_min) * 0.5;
+            frustum.planes.iter().any(|p| {
+                let n = p.truncate();
+                n.dot(center) + n.abs().dot(half) + p.w < 0.0
+            })
+        };
+
+        let mut found = Vec::new();
+        self.walk_overlapping(
+            |node| !box_outside(node),
+            |leaf, triangles| {
+                if leaves_only {
+                    found.push(leaf.map_or(PENDING_LEAF, |l| l as u32));
+                    return;
+                }
+                for &tri_idx in triangles {
+                    let tri = &self.triangles[tri_idx];
+                    if Self::triangle_in_frustum(tri, &frustum) {
+                        found.push(tri.index as u32);
+                    }
+                }
+            },
+        );
+        found.sort_unstable();
+        found.dedup();
+        Ok(found)
+    }
+
+    /// Original indices of the triangles in a leaf returned by query_frustum
+    ///
+    /// # Returns
+    /// The triangles, or an empty list for an unknown ID or internal node
+    #[wasm_bindgen]
+    pub fn get_leaf_triangles(&self, leaf: u32) -> Vec<u32> {
+        let triangles = if leaf == PENDING_LEAF {
+            &self.pending[..]
+        } else {
+            match self.nodes.get(leaf as usize) {
+                Some(node) => &self.triangle_indices[node.triangle_range()],
+                None => &[],
+            }
+        };
+        triangles
+            .iter()
+            .map(|&t| self.triangles[t].index as u32)
+            .collect()
+    }
+
+    /// True if the segment from `origin` to `target` crosses the mesh at all
+    ///
+    /// Any-hit query for shadow/visibility checks: stops at the first
+    /// triangle found instead of searching for the closest one. Both
+    /// triangle sides block, whatever the cull mode; the end points
+    /// themselves don't count. False if the model matrix is not 16 floats.
+    #[wasm_bindgen]
+    pub fn is_occluded(&self, origin: &[f32], target: &[f32], model_matrix: &[f32]) -> bool {
+        let Ok((_, inverse_model)) = Self::model_transforms(model_matrix) else {
+            return false;
+        };
+        let offset = self.origin.as_vec3();
+        let from = inverse_model.transform_point3(Vec3::from_slice(origin)) - offset;
+        let to = inverse_model.transform_point3(Vec3::from_slice(target)) - offset;
+        let length = from.distance(to);
+        if length <= 0.0 {
+            return false;
+        }
+        let direction = (to - from) / length;
+        let t_epsilon = RELATIVE_T_EPSILON * self.epsilon_scale;
+
+        let mut occluded = false;
+        self.walk(
+            from,
+            direction.recip(),
+            length,
+            Vec3::ZERO,
+            |tri_idx, max_t| {
+                if !self.is_enabled(tri_idx) {
+                    return Some(max_t);
+                }
+                let hit = self.ray_triangle_intersect_culled(
+                    from,
+                    direction,
+                    &self.triangles[tri_idx],
+                    tri_idx,
+                    CULL_NONE,
+                );
+                match hit {
+                    Some(hit) if hit.distance < length - t_epsilon => {
+                        occluded = true;
+                        None
+                    }
+                    _ => Some(max_t),
+                }
+            },
+        );
+        occluded
+    }
+
+    /// True if a point is inside a closed mesh (points on the surface count)
+    ///
+    /// Counts surface crossings along a ray from the point, both triangle
+    /// sides whatever the cull mode: odd is inside. A ray through an edge or
+    /// vertex could count one crossing twice or not at all, so it is cast
+    /// again in another direction, up to 8 directions. False if the model
+    /// matrix is not 16 floats.
+    #[wasm_bindgen]
+    pub fn contains_point(&self, point: &[f32], model_matrix: &[f32]) -> bool {
+        let Ok((_, inverse_model)) = Self::model_transforms(model_matrix) else {
+            return false;
+        };
+        let local = inverse_model.transform_point3(Vec3::from_slice(point)) - self.origin.as_vec3();
+        let t_epsilon = RELATIVE_T_EPSILON * self.epsilon_scale;
+
+        match self.nearest_triangle(local) {
+            None => return false,
+            Some((_, closest, _, _)) if closest.distance(local) <= t_epsilon => return true,
+            _ => {}
+        }
+
+        // Parity is the same in object space, so directions needn't be transformed
+        let mut first = None;
+        for attempt in 0..CONTAINMENT_ATTEMPTS {
+            let direction = Self::containment_direction(attempt);
+            let (mut crossings, mut ambiguous) = (0usize, false);
+            self.walk(
+                local,
+                direction.recip(),
+                f32::INFINITY,
+                Vec3::splat(t_epsilon),
+                |tri_idx, max_t| {
+                    match self.crossing(local, direction, &self.triangles[tri_idx]) {
+                        Crossing::Miss => {}
+                        Crossing::Hit => crossings += 1,
+                        Crossing::Ambiguous => {
+                            crossings += 1;
+                            ambiguous = true;
+                        }
+                    }
+                    Some(max_t)
+                },
+            );
+            let inside = crossings % 2 == 1;
+            if !ambiguous {
+                return inside;
+            }
+            first.get_or_insert(inside);
+        }
+        // Every direction grazed an edge (degenerate mesh); take the first count
+        first.unwrap_or(false)
+    }
+
+    /// Nearest point on the mesh surface to a world-space point (snapping)
+    ///
+    /// Branch-and-bound over the tree: nodes farther than the best triangle
+    /// so far are skipped. Both triangle sides count. Exact under rigid
+    /// transforms and uniform scale; with non-uniform scale the point is the
+    /// nearest in object space.
+    ///
+    /// # Returns
+    /// [px, py, pz, nx, ny, nz, distance, triangle_index] (world space, face or
+    /// smooth normal as for hits, original triangle index), or empty for an
+    /// empty mesh or a model matrix that is not 16 floats
+    #[wasm_bindgen]
+    pub fn closest_point(&self, point: &[f32], model_matrix: &[f32]) -> Vec<f32> {
+        let Ok((model, inverse_model)) = Self::model_transforms(model_matrix) else {
+            return Vec::new();
+        };
+        let world = Vec3::from_slice(point);
+        let local = inverse_model.transform_point3(world) - self.origin.as_vec3();
+
+        let Some((tri_idx, closest, u, v)) = self.nearest_triangle(local) else {
+            return Vec::new();
+        };
+        let tri = &self.triangles[tri_idx];
+        let world_point = model.transform_point3(closest + self.origin.as_vec3());
+        let world_normal = inverse_model
+            .transpose()
+            .transform_vector3(Self::shading_normal(tri, u, v))
+            .normalize_or_zero();
+        vec![
+            world_point.x,
+            world_point.y,
+            world_point.z,
+            world_normal.x,
+            world_normal.y,
+            world_normal.z,
+            world_point.distance(world),
+            tri.index as f32,
+        ]
+    }
+
+    /// Signed distance from a world-space point to the surface: negative
+    /// inside, positive outside, e.g. for proximity fades
+    ///
+    /// The sign comes from the angle-weighted pseudo-normal of the closest
+    /// feature (face, edge or vertex), so it stays right next to edges and
+    /// corners where one face normal would be misleading. Needs a closed mesh
+    /// with consistent outward winding; vertices are matched by position, so
+    /// split (flat-shaded) vertices are fine. Distance as in closest_point.
+    ///
+    /// # Returns
+    /// The signed world distance, 0 on the surface, +inf for an empty mesh
+    /// or a model matrix that is not 16 floats
+    #[wasm_bindgen]
+    pub fn signed_distance(&self, point: &[f32], model_matrix: &[f32]) -> f32 {
+        let Ok((model, inverse_model)) = Self::model_transforms(model_matrix) else {
+            return f32::INFINITY;
+        };
+        let world = Vec3::from_slice(point);
+        let local = inverse_model.transform_point3(world) - self.origin.as_vec3();
+
+        let Some((tri_idx, closest, u, v)) = self.nearest_triangle(local) else {
+            return f32::INFINITY;
+        };
+        let distance = model
+            .transform_point3(closest + self.origin.as_vec3())
+            .distance(world);
+        if (local - closest).dot(self.pseudo_normal(tri_idx, u, v)) < 0.0 {
+            -distance
+        } else {
+            distance
+        }
+    }
+
+    /// The k triangles nearest to a world-space point, e.g. for proximity glows
+    ///
+    /// Best-first over the tree: nodes are opened nearest first until none
+    /// can beat the k-th candidate. Distances as in closest_point.
+    ///
+    /// # Returns
+    /// min(k, triangle count) entries of [triangle_index, distance, px, py, pz]
+    /// (original index, world-space closest point), nearest first; none if
+    /// the model matrix is not 16 floats
+    #[wasm_bindgen]
+    pub fn nearest_triangles(&self, point: &[f32], k: usize, model_matrix: &[f32]) -> Vec<f32> {
+        let Ok((model, inverse_model)) = Self::model_transforms(model_matrix) else {
+            return Vec::new();
+        };
+        let world = Vec3::from_slice(point);
+        let local = inverse_model.transform_point3(world) - self.origin.as_vec3();
+        // k comes from JS; never reserve more than the mesh can fill
+        let k = k.min(self.triangles.len());
+
+        // Squared distances are non-negative, so their bit patterns sort like them
+        let key = |dist_sq: f32| dist_sq.to_bits();
+        // Max-heap of the best k so far: (key, triangle)
+        let mut best: BinaryHeap<(u32, usize)> = BinaryHeap::with_capacity(k + 1);
+        let bound = |best: &BinaryHeap<(u32, usize)>| match best.peek() {
+            Some(&(worst, _)) if best.len() == k => worst,
+            _ => u32::MAX,
+        };
+        let consider = |tri_idx: usize, best: &mut BinaryHeap<(u32, usize)>| {
+            let (closest, _, _) = Self::closest_point_on_triangle(local, &self.triangles[tri_idx]);
+            best.push((key(closest.distance_squared(local)), tri_idx));
+            if best.len() > k {
+                best.pop();
+            }
+        };
+
+        if k > 0 && !self.triangle_indices.is_empty() {
+            let mut open = BinaryHeap::from([Reverse((0u32, 0usize))]);
+            while let Some(Reverse((dist, node_idx))) = open.pop() {
+                if dist >= bound(&best) {
+                    break;
+                }
+                let node = &self.nodes[node_idx];
+                if let Some((left, right)) = node.children(node_idx) {
+                    for child in [left, right] {
+                        let child_dist =
+                            key(Self::point_aabb_distance_sq(local, &self.nodes[child]));
+                        if child_dist < bound(&best) {
+                            open.push(Reverse((child_dist, child)));
+                        }
+                    }
+                } else {
+                    for &tri_idx in &self.triangle_indices[node.triangle_range()] {
+                        consider(tri_idx, &mut best);
+                    }
+                }
+            }
+        }
+        if k > 0
+            && !self.pending.is_empty()
+            && key(Self::point_aabb_distance_sq(local, &self.pending_bounds)) < bound(&best)
+        {
+            for &tri_idx in &self.pending {
+                consider(tri_idx, &mut best);
+            }
+        }
+
+        let mut out = Vec::with_capacity(best.len() * NEAREST_STRIDE);
+        for (_, tri_idx) in best.into_sorted_vec() {
+            let tri = &self.triangles[tri_idx];
+            let (closest, _, _) = Self::closest_point_on_triangle(local, tri);
+            let world_point = model.transform_point3(closest + self.origin.as_vec3());
+            out.extend([
+                tri.index as f32,
+                world_point.distance(world),
+                world_point.x,
+                world_point.y,
+                world_point.z,
+            ]);
+        }
+        out
+    }
+
+    /// Override the length scale intersection epsilons are derived from
+    ///
+    /// Defaults to the mesh's bounding box diagonal; pass 0 (or less) to restore it.
+    #[wasm_bindgen]
+    pub fn set_epsilon_scale(&mut self, scale: f32) {
+        self.epsilon_scale = if scale > 0.0 { scale } else { self.scene_scale };
+    }
+
+    /// Choose which triangle sides rays hit, for every query
+    ///
+    /// # Arguments
+    /// * `mode` - 0 = front faces only (default, matches Three.js FrontSide),
+    ///   1 = both sides (double-sided meshes), 2 = back faces only.
+    ///   Back face hits report the normal flipped to face the ray.
+    #[wasm_bindgen]
+    pub fn set_cull_mode(&mut self, mode: u8) -> Result<(), String> {
+        match mode {
+            CULL_BACK | CULL_NONE | CULL_FRONT => {
+                self.cull_mode = mode;
+                Ok(())
+            }
+            _ => Err(format!("unknown cull mode {mode}")),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn get_cull_mode(&self) -> u8 {
+        self.cull_mode
+    }
+
+    /// Run intersect, intersect_cached and intersect_into in double precision
+    ///
+    /// For meshes or cameras far from the origin, where f32 ray math makes
+    /// hit points jitter. The model matrix is inverted and the ray and
+    /// triangle tests are done in f64 (the f32 vertices are exact in f64, so
+    /// the stored mesh is unchanged); only the result is rounded to f32.
+    /// Off by default, kept across update_geometry, not serialized.
+    #[wasm_bindgen]
+    pub fn set_precision_high(&mut self, enabled: bool) {
+        self.precision_high = enabled;
+    }
+
+    #[wasm_bindgen]
+    pub fn get_precision_high(&self) -> bool {
+        self.precision_high
+    }
+
+    /// Assign user IDs to triangle ranges (for merged geometry picking)
+    ///
+    /// # Arguments
+    /// * `ranges` - Flat triples: [start_triangle, count, user_id, ...] in input triangle order
+    ///
+    /// Overlapping ranges are rejected and leave the previous mapping untouched.
+    #[wasm_bindgen]
+    pub fn set_id_ranges(&mut self, ranges: &[u32]) -> Result<(), String> {
+        if self.disposed {
+            return Err("raycaster is disposed".into());
+        }
+        if !ranges.len().is_multiple_of(3) {
+            return Err("id ranges must be (start, count, id) triples".into());
+        }
+
+        let mut parsed: Vec<(u32, u32, u32)> = ranges
+            .chunks(3)
+            .filter(|c| c[1] > 0)
+            .map(|c| (c[0], c[1], c[2]))
+            .collect();
+        parsed.sort_by_key(|r| r.0);
+
+        for pair in parsed.windows(2) {
+            if pair[0].0 as u64 + pair[0].1 as u64 > pair[1].0 as u64 {
+                return Err(format!(
+                    "id range starting at triangle {} overlaps range starting at {}",
+                    pair[1].0, pair[0].0
+                ));
+            }
+        }
+
+        self.id_ranges = parsed;
+        Ok(())
+    }
+
+    /// Closest hit plus the resolved user ID
+    ///
+    /// # Returns
+    /// `Some([px, py, pz, nx, ny, nz, distance, user_id])` or `None`.
+    /// Unmapped triangles report u32::MAX; IDs above 2^24 lose precision as f32,
+    /// use `query_id_under_ray` when exact IDs are needed.
+    #[wasm_bindgen]
+    pub fn intersect_with_id(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+    ) -> Option<Vec<f32>> {
+        let ray = self
+            .local_ray(ray_origin, ray_direction, model_matrix)
+            .ok()?;
+
+        self.traverse_bvh(ray.origin, ray.direction, ray.inv_dir)
+            .map(|hit| {
+                let mut out = self.hit_to_world(&ray, &hit);
+                out.push(self.resolve_id(self.triangles[hit.triangle].index) as f32);
+                out
+            })
+    }
+
+    /// Assign a group (submesh / material) ID to every input triangle
+    ///
+    /// Unlike set_id_ranges this is one ID per triangle, and groups can be
+    /// filtered on with intersect_filtered. update_geometry drops them;
+    /// triangles appended later have no group.
+    ///
+    /// # Arguments
+    /// * `groups` - One ID per triangle of the index buffer, in input order;
+    ///   empty removes them
+    #[wasm_bindgen]
+    pub fn set_groups(&mut self, groups: &[u32]) -> Result<(), String> {
+        if self.disposed {
+            return Err("raycaster is disposed".into());
+        }
+        if !groups.is_empty() && groups.len() != self.input_triangle_count {
+            return Err(format!(
+                "expected {} group IDs, got {}",
+                self.input_triangle_count,
+                groups.len()
+            ));
+        }
+        self.groups = groups.to_vec();
+        Ok(())
+    }
+
+    /// Exclude triangles from ray queries without rebuilding the tree, e.g.
+    /// parts animating away
+    ///
+    /// Masked triangles are transparent to intersect and its variants,
+    /// intersect_all, intersect_n, intersect_packet4, is_occluded,
+    /// sphere_cast and capsule_cast. Containment and distance queries still
+    /// see the whole mesh. update_geometry drops the mask; triangles appended
+    /// later are enabled. Not serialized.
+    ///
+    /// # Arguments
+    /// * `mask` - One byte per triangle of the index buffer, in input order:
+    ///   0 = skip, anything else = hit
+    #[wasm_bindgen]
+    pub fn set_triangle_mask(&mut self, mask: &[u8]) -> Result<(), String> {
+        if self.disposed {
+            return Err("raycaster is disposed".into());
+        }
+        if mask.len() != self.input_triangle_count {
+            return Err(format!(
+                "expected {} mask bytes, got {}",
+                self.input_triangle_count,
+                mask.len()
+            ));
+        }
+        self.triangle_mask = mask.to_vec();
+        Ok(())
+    }
+
+    /// Make every triangle hittable again (see set_triangle_mask)
+    #[wasm_bindgen]
+    pub fn clear_triangle_mask(&mut self) {
+        self.triangle_mask = Vec::new();
+    }
+
+    /// Closest hit plus the group ID of the hit triangle
+    ///
+    /// # Returns
+    /// `Some([px, py, pz, nx, ny, nz, distance, group])` or `None`. Triangles
+    /// without a group report u32::MAX (as f32, like intersect_with_id).
+    #[wasm_bindgen]
+    pub fn intersect_with_group(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+    ) -> Option<Vec<f32>> {
+        self.intersect_filtered(ray_origin, ray_direction, model_matrix, None)
+    }
+
+    /// Closest hit among the triangles whose group is in `group_mask`
+    ///
+    /// Other triangles are transparent to the ray, e.g. to pick only the
+    /// interactive parts of a merged mesh.
+    ///
+    /// # Arguments
+    /// * `group_mask` - Group IDs to hit (u32::MAX matches triangles
+    ///   without a group); `None` hits every group
+    ///
+    /// # Returns
+    /// Same as intersect_with_group
+    #[wasm_bindgen]
+    pub fn intersect_filtered(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+        group_mask: Option<Vec<u32>>,
+    ) -> Option<Vec<f32>> {
+        let ray = self
+            .local_ray(ray_origin, ray_direction, model_matrix)
+            .ok()?;
+        let group = |tri_idx: usize| {
+            self.groups
+                .get(self.triangles[tri_idx].index)
+                .copied()
+                .unwrap_or(u32::MAX)
+        };
+
+        let hit = match &group_mask {
+            Some(mask) => self.traverse_bvh_where(
+                ray.origin,
+                ray.direction,
+                ray.inv_dir,
+                f32::MAX,
+                |tri_idx| mask.contains(&group(tri_idx)),
+            ),
+            None => self.traverse_bvh(ray.origin, ray.direction, ray.inv_dir),
+        };
+        hit.map(|hit| {
+            let mut out = self.hit_to_world(&ray, &hit);
+            out.push(group(hit.triangle) as f32);
+            out
+        })
+    }
+
+    /// User ID under the ray without building the hit vector
+    ///
+    /// # Returns
+    /// The user ID, u32::MAX if the hit triangle is unmapped, or -1 on miss
+    /// (or if the model matrix is not 16 floats)
+    #[wasm_bindgen]
+    pub fn query_id_under_ray(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+    ) -> i64 {
+        let Ok(ray) = self.local_ray(ray_origin, ray_direction, model_matrix) else {
+            return -1;
+        };
+
+        self.traverse_bvh(ray.origin, ray.direction, ray.inv_dir)
+            .map_or(-1, |hit| {
+                self.resolve_id(self.triangles[hit.triangle].index) as i64
+            })
+    }
+
+    /// Every hit along the ray, nearest first (transparency, slicing)
+    ///
+    /// Sides are culled per set_cull_mode. A ray crossing an edge or vertex
+    /// shared by several triangles reports it once.
+    ///
+    /// # Arguments
+    /// * `max_hits` - Keep at most this many (nearest) hits; 0 = no limit
+    ///
+    /// # Returns
+    /// 8 floats per hit: [px, py, pz, nx, ny, nz, distance, triangle_index],
+    /// with the triangle index in original input order; none if the model
+    /// matrix is not 16 floats
+    #[wasm_bindgen]
+    pub fn intersect_all(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+        max_hits: usize,
+    ) -> Vec<f32> {
+        let Ok(ray) = self.local_ray(ray_origin, ray_direction, model_matrix) else {
+            return Vec::new();
+        };
+
+        let mut hits = Vec::new();
+        self.walk(
+            ray.origin,
+            ray.inv_dir,
+            f32::INFINITY,
+            Vec3::ZERO,
+            |tri_idx, max_t| {
+                if self.is_enabled(tri_idx) {
+                    hits.extend(self.ray_triangle_intersect(
+                        ray.origin,
+                        ray.direction,
+                        &self.triangles[tri_idx],
+                        tri_idx,
+                    ));
+                }
+                Some(max_t)
+            },
+        );
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+        let duplicate_epsilon = RELATIVE_DUPLICATE_HIT_EPSILON * self.epsilon_scale;
+        let mut last_distance = f32::NEG_INFINITY;
+        let mut out = Vec::new();
+        for hit in &hits {
+            if hit.distance - last_distance <= duplicate_epsilon {
+                continue;
+            }
+            if max_hits > 0 && out.len() / ALL_HITS_STRIDE >= max_hits {
+                break;
+            }
+            last_distance = hit.distance;
+            out.extend(self.hit_to_world(&ray, hit));
+            out.push(self.triangles[hit.triangle].index as f32);
+        }
+        out
+    }
+
+    /// The n nearest hits along the ray
+    ///
+    /// Like intersect_all with max_hits = n, but subtrees entered beyond the
+    /// current nth hit are skipped instead of collecting every hit first.
+    ///
+    /// # Returns
+    /// n records of 8 floats as intersect_all, nearest first; records past
+    /// the last hit are zeros with distance and triangle index -1 (all of
+    /// them if the model matrix is not 16 floats)
+    #[wasm_bindgen]
+    pub fn intersect_n(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+        n: usize,
+    ) -> Vec<f32> {
+        let Ok(ray) = self.local_ray(ray_origin, ray_direction, model_matrix) else {
+            return [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0, -1.0].repeat(n);
+        };
+        let duplicate_epsilon = RELATIVE_DUPLICATE_HIT_EPSILON * self.epsilon_scale;
+
+        // Sorted by distance, at most n long
+        let mut hits: Vec<RayHit> = Vec::with_capacity(n);
+        if n > 0 {
+            self.walk(
+                ray.origin,
+                ray.inv_dir,
+                f32::INFINITY,
+                Vec3::ZERO,
+                |tri_idx, max_t| {
+                    let Some(hit) = self
+                        .is_enabled(tri_idx)
+                        .then(|| {
+                            self.ray_triangle_intersect(
+                                ray.origin,
+                                ray.direction,
+                                &self.triangles[tri_idx],
+                                tri_idx,
+                            )
+                        })
+                        .flatten()
+                    else {
+                        return Some(max_t);
+                    };
+                    let at = hits.partition_point(|h| h.distance < hit.distance);
+                    let duplicate = [at.checked_sub(1), Some(at)].into_iter().any(|i| {
+                        i.and_then(|i| hits.get(i))
+                            .is_some_and(|h| (h.distance - hit.distance).abs() <= duplicate_epsilon)
+                    });
+                    if duplicate || at == n {
+                        return Some(max_t);
+                    }
+                    hits.truncate(n - 1);
+                    hits.insert(at, hit);
+                    // Once full, only nodes entered before the nth hit matter
+                    Some(if hits.len() == n {
+                        hits[n - 1].distance
+                    } else {
+                        max_t
+                    })
+                },
+            );
+        }
+
+        let mut out = Vec::with_capacity(n * ALL_HITS_STRIDE);
+        for hit in &hits {
+            out.extend(self.hit_to_world(&ray, hit));
+            out.push(self.triangles[hit.triangle].index as f32);
+        }
+        while out.len() < n * ALL_HITS_STRIDE {
+            out.extend([0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0, -1.0]);
+        }
+        out
+    }
+
+    // --- Streaming ---
+
+    /// Add a chunk of triangles without rebuilding the tree
+    ///
+    /// The triangles are tested linearly (alongside the tree) until compact()
+    /// merges them in, so picking works while a mesh streams in. If the
+    /// unsorted set grows past 4096 triangles, it is compacted right away.
+    ///
+    /// # Arguments
+    /// * `positions` - The chunk's vertices, object space
+    /// * `indices` - Triangle indices into the whole vertex buffer; vertex
+    ///   `base_vertex` is positions[0..3]. Triangles referencing vertices outside
+    ///   the chunk are skipped. Cleanup flags from the build apply, with
+    ///   duplicates only detected within the chunk.
+    /// * `base_vertex` - Index of the chunk's first vertex
+    ///
+    /// # Returns
+    /// [first_triangle, triangle_count] in input triangle order (for set_id_ranges)
+    #[wasm_bindgen]
+    pub fn append_geometry(
+        &mut self,
+        positions: &[f32],
+        indices: &[u32],
+        base_vertex: u32,
+    ) -> Result<Vec<u32>, String> {
+        if self.disposed {
+            return Err("raycaster is disposed".into());
+        }
+
+        // Rebase into chunk-local vertices (out-of-chunk indices become out of range)
+        let local: Vec<u32> = indices
+            .iter()
+            .map(|&i| i.checked_sub(base_vertex).unwrap_or(u32::MAX))
+            .collect();
+        let rebased: Vec<f32>;
+        let positions = if self.origin == DVec3::ZERO {
+            positions
+        } else {
+            rebased = positions
+                .chunks_exact(3)
+                .flat_map(|p| {
+                    (Vec3::from_slice(p).as_dvec3() - self.origin)
+                        .as_vec3()
+                        .to_array()
+                })
+                .collect();
+            &rebased
+        };
+
+        let first_triangle = self.input_triangle_count;
+        let (mut added, (degenerate, out_of_range)) =
+            Self::build_triangles(VertexPositions::flat(positions), Self::faces(&local));
+        self.skipped_degenerate += degenerate;
+        self.skipped_out_of_range += out_of_range;
+        let (dropped, merged) = Self::clean_triangles(&mut added, self.cleanup, self.scene_scale);
+        self.dropped_degenerate += dropped;
+        self.merged_duplicates += merged;
+        for tri in &mut added {
+            tri.index += first_triangle;
+            for v in &mut tri.vertices {
+                *v += base_vertex;
+            }
+        }
+
+        let start = self.triangles.len();
+        let (min, max) = Self::compute_bounds(&added, &(0..added.len()).collect::<Vec<_>>());
+        if !added.is_empty() {
+            if self.pending.is_empty() {
+                self.pending_bounds.bounds_min = min;
+                self.pending_bounds.bounds_max = max;
+            } else {
+                self.pending_bounds.bounds_min = self.pending_bounds.bounds_min.min(min);
+                self.pending_bounds.bounds_max = self.pending_bounds.bounds_max.max(max);
+            }
+        }
+        self.triangles.extend(added);
+        self.pending.extend(start..self.triangles.len());
+        self.input_triangle_count += indices.len() / 3;
+        self.vertex_count = self
+            .vertex_count
+            .max(base_vertex as usize + positions.len() / 3);
+        self.resize_profiling();
+        if self.pending.len() > MAX_PENDING_TRIANGLES {
+            self.compact();
+        }
+
+        Ok(vec![first_triangle as u32, (indices.len() / 3) as u32])
+    }
+
+    /// Merge appended triangles into the tree (call when idle)
+    ///
+    /// Small appended sets are built into their own subtree beside the existing
+    /// root; sets that are large relative to the tree (or an empty tree)
+    /// trigger a full rebuild.
+    #[wasm_bindgen]
+    pub fn compact(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let pending = std::mem::take(&mut self.pending);
+        self.pending_bounds = Self::empty_node();
+
+        let tree_triangles = self.triangle_indices.len();
+        let full = tree_triangles == 0
+            || pending.len() as f32 >= tree_triangles as f32 * FULL_REBUILD_FRACTION;
+
+        if full {
+            self.triangle_indices = (0..self.triangles.len()).collect();
+            self.nodes = Vec::with_capacity(self.triangles.len() * 2);
+            Self::build_bvh(
+                &self.triangles,
+                &mut self.triangle_indices,
+                0,
+                self.triangles.len(),
+                &mut self.nodes,
+                self.sah_bins,
+            );
+        } else {
+            // Subtree over the new triangles, then a new root over (old root, subtree)
+            let start = self.triangle_indices.len();
+            self.triangle_indices.extend(&pending);
+            let end = self.triangle_indices.len();
+            let subtree = Self::build_bvh(
+                &self.triangles,
+                &mut self.triangle_indices,
+                start,
+                end,
+                &mut self.nodes,
+                self.sah_bins,
+            );
+
+            // Insert the new root in front, so the old root is its adjacent
+            // left child; every stored child index moves up by one
+            let (old_root, new) = (self.nodes[0], self.nodes[subtree]);
+            for node in &mut self.nodes {
+                if !node.is_leaf() {
+                    node.offset += 1;
+                }
+            }
+            self.nodes.insert(
+                0,
+                BVHNode {
+                    bounds_min: old_root.bounds_min.min(new.bounds_min),
+                    bounds_max: old_root.bounds_max.max(new.bounds_max),
+                    offset: subtree as u32 + 1,
+                    triangle_count: 0,
+                },
+            );
+        }
+
+        // Follow the new bounds unless the epsilon scale was overridden
+        let auto = self.epsilon_scale == self.scene_scale;
+        self.scene_scale = Self::auto_epsilon_scale(&self.nodes[0]);
+        if auto {
+            self.epsilon_scale = self.scene_scale;
+        }
+        self.resize_profiling();
+    }
+
+    /// Replace the mesh, rebuilding the tree in place
+    ///
+    /// Reuses the existing buffers instead of allocating a new raycaster. Cull
+    /// mode, build settings and profiling stay; ID ranges, vertex normals,
+    /// appended triangles and the floating origin are reset with the old mesh.
+    /// Empty geometry leaves an empty raycaster that never hits.
+    ///
+    /// # Arguments
+    /// * `positions` - Flat vertex positions [x, y, z, ...]
+    /// * `indices` - Triangle indices
+    #[wasm_bindgen]
+    pub fn update_geometry(&mut self, positions: &[f32], indices: &[u32]) {
+        self.triangles.clear();
+        (self.skipped_degenerate, self.skipped_out_of_range) = Self::fill_triangles(
+            &mut self.triangles,
+            VertexPositions::flat(positions),
+            Self::faces(indices),
+        );
+        let all: Vec<usize> = (0..self.triangles.len()).collect();
+        let (min, max) = Self::compute_bounds(&self.triangles, &all);
+        (self.dropped_degenerate, self.merged_duplicates) =
+            Self::clean_triangles(&mut self.triangles, self.cleanup, (max - min).length());
+
+        let num_triangles = self.triangles.len();
+        self.triangle_indices.clear();
+        self.triangle_indices.extend(0..num_triangles);
+        self.nodes.clear();
+        if num_triangles > 0 {
+            Self::build_bvh(
+                &self.triangles,
+                &mut self.triangle_indices,
+                0,
+                num_triangles,
+                &mut self.nodes,
+                self.sah_bins,
+            );
+        } else {
+            self.nodes.push(Self::empty_node());
+        }
+
+        self.id_ranges.clear();
+        self.groups.clear();
+        self.triangle_mask.clear();
+        self.input_triangle_count = indices.len() / 3;
+        self.vertex_count = positions.len() / 3;
+        self.uvs.clear();
+        self.origin = DVec3::ZERO;
+        self.pending.clear();
+        self.pending_bounds = Self::empty_node();
+        self.disposed = false;
+
+        // Follow the new bounds unless the epsilon scale was overridden
+        let auto = self.epsilon_scale == self.scene_scale;
+        self.scene_scale = Self::auto_epsilon_scale(&self.nodes[0]);
+        if auto {
+            self.epsilon_scale = self.scene_scale;
+        }
+        // Counters are per node and triangle, so old counts are meaningless
+        self.enable_profiling(self.profiling);
+    }
+
+    /// Serialize the built tree, so it can be baked offline and loaded
+    /// with from_bytes instead of rebuilt
+    ///
+    /// The data is self-contained (triangles included) and also keeps ID
+    /// ranges, groups, vertex normals, UVs, appended triangles and all
+    /// settings except profiling and the set_model_matrix matrix. A disposed
+    /// raycaster serializes as empty.
+    ///
+    /// Layout (little endian): `"CJBV"`, version (u8), cleanup, cull mode and
+    /// flag bytes, then settings, counts and the triangle, node, index,
+    /// appended-triangle and ID range arrays.
+    #[wasm_bindgen]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let has_normals = self.triangles.iter().any(|t| t.vertex_normals.is_some());
+        let auto_epsilon = self.epsilon_scale == self.scene_scale;
+
+        let mut out = Vec::new();
+        out.extend_from_slice(BVH_MAGIC);
+        out.push(BVH_FORMAT_VERSION);
+        out.push(self.cleanup);
+        out.push(self.cull_mode);
+        out.push(if has_normals {
+            BVH_HAS_VERTEX_NORMALS
+        } else {
+            0
+        });
+        for value in [
+            self.sah_bins,
+            self.input_triangle_count as u32,
+            self.vertex_count as u32,
+            self.dropped_degenerate as u32,
+            self.merged_duplicates as u32,
+            self.skipped_degenerate as u32,
+            self.skipped_out_of_range as u32,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        // 0 = automatic epsilon scale
+        let epsilon_scale = if auto_epsilon {
+            0.0
+        } else {
+            self.epsilon_scale
+        };
+        out.extend_from_slice(&epsilon_scale.to_le_bytes());
+        for axis in self.origin.to_array() {
+            out.extend_from_slice(&axis.to_le_bytes());
+        }
+        for count in [
+            self.triangles.len(),
+            self.nodes.len(),
+            self.triangle_indices.len(),
+            self.pending.len(),
+            self.id_ranges.len(),
+            self.uvs.len(),
+            self.groups.len(),
+        ] {
+            out.extend_from_slice(&(count as u32).to_le_bytes());
+        }
+
+        let put_vec3 = |out: &mut Vec<u8>, v: Vec3| {
+            for axis in v.to_array() {
+                out.extend_from_slice(&axis.to_le_bytes());
+            }
+        };
+        for tri in &self.triangles {
+            for v in [tri.v0, tri.v1, tri.v2] {
+                put_vec3(&mut out, v);
+            }
+            out.extend_from_slice(&(tri.index as u32).to_le_bytes());
+            for v in tri.vertices {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            if has_normals {
+                // Flat triangles store zero normals
+                for n in tri.vertex_normals.unwrap_or([Vec3::ZERO; 3]) {
+                    put_vec3(&mut out, n);
+                }
+            }
+        }
+        for node in &self.nodes {
+            put_vec3(&mut out, node.bounds_min);
+            put_vec3(&mut out, node.bounds_max);
+            out.extend_from_slice(&node.offset.to_le_bytes());
+            out.extend_from_slice(&node.triangle_count.to_le_bytes());
+        }
+        for &index in self.triangle_indices.iter().chain(&self.pending) {
+            out.extend_from_slice(&(index as u32).to_le_bytes());
+        }
+        for &(start, count, id) in &self.id_ranges {
+            for value in [start, count, id] {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        for uv in &self.uvs {
+            out.extend_from_slice(&uv.x.to_le_bytes());
+            out.extend_from_slice(&uv.y.to_le_bytes());
+        }
+        for &group in &self.groups {
+            out.extend_from_slice(&group.to_le_bytes());
+        }
+        out
+    }
+
+    /// Load a tree written by to_bytes
+    ///
+    /// Every index in the data is checked, so corrupted or truncated input
+    /// returns an error instead of trapping later.
+    ///
+    /// # Returns
+    /// The raycaster, or an error for foreign, newer or corrupted data
+    #[wasm_bindgen]
+    pub fn from_bytes(bytes: &[u8]) -> Result<BVHRaycaster, String> {
+        let mut data = ByteReader { bytes, pos: 0 };
+        if data.take(4)? != BVH_MAGIC {
+            return Err("not a serialized BVH (bad magic)".into());
+        }
+        let version = data.u8()?;
+        if version != BVH_FORMAT_VERSION {
+            return Err(format!("unsupported BVH format version {version}"));
+        }
+        let cleanup = data.u8()?;
+        let cull_mode = data.u8()?;
+        if cull_mode > CULL_FRONT {
+            return Err(format!("invalid cull mode {cull_mode}"));
+        }
+        let has_normals = data.u8()? & BVH_HAS_VERTEX_NORMALS != 0;
+        let sah_bins = data.u32()?;
+        if sah_bins != 0 && !(MIN_SAH_BINS..=MAX_SAH_BINS).contains(&sah_bins) {
+            return Err(format!("invalid SAH bin count {sah_bins}"));
+        }
+        let input_triangle_count = data.u32()? as usize;
+        let vertex_count = data.u32()? as usize;
+        let dropped_degenerate = data.u32()? as usize;
+        let merged_duplicates = data.u32()? as usize;
+        let skipped_degenerate = data.u32()? as usize;
+        let skipped_out_of_range = data.u32()? as usize;
+        let epsilon_scale = data.f32()?;
+        let origin = DVec3::new(data.f64()?, data.f64()?, data.f64()?);
+        let triangle_count = data.u32()? as usize;
+        let node_count = data.u32()? as usize;
+        let index_count = data.u32()? as usize;
+        let pending_count = data.u32()? as usize;
+        let range_count = data.u32()? as usize;
+        let uv_count = data.u32()? as usize;
+        if uv_count > vertex_count {
+            return Err(format!("{uv_count} UVs for {vertex_count} vertices"));
+        }
+        let group_count = data.u32()? as usize;
+        if group_count > input_triangle_count {
+            return Err(format!(
+                "{group_count} groups for {input_triangle_count} triangles"
+            ));
+        }
+
+        // Reject impossible counts before allocating for them
+        let triangle_size = 52 + if has_normals { 36 } else { 0 };
+        let needed = [
+            (triangle_count, triangle_size),
+            (node_count, 32),
+            (index_count + pending_count, 4),
+            (range_count, 12),
+            (uv_count, 8),
+            (group_count, 4),
+        ]
+        .iter()
+        .try_fold(0usize, |sum, &(count, size)| {
+            count.checked_mul(size).and_then(|n| n.checked_add(sum))
+        });
+        if needed != Some(data.remaining()) {
+            return Err("serialized BVH size does not match its counts".into());
+        }
+
+        let mut triangles = Vec::with_capacity(triangle_count);
+        for _ in 0..triangle_count {
+            let (v0, v1, v2) = (data.vec3()?, data.vec3()?, data.vec3()?);
+            let index = data.u32()? as usize;
+            let vertices = [data.u32()?, data.u32()?, data.u32()?];
+            if index >= input_triangle_count || vertices.iter().any(|&v| v as usize >= vertex_count)
+            {
+                return Err(format!("triangle {} is out of range", triangles.len()));
+            }
+            let vertex_normals = if has_normals {
+                let normals = [data.vec3()?, data.vec3()?, data.vec3()?];
+                (normals != [Vec3::ZERO; 3]).then_some(normals)
+            } else {
+                None
+            };
+            triangles.push(Triangle {
+                v0,
+                v1,
+                v2,
+                normal: (v1 - v0).cross(v2 - v0).normalize_or_zero(),
+                centroid: (v0 + v1 + v2) / 3.0,
+                index,
+                vertices,
+                vertex_normals,
+            });
+        }
+
+        let mut nodes = Vec::with_capacity(node_count);
+        for _ in 0..node_count {
+            nodes.push(BVHNode {
+                bounds_min: data.vec3()?,
+                bounds_max: data.vec3()?,
+                offset: data.u32()?,
+                triangle_count: data.u32()?,
+            });
+        }
+
+        let mut read_triangle_indices = |count: usize| -> Result<Vec<usize>, String> {
+            (0..count)
+                .map(|_| match data.u32()? as usize {
+                    i if i < triangle_count => Ok(i),
+                    i => Err(format!("triangle index {i} is out of range")),
+                })
+                .collect()
+        };
+        let triangle_indices = read_triangle_indices(index_count)?;
+        let pending = read_triangle_indices(pending_count)?;
+        Self::validate_tree(&nodes, triangle_indices.len())?;
+
+        let mut ranges = Vec::with_capacity(range_count * 3);
+        for _ in 0..range_count * 3 {
+            ranges.push(data.u32()?);
+        }
+        let mut uvs = Vec::with_capacity(uv_count);
+        for _ in 0..uv_count {
+            uvs.push(Vec2::new(data.f32()?, data.f32()?));
+        }
+        let groups = (0..group_count)
+            .map(|_| data.u32())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let scene_scale = Self::auto_epsilon_scale(&nodes[0]);
+        let mut raycaster = Self {
+            nodes,
+            triangles,
+            triangle_indices,
+            id_ranges: Vec::new(),
+            groups,
+            triangle_mask: Vec::new(),
+            input_triangle_count,
+            vertex_count,
+            uvs,
+            profiling: false,
+            node_visit_counts: Vec::new(),
+            triangle_test_counts: Vec::new(),
+            scene_scale,
+            epsilon_scale: if epsilon_scale > 0.0 {
+                epsilon_scale
+            } else {
+                scene_scale
+            },
+            cull_mode,
+            precision_high: false,
+            traversal_stack: RefCell::new(Vec::with_capacity(64)),
+            model: Mat4::IDENTITY,
+            inverse_model: Mat4::IDENTITY,
+            hit_buffer: Self::MISS_BUFFER,
+            disposed: false,
+            sah_bins,
+            origin,
+            pending: Vec::new(),
+            pending_bounds: Self::empty_node(),
+            cleanup,
+            dropped_degenerate,
+            merged_duplicates,
+            skipped_degenerate,
+            skipped_out_of_range,
+        };
+        if !pending.is_empty() {
+            let (min, max) = Self::compute_bounds(&raycaster.triangles, &pending);
+            raycaster.pending_bounds.bounds_min = min;
+            raycaster.pending_bounds.bounds_max = max;
+            raycaster.pending = pending;
+        }
+        raycaster.set_id_ranges(&ranges)?;
+        Ok(raycaster)
+    }
+
+    /// Per-vertex normals for smooth-shaded hits
+    ///
+    /// Hit normals become the barycentric interpolation of the triangle's
+    /// vertex normals (flipped for back face hits) instead of the flat face
+    /// normal. refit() keeps these; pass the deformed normals again if needed.
+    ///
+    /// # Arguments
+    /// * `normals` - Same layout and vertex count as the positions; empty
+    ///   restores flat face normals
+    #[wasm_bindgen]
+    pub fn set_vertex_normals(&mut self, normals: &[f32]) -> Result<(), String> {
+        if self.disposed {
+            return Err("raycaster is disposed".into());
+        }
+        if normals.is_empty() {
+            for tri in &mut self.triangles {
+                tri.vertex_normals = None;
+            }
+            return Ok(());
+        }
+        if normals.len() != self.vertex_count * 3 {
+            return Err(format!(
+                "expected normals for {} vertices, got {} floats",
+                self.vertex_count,
+                normals.len()
+            ));
+        }
+
+        let normal = |v: u32| Vec3::from_slice(&normals[v as usize * 3..]);
+        for tri in &mut self.triangles {
+            tri.vertex_normals = Some(tri.vertices.map(normal));
+        }
+        Ok(())
+    }
+
+    /// Per-vertex texture coordinates for intersect_uv
+    ///
+    /// Indexed like the positions. Rebuilding with update_geometry drops
+    /// them; triangles appended later have none until this is called again.
+    ///
+    /// # Arguments
+    /// * `uvs` - Flat [u0, v0, u1, v1, ...], one pair per vertex; empty
+    ///   removes them
+    #[wasm_bindgen]
+    pub fn set_uvs(&mut self, uvs: &[f32]) -> Result<(), String> {
+        if self.disposed {
+            return Err("raycaster is disposed".into());
+        }
+        if !uvs.is_empty() && uvs.len() != self.vertex_count * 2 {
+            return Err(format!(
+                "expected UVs for {} vertices, got {} floats",
+                self.vertex_count,
+                uvs.len()
+            ));
+        }
+        self.uvs = uvs.chunks_exact(2).map(Vec2::from_slice).collect();
+        Ok(())
+    }
+
+    // --- Deformation ---
+
+    /// Move the vertices and update node bounds, keeping the tree topology
+    ///
+    /// For meshes deformed every frame (e.g. by ripple displacement): much
+    /// cheaper than a rebuild, but queries slow down if vertices travel far
+    /// from where the tree was built. Rebuild after large deformations.
+    ///
+    /// # Arguments
+    /// * `positions` - The whole position buffer, same vertex count as the
+    ///   geometry the tree was built from (plus appended chunks)
+    #[wasm_bindgen]
+    pub fn refit(&mut self, positions: &[f32]) -> Result<(), String> {
+        if self.disposed {
+            return Err("raycaster is disposed".into());
+        }
+        if positions.len() != self.vertex_count * 3 {
+            return Err(format!(
+                "refit needs {} vertices, got {} floats",
+                self.vertex_count,
+                positions.len()
+            ));
+        }
+
+        let origin = self.origin.as_vec3();
+        for tri in &mut self.triangles {
+            Self::move_triangle(tri, positions, origin);
+        }
+
+        if !self.triangle_indices.is_empty() {
+            self.refit_node(0);
+        }
+        if !self.pending.is_empty() {
+            let (min, max) = Self::compute_bounds(&self.triangles, &self.pending);
+            self.pending_bounds.bounds_min = min;
+            self.pending_bounds.bounds_max = max;
+        }
+        Ok(())
+    }
+
+    /// Move some triangles and rebuild only the part of the tree they span
+    ///
+    /// For local edits (sculpting strokes): the smallest subtree holding all
+    /// the changed triangles is rebuilt in place and the bounds above it are
+    /// refit, so unlike refit() the tree quality holds up over many edits.
+    /// Edits spread across the whole mesh end up rebuilding from the root.
+    /// Appended triangles not merged yet are moved but stay unsorted.
+    ///
+    /// # Arguments
+    /// * `changed_triangle_indices` - Triangles whose vertices moved, in
+    ///   input triangle order (triangles skipped at build time are ignored)
+    /// * `positions` - The whole position buffer, as for refit
+    #[wasm_bindgen]
+    pub fn update_region(
+        &mut self,
+        changed_triangle_indices: &[u32],
+        positions: &[f32],
+    ) -> Result<(), String> {
+        if self.disposed {
+            return Err("raycaster is disposed".into());
+        }
+        if positions.len() != self.vertex_count * 3 {
+            return Err(format!(
+                "update_region needs {} vertices, got {} floats",
+                self.vertex_count,
+                positions.len()
+            ));
+        }
+        if let Some(&index) = changed_triangle_indices
+            .iter()
+            .find(|&&index| index as usize >= self.input_triangle_count)
+        {
+            return Err(format!("triangle {index} is out of range"));
+        }
+
+        let origin = self.origin.as_vec3();
+        let mut changed = vec![false; self.triangles.len()];
+        for &index in changed_triangle_indices {
+            if let Ok(slot) = self
+                .triangles
+                .binary_search_by_key(&(index as usize), |tri| tri.index)
+            {
+                Self::move_triangle(&mut self.triangles[slot], positions, origin);
+                changed[slot] = true;
+            }
+        }
+
+        if !self.pending.is_empty() {
+            let (min, max) = Self::compute_bounds(&self.triangles, &self.pending);
+            self.pending_bounds.bounds_min = min;
+            self.pending_bounds.bounds_max = max;
+        }
+
+        // Every subtree spans a contiguous run of triangle_indices
+        let moved: Vec<usize> = (0..self.triangle_indices.len())
+            .filter(|&at| changed[self.triangle_indices[at]])
+            .collect();
+        let (Some(&first), Some(&last)) = (moved.first(), moved.last()) else {
+            return Ok(());
+        };
+
+        // Descend to the smallest subtree spanning every moved triangle
+        let mut path = Vec::new();
+        let mut node_idx = 0;
+        while let Some((left, right)) = self.nodes[node_idx].children(node_idx) {
+            let next = [left, right].into_iter().find(|&child| {
+                let span = self.node_span(child);
+                span.contains(&first) && span.contains(&last)
+            });
+            let Some(child) = next else {
+                break;
+            };
+            path.push(node_idx);
+            node_idx = child;
+        }
+
+        // Rebuild it in place; nodes after it shift by the change in size
+        let span = self.node_span(node_idx);
+        let old_len = self.subtree_len(node_idx);
+        let mut subtree = Vec::with_capacity(span.len() * 2);
+        Self::build_bvh(
+            &self.triangles,
+            &mut self.triangle_indices,
+            span.start,
+            span.end,
+            &mut subtree,
+            self.sah_bins,
+        );
+        for node in &mut subtree {
+            if !node.is_leaf() {
+                node.offset += node_idx as u32;
+            }
+        }
+        let (new_len, old_end) = (subtree.len(), node_idx + old_len);
+        self.nodes.splice(node_idx..old_end, subtree);
+        let (before, rest) = self.nodes.split_at_mut(node_idx);
+        for node in before.iter_mut().chain(&mut rest[new_len..]) {
+            if !node.is_leaf() && node.offset as usize >= old_end {
+                node.offset = (node.offset as usize + new_len - old_len) as u32;
+            }
+        }
+
+        // Bounds above the rebuilt subtree, bottom-up
+        for &ancestor in path.iter().rev() {
+            if let Some((left, right)) = self.nodes[ancestor].children(ancestor) {
+                let (left, right) = (self.nodes[left], self.nodes[right]);
+                let node = &mut self.nodes[ancestor];
+                node.bounds_min = left.bounds_min.min(right.bounds_min);
+                node.bounds_max = left.bounds_max.max(right.bounds_max);
+            }
+        }
+        self.resize_profiling();
+        Ok(())
+    }
+
+    /// Appended triangles still waiting for compact()
+    #[wasm_bindgen]
+    pub fn pending_triangle_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    // --- Traversal profiling ---
+
+    /// Enable/disable per-node and per-triangle visit counting in all queries
+    ///
+    /// Disabling drops the counters; enabling starts from zero.
+    #[wasm_bindgen]
+    pub fn enable_profiling(&mut self, enabled: bool) {
+        self.profiling = enabled;
+        self.node_visit_counts.clear();
+        self.triangle_test_counts.clear();
+        if enabled {
+            self.node_visit_counts
+                .resize(self.nodes.len(), Cell::new(0));
+            self.triangle_test_counts
+                .resize(self.triangles.len(), Cell::new(0));
+        }
+    }
+
+    /// Zero all profiling counters
+    #[wasm_bindgen]
+    pub fn reset_profiling(&mut self) {
+        for count in self
+            .node_visit_counts
+            .iter()
+            .chain(&self.triangle_test_counts)
+        {
+            count.set(0);
+        }
+    }
+
+    /// Visit count per BVH node (node order, empty when profiling is off)
+    #[wasm_bindgen]
+    pub fn get_node_visit_counts(&self) -> Vec<u32> {
+        self.node_visit_counts.iter().map(Cell::get).collect()
+    }
+
+    /// Ray-triangle test count per triangle, in original input triangle order
+    #[wasm_bindgen]
+    pub fn get_triangle_test_counts(&self) -> Vec<u32> {
+        if !self.profiling {
+            return Vec::new();
+        }
+        let mut counts = vec![0u32; self.input_triangle_count];
+        for (tri, count) in self.triangles.iter().zip(&self.triangle_test_counts) {
+            counts[tri.index] = count.get();
+        }
+        counts
+    }
+
+    /// Turn per-triangle counts into a blue-green-red heatmap of vertex colors
+    ///
+    /// # Arguments
+    /// * `indices` - The mesh index buffer the counts refer to
+    /// * `counts` - One count per triangle (e.g. get_triangle_test_counts())
+    ///
+    /// # Returns
+    /// RGB per vertex (max index + 1 vertices); each vertex takes its hottest triangle
+    #[wasm_bindgen]
+    pub fn counts_to_vertex_colors(indices: &[u32], counts: &[u32]) -> Vec<f32> {
+        let vertex_count = indices.iter().max().map_or(0, |&m| m as usize + 1);
+        let mut heat = vec![0u32; vertex_count];
+        for (tri, &count) in indices.chunks_exact(3).zip(counts) {
+            for &v in tri {
+                heat[v as usize] = heat[v as usize].max(count);
+            }
+        }
+
+        let max = heat.iter().copied().max().unwrap_or(0).max(1) as f32;
+        heat.iter()
+            .flat_map(|&h| {
+                let t = h as f32 / max;
+                [
+                    (2.0 * t - 1.0).clamp(0.0, 1.0),
+                    1.0 - (2.0 * t - 1.0).abs(),
+                    (1.0 - 2.0 * t).clamp(0.0, 1.0),
+                ]
+            })
+            .collect()
+    }
+
+    /// Free the BVH and triangle buffers now instead of waiting for JS GC
+    ///
+    /// The object stays valid: queries miss, stats are zero and fallible
+    /// calls return an error.
+    #[wasm_bindgen]
+    pub fn dispose(&mut self) {
+        self.nodes = Vec::new();
+        self.triangles = Vec::new();
+        self.triangle_indices = Vec::new();
+        self.id_ranges = Vec::new();
+        self.groups = Vec::new();
+        self.triangle_mask = Vec::new();
+        self.input_triangle_count = 0;
+        self.vertex_count = 0;
+        self.uvs = Vec::new();
+        self.profiling = false;
+        self.node_visit_counts = Vec::new();
+        self.triangle_test_counts = Vec::new();
+        self.pending = Vec::new();
+        self.pending_bounds = Self::empty_node();
+        self.dropped_degenerate = 0;
+        self.merged_duplicates = 0;
+        self.skipped_degenerate = 0;
+        self.skipped_out_of_range = 0;
+        self.disposed = true;
+    }
+
+    #[wasm_bindgen]
+    pub fn is_disposed(&self) -> bool {
+        self.disposed
+    }
+
+    /// Object-space bounding box of the mesh, e.g. for camera framing
+    ///
+    /// The root node's box, grown by triangles appended since the last
+    /// build. Zeros for empty geometry (the dummy root) and after dispose().
+    ///
+    /// # Returns
+    /// [min_x, min_y, min_z, max_x, max_y, max_z]
+    #[wasm_bindgen]
+    pub fn get_bounds(&self) -> Vec<f32> {
+        let tree = self
+            .nodes
+            .first()
+            .filter(|_| !self.triangle_indices.is_empty());
+        let pending = Some(&self.pending_bounds).filter(|_| !self.pending.is_empty());
+        let (min, max) = match (tree, pending) {
+            (Some(a), Some(b)) => (
+                a.bounds_min.min(b.bounds_min),
+                a.bounds_max.max(b.bounds_max),
+            ),
+            (Some(node), None) | (None, Some(node)) => (node.bounds_min, node.bounds_max),
+            (None, None) => return vec![0.0; 6],
+        };
+        let origin = self.origin.as_vec3();
+        [(min + origin).to_array(), (max + origin).to_array()].concat()
+    }
+
+    /// One triangle in object space, e.g. to outline the hit triangle
+    ///
+    /// # Arguments
+    /// * `index` - Triangle index in original input order (as reported by
+    ///   intersect_detailed)
+    ///
+    /// # Returns
+    /// [v0 xyz, v1 xyz, v2 xyz, face normal xyz], or empty if the index is out
+    /// of range or the triangle was left out of the tree (see
+    /// get_build_warnings and get_cleanup_stats)
+    #[wasm_bindgen]
+    pub fn get_triangle(&self, index: u32) -> Vec<f32> {
+        // Triangles are kept in input order, skipped ones missing
+        let index = index as usize;
+        let Ok(slot) = self.triangles.binary_search_by_key(&index, |tri| tri.index) else {
+            return Vec::new();
+        };
+        let tri = &self.triangles[slot];
+        let origin = self.origin.as_vec3();
+        [
+            tri.v0 + origin,
+            tri.v1 + origin,
+            tri.v2 + origin,
+            tri.normal,
+        ]
+        .iter()
+        .flat_map(|v| v.to_array())
+        .collect()
+    }
+
+    /// Get BVH statistics for debugging/benchmarking
+    ///
+    /// # Returns
+    /// [nodes, leaves, internal nodes, max depth, triangles, node array bytes]
+    #[wasm_bindgen]
+    pub fn get_stats(&self) -> Vec<u32> {
+        let total_nodes = self.nodes.len();
+        let leaf_nodes = self.nodes.iter().filter(|n| n.is_leaf()).count();
+        let internal_nodes = total_nodes - leaf_nodes;
+        let max_depth = self.compute_max_depth(0, 0);
+        let total_triangles = self.triangles.len();
+        let node_bytes = total_nodes * std::mem::size_of::<BVHNode>();
+
+        vec![
+            total_nodes as u32,
+            leaf_nodes as u32,
+            internal_nodes as u32,
+            max_depth as u32,
+            total_triangles as u32,
+            node_bytes as u32,
+        ]
+    }
+
+    /// Expected cost of a random ray query under the surface area heuristic
+    ///
+    /// Sum over nodes of (node area / root area) times 1 per internal node or
+    /// 1 per triangle in a leaf. Lower is better; use it to compare builds of
+    /// the same mesh (e.g. from_geometry against from_geometry_sah).
+    #[wasm_bindgen]
+    pub fn get_sah_cost(&self) -> f32 {
+        let Some(root) = self.nodes.first() else {
+            return 0.0;
+        };
+        let root_area = Self::surface_area(root.bounds_min, root.bounds_max);
+        if root_area <= 0.0 {
+            return 0.0;
+        }
+        self.nodes
+            .iter()
+            .map(|node| {
+                let weight = Self::surface_area(node.bounds_min, node.bounds_max) / root_area;
+                if node.is_leaf() {
+                    weight * node.triangle_count as f32 * SAH_INTERSECTION_COST
+                } else {
+                    weight * SAH_TRAVERSAL_COST
+                }
+            })
+            .sum()
+    }
+
+    /// Tree quality and memory figures, e.g. for tuning tessellation
+    ///
+    /// Computed by walking the tree from the root, so unreachable nodes
+    /// don't count. Appended triangles not yet compacted are in no leaf but
+    /// their indices count towards the index bytes.
+    ///
+    /// # Returns
+    /// [SAH cost (as get_sah_cost), leaves, triangles in leaves, average and
+    /// max triangles per leaf, empty leaves, node bytes, triangle bytes,
+    /// index bytes, total bytes]
+    #[wasm_bindgen]
+    pub fn get_stats_detailed(&self) -> Vec<f32> {
+        let (mut leaves, mut leaf_triangles, mut max_leaf, mut empty_leaves) = (0, 0, 0, 0);
+        let (mut sah_cost, mut node_count) = (0.0, 0);
+        if let Some(root) = self.nodes.first() {
+            let root_area = Self::surface_area(root.bounds_min, root.bounds_max);
+            let mut stack = vec![0usize];
+            while let Some(node_idx) = stack.pop() {
+                let node = &self.nodes[node_idx];
+                node_count += 1;
+                let weight = if root_area > 0.0 {
+                    Self::surface_area(node.bounds_min, node.bounds_max) / root_area
+                } else {
+                    0.0
+                };
+                if let Some((left, right)) = node.children(node_idx) {
+                    sah_cost += weight * SAH_TRAVERSAL_COST;
+                    stack.extend([right, left]);
+                    continue;
+                }
+                leaves += 1;
+                leaf_triangles += node.triangle_count as usize;
+                max_leaf = max_leaf.max(node.triangle_count);
+                if node.is_leaf() {
+                    sah_cost += weight * node.triangle_count as f32 * SAH_INTERSECTION_COST;
+                } else {
+                    empty_leaves += 1;
+                }
+            }
+        }
+
+        let node_bytes = node_count * std::mem::size_of::<BVHNode>();
+        let triangle_bytes = self.triangles.len() * std::mem::size_of::<Triangle>();
+        let index_bytes =
+            (self.triangle_indices.len() + self.pending.len()) * std::mem::size_of::<usize>();
+        let average = if leaves > 0 {
+            leaf_triangles as f32 / leaves as f32
+        } else {
+            0.0
+        };
+        vec![
+            sah_cost,
+            leaves as f32,
+            leaf_triangles as f32,
+            average,
+            max_leaf as f32,
+            empty_leaves as f32,
+            node_bytes as f32,
+            triangle_bytes as f32,
+            index_bytes as f32,
+            (node_bytes + triangle_bytes + index_bytes) as f32,
+        ]
+    }
+
+    /// Triangles removed by the build's cleanup flags: [degenerate, duplicates]
+    #[wasm_bindgen]
+    pub fn get_cleanup_stats(&self) -> Vec<u32> {
+        vec![
+            self.dropped_degenerate as u32,
+            self.merged_duplicates as u32,
+        ]
+    }
+
+    /// Input triangles left out of the tree because they cannot be hit:
+    /// [degenerate, out_of_range]
+    ///
+    /// Degenerate triangles repeat a vertex index or have a zero (or
+    /// non-finite) cross product; out-of-range ones index past the position
+    /// buffer. Both are always skipped, cleanup flags or not.
+    #[wasm_bindgen]
+    pub fn get_build_warnings(&self) -> Vec<u32> {
+        vec![
+            self.skipped_degenerate as u32,
+            self.skipped_out_of_range as u32,
+        ]
+    }
+
+    /// Export node bounds as colored wireframe boxes for a LineSegments draw
+    ///
+    /// Nodes are emitted breadth-first, colored by depth (blue = root, red =
+    /// deepest emitted level). Output stops at 100k boxes; check is_truncated().
+    ///
+    /// # Arguments
+    /// * `max_depth` - Deepest level to include (root = 0)
+    /// * `leaves_only` - Only emit leaf nodes
+    #[wasm_bindgen]
+    pub fn export_debug_geometry(&self, max_depth: u32, leaves_only: bool) -> GeometryData {
+        let mut geometry = GeometryData::new();
+        if self.triangles.is_empty() {
+            return geometry;
+        }
+
+        let color_depth = (self.compute_max_depth(0, 0) as u32).min(max_depth);
+        let mut boxes = 0;
+        let mut queue = VecDeque::from([(0usize, 0u32)]);
+
+        while let Some((node_idx, depth)) = queue.pop_front() {
+            let node = &self.nodes[node_idx];
+            let is_leaf = node.is_leaf();
+
+            if !leaves_only || is_leaf {
+                if boxes == MAX_DEBUG_BOXES {
+                    geometry.set_truncated();
+                    break;
+                }
+                geometry.push_line_box(
+                    node.bounds_min,
+                    node.bounds_max,
+                    depth_color(depth, color_depth),
+                );
+                boxes += 1;
+            }
+
+            if let Some((left, right)) = node.children(node_idx).filter(|_| depth < max_depth) {
+                queue.extend([(left, depth + 1), (right, depth + 1)]);
+            }
+        }
+
+        geometry
+    }
+}
+
+// --- Private implementation ---
+
+impl BVHRaycaster {
+    /// Shared constructor: `faces` as from faces() or soup_faces(), cleanup
+    /// flags as in from_geometry_with_cleanup, `sah_bins` 0 for median
+    /// splits, `morton` for a linear BVH instead
+    fn build(
+        positions: VertexPositions,
+        faces: impl ExactSizeIterator<Item = [u32; 3]>,
+        cleanup: u8,
+        sah_bins: u32,
+        morton: bool,
+    ) -> Self {
+        let input_triangle_count = faces.len();
+        let (mut triangles, (skipped_degenerate, skipped_out_of_range)) =
+            Self::build_triangles(positions, faces);
+        let (min, max) =
+            Self::compute_bounds(&triangles, &(0..triangles.len()).collect::<Vec<_>>());
+        let (dropped_degenerate, merged_duplicates) =
+            Self::clean_triangles(&mut triangles, cleanup, (max - min).length());
+        let num_triangles = triangles.len();
+
+        // Initial indices: 0, 1, 2, ..., n-1
+        let mut triangle_indices: Vec<usize> = (0..num_triangles).collect();
+
+        // Build BVH recursively
+        let mut nodes = Vec::with_capacity(num_triangles * 2); // Approximate size
+
+        if morton && !triangles.is_empty() {
+            let codes = Self::sort_by_morton_code(&triangles, &mut triangle_indices);
+            Self::build_lbvh_recursive(
+                &triangles,
+                &codes,
+                &triangle_indices,
+                0,
+                num_triangles,
+                &mut nodes,
+            );
+        } else if !triangles.is_empty() {
+            Self::build_bvh(
+                &triangles,
+                &mut triangle_indices,
+                0,
+                num_triangles,
+                &mut nodes,
+                sah_bins,
+            );
+        } else {
+            // Empty geometry - create dummy root
+            nodes.push(Self::empty_node());
+        }
+
+        let scene_scale = Self::auto_epsilon_scale(&nodes[0]);
+
+        Self {
+            nodes,
+            triangles,
+            triangle_indices,
+            id_ranges: Vec::new(),
+            groups: Vec::new(),
+            triangle_mask: Vec::new(),
+            input_triangle_count,
+            vertex_count: positions.len(),
+            uvs: Vec::new(),
+            profiling: false,
+            node_visit_counts: Vec::new(),
+            triangle_test_counts: Vec::new(),
+            scene_scale,
+            epsilon_scale: scene_scale,
+            cull_mode: CULL_BACK,
+            precision_high: false,
+            traversal_stack: RefCell::new(Vec::with_capacity(64)),
+            model: Mat4::IDENTITY,
+            inverse_model: Mat4::IDENTITY,
+            hit_buffer: Self::MISS_BUFFER,
+            disposed: false,
+            sah_bins,
+            origin: DVec3::ZERO,
+            pending: Vec::new(),
+            pending_bounds: Self::empty_node(),
+            cleanup,
+            dropped_degenerate,
+            merged_duplicates,
+            skipped_degenerate,
+            skipped_out_of_range,
+        }
+    }
+
+    /// Transform a world-space ray into object space
+    fn local_ray(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+    ) -> Result<LocalRay, String> {
+        let (model, inverse_model) = Self::model_transforms(model_matrix)?;
+        Ok(self.transform_ray(model, inverse_model, ray_origin, ray_direction))
+    }
+
+    /// hit_buffer contents after a miss
+    const MISS_BUFFER: [f32; HIT_BUFFER_LEN] = {
+        let mut buffer = [0.0; HIT_BUFFER_LEN];
+        buffer[6] = -1.0;
+        buffer[7] = -1.0;
+        buffer
+    };
+
+    /// Column-major model matrix from JS, checking the length
+    fn parse_matrix(model_matrix: &[f32]) -> Result<Mat4, String> {
+        let cols: &[f32; 16] = model_matrix
+            .try_into()
+            .map_err(|_| format!("model matrix needs 16 floats, got {}", model_matrix.len()))?;
+        Ok(Mat4::from_cols_array(cols))
+    }
+
+    /// Model matrix and its inverse, checking the length
+    fn model_transforms(model_matrix: &[f32]) -> Result<(Mat4, Mat4), String> {
+        let model = Self::parse_matrix(model_matrix)?;
+        Ok((model, model.inverse()))
+    }
+
+    /// local_ray with the matrices already prepared
+    fn transform_ray(
+        &self,
+        model: Mat4,
+        inverse_model: Mat4,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+    ) -> LocalRay {
+        let origin = Vec3::from_slice(ray_origin);
+        let direction = Vec3::from_slice(ray_direction).normalize();
+
+        // Transform ray to object space
+        let local_origin = inverse_model.transform_point3(origin) - self.origin.as_vec3();
+        let local_dir = inverse_model.transform_vector3(direction).normalize();
+
+        // Pre-compute inverse direction for AABB tests (±inf for ±0 components)
+        let inv_dir = local_dir.recip();
+
+        LocalRay {
+            model,
+            normal_matrix: inverse_model.transpose(),
+            origin: local_origin,
+            direction: local_dir,
+            inv_dir,
+        }
+    }
+
+    /// Closest hit of a world-space ray and its world-space [px, py, pz, nx,
+    /// ny, nz, distance], in f64 if set_precision_high is on
+    fn closest_hit(
+        &self,
+        model: Mat4,
+        inverse_model: Mat4,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+    ) -> Option<(RayHit, [f32; 7])> {
+        if self.precision_high {
+            return self.closest_hit_f64(model, ray_origin, ray_direction);
+        }
+        let ray = self.transform_ray(model, inverse_model, ray_origin, ray_direction);
+        self.traverse_bvh(ray.origin, ray.direction, ray.inv_dir)
+            .map(|hit| {
+                let world = self.hit_to_world_array(&ray, &hit);
+                (hit, world)
+            })
+    }
+
+    /// closest_hit with the ray and triangle math in f64
+    ///
+    /// The boxes are still tested in f32, against a rounded copy of the ray
+    /// with the boxes grown to cover the rounding.
+    fn closest_hit_f64(
+        &self,
+        model: Mat4,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+    ) -> Option<(RayHit, [f32; 7])> {
+        let root = self.nodes.first()?;
+        let model = model.as_dmat4();
+        let inverse_model = model.inverse();
+        let origin =
+            inverse_model.transform_point3(Vec3::from_slice(ray_origin).as_dvec3()) - self.origin;
+        let direction = inverse_model
+            .transform_vector3(Vec3::from_slice(ray_direction).as_dvec3().normalize())
+            .normalize();
+
+        let (origin_f32, direction_f32) = (origin.as_vec3(), direction.as_vec3());
+        let reach = origin_f32.abs().max_element()
+            + root
+                .bounds_min
+                .abs()
+                .max(root.bounds_max.abs())
+                .max(self.pending_bounds.bounds_min.abs())
+                .max(self.pending_bounds.bounds_max.abs())
+                .max_element();
+        let margin = Vec3::splat(reach * RELATIVE_PRECISE_BOX_MARGIN);
+
+        let mut closest: Option<(f64, RayHit)> = None;
+        self.walk(
+            origin_f32,
+            direction_f32.recip(),
+            f32::MAX,
+            margin,
+            |tri_idx, max_t| match self
+                .is_enabled(tri_idx)
+                .then(|| {
+                    self.ray_triangle_intersect_f64(
+                        origin,
+                        direction,
+                        &self.triangles[tri_idx],
+                        tri_idx,
+                    )
+                })
+                .flatten()
+            {
+                Some((t, hit)) if closest.as_ref().is_none_or(|(best, _)| t < *best) => {
+                    closest = Some((t, hit));
+                    // Boxes are entered earlier than the f64 ray would enter them
+                    Some((t as f32).next_up())
+                }
+                _ => Some(max_t),
+            },
+        );
+
+        let (t, hit) = closest?;
+        let world_point = model.transform_point3(origin + direction * t + self.origin);
+        let world_normal = inverse_model
+            .transpose()
+            .transform_vector3(hit.normal.as_dvec3())
+            .normalize();
+        let world = [
+            world_point.x as f32,
+            world_point.y as f32,
+            world_point.z as f32,
+            world_normal.x as f32,
+            world_normal.y as f32,
+            world_normal.z as f32,
+            hit.distance,
+        ];
+        Some((hit, world))
+    }
+
+    /// Transform an object-space hit back to world space: [px, py, pz, nx, ny, nz, distance]
+    fn hit_to_world(&self, ray: &LocalRay, hit: &RayHit) -> Vec<f32> {
+        self.hit_to_world_array(ray, hit).to_vec()
+    }
+
+    fn hit_to_world_array(&self, ray: &LocalRay, hit: &RayHit) -> [f32; 7] {
+        let world_point = ray
+            .model
+            .transform_point3(hit.point + self.origin.as_vec3());
+        let world_normal = ray.normal_matrix.transform_vector3(hit.normal).normalize();
+        [
+            world_point.x,
+            world_point.y,
+            world_point.z,
+            world_normal.x,
+            world_normal.y,
+            world_normal.z,
+            hit.distance,
+        ]
+    }
+
+    #[inline]
+    fn bump(counter: &Cell<u32>) {
+        counter.set(counter.get().saturating_add(1));
+    }
+
+    /// Map an original triangle index to its user ID (u32::MAX if unmapped)
+    fn resolve_id(&self, triangle: usize) -> u32 {
+        let triangle = triangle as u64;
+        let idx = self.id_ranges.partition_point(|r| r.0 as u64 <= triangle);
+        if idx == 0 {
+            return u32::MAX;
+        }
+        let (start, count, id) = self.id_ranges[idx - 1];
+        if triangle < start as u64 + count as u64 {
+            id
+        } else {
+            u32::MAX
+        }
+    }
+
+    fn empty_node() -> BVHNode {
+        BVHNode {
+            bounds_min: Vec3::ZERO,
+            bounds_max: Vec3::ZERO,
+            offset: 0,
+            triangle_count: 0,
+        }
+    }
+
+    /// Check that deserialized nodes form a tree over `index_count` entries
+    ///
+    /// Every child index must be in range, after its parent, and reached
+    /// only once from the root, so traversal can neither index out of bounds
+    /// nor loop.
+    fn validate_tree(nodes: &[BVHNode], index_count: usize) -> Result<(), String> {
+        if nodes.is_empty() {
+            return Err("serialized BVH has no root node".into());
+        }
+
+        let mut reached = vec![false; nodes.len()];
+        let mut stack = vec![0usize];
+        reached[0] = true;
+        while let Some(node_idx) = stack.pop() {
+            let node = &nodes[node_idx];
+            if node.is_leaf() {
+                let in_range = (node.offset as usize)
+                    .checked_add(node.triangle_count as usize)
+                    .is_some_and(|end| end <= index_count);
+                if !in_range {
+                    return Err(format!("leaf node {node_idx} is malformed"));
+                }
+                continue;
+            }
+            let Some((left, right)) = node.children(node_idx) else {
+                continue;
+            };
+            for child in [left, right] {
+                if child <= node_idx || child >= nodes.len() || reached[child] {
+                    return Err(format!("node {node_idx} has an invalid child {child}"));
+                }
+                reached[child] = true;
+                stack.push(child);
+            }
+        }
+        Ok(())
+    }
+
+    /// Grow the profiling counters after the tree or triangle set changed
+    fn resize_profiling(&mut self) {
+        if self.profiling {
+            self.node_visit_counts
+                .resize(self.nodes.len(), Cell::new(0));
+            self.triangle_test_counts
+                .resize(self.triangles.len(), Cell::new(0));
+        }
+    }
+
+    /// Vertex index triples of an index buffer (u16 or u32); a trailing
+    /// partial triangle is ignored
+    fn faces<T: Copy + Into<u32>>(indices: &[T]) -> impl ExactSizeIterator<Item = [u32; 3]> + '_ {
+        indices
+            .chunks_exact(3)
+            .map(|chunk| [chunk[0].into(), chunk[1].into(), chunk[2].into()])
+    }
+
+    /// Vertex index triples of non-indexed geometry: (0, 1, 2), (3, 4, 5), ...
+    fn soup_faces(vertex_count: usize) -> impl ExactSizeIterator<Item = [u32; 3]> {
+        (0..(vertex_count / 3) as u32).map(|tri| [tri * 3, tri * 3 + 1, tri * 3 + 2])
+    }
+
+    /// Build triangles from vertex positions and index triples
+    fn build_triangles(
+        positions: VertexPositions,
+        faces: impl ExactSizeIterator<Item = [u32; 3]>,
+    ) -> (Vec<Triangle>, (usize, usize)) {
+        let mut triangles = Vec::with_capacity(faces.len());
+        let skipped = Self::fill_triangles(&mut triangles, positions, faces);
+        (triangles, skipped)
+    }
+
+    /// build_triangles into an existing (cleared) buffer
+    ///
+    /// # Returns
+    /// (degenerate, out-of-range) triangles skipped, see get_build_warnings
+    fn fill_triangles(
+        triangles: &mut Vec<Triangle>,
+        positions: VertexPositions,
+        faces: impl ExactSizeIterator<Item = [u32; 3]>,
+    ) -> (usize, usize) {
+        triangles.reserve(faces.len());
+        let (mut degenerate, mut out_of_range) = (0, 0);
+
+        let vertex_count = positions.len();
+        for (index, face) in faces.enumerate() {
+            let [i0, i1, i2] = face.map(|vertex| vertex as usize);
+
+            // Bounds check
+            if i0 >= vertex_count || i1 >= vertex_count || i2 >= vertex_count {
+                out_of_range += 1;
+                continue;
+            }
+
+            let v0 = positions.get(i0);
+            let v1 = positions.get(i1);
+            let v2 = positions.get(i2);
+
+            // Calculate face normal
+            let edge1 = v1 - v0;
+            let edge2 = v2 - v0;
+            let normal = edge1.cross(edge2).normalize_or_zero();
+
+            // Collapsed: can never be hit, and would report a zero normal
+            if i0 == i1 || i1 == i2 || i2 == i0 || normal == Vec3::ZERO {
+                degenerate += 1;
+                continue;
+            }
+
+            // Calculate centroid for sorting
+            let centroid = (v0 + v1 + v2) / 3.0;
+
+            triangles.push(Triangle {
+                v0,
+                v1,
+                v2,
+                normal,
+                centroid,
+                index,
+                vertices: face,
+                vertex_normals: None,
+            });
+        }
+        (degenerate, out_of_range)
+    }
+
+    /// Remove degenerate and/or duplicate triangles (see from_geometry_with_cleanup)
+    ///
+    /// `scale` is the length the area epsilon is relative to.
+    ///
+    /// # Returns
+    /// (degenerate triangles dropped, duplicates dropped)
+    fn clean_triangles(triangles: &mut Vec<Triangle>, flags: u8, scale: f32) -> (usize, usize) {
+        let before = triangles.len();
+        if flags & CLEANUP_DEGENERATE != 0 {
+            let min_area = RELATIVE_AREA_EPSILON * scale * scale;
+            triangles
+                .retain(|tri| (tri.v1 - tri.v0).cross(tri.v2 - tri.v0).length() * 0.5 >= min_area);
+        }
+        let degenerate = before - triangles.len();
+
+        let before = triangles.len();
+        if flags & CLEANUP_DUPLICATES != 0 {
+            let mut seen = HashSet::with_capacity(triangles.len());
+            triangles.retain(|tri| {
+                let mut key = tri.vertices;
+                // Rotate the smallest index first; rotations keep the winding
+                let first = (0..3).min_by_key(|&i| key[i]).unwrap_or(0);
+                key.rotate_left(first);
+                seen.insert(key)
+            });
+        }
+        (degenerate, before - triangles.len())
+    }
+
+    /// Build BVH using SAH or median-split on longest axis
+    ///
+    /// Runs off an explicit work stack rather than recursion, so skewed
+    /// meshes cannot exhaust the (small) WASM call stack.
+    ///
+    /// # Arguments
+    /// * `triangles` - All triangles (immutable reference)
+    /// * `indices` - Mutable triangle index array (reordered in place)
+    /// * `start` - Start index in `indices` for the subtree
+    /// * `end` - End index (exclusive) in `indices` for the subtree
+    /// * `nodes` - Output node array
+    /// * `sah_bins` - Binned SAH candidates per axis, 0 = always median split
+    ///
+    /// # Returns
+    /// Index of the subtree's root in `nodes`
+    fn build_bvh(
+        triangles: &[Triangle],
+        indices: &mut [usize],
+        start: usize,
+        end: usize,
+        nodes: &mut Vec<BVHNode>,
+        sah_bins: u32,
+    ) -> usize {
+        let root_idx = nodes.len();
+
+        // (start, end, depth, parent to point at this node if it is a right child)
+        let mut work: Vec<(usize, usize, usize, Option<usize>)> = vec![(start, end, 0, None)];
+        while let Some((start, end, depth, right_of)) = work.pop() {
+            let node_idx = nodes.len();
+            if let Some(parent) = right_of {
+                nodes[parent].offset = node_idx as u32;
+            }
+
+            // Compute bounds for this subset
+            let (bounds_min, bounds_max) = Self::compute_bounds(triangles, &indices[start..end]);
+            let count = end - start;
+            let leaf = BVHNode {
+                bounds_min,
+                bounds_max,
+                offset: start as u32,
+                triangle_count: count as u32,
+            };
+
+            // Create leaf node if few enough triangles
+            if count <= MAX_LEAF_TRIANGLES {
+                nodes.push(leaf);
+                continue;
+            }
+
+            let bins = if depth < MAX_SAH_DEPTH { sah_bins } else { 0 };
+            let mid = Self::sah_split(triangles, indices, start, end, bounds_min, bounds_max, bins)
+                .unwrap_or_else(|| {
+                    Self::median_split(triangles, indices, start, end, bounds_min, bounds_max)
+                });
+            // A split that leaves a side empty would repeat forever
+            if mid == start || mid == end {
+                nodes.push(leaf);
+                continue;
+            }
+
+            nodes.push(BVHNode {
+                bounds_min,
+                bounds_max,
+                offset: 0,         // Right child, filled in when it is built
+                triangle_count: 0, // 0 indicates internal node
+            });
+
+            // Left on top, so the left subtree directly follows this node
+            work.push((mid, end, depth + 1, Some(node_idx)));
+            work.push((start, mid, depth + 1, None));
+        }
+
+        root_idx
+    }
+
+    /// Sort `indices` by the Morton code of each triangle's centroid
+    ///
+    /// 10 bits per axis over the centroid bounds; LSD radix sort, stable, so
+    /// equal codes keep triangle order.
+    ///
+    /// # Returns
+    /// The codes, in the new order of `indices`
+    fn sort_by_morton_code(triangles: &[Triangle], indices: &mut [usize]) -> Vec<u32> {
+        // Spread the low 10 bits of v so two zero bits follow each one
+        fn spread(v: u32) -> u32 {
+            let v = (v | (v << 16)) & 0x0300_00ff;
+            let v = (v | (v << 8)) & 0x0300_f00f;
+            let v = (v | (v << 4)) & 0x030c_30c3;
+            (v | (v << 2)) & 0x0924_9249
+        }
+
+        let (min, max) = indices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), &i| {
+                (
+                    min.min(triangles[i].centroid),
+                    max.max(triangles[i].centroid),
+                )
+            },
+        );
+        // Flat axes (all centroids equal) quantize to 0
+        let extent = max - min;
+        let scale = Vec3::select(extent.cmpgt(Vec3::ZERO), 1023.0 / extent, Vec3::ZERO);
+
+        // (code, triangle) packed so one radix pass moves both
+        let mut keys: Vec<u64> = indices
+            .iter()
+            .map(|&i| {
+                let q = ((triangles[i].centroid - min) * scale)
+                    .clamp(Vec3::ZERO, Vec3::splat(1023.0))
+                    .as_uvec3();
+                let code = (spread(q.x) << 2) | (spread(q.y) << 1) | spread(q.z);
+                ((code as u64) << 32) | i as u64
+            })
+            .collect();
+        let mut scratch = vec![0u64; keys.len()];
+        for shift in [32, 40, 48, 56] {
+            let mut offsets = [0usize; 256];
+            for &key in &keys {
+                offsets[(key >> shift) as usize & 0xff] += 1;
+            }
+            let mut total = 0;
+            for slot in offsets.iter_mut() {
+                (*slot, total) = (total, total + *slot);
+            }
+            for &key in &keys {
+                let digit = (key >> shift) as usize & 0xff;
+                scratch[offsets[digit]] = key;
+                offsets[digit] += 1;
+            }
+            std::mem::swap(&mut keys, &mut scratch);
+        }
+
+        for (slot, key) in indices.iter_mut().zip(&keys) {
+            *slot = (*key & 0xffff_ffff) as usize;
+        }
+        keys.iter().map(|key| (key >> 32) as u32).collect()
+    }
+
+    /// Build a subtree over Morton-sorted indices[start..end]
+    ///
+    /// Splits where the highest differing code bit flips (halves runs of equal
+    /// codes). Bounds come from the children, so each triangle is read once.
+    ///
+    /// # Returns
+    /// Index of the created node in `nodes`
+    fn build_lbvh_recursive(
+        triangles: &[Triangle],
+        codes: &[u32],
+        indices: &[usize],
+        start: usize,
+        end: usize,
+        nodes: &mut Vec<BVHNode>,
+    ) -> usize {
+        let node_idx = nodes.len();
+        if end - start <= MAX_LEAF_TRIANGLES {
+            let (bounds_min, bounds_max) = Self::compute_bounds(triangles, &indices[start..end]);
+            nodes.push(BVHNode {
+                bounds_min,
+                bounds_max,
+                offset: start as u32,
+                triangle_count: (end - start) as u32,
+            });
+            return node_idx;
+        }
+
+        let (first, last) = (codes[start], codes[end - 1]);
+        let mid = if first == last {
+            start + (end - start) / 2
+        } else {
+            // First code with the highest differing bit set
+            let bit = 31 - (first ^ last).leading_zeros();
+            start + codes[start..end].partition_point(|code| code & (1 << bit) == 0)
+        };
+
+        nodes.push(Self::empty_node());
+        let left = Self::build_lbvh_recursive(triangles, codes, indices, start, mid, nodes);
+        let right = Self::build_lbvh_recursive(triangles, codes, indices, mid, end, nodes);
+        nodes[node_idx] = BVHNode {
+            bounds_min: nodes[left].bounds_min.min(nodes[right].bounds_min),
+            bounds_max: nodes[left].bounds_max.max(nodes[right].bounds_max),
+            offset: right as u32,
+            triangle_count: 0,
+        };
+        node_idx
+    }
+
+    /// Sort a node's triangles along its longest axis and split at the median
+    ///
+    /// Ties (and NaN centroids) are ordered by triangle index, and a node whose
+    /// centroids all coincide is split by triangle index alone, so piles of
+    /// identical triangles still halve at every level.
+    ///
+    /// # Returns
+    /// The split point in `indices`
+    fn median_split(
+        triangles: &[Triangle],
+        indices: &mut [usize],
+        start: usize,
+        end: usize,
+        bounds_min: Vec3,
+        bounds_max: Vec3,
+    ) -> usize {
+        let axis = Self::longest_axis(bounds_max - bounds_min);
+
+        // Sort indices by triangle centroid along chosen axis
+        let tri_ref = triangles;
+        let first_centroid = tri_ref[indices[start]].centroid;
+        if indices[start + 1..end]
+            .iter()
+            .all(|&i| tri_ref[i].centroid == first_centroid)
+        {
+            indices[start..end].sort_unstable();
+        } else {
+            indices[start..end].sort_by(|&a, &b| {
+                let ca = match axis {
+                    0 => tri_ref[a].centroid.x,
+                    1 => tri_ref[a].centroid.y,
+                    _ => tri_ref[a].centroid.z,
+                };
+                let cb = match axis {
+                    0 => tri_ref[b].centroid.x,
+                    1 => tri_ref[b].centroid.y,
+                    _ => tri_ref[b].centroid.z,
+                };
+                ca.total_cmp(&cb).then(a.cmp(&b))
+            });
+        }
+
+        // Split at median
+        start + (end - start) / 2
+    }
+
+    /// Partition a node's triangles at the cheapest binned SAH candidate
+    ///
+    /// Centroids are binned along each axis and every boundary between bins is
+    /// costed as traversal + (area_left * n_left + area_right * n_right) /
+    /// area_node * intersection.
+    ///
+    /// # Returns
+    /// The split point in `indices`, or None (indices untouched) when SAH is
+    /// off or no candidate is cheaper than testing every triangle in the node
+    #[allow(clippy::too_many_arguments)]
+    fn sah_split(
+        triangles: &[Triangle],
+        indices: &mut [usize],
+        start: usize,
+        end: usize,
+        bounds_min: Vec3,
+        bounds_max: Vec3,
+        bins: u32,
+    ) -> Option<usize> {
+        if bins == 0 {
+            return None;
+        }
+        let bins = bins as usize;
+        let node_area = Self::surface_area(bounds_min, bounds_max);
+        if node_area <= 0.0 {
+            return None;
+        }
+
+        let (centroid_min, centroid_max) = indices[start..end].iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), &i| {
+                (
+                    min.min(triangles[i].centroid),
+                    max.max(triangles[i].centroid),
+                )
+            },
+        );
+        let bin_of = |axis: usize, tri: &Triangle| {
+            let scale = bins as f32 / (centroid_max[axis] - centroid_min[axis]);
+            (((tri.centroid[axis] - centroid_min[axis]) * scale) as usize).min(bins - 1)
+        };
+
+        // (cost, axis, last bin on the left)
+        let mut best: Option<(f32, usize, usize)> = None;
+        for axis in 0..3 {
+            if centroid_max[axis] - centroid_min[axis] <= 0.0 {
+                continue;
+            }
+            let mut counts = vec![0usize; bins];
+            let mut bin_bounds = vec![(Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)); bins];
+            for &i in &indices[start..end] {
+                let tri = &triangles[i];
+                let bin = bin_of(axis, tri);
+                counts[bin] += 1;
+                let (min, max) = &mut bin_bounds[bin];
+                *min = min.min(tri.v0).min(tri.v1).min(tri.v2);
+                *max = max.max(tri.v0).max(tri.v1).max(tri.v2);
+            }
+
+            // Right side of every boundary, swept from the last bin
+            let mut right = vec![(0.0f32, 0usize); bins];
+            let (mut min, mut max, mut count) = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN), 0);
+            for bin in (1..bins).rev() {
+                min = min.min(bin_bounds[bin].0);
+                max = max.max(bin_bounds[bin].1);
+                count += counts[bin];
+                right[bin] = (Self::surface_area(min, max), count);
+            }
+
+            let (mut min, mut max, mut count) = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN), 0);
+            for bin in 0..bins - 1 {
+                min = min.min(bin_bounds[bin].0);
+                max = max.max(bin_bounds[bin].1);
+                count += counts[bin];
+                let (right_area, right_count) = right[bin + 1];
+                if count == 0 || right_count == 0 {
+                    continue;
+                }
+                let cost = SAH_TRAVERSAL_COST
+                    + (Self::surface_area(min, max) * count as f32
+                        + right_area * right_count as f32)
+                        / node_area
+                        * SAH_INTERSECTION_COST;
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, bin));
+                }
+            }
+        }
+
+        let (cost, axis, split_bin) = best?;
+        if cost >= (end - start) as f32 * SAH_INTERSECTION_COST {
+            return None;
+        }
+
+        let mut mid = start;
+        for i in start..end {
+            if bin_of(axis, &triangles[indices[i]]) <= split_bin {
+                indices.swap(i, mid);
+                mid += 1;
+            }
+        }
+        Some(mid)
+    }
+
+    /// Index of the largest component (ties prefer x, then y)
+    fn longest_axis(extent: Vec3) -> usize {
+        if extent.x >= extent.y && extent.x >= extent.z {
+            0 // X
+        } else if extent.y >= extent.z {
+            1 // Y
+        } else {
+            2 // Z
+        }
+    }
+
+    /// Surface area of an AABB (0 for an empty box)
+    fn surface_area(min: Vec3, max: Vec3) -> f32 {
+        let d = (max - min).max(Vec3::ZERO);
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Set a triangle's vertices from a position buffer (refit, update_region)
+    fn move_triangle(tri: &mut Triangle, positions: &[f32], origin: Vec3) {
+        let vertex = |v: u32| Vec3::from_slice(&positions[v as usize * 3..]) - origin;
+        let [a, b, c] = tri.vertices;
+        tri.v0 = vertex(a);
+        tri.v1 = vertex(b);
+        tri.v2 = vertex(c);
+        tri.normal = (tri.v1 - tri.v0).cross(tri.v2 - tri.v0).normalize_or_zero();
+        tri.centroid = (tri.v0 + tri.v1 + tri.v2) / 3.0;
+    }
+
+    /// Range of triangle_indices under a node: from its leftmost leaf to the
+    /// end of its rightmost one
+    fn node_span(&self, node_idx: usize) -> std::ops::Range<usize> {
+        let (mut first, mut last) = (node_idx, node_idx);
+        while let Some((left, _)) = self.nodes[first].children(first) {
+            first = left;
+        }
+        while let Some((_, right)) = self.nodes[last].children(last) {
+            last = right;
+        }
+        let end = self.nodes[last].triangle_range().end;
+        self.nodes[first].triangle_range().start..end
+    }
+
+    /// Number of nodes in a subtree; they follow its root contiguously
+    fn subtree_len(&self, node_idx: usize) -> usize {
+        let mut last = node_idx;
+        while let Some((_, right)) = self.nodes[last].children(last) {
+            last = right;
+        }
+        last + 1 - node_idx
+    }
+
+    /// Recompute a subtree's bounds from its triangles (post-order)
+    fn refit_node(&mut self, node_idx: usize) -> (Vec3, Vec3) {
+        let node = self.nodes[node_idx];
+        let (min, max) = if node.is_leaf() {
+            Self::compute_bounds(
+                &self.triangles,
+                &self.triangle_indices[node.triangle_range()],
+            )
+        } else {
+            let (mut min, mut max) = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN));
+            for child in node
+                .children(node_idx)
+                .into_iter()
+                .flat_map(<[usize; 2]>::from)
+            {
+                let (child_min, child_max) = self.refit_node(child);
+                min = min.min(child_min);
+                max = max.max(child_max);
+            }
+            (min, max)
+        };
+        self.nodes[node_idx].bounds_min = min;
+        self.nodes[node_idx].bounds_max = max;
+        (min, max)
+    }
+
+    /// Compute bounding box for a subset of triangles
+    fn compute_bounds(triangles: &[Triangle], indices: &[usize]) -> (Vec3, Vec3) {
+        let mut bounds_min = Vec3::splat(f32::MAX);
+        let mut bounds_max = Vec3::splat(f32::MIN);
+
+        for &idx in indices {
+            let tri = &triangles[idx];
+            bounds_min = bounds_min.min(tri.v0).min(tri.v1).min(tri.v2);
+            bounds_max = bounds_max.max(tri.v0).max(tri.v1).max(tri.v2);
+        }
+
+        (bounds_min, bounds_max)
+    }
+
+    /// Epsilon scale for a tree: the root AABB diagonal (1 for empty/degenerate meshes)
+    fn auto_epsilon_scale(root: &BVHNode) -> f32 {
+        let diagonal = (root.bounds_max - root.bounds_min).length();
+        if diagonal.is_finite() && diagonal > 0.0 {
+            diagonal
+        } else {
+            1.0
+        }
     }
 
     /// Compute maximum depth of BVH (for stats)
@@ -321,201 +3915,4145 @@ impl BVHRaycaster {
             return current_depth;
         }
 
-        let node = &self.nodes[node_idx];
+        let node = &self.nodes[node_idx];
+
+        match node.children(node_idx) {
+            Some((left, right)) => self
+                .compute_max_depth(left, current_depth + 1)
+                .max(self.compute_max_depth(right, current_depth + 1)),
+            // Leaf node (or empty root)
+            None => current_depth,
+        }
+    }
+
+    /// Closest hit along a ray
+    fn traverse_bvh(&self, origin: Vec3, direction: Vec3, inv_dir: Vec3) -> Option<RayHit> {
+        self.traverse_bvh_within(origin, direction, inv_dir, f32::MAX)
+    }
+
+    /// Closest hit nearer than `max_t`
+    fn traverse_bvh_within(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        inv_dir: Vec3,
+        max_t: f32,
+    ) -> Option<RayHit> {
+        self.traverse_bvh_where(origin, direction, inv_dir, max_t, |_| true)
+    }
+
+    /// traverse_bvh_within over the triangles `keep` accepts
+    fn traverse_bvh_where(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        inv_dir: Vec3,
+        max_t: f32,
+        keep: impl Fn(usize) -> bool,
+    ) -> Option<RayHit> {
+        let mut closest_hit: Option<RayHit> = None;
+        self.walk(
+            origin,
+            inv_dir,
+            max_t,
+            Vec3::ZERO,
+            |tri_idx, closest_t| match (self.is_enabled(tri_idx) && keep(tri_idx))
+                .then(|| {
+                    self.ray_triangle_intersect(
+                        origin,
+                        direction,
+                        &self.triangles[tri_idx],
+                        tri_idx,
+                    )
+                })
+                .flatten()
+            {
+                Some(hit) if hit.distance < closest_t => {
+                    let t = hit.distance;
+                    closest_hit = Some(hit);
+                    Some(t)
+                }
+                _ => Some(closest_t),
+            },
+        );
+        closest_hit
+    }
+
+    /// Whether ray queries may hit a triangle (see set_triangle_mask)
+    #[inline]
+    fn is_enabled(&self, tri_idx: usize) -> bool {
+        self.triangle_mask
+            .get(self.triangles[tri_idx].index)
+            .is_none_or(|&enabled| enabled != 0)
+    }
+
+    /// Closest hits for 4 rays in one walk
+    ///
+    /// Each stack entry carries the lanes whose ray entered the parent, and a
+    /// lane only tests a node's triangles if its own ray enters the node, so
+    /// every ray sees the same boxes and triangles as traverse_bvh would
+    /// show it (left child first rather than nearest first).
+    fn traverse_packet4(&self, rays: &[LocalRay; PACKET_RAYS]) -> [Option<RayHit>; PACKET_RAYS] {
+        let mut hits: [Option<RayHit>; PACKET_RAYS] = Default::default();
+        let mut max_t = [f32::MAX; PACKET_RAYS];
+        let packet = RayPacket4 {
+            origin: std::array::from_fn(|axis| std::array::from_fn(|l| rays[l].origin[axis])),
+            inv_dir: std::array::from_fn(|axis| std::array::from_fn(|l| rays[l].inv_dir[axis])),
+        };
+        let mut test_lanes = |lanes: u8, tri_idx: usize, max_t: &mut [f32; PACKET_RAYS]| {
+            if !self.is_enabled(tri_idx) {
+                return;
+            }
+            for lane in (0..PACKET_RAYS).filter(|l| lanes & (1 << l) != 0) {
+                let ray = &rays[lane];
+                let tri = &self.triangles[tri_idx];
+                match self.ray_triangle_intersect(ray.origin, ray.direction, tri, tri_idx) {
+                    Some(hit) if hit.distance < max_t[lane] => {
+                        max_t[lane] = hit.distance;
+                        hits[lane] = Some(hit);
+                    }
+                    _ => {}
+                }
+            }
+        };
+
+        if !self.nodes.is_empty() {
+            let mut stack: Vec<(usize, u8)> = Vec::with_capacity(64);
+            stack.push((0, (1 << PACKET_RAYS) - 1));
+            while let Some((node_idx, lanes)) = stack.pop() {
+                let node = &self.nodes[node_idx];
+                let lanes = lanes & Self::packet_aabb4(&packet, node, &max_t);
+                if lanes == 0 {
+                    continue;
+                }
+                if node.is_leaf() {
+                    for &tri_idx in &self.triangle_indices[node.triangle_range()] {
+                        test_lanes(lanes, tri_idx, &mut max_t);
+                    }
+                } else if let Some((left, right)) = node.children(node_idx) {
+                    stack.push((right, lanes));
+                    stack.push((left, lanes));
+                }
+            }
+        }
+
+        // Appended triangles not merged into the tree yet
+        if !self.pending.is_empty() {
+            let lanes = Self::packet_aabb4(&packet, &self.pending_bounds, &max_t);
+            for &tri_idx in &self.pending {
+                test_lanes(lanes, tri_idx, &mut max_t);
+            }
+        }
+        hits
+    }
+
+    /// ray_aabb_intersect for each ray of a packet, as a lane bit mask
+    #[cfg(not(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128")))]
+    #[inline]
+    fn packet_aabb4(packet: &RayPacket4, node: &BVHNode, max_t: &[f32; PACKET_RAYS]) -> u8 {
+        (0..PACKET_RAYS)
+            .filter(|&l| {
+                let origin = Vec3::new(
+                    packet.origin[0][l],
+                    packet.origin[1][l],
+                    packet.origin[2][l],
+                );
+                let inv_dir = Vec3::new(
+                    packet.inv_dir[0][l],
+                    packet.inv_dir[1][l],
+                    packet.inv_dir[2][l],
+                );
+                Self::ray_aabb_intersect(origin, inv_dir, node, max_t[l], Vec3::ZERO)
+            })
+            .fold(0, |lanes, l| lanes | 1 << l)
+    }
+
+    /// ray_aabb_intersect for each ray of a packet, as a lane bit mask
+    ///
+    /// Same slab test lane by lane, including the containment test on axes a
+    /// ray is parallel to, so the masks match the scalar version.
+    #[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+    #[inline]
+    fn packet_aabb4(packet: &RayPacket4, node: &BVHNode, max_t: &[f32; PACKET_RAYS]) -> u8 {
+        use core::arch::wasm32::*;
+
+        let load = |v: &[f32; PACKET_RAYS]| f32x4(v[0], v[1], v[2], v[3]);
+        let mut t_enter = f32x4_splat(f32::NEG_INFINITY);
+        let mut t_exit = f32x4_splat(f32::INFINITY);
+        let mut contained = u32x4_splat(u32::MAX);
+
+        for axis in 0..3 {
+            let lo = f32x4_splat(node.bounds_min[axis]);
+            let hi = f32x4_splat(node.bounds_max[axis]);
+            let o = load(&packet.origin[axis]);
+            let inv = load(&packet.inv_dir[axis]);
+
+            // Parallel lanes: origin must lie within the slab, t is unchanged
+            let parallel = f32x4_eq(f32x4_abs(inv), f32x4_splat(f32::INFINITY));
+            let within = v128_and(f32x4_ge(o, lo), f32x4_le(o, hi));
+            contained = v128_and(contained, v128_or(v128_not(parallel), within));
+
+            // Sign bit set (including -0.0) swaps near and far
+            let negative = i32x4_lt(inv, i32x4_splat(0));
+            let near = v128_bitselect(hi, lo, negative);
+            let far = v128_bitselect(lo, hi, negative);
+            let t_near = f32x4_mul(f32x4_sub(near, o), inv);
+            let t_far = f32x4_mul(f32x4_sub(far, o), inv);
+            t_enter = v128_bitselect(t_enter, f32x4_max(t_enter, t_near), parallel);
+            t_exit = v128_bitselect(t_exit, f32x4_min(t_exit, t_far), parallel);
+        }
+
+        let hit = v128_and(
+            v128_and(contained, f32x4_le(t_enter, t_exit)),
+            v128_and(
+                f32x4_ge(t_exit, f32x4_splat(0.0)),
+                f32x4_lt(t_enter, load(max_t)),
+            ),
+        );
+        i32x4_bitmask(hit)
+    }
+
+    /// Iterative BVH traversal (faster than recursive for WASM)
+    ///
+    /// Calls `test` for every triangle in a leaf (or the pending set) whose box
+    /// (grown by `expand`) the ray enters before the current max distance.
+    /// `test` gets the triangle and that distance and returns the new max
+    /// distance, or None to stop the walk.
+    fn walk(
+        &self,
+        origin: Vec3,
+        inv_dir: Vec3,
+        max_t: f32,
+        expand: Vec3,
+        mut test: impl FnMut(usize, f32) -> Option<f32>,
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        // Reuse the shared stack (a nested walk would just get a fresh one)
+        let mut stack = self.traversal_stack.take();
+        stack.clear();
+        stack.push(0usize); // Root node
+        let mut max_t = max_t;
+
+        let stopped = 'tree: {
+            while let Some(node_idx) = stack.pop() {
+                let node = &self.nodes[node_idx];
+                if self.profiling {
+                    Self::bump(&self.node_visit_counts[node_idx]);
+                }
+
+                // AABB intersection test with early termination
+                if !Self::ray_aabb_intersect(origin, inv_dir, node, max_t, expand) {
+                    continue;
+                }
+
+                if node.is_leaf() {
+                    // Leaf node - test triangles
+                    for &tri_idx in &self.triangle_indices[node.triangle_range()] {
+                        if self.profiling {
+                            Self::bump(&self.triangle_test_counts[tri_idx]);
+                        }
+                        match test(tri_idx, max_t) {
+                            Some(t) => max_t = t,
+                            None => break 'tree true,
+                        }
+                    }
+                } else if let Some((left, right)) = node.children(node_idx) {
+                    // Internal node - nearer child on top of the stack (front-to-back),
+                    // so max_t shrinks early and prunes the farther one
+                    let enter = |child: usize| {
+                        Self::ray_aabb_entry(origin, inv_dir, &self.nodes[child], max_t, expand)
+                    };
+                    match (enter(left), enter(right)) {
+                        (Some(l), Some(r)) if r < l => stack.extend([left, right]),
+                        (Some(_), Some(_)) => stack.extend([right, left]),
+                        (Some(_), None) => stack.push(left),
+                        (None, Some(_)) => stack.push(right),
+                        (None, None) => {}
+                    }
+                }
+            }
+            false
+        };
+        self.traversal_stack.replace(stack);
+        if stopped {
+            return;
+        }
+
+        // Appended triangles not merged into the tree yet
+        if !self.pending.is_empty()
+            && Self::ray_aabb_intersect(origin, inv_dir, &self.pending_bounds, max_t, expand)
+        {
+            for &tri_idx in &self.pending {
+                if self.profiling {
+                    Self::bump(&self.triangle_test_counts[tri_idx]);
+                }
+                match test(tri_idx, max_t) {
+                    Some(t) => max_t = t,
+                    None => return,
+                }
+            }
+        }
+    }
+
+    /// contains_point ray direction for an attempt: spread over the sphere
+    /// (golden-angle spiral) and off the axes and diagonals meshes align to
+    fn containment_direction(attempt: usize) -> Vec3 {
+        const GOLDEN_ANGLE: f32 = 2.399_963;
+        let z = 1.0 - (2 * attempt + 1) as f32 / CONTAINMENT_ATTEMPTS as f32;
+        let r = (1.0 - z * z).sqrt();
+        let phi = (attempt as f32 + 0.3) * GOLDEN_ANGLE;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z).normalize()
+    }
+
+    /// Classify a ray crossing for parity counting (both sides count)
+    fn crossing(&self, origin: Vec3, dir: Vec3, tri: &Triangle) -> Crossing {
+        let det_epsilon = RELATIVE_DET_EPSILON * self.epsilon_scale * self.epsilon_scale;
+        let t_epsilon = RELATIVE_T_EPSILON * self.epsilon_scale;
+
+        let edge1 = tri.v1 - tri.v0;
+        let edge2 = tri.v2 - tri.v0;
+        let h = dir.cross(edge2);
+        let a = edge1.dot(h);
+        let s = origin - tri.v0;
+        if a.abs() < det_epsilon {
+            // Parallel: only matters if the ray runs in the triangle's plane
+            let plane_distance = s.dot(edge1.cross(edge2).normalize_or_zero());
+            return if plane_distance.abs() <= t_epsilon {
+                Crossing::Ambiguous
+            } else {
+                Crossing::Miss
+            };
+        }
+
+        let f = 1.0 / a;
+        let u = f * s.dot(h);
+        let q = s.cross(edge1);
+        let v = f * dir.dot(q);
+        let t = f * edge2.dot(q);
+        let margin = EDGE_BARYCENTRIC_EPSILON;
+        if t < -t_epsilon || u < -margin || v < -margin || u + v > 1.0 + margin {
+            Crossing::Miss
+        } else if t <= t_epsilon || u < margin || v < margin || u + v > 1.0 - margin {
+            Crossing::Ambiguous
+        } else {
+            Crossing::Hit
+        }
+    }
+
+    /// Closest triangle to an object-space point: (triangle, point on it, u, v)
+    fn nearest_triangle(&self, point: Vec3) -> Option<(usize, Vec3, f32, f32)> {
+        let mut best: Option<(usize, Vec3, f32, f32)> = None;
+        let mut best_sq = f32::INFINITY;
+        let mut consider = |tri_idx: usize, best_sq: &mut f32| {
+            let tri = &self.triangles[tri_idx];
+            let (closest, u, v) = Self::closest_point_on_triangle(point, tri);
+            let dist_sq = closest.distance_squared(point);
+            if dist_sq < *best_sq {
+                *best_sq = dist_sq;
+                best = Some((tri_idx, closest, u, v));
+            }
+        };
+
+        if !self.triangle_indices.is_empty() {
+            let mut stack = self.traversal_stack.take();
+            stack.clear();
+            stack.push(0usize);
+            while let Some(node_idx) = stack.pop() {
+                let node = &self.nodes[node_idx];
+                if Self::point_aabb_distance_sq(point, node) >= best_sq {
+                    continue;
+                }
+                if node.is_leaf() {
+                    for &tri_idx in &self.triangle_indices[node.triangle_range()] {
+                        consider(tri_idx, &mut best_sq);
+                    }
+                } else if let Some((left, right)) = node.children(node_idx) {
+                    // Nearer child on top of the stack
+                    let left_sq = Self::point_aabb_distance_sq(point, &self.nodes[left]);
+                    let right_sq = Self::point_aabb_distance_sq(point, &self.nodes[right]);
+                    if left_sq < right_sq {
+                        stack.extend([right, left]);
+                    } else {
+                        stack.extend([left, right]);
+                    }
+                }
+            }
+            self.traversal_stack.replace(stack);
+        }
+
+        if !self.pending.is_empty()
+            && Self::point_aabb_distance_sq(point, &self.pending_bounds) < best_sq
+        {
+            for &tri_idx in &self.pending {
+                consider(tri_idx, &mut best_sq);
+            }
+        }
+        best
+    }
+
+    /// Angle-weighted pseudo-normal (Baerentzen & Aanaes) of the feature of
+    /// a triangle at barycentrics (u, v) from closest_point_on_triangle
+    ///
+    /// Face interior: the face normal. Edge: the sum of the normals of the
+    /// triangles sharing it. Vertex: the normals of the triangles around it,
+    /// weighted by their angle at the vertex. Neighbours are found through the
+    /// tree by exact vertex position.
+    fn pseudo_normal(&self, tri_idx: usize, u: f32, v: f32) -> Vec3 {
+        let tri = &self.triangles[tri_idx];
+        let corners = [tri.v0, tri.v1, tri.v2];
+        let feature: &[Vec3] = match (u, v) {
+            (0.0, 0.0) => &corners[0..1],
+            (1.0, 0.0) => &corners[1..2],
+            (0.0, 1.0) => &corners[2..3],
+            (_, 0.0) => &corners[0..2],
+            (0.0, _) => &[tri.v0, tri.v2],
+            _ if u == 1.0 - v => &corners[1..3],
+            _ => return tri.normal,
+        };
+
+        // The vertex, or the edge midpoint, lies in every neighbour's boxes
+        let probe = feature.iter().sum::<Vec3>() / feature.len() as f32;
+        let mut sum = Vec3::ZERO;
+        self.walk_overlapping(
+            |node| Self::point_aabb_distance_sq(probe, node) == 0.0,
+            |_, triangles| {
+                for &other in triangles {
+                    let other = &self.triangles[other];
+                    let corners = [other.v0, other.v1, other.v2];
+                    if !feature.iter().all(|p| corners.contains(p)) {
+                        continue;
+                    }
+                    sum += match feature {
+                        [vertex] => {
+                            let at = corners.iter().position(|c| c == vertex).unwrap_or(0);
+                            let (a, b) = (corners[(at + 1) % 3], corners[(at + 2) % 3]);
+                            other.normal * (a - *vertex).angle_between(b - *vertex)
+                        }
+                        _ => other.normal,
+                    };
+                }
+            },
+        );
+        sum.try_normalize().unwrap_or(tri.normal)
+    }
+
+    /// First time a sphere moving from `origin` along unit `dir` touches a
+    /// triangle: (t, contact point on the triangle)
+    ///
+    /// The contact is the earliest of the face interior, the three edges
+    /// (ray vs capsule side) and the three vertices (ray vs sphere).
+    fn sweep_sphere_triangle(
+        origin: Vec3,
+        dir: Vec3,
+        radius: f32,
+        tri: &Triangle,
+    ) -> Option<(f32, Vec3)> {
+        // Already touching
+        let (nearest, _, _) = Self::closest_point_on_triangle(origin, tri);
+        if nearest.distance_squared(origin) <= radius * radius {
+            return Some((0.0, nearest));
+        }
+
+        let mut best: Option<(f32, Vec3)> = None;
+        let mut offer = |t: f32, contact: Vec3| {
+            if t >= 0.0 && best.is_none_or(|(best_t, _)| t < best_t) {
+                best = Some((t, contact));
+            }
+        };
+
+        // Face: the sphere's leading point reaches the plane inside the triangle
+        let n = tri.normal;
+        if n != Vec3::ZERO {
+            let side = (origin - tri.v0).dot(n).signum();
+            let approach = dir.dot(n) * side;
+            if approach < 0.0 {
+                let t = ((origin - tri.v0).dot(n).abs() - radius) / -approach;
+                let contact = origin + dir * t - n * side * radius;
+                // Inside test with a sliver of slack; edges cover the boundary anyway
+                let (projected, _, _) = Self::closest_point_on_triangle(contact, tri);
+                let slack = RELATIVE_T_EPSILON * (tri.v1 - tri.v0).length();
+                if projected.distance_squared(contact) <= slack * slack {
+                    offer(t, contact);
+                }
+            }
+        }
+
+        // Edges: infinite cylinder of the edge, clipped to the segment
+        for (p, q) in [(tri.v0, tri.v1), (tri.v1, tri.v2), (tri.v2, tri.v0)] {
+            if let Some((t, s)) = Self::ray_cylinder(origin, dir, p, q, radius) {
+                offer(t, p + (q - p) * s);
+            }
+        }
+
+        // Vertices
+        for v in [tri.v0, tri.v1, tri.v2] {
+            let m = origin - v;
+            let b = m.dot(dir);
+            let disc = b * b - (m.dot(m) - radius * radius);
+            if disc >= 0.0 {
+                offer(-b - disc.sqrt(), v);
+            }
+        }
+
+        best
+    }
+
+    /// First time a capsule (segment a-b, `radius`) moving along unit `dir`
+    /// touches a triangle: (t, contact point on the triangle, capsule axis
+    /// point at contact)
+    ///
+    /// Overlap at the start gives t = 0 with the closest pair. Otherwise the
+    /// earliest of the end spheres (sweep_sphere_triangle), triangle vertices
+    /// against the side (ray vs cylinder) and triangle edges against the axis
+    /// (skew segments closing to `radius`); a side lying flat on the face
+    /// touches an edge or an end sphere first, so the face needs no own case.
+    fn sweep_capsule_triangle(
+        a: Vec3,
+        b: Vec3,
+        dir: Vec3,
+        radius: f32,
+        tri: &Triangle,
+    ) -> Option<(f32, Vec3, Vec3)> {
+        let (axis, nearest) = Self::closest_points_segment_triangle(a, b, tri);
+        if axis.distance_squared(nearest) <= radius * radius {
+            return Some((0.0, nearest, axis));
+        }
+
+        let mut best: Option<(f32, Vec3, Vec3)> = None;
+        let mut offer = |t: f32, contact: Vec3, axis: Vec3| {
+            if t >= 0.0 && best.is_none_or(|(best_t, _, _)| t < best_t) {
+                best = Some((t, contact, axis));
+            }
+        };
+
+        for end in [a, b] {
+            if let Some((t, contact)) = Self::sweep_sphere_triangle(end, dir, radius, tri) {
+                offer(t, contact, end + dir * t);
+            }
+        }
+
+        // Vertices: ray from the vertex against the moving side
+        let axis_dir = b - a;
+        for v in [tri.v0, tri.v1, tri.v2] {
+            if let Some((t, s)) = Self::ray_cylinder(v, -dir, a, b, radius) {
+                offer(t, v, a + axis_dir * s + dir * t);
+            }
+        }
+
+        // Edges: axis and edge lines `radius` apart along their common normal
+        for (p, q) in [(tri.v0, tri.v1), (tri.v1, tri.v2), (tri.v2, tri.v0)] {
+            let edge = q - p;
+            let Some(n) = axis_dir.cross(edge).try_normalize() else {
+                continue; // Parallel: the vertex and end cases cover it
+            };
+            let gap = (a - p).dot(n);
+            let side = gap.signum();
+            let approach = dir.dot(n) * side;
+            if approach >= 0.0 || gap.abs() < radius {
+                continue;
+            }
+            let t = (gap.abs() - radius) / -approach;
+
+            // Closest points of the two lines at that time (Ericson 5.1.9)
+            let r = a + dir * t - p;
+            let (aa, ee, ab) = (axis_dir.dot(axis_dir), edge.dot(edge), axis_dir.dot(edge));
+            let (c, f) = (axis_dir.dot(r), edge.dot(r));
+            let s = (ab * f - c * ee) / (aa * ee - ab * ab);
+            let u = (ab * s + f) / ee;
+            if (0.0..=1.0).contains(&s) && (0.0..=1.0).contains(&u) {
+                offer(t, p + edge * u, a + axis_dir * s + dir * t);
+            }
+        }
+
+        best
+    }
+
+    /// Ray against the side of the cylinder around segment p-q
+    ///
+    /// # Returns
+    /// (t, s) of the first crossing, s along p-q in [0, 1]; None for a miss,
+    /// a ray parallel to the axis or a crossing beyond the ends
+    fn ray_cylinder(origin: Vec3, dir: Vec3, p: Vec3, q: Vec3, radius: f32) -> Option<(f32, f32)> {
+        let e = q - p;
+        let m = origin - p;
+        let (dd, md, nd) = (e.dot(e), m.dot(e), dir.dot(e));
+        let a = dd - nd * nd;
+        if a.abs() <= f32::EPSILON * dd {
+            return None;
+        }
+        let b = dd * m.dot(dir) - nd * md;
+        let c = dd * (m.dot(m) - radius * radius) - md * md;
+        let disc = b * b - a * c;
+        if disc < 0.0 {
+            return None;
+        }
+        let t = (-b - disc.sqrt()) / a;
+        let s = (md + t * nd) / dd;
+        (0.0..=1.0).contains(&s).then_some((t, s))
+    }
+
+    /// Closest points of segment a-b and a triangle: (on segment, on triangle)
+    fn closest_points_segment_triangle(a: Vec3, b: Vec3, tri: &Triangle) -> (Vec3, Vec3) {
+        // Segment through the face
+        let n = tri.normal;
+        let (da, db) = ((a - tri.v0).dot(n), (b - tri.v0).dot(n));
+        if n != Vec3::ZERO && da * db <= 0.0 && da != db {
+            let crossing = a + (b - a) * (da / (da - db));
+            let (projected, _, _) = Self::closest_point_on_triangle(crossing, tri);
+            let slack = RELATIVE_T_EPSILON * (tri.v1 - tri.v0).length();
+            if projected.distance_squared(crossing) <= slack * slack {
+                return (crossing, projected);
+            }
+        }
+
+        let mut best = (a, Self::closest_point_on_triangle(a, tri).0);
+        let mut consider = |pair: (Vec3, Vec3)| {
+            if pair.0.distance_squared(pair.1) < best.0.distance_squared(best.1) {
+                best = pair;
+            }
+        };
+        consider((b, Self::closest_point_on_triangle(b, tri).0));
+        for (p, q) in [(tri.v0, tri.v1), (tri.v1, tri.v2), (tri.v2, tri.v0)] {
+            consider(Self::closest_points_segments(a, b, p, q));
+        }
+        best
+    }
+
+    /// Closest points of segments p1-q1 and p2-q2
+    ///
+    /// Clamped line solution from Ericson, Real-Time Collision Detection 5.1.9.
+    fn closest_points_segments(p1: Vec3, q1: Vec3, p2: Vec3, q2: Vec3) -> (Vec3, Vec3) {
+        let (d1, d2, r) = (q1 - p1, q2 - p2, p1 - p2);
+        let (a, e, f) = (d1.dot(d1), d2.dot(d2), d2.dot(r));
+        if a <= f32::EPSILON && e <= f32::EPSILON {
+            return (p1, p2);
+        }
+        let (s, t) = if a <= f32::EPSILON {
+            (0.0, (f / e).clamp(0.0, 1.0))
+        } else {
+            let c = d1.dot(r);
+            if e <= f32::EPSILON {
+                ((-c / a).clamp(0.0, 1.0), 0.0)
+            } else {
+                let b = d1.dot(d2);
+                let denom = a * e - b * b;
+                let s = if denom > 0.0 {
+                    ((b * f - c * e) / denom).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let t = (b * s + f) / e;
+                if t < 0.0 {
+                    ((-c / a).clamp(0.0, 1.0), 0.0)
+                } else if t > 1.0 {
+                    (((b - c) / a).clamp(0.0, 1.0), 1.0)
+                } else {
+                    (s, t)
+                }
+            }
+        };
+        (p1 + d1 * s, p2 + d2 * t)
+    }
+
+    /// Axis-aligned bounds of a transformed box (from its 8 corners)
+    fn transform_bounds(matrix: Mat4, min: Vec3, max: Vec3) -> (Vec3, Vec3) {
+        let (mut out_min, mut out_max) = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN));
+        for corner in 0..8 {
+            let p = Vec3::select(
+                glam::BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+                max,
+                min,
+            );
+            let p = matrix.transform_point3(p);
+            out_min = out_min.min(p);
+            out_max = out_max.max(p);
+        }
+        (out_min, out_max)
+    }
+
+    /// World-space frustum planes moved into object space
+    ///
+    /// # Returns
+    /// None if a plane is invalid or the frustum has no volume
+    fn local_frustum(&self, planes: &[f32], model: Mat4) -> Option<Frustum> {
+        let offset = self.origin.as_vec3();
+        let mut local = [Vec4::ZERO; FRUSTUM_PLANES];
+        for (plane, world) in local.iter_mut().zip(planes.chunks_exact(PLANE_STRIDE)) {
+            // Planes transform by the transpose of the point transform
+            let p = model.transpose() * Vec4::from_slice(world);
+            let n = p.truncate();
+            let length = n.length();
+            if !(length.is_finite() && length > 0.0 && p.w.is_finite()) {
+                return None;
+            }
+            *plane = Vec4::from((n, p.w + n.dot(offset))) / length;
+        }
+
+        // Corners are the plane-triple intersections that lie inside the rest
+        let mut corners = Vec::new();
+        for a in 0..FRUSTUM_PLANES {
+            for b in a + 1..FRUSTUM_PLANES {
+                for c in b + 1..FRUSTUM_PLANES {
+                    let [pa, pb, pc] = [local[a], local[b], local[c]];
+                    let (na, nb, nc) = (pa.truncate(), pb.truncate(), pc.truncate());
+                    let det = na.dot(nb.cross(nc));
+                    if det.abs() < RELATIVE_FRUSTUM_EPSILON {
+                        continue;
+                    }
+                    let point =
+                        (nb.cross(nc) * -pa.w + nc.cross(na) * -pb.w + na.cross(nb) * -pc.w) / det;
+                    let tolerance = RELATIVE_FRUSTUM_EPSILON * (1.0 + point.abs().max_element());
+                    if local
+                        .iter()
+                        .all(|p| p.truncate().dot(point) + p.w >= -tolerance)
+                    {
+                        corners.push(point);
+                    }
+                }
+            }
+        }
+
+        // Zero volume: the corners don't span all three dimensions
+        let first = *corners.first()?;
+        let farthest = |from: &dyn Fn(Vec3) -> f32| {
+            corners
+                .iter()
+                .copied()
+                .max_by(|a, b| from(*a).total_cmp(&from(*b)))
+                .unwrap()
+        };
+        let second = farthest(&|p| p.distance(first));
+        let size = second.distance(first);
+        let axis = (second - first).normalize_or_zero();
+        let third = farthest(&|p| (p - first).cross(axis).length());
+        let normal = axis.cross(third - first).normalize_or_zero();
+        let fourth = farthest(&|p| (p - first).dot(normal).abs());
+        let thin = RELATIVE_FRUSTUM_EPSILON * size;
+        if size <= 0.0
+            || (third - first).cross(axis).length() <= thin
+            || (fourth - first).dot(normal).abs() <= thin
+        {
+            return None;
+        }
+
+        let mut edges = Vec::new();
+        for a in 0..FRUSTUM_PLANES {
+            for b in a + 1..FRUSTUM_PLANES {
+                let edge = local[a].truncate().cross(local[b].truncate());
+                if edge.length_squared() > RELATIVE_FRUSTUM_EPSILON {
+                    edges.push(edge);
+                }
+            }
+        }
+
+        Some(Frustum {
+            planes: local,
+            corners,
+            edges,
+        })
+    }
+
+    /// True if a triangle is inside or crosses a frustum
+    fn triangle_in_frustum(tri: &Triangle, frustum: &Frustum) -> bool {
+        let v = [tri.v0, tri.v1, tri.v2];
+        let mut inside = true;
+        for plane in &frustum.planes {
+            let n = plane.truncate();
+            let outside = v.iter().filter(|p| n.dot(**p) + plane.w < 0.0).count();
+            if outside == 3 {
+                return false;
+            }
+            inside &= outside == 0;
+        }
+        if inside {
+            return true;
+        }
+
+        // Crosses a plane: look for a separating axis among the triangle
+        // normal and the triangle edges crossed with the frustum edges
+        let separated = |axis: Vec3| {
+            let (tri_min, tri_max) = v
+                .iter()
+                .map(|p| axis.dot(*p))
+                .fold((f32::MAX, f32::MIN), |(lo, hi), d| (lo.min(d), hi.max(d)));
+            let (min, max) = frustum
+                .corners
+                .iter()
+                .map(|p| axis.dot(*p))
+                .fold((f32::MAX, f32::MIN), |(lo, hi), d| (lo.min(d), hi.max(d)));
+            tri_max < min || tri_min > max
+        };
+        let tri_edges = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+        if separated(tri_edges[0].cross(tri_edges[1])) {
+            return false;
+        }
+        !tri_edges
+            .iter()
+            .flat_map(|e| frustum.edges.iter().map(move |f| e.cross(*f)))
+            .any(separated)
+    }
+
+    /// Visit every leaf whose bounds pass `overlaps`, pruning failed subtrees
+    ///
+    /// `visit` gets the leaf's node index and its triangles; appended
+    /// triangles not in the tree yet come last, with no node index.
+    fn walk_overlapping(
+        &self,
+        overlaps: impl Fn(&BVHNode) -> bool,
+        mut visit: impl FnMut(Option<usize>, &[usize]),
+    ) {
+        if !self.triangle_indices.is_empty() {
+            let mut stack = self.traversal_stack.take();
+            stack.clear();
+            stack.push(0usize);
+            while let Some(node_idx) = stack.pop() {
+                let node = &self.nodes[node_idx];
+                if !overlaps(node) {
+                    continue;
+                }
+                if let Some((left, right)) = node.children(node_idx) {
+                    stack.push(right);
+                    stack.push(left);
+                } else {
+                    visit(
+                        Some(node_idx),
+                        &self.triangle_indices[node.triangle_range()],
+                    );
+                }
+            }
+            self.traversal_stack.replace(stack);
+        }
+
+        if !self.pending.is_empty() && overlaps(&self.pending_bounds) {
+            visit(None, &self.pending);
+        }
+    }
+
+    /// Separating axis test between a triangle and a box (center, half extents)
+    ///
+    /// Akenine-Moller: the box axes, the triangle normal and the 9 edge
+    /// cross products. Touching counts as overlapping.
+    fn triangle_overlaps_box(tri: &Triangle, center: Vec3, half: Vec3) -> bool {
+        let v = [tri.v0 - center, tri.v1 - center, tri.v2 - center];
+        let edges = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+        let separated = |axis: Vec3| {
+            let p = [axis.dot(v[0]), axis.dot(v[1]), axis.dot(v[2])];
+            let r = half.dot(axis.abs());
+            p[0].min(p[1]).min(p[2]) > r || p[0].max(p[1]).max(p[2]) < -r
+        };
+
+        if [Vec3::X, Vec3::Y, Vec3::Z].into_iter().any(separated) {
+            return false;
+        }
+        if separated(edges[0].cross(edges[1])) {
+            return false;
+        }
+        !edges
+            .iter()
+            .flat_map(|e| [Vec3::X.cross(*e), Vec3::Y.cross(*e), Vec3::Z.cross(*e)])
+            .any(separated)
+    }
+
+    /// Squared distance from a point to a node's box (0 inside)
+    fn point_aabb_distance_sq(point: Vec3, node: &BVHNode) -> f32 {
+        let outside = (node.bounds_min - point)
+            .max(point - node.bounds_max)
+            .max(Vec3::ZERO);
+        outside.length_squared()
+    }
+
+    /// Closest point on a triangle and its barycentrics (u on v1, v on v2)
+    ///
+    /// Voronoi-region walk from Ericson, Real-Time Collision Detection 5.1.5.
+    fn closest_point_on_triangle(p: Vec3, tri: &Triangle) -> (Vec3, f32, f32) {
+        let (a, b, c) = (tri.v0, tri.v1, tri.v2);
+        let ab = b - a;
+        let ac = c - a;
+        let ap = p - a;
+        let d1 = ab.dot(ap);
+        let d2 = ac.dot(ap);
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return (a, 0.0, 0.0);
+        }
+
+        let bp = p - b;
+        let d3 = ab.dot(bp);
+        let d4 = ac.dot(bp);
+        if d3 >= 0.0 && d4 <= d3 {
+            return (b, 1.0, 0.0);
+        }
+
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            let u = d1 / (d1 - d3);
+            return (a + ab * u, u, 0.0);
+        }
+
+        let cp = p - c;
+        let d5 = ab.dot(cp);
+        let d6 = ac.dot(cp);
+        if d6 >= 0.0 && d5 <= d6 {
+            return (c, 0.0, 1.0);
+        }
+
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            let v = d2 / (d2 - d6);
+            return (a + ac * v, 0.0, v);
+        }
+
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+            let v = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+            return (b + (c - b) * v, 1.0 - v, v);
+        }
+
+        let denom = 1.0 / (va + vb + vc);
+        let u = vb * denom;
+        let v = vc * denom;
+        (a + ab * u + ac * v, u, v)
+    }
+
+    /// Interpolated vertex normal at barycentrics (u, v), or the face normal
+    fn shading_normal(tri: &Triangle, u: f32, v: f32) -> Vec3 {
+        match tri.vertex_normals {
+            Some([n0, n1, n2]) => {
+                let smooth = (n0 * (1.0 - u - v) + n1 * u + n2 * v).normalize_or_zero();
+                if smooth == Vec3::ZERO {
+                    tri.normal
+                } else {
+                    smooth
+                }
+            }
+            None => tri.normal,
+        }
+    }
+
+    /// Ray-AABB intersection using slab method
+    ///
+    /// Uses pre-computed inverse direction for efficiency. The near slab is
+    /// picked by the sign of inv_dir, and axes the ray is parallel to (inv_dir
+    /// = ±inf) are a plain containment test, so rays lying exactly on a face
+    /// never produce 0 * inf = NaN.
+    ///
+    /// `expand` grows the box by that much per axis (swept volume culling).
+    #[inline]
+    fn ray_aabb_intersect(
+        origin: Vec3,
+        inv_dir: Vec3,
+        node: &BVHNode,
+        max_t: f32,
+        expand: Vec3,
+    ) -> bool {
+        Self::ray_aabb_entry(origin, inv_dir, node, max_t, expand).is_some()
+    }
+
+    /// Entry distance of the ray into the box (negative when the origin is
+    /// inside), or None if it misses or enters at or past max_t
+    #[inline]
+    fn ray_aabb_entry(
+        origin: Vec3,
+        inv_dir: Vec3,
+        node: &BVHNode,
+        max_t: f32,
+        expand: Vec3,
+    ) -> Option<f32> {
+        let mut t_enter = f32::NEG_INFINITY;
+        let mut t_exit = f32::INFINITY;
+
+        for axis in 0..3 {
+            let (lo, hi, o, inv) = (
+                node.bounds_min[axis] - expand[axis],
+                node.bounds_max[axis] + expand[axis],
+                origin[axis],
+                inv_dir[axis],
+            );
+            if inv.is_infinite() {
+                if o < lo || o > hi {
+                    return None;
+                }
+                continue;
+            }
+            let (near, far) = if inv.is_sign_negative() {
+                (hi, lo)
+            } else {
+                (lo, hi)
+            };
+            t_enter = t_enter.max((near - o) * inv);
+            t_exit = t_exit.min((far - o) * inv);
+        }
+
+        // Ray intersects if entry < exit, exit >= 0, and entry < current best
+        (t_enter <= t_exit && t_exit >= 0.0 && t_enter < max_t).then_some(t_enter)
+    }
+
+    /// Möller–Trumbore ray-triangle intersection
+    ///
+    /// Fast algorithm that computes barycentric coordinates directly.
+    /// Culls by cull_mode (default: backface hits rejected to match Three.js)
+    #[inline]
+    fn ray_triangle_intersect(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        tri: &Triangle,
+        tri_idx: usize,
+    ) -> Option<RayHit> {
+        self.ray_triangle_intersect_culled(origin, dir, tri, tri_idx, self.cull_mode)
+    }
+
+    /// ray_triangle_intersect with an explicit cull mode (CULL_*)
+    #[inline]
+    fn ray_triangle_intersect_culled(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        tri: &Triangle,
+        tri_idx: usize,
+        cull_mode: u8,
+    ) -> Option<RayHit> {
+        tri.intersect_ray(origin, dir, tri_idx, cull_mode, self.epsilon_scale)
+    }
+
+    /// ray_triangle_intersect in f64, with the same culling and epsilons
+    ///
+    /// # Returns
+    /// The f64 hit distance and the hit rounded to f32
+    fn ray_triangle_intersect_f64(
+        &self,
+        origin: DVec3,
+        dir: DVec3,
+        tri: &Triangle,
+        tri_idx: usize,
+    ) -> Option<(f64, RayHit)> {
+        let scale = self.epsilon_scale as f64;
+        let det_epsilon = RELATIVE_DET_EPSILON as f64 * scale * scale;
+        let t_epsilon = RELATIVE_T_EPSILON as f64 * scale;
+
+        let v0 = tri.v0.as_dvec3();
+        let edge1 = tri.v1.as_dvec3() - v0;
+        let edge2 = tri.v2.as_dvec3() - v0;
+        let h = dir.cross(edge2);
+        let a = edge1.dot(h);
+
+        let culled = match self.cull_mode {
+            CULL_NONE => a.abs() < det_epsilon,
+            CULL_FRONT => a > -det_epsilon,
+            _ => a < det_epsilon,
+        };
+        if culled {
+            return None;
+        }
+
+        let f = 1.0 / a;
+        let s = origin - v0;
+        let u = f * s.dot(h);
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge1);
+        let v = f * dir.dot(q);
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = f * edge2.dot(q);
+        if t <= t_epsilon {
+            return None;
+        }
+
+        let (u, v) = (u as f32, v as f32);
+        let normal = Self::shading_normal(tri, u, v);
+        Some((
+            t,
+            RayHit {
+                point: (origin + dir * t).as_vec3(),
+                normal: if a < 0.0 { -normal } else { normal },
+                distance: t as f32,
+                triangle: tri_idx,
+                u,
+                v,
+            },
+        ))
+    }
+}
+
+/// One placement of a SceneRaycaster mesh
+struct Instance {
+    mesh: usize,
+    model: Mat4,
+    inverse_model: Mat4,
+    /// World-space bounds of the mesh under `model`
+    bounds_min: Vec3,
+    bounds_max: Vec3,
+}
+
+/// Two-level BVH: a tree over instance bounds above one BVHRaycaster per mesh
+///
+/// Meshes are added once and placed any number of times with a model
+/// matrix each, so a single call finds the closest hit across the scene.
+/// Moving an instance only refits the top-level tree; adding one rebuilds it.
+#[wasm_bindgen]
+pub struct SceneRaycaster {
+    meshes: Vec<BVHRaycaster>,
+    instances: Vec<Instance>,
+    /// Top-level tree, same layout as BVHRaycaster's; leaf ranges index `order`
+    nodes: Vec<BVHNode>,
+    /// Instance IDs, reordered during the top-level build
+    order: Vec<usize>,
+    traversal_stack: RefCell<Vec<usize>>,
+}
+
+impl Default for SceneRaycaster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl SceneRaycaster {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            meshes: Vec::new(),
+            instances: Vec::new(),
+            nodes: Vec::new(),
+            order: Vec::new(),
+            traversal_stack: RefCell::new(Vec::with_capacity(64)),
+        }
+    }
+
+    /// Take ownership of a built raycaster, returns its mesh ID
+    #[wasm_bindgen]
+    pub fn add_mesh(&mut self, raycaster: BVHRaycaster) -> u32 {
+        self.meshes.push(raycaster);
+        (self.meshes.len() - 1) as u32
+    }
+
+    /// Place a mesh in the scene
+    ///
+    /// # Arguments
+    /// * `mesh_id` - ID returned by add_mesh
+    /// * `model_matrix` - 4x4 column-major world transform
+    ///
+    /// # Returns
+    /// The instance ID, reported by intersect hits
+    #[wasm_bindgen]
+    pub fn add_instance(&mut self, mesh_id: u32, model_matrix: &[f32]) -> Result<u32, String> {
+        if mesh_id as usize >= self.meshes.len() {
+            return Err(format!("no mesh {mesh_id}"));
+        }
+        let model = BVHRaycaster::parse_matrix(model_matrix)?;
+        let mut instance = Instance {
+            mesh: mesh_id as usize,
+            model,
+            inverse_model: model.inverse(),
+            bounds_min: Vec3::ZERO,
+            bounds_max: Vec3::ZERO,
+        };
+        self.update_bounds(&mut instance);
+        self.instances.push(instance);
+        self.rebuild();
+        Ok((self.instances.len() - 1) as u32)
+    }
+
+    /// Move an instance; refits the top-level tree without rebuilding it
+    #[wasm_bindgen]
+    pub fn set_instance_matrix(
+        &mut self,
+        instance_id: u32,
+        model_matrix: &[f32],
+    ) -> Result<(), String> {
+        let model = BVHRaycaster::parse_matrix(model_matrix)?;
+        let mut instance = self
+            .instances
+            .get(instance_id as usize)
+            .map(|i| Instance {
+                model,
+                inverse_model: model.inverse(),
+                ..*i
+            })
+            .ok_or_else(|| format!("no instance {instance_id}"))?;
+        self.update_bounds(&mut instance);
+        self.instances[instance_id as usize] = instance;
+        self.refit();
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn instance_count(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Closest hit across all instances
+    ///
+    /// # Arguments
+    /// * `ray_origin` - World-space ray origin
+    /// * `ray_direction` - World-space ray direction
+    ///
+    /// # Returns
+    /// `Some([px, py, pz, nx, ny, nz, distance, instance_id, triangle_id])` or
+    /// `None`. Point, normal and distance are in world space; the triangle
+    /// index is in the mesh's original input order.
+    #[wasm_bindgen]
+    pub fn intersect(&self, ray_origin: &[f32], ray_direction: &[f32]) -> Option<Vec<f32>> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let origin = Vec3::from_slice(ray_origin);
+        let direction = Vec3::from_slice(ray_direction).normalize();
+        let inv_dir = direction.recip();
+
+        // (world distance, hit array)
+        let mut closest: Option<(f32, [f32; SCENE_HIT_STRIDE])> = None;
+        let mut max_t = f32::INFINITY;
+        let mut stack = self.traversal_stack.take();
+        stack.clear();
+        stack.push(0usize);
+        while let Some(node_idx) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            if !BVHRaycaster::ray_aabb_intersect(origin, inv_dir, node, max_t, Vec3::ZERO) {
+                continue;
+            }
+            if let Some((left, right)) = node.children(node_idx) {
+                stack.push(right);
+                stack.push(left);
+                continue;
+            }
+            for &instance_id in &self.order[node.triangle_range()] {
+                let instance = &self.instances[instance_id];
+                let mesh = &self.meshes[instance.mesh];
+                let ray = mesh.transform_ray(
+                    instance.model,
+                    instance.inverse_model,
+                    ray_origin,
+                    ray_direction,
+                );
+                // Object units per world unit along the ray
+                let stretch = instance.inverse_model.transform_vector3(direction).length();
+                let Some(hit) = mesh.traverse_bvh_within(
+                    ray.origin,
+                    ray.direction,
+                    ray.inv_dir,
+                    max_t * stretch,
+                ) else {
+                    continue;
+                };
+
+                let [px, py, pz, nx, ny, nz, _] = mesh.hit_to_world_array(&ray, &hit);
+                max_t = hit.distance / stretch;
+                let triangle = mesh.triangles[hit.triangle].index;
+                closest = Some((
+                    max_t,
+                    [
+                        px,
+                        py,
+                        pz,
+                        nx,
+                        ny,
+                        nz,
+                        max_t,
+                        instance_id as f32,
+                        triangle as f32,
+                    ],
+                ));
+            }
+        }
+        self.traversal_stack.replace(stack);
+
+        closest.map(|(_, hit)| hit.to_vec())
+    }
+}
+
+impl SceneRaycaster {
+    /// World bounds of an instance: its mesh's root box under the model matrix
+    fn update_bounds(&self, instance: &mut Instance) {
+        let mesh = &self.meshes[instance.mesh];
+        let (min, max) = match mesh.nodes.first() {
+            Some(root) if !mesh.triangles.is_empty() => (root.bounds_min, root.bounds_max),
+            // Nothing to hit; a point at the instance origin keeps the tree valid
+            _ => (Vec3::ZERO, Vec3::ZERO),
+        };
+        let offset = mesh.origin.as_vec3();
+        (instance.bounds_min, instance.bounds_max) =
+            BVHRaycaster::transform_bounds(instance.model, min + offset, max + offset);
+    }
+
+    /// Rebuild the top-level tree from scratch (median splits)
+    fn rebuild(&mut self) {
+        self.nodes.clear();
+        self.order = (0..self.instances.len()).collect();
+        if !self.instances.is_empty() {
+            Self::build_recursive(
+                &self.instances,
+                &mut self.order,
+                0,
+                self.instances.len(),
+                &mut self.nodes,
+            );
+        }
+    }
+
+    /// Build a top-level subtree over order[start..end], one instance per leaf
+    ///
+    /// # Returns
+    /// Index of the created node in `nodes`
+    fn build_recursive(
+        instances: &[Instance],
+        order: &mut [usize],
+        start: usize,
+        end: usize,
+        nodes: &mut Vec<BVHNode>,
+    ) -> usize {
+        let (bounds_min, bounds_max) = order[start..end].iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), &i| {
+                (
+                    min.min(instances[i].bounds_min),
+                    max.max(instances[i].bounds_max),
+                )
+            },
+        );
+        let node_idx = nodes.len();
+        nodes.push(BVHNode {
+            bounds_min,
+            bounds_max,
+            offset: start as u32,
+            triangle_count: (end - start) as u32,
+        });
+        if end - start == 1 {
+            return node_idx;
+        }
+
+        // Split at the median center along the longest axis
+        let axis = BVHRaycaster::longest_axis(bounds_max - bounds_min);
+        let center = |i: usize| (instances[i].bounds_min + instances[i].bounds_max)[axis];
+        let mid = start + (end - start) / 2;
+        order[start..end]
+            .select_nth_unstable_by(mid - start, |&a, &b| center(a).total_cmp(&center(b)));
+
+        nodes[node_idx].triangle_count = 0;
+        Self::build_recursive(instances, order, start, mid, nodes);
+        let right_idx = Self::build_recursive(instances, order, mid, end, nodes);
+        nodes[node_idx].offset = right_idx as u32;
+        node_idx
+    }
+
+    /// Recompute top-level bounds after instances moved
+    ///
+    /// Children always follow their parent, so a backwards sweep sees both
+    /// children before the node itself.
+    fn refit(&mut self) {
+        for node_idx in (0..self.nodes.len()).rev() {
+            let node = self.nodes[node_idx];
+            let (min, max) = match node.children(node_idx) {
+                Some((left, right)) => (
+                    self.nodes[left]
+                        .bounds_min
+                        .min(self.nodes[right].bounds_min),
+                    self.nodes[left]
+                        .bounds_max
+                        .max(self.nodes[right].bounds_max),
+                ),
+                None => {
+                    let instance = &self.instances[self.order[node.offset as usize]];
+                    (instance.bounds_min, instance.bounds_max)
+                }
+            };
+            self.nodes[node_idx].bounds_min = min;
+            self.nodes[node_idx].bounds_max = max;
+        }
+    }
+}
+
+/// Uniform grid accelerator for flat meshes (terrain, floors, walls)
+///
+/// Cells tile the two longest axes of the mesh bounds and span the whole
+/// third one, so a ray walks them with a 2D DDA and stops at the first cell
+/// holding a hit. Cheaper to build than a BVH and as fast on evenly spread
+/// surfaces; clumped or deep meshes are better served by BVHRaycaster.
+/// Hits are the same as BVHRaycaster's with backface culling.
+#[wasm_bindgen]
+pub struct GridRaycaster {
+    triangles: Vec<Triangle>,
+    /// Mesh bounds, as a node for ray_aabb_entry
+    bounds: BVHNode,
+    /// Mesh axes the cells tile; cells run the full extent of the third
+    axes: [usize; 2],
+    /// Cells along axes[0] and axes[1]
+    resolution: [usize; 2],
+    cell_size: Vec2,
+    /// Cell (i, j) holds cell_triangles[cell_start[c]..cell_start[c + 1]]
+    /// with c = j * resolution[0] + i
+    cell_start: Vec<u32>,
+    /// Indices into `triangles`; a triangle is listed in every cell it overlaps
+    cell_triangles: Vec<u32>,
+    /// Bounds diagonal, the length scale of the intersection epsilons
+    epsilon_scale: f32,
+}
+
+#[wasm_bindgen]
+impl GridRaycaster {
+    /// Build the grid from mesh geometry, resolution picked from the
+    /// triangle density
+    ///
+    /// # Arguments
+    /// * `positions` - Flat vertex array: [x0,y0,z0, x1,y1,z1, ...]
+    /// * `indices` - Triangle indices: [i0,i1,i2, i3,i4,i5, ...]
+    #[wasm_bindgen(constructor)]
+    pub fn from_geometry(positions: &[f32], indices: &[u32]) -> Self {
+        Self::with_resolution(positions, indices, 0)
+    }
+
+    /// from_geometry with a fixed resolution
+    ///
+    /// # Arguments
+    /// * `resolution` - Cells along the longest axis (the other axis gets
+    ///   square-ish cells), 0 = about GRID_TRIANGLES_PER_CELL triangles per
+    ///   cell. Capped at MAX_GRID_RESOLUTION.
+    #[wasm_bindgen]
+    pub fn with_resolution(positions: &[f32], indices: &[u32], resolution: u32) -> Self {
+        let (triangles, _) = BVHRaycaster::build_triangles(
+            VertexPositions::flat(positions),
+            BVHRaycaster::faces(indices),
+        );
+        let all: Vec<usize> = (0..triangles.len()).collect();
+        let (bounds_min, bounds_max) = if triangles.is_empty() {
+            (Vec3::ZERO, Vec3::ZERO)
+        } else {
+            BVHRaycaster::compute_bounds(&triangles, &all)
+        };
+        let bounds = BVHNode {
+            bounds_min,
+            bounds_max,
+            offset: 0,
+            triangle_count: 0,
+        };
+        let epsilon_scale = BVHRaycaster::auto_epsilon_scale(&bounds);
+
+        // Cells tile the two longest axes, in axis order
+        let extent = bounds_max - bounds_min;
+        let thin = (0..3)
+            .min_by(|&a, &b| extent[a].total_cmp(&extent[b]))
+            .unwrap();
+        let axes = match thin {
+            0 => [1, 2],
+            1 => [0, 2],
+            _ => [0, 1],
+        };
+        let extent = Vec2::new(extent[axes[0]], extent[axes[1]]);
+        let longest = extent.max_element();
+
+        let cells_long = if resolution > 0 {
+            resolution as f32
+        } else {
+            let cells = (triangles.len() as f32 / GRID_TRIANGLES_PER_CELL).max(1.0);
+            let area = extent.x * extent.y;
+            let cell_len = if area > 0.0 {
+                (area / cells).sqrt()
+            } else {
+                longest / cells
+            };
+            (longest / cell_len).ceil()
+        };
+        let cells_along = |e: f32| {
+            if longest > 0.0 {
+                ((e / longest * cells_long).ceil() as u32).clamp(1, MAX_GRID_RESOLUTION) as usize
+            } else {
+                1
+            }
+        };
+        let resolution = [cells_along(extent.x), cells_along(extent.y)];
+        let cell_size = extent / Vec2::new(resolution[0] as f32, resolution[1] as f32);
+
+        let mut grid = Self {
+            triangles,
+            bounds,
+            axes,
+            resolution,
+            cell_size,
+            cell_start: Vec::new(),
+            cell_triangles: Vec::new(),
+            epsilon_scale,
+        };
+        grid.fill_cells();
+        grid
+    }
+
+    /// Cells along the two tiled axes
+    ///
+    /// # Returns
+    /// [cells along axes[0], cells along axes[1], axes[0], axes[1]]
+    #[wasm_bindgen]
+    pub fn get_resolution(&self) -> Vec<u32> {
+        vec![
+            self.resolution[0] as u32,
+            self.resolution[1] as u32,
+            self.axes[0] as u32,
+            self.axes[1] as u32,
+        ]
+    }
+
+    /// Ray-mesh intersection by walking the grid
+    ///
+    /// # Returns
+    /// `Some([px, py, pz, nx, ny, nz, distance])`, or `None` on a miss or if
+    /// the model matrix is not 16 floats (same as BVHRaycaster::intersect)
+    #[wasm_bindgen]
+    pub fn intersect(
+        &self,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+    ) -> Option<Vec<f32>> {
+        let model = BVHRaycaster::parse_matrix(model_matrix).ok()?;
+        let inverse_model = model.inverse();
+        let origin = inverse_model.transform_point3(Vec3::from_slice(ray_origin));
+        let direction = inverse_model
+            .transform_vector3(Vec3::from_slice(ray_direction).normalize())
+            .normalize();
+
+        let hit = self.walk(origin, direction)?;
+        let point = model.transform_point3(hit.point);
+        let normal = inverse_model
+            .transpose()
+            .transform_vector3(hit.normal)
+            .normalize();
+        Some(vec![
+            point.x,
+            point.y,
+            point.z,
+            normal.x,
+            normal.y,
+            normal.z,
+            hit.distance,
+        ])
+    }
+}
+
+impl GridRaycaster {
+    /// Cell of a coordinate along tiled axis `k`, clamped to the grid
+    fn cell_coord(&self, k: usize, p: f32) -> usize {
+        let min = self.bounds.bounds_min[self.axes[k]];
+        // NaN (zero-size cells) and negatives saturate to 0
+        (((p - min) / self.cell_size[k]) as usize).min(self.resolution[k] - 1)
+    }
+
+    /// Bin every triangle into the cells its bounds overlap (CSR layout)
+    fn fill_cells(&mut self) {
+        let cell_count = self.resolution[0] * self.resolution[1];
+        // Widen each triangle a little so one lying on a cell border is
+        // found from both sides
+        let margin = RELATIVE_T_EPSILON * self.epsilon_scale;
+        let spans: Vec<[usize; 4]> = self
+            .triangles
+            .iter()
+            .map(|tri| {
+                let min = tri.v0.min(tri.v1).min(tri.v2) - margin;
+                let max = tri.v0.max(tri.v1).max(tri.v2) + margin;
+                [
+                    self.cell_coord(0, min[self.axes[0]]),
+                    self.cell_coord(0, max[self.axes[0]]),
+                    self.cell_coord(1, min[self.axes[1]]),
+                    self.cell_coord(1, max[self.axes[1]]),
+                ]
+            })
+            .collect();
+        let cells = |&[i0, i1, j0, j1]: &[usize; 4]| {
+            (j0..=j1).flat_map(move |j| (i0..=i1).map(move |i| (i, j)))
+        };
+
+        let mut counts = vec![0u32; cell_count + 1];
+        for span in &spans {
+            for (i, j) in cells(span) {
+                counts[j * self.resolution[0] + i + 1] += 1;
+            }
+        }
+        for c in 0..cell_count {
+            counts[c + 1] += counts[c];
+        }
+        let mut cursor = counts.clone();
+        self.cell_triangles = vec![0; counts[cell_count] as usize];
+        for (tri_idx, span) in spans.iter().enumerate() {
+            for (i, j) in cells(span) {
+                let slot = &mut cursor[j * self.resolution[0] + i];
+                self.cell_triangles[*slot as usize] = tri_idx as u32;
+                *slot += 1;
+            }
+        }
+        self.cell_start = counts;
+    }
+
+    /// Closest object-space hit, visiting cells in ray order (2D DDA)
+    ///
+    /// A hit is only final once it is no farther than the current cell's
+    /// exit: a triangle spanning several cells can be hit in a later one
+    /// while a nearer triangle waits in the cells in between.
+    fn walk(&self, origin: Vec3, dir: Vec3) -> Option<RayHit> {
+        if self.triangles.is_empty() {
+            return None;
+        }
+        let t_enter = BVHRaycaster::ray_aabb_entry(
+            origin,
+            dir.recip(),
+            &self.bounds,
+            f32::INFINITY,
+            Vec3::ZERO,
+        )?;
+        let start = origin + dir * t_enter.max(0.0);
+
+        let mut cell = [0usize; 2];
+        let mut step = [0isize; 2];
+        let mut t_next = [f32::INFINITY; 2];
+        let mut t_delta = [f32::INFINITY; 2];
+        for k in 0..2 {
+            let axis = self.axes[k];
+            let (o, d) = (origin[axis], dir[axis]);
+            cell[k] = self.cell_coord(k, start[axis]);
+            if d == 0.0 || self.resolution[k] == 1 {
+                continue;
+            }
+            let min = self.bounds.bounds_min[axis];
+            let size = self.cell_size[k];
+            let (border, sign) = if d > 0.0 {
+                (cell[k] + 1, 1)
+            } else {
+                (cell[k], -1)
+            };
+            step[k] = sign;
+            t_next[k] = (min + border as f32 * size - o) / d;
+            t_delta[k] = size / d.abs();
+        }
+
+        let mut best: Option<RayHit> = None;
+        loop {
+            let c = cell[1] * self.resolution[0] + cell[0];
+            let range = self.cell_start[c] as usize..self.cell_start[c + 1] as usize;
+            for &tri_idx in &self.cell_triangles[range] {
+                let tri_idx = tri_idx as usize;
+                let Some(hit) = self.triangles[tri_idx].intersect_ray(
+                    origin,
+                    dir,
+                    tri_idx,
+                    CULL_BACK,
+                    self.epsilon_scale,
+                ) else {
+                    continue;
+                };
+                if best.as_ref().is_none_or(|b| hit.distance < b.distance) {
+                    best = Some(hit);
+                }
+            }
+
+            let k = if t_next[0] <= t_next[1] { 0 } else { 1 };
+            if best.as_ref().is_some_and(|b| b.distance <= t_next[k]) || t_next[k].is_infinite() {
+                return best;
+            }
+            let next = cell[k] as isize + step[k];
+            if next < 0 || next as usize >= self.resolution[k] {
+                return best;
+            }
+            cell[k] = next as usize;
+            t_next[k] += t_delta[k];
+        }
+    }
+}
+
+/// Little-endian cursor over serialized BVH data
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let chunk = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or("serialized BVH is truncated")?;
+        self.pos += len;
+        Ok(chunk)
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn vec3(&mut self) -> Result<Vec3, String> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: [f32; 16] = [
+        1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+    ];
+
+    /// Cube spanning [-1, 1]^3, front face (+Z) is triangles 0 and 1
+    fn cube_geometry() -> (Vec<f32>, Vec<u32>) {
+        let positions = vec![
+            -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0, -1.0, -1.0, -1.0, 1.0,
+            -1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0,
+        ];
+        let indices = vec![
+            0, 1, 2, 0, 2, 3, 4, 6, 5, 4, 7, 6, 0, 3, 7, 0, 7, 4, 1, 5, 6, 1, 6, 2, 3, 2, 6, 3, 6,
+            7, 0, 4, 5, 0, 5, 1,
+        ];
+        (positions, indices)
+    }
+
+    fn cube() -> BVHRaycaster {
+        let (positions, indices) = cube_geometry();
+        BVHRaycaster::from_geometry(&positions, &indices)
+    }
+
+    #[test]
+    fn test_bvh_build() {
+        // Simple cube geometry (8 vertices, 12 triangles)
+        let positions: Vec<f32> = vec![
+            // Front face
+            -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0, // Back face
+            -1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0,
+        ];
+
+        let indices: Vec<u32> = vec![
+            0, 1, 2, 0, 2, 3, // Front
+            4, 6, 5, 4, 7, 6, // Back
+            0, 3, 7, 0, 7, 4, // Left
+            1, 5, 6, 1, 6, 2, // Right
+            3, 2, 6, 3, 6, 7, // Top
+            0, 4, 5, 0, 5, 1, // Bottom
+        ];
+
+        let raycaster = BVHRaycaster::from_geometry(&positions, &indices);
+
+        assert_eq!(raycaster.triangles.len(), 12);
+        assert!(raycaster.nodes.len() > 1, "BVH should have multiple nodes");
+
+        let stats = raycaster.get_stats();
+        println!(
+            "BVH Stats: nodes={}, leaves={}, internal={}, depth={}, tris={}",
+            stats[0], stats[1], stats[2], stats[3], stats[4]
+        );
+    }
+
+    #[test]
+    fn test_ray_hit() {
+        // Unit cube centered at origin
+        let positions: Vec<f32> = vec![
+            -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0, -1.0, -1.0, -1.0, 1.0,
+            -1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0,
+        ];
+        let indices: Vec<u32> = vec![
+            0, 1, 2, 0, 2, 3, 4, 6, 5, 4, 7, 6, 0, 3, 7, 0, 7, 4, 1, 5, 6, 1, 6, 2, 3, 2, 6, 3, 6,
+            7, 0, 4, 5, 0, 5, 1,
+        ];
+
+        let raycaster = BVHRaycaster::from_geometry(&positions, &indices);
+
+        // Ray from z=5 toward origin should hit front face at z=1
+        let identity: [f32; 16] = [
+            1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+        ];
+
+        let hit = raycaster.intersect(&[0.0, 0.0, 5.0], &[0.0, 0.0, -1.0], &identity);
+        assert!(hit.is_some(), "Should hit cube");
+
+        let hit = hit.unwrap();
+        assert!(
+            (hit[2] - 1.0).abs() < 0.01,
+            "Should hit at z=1, got z={}",
+            hit[2]
+        );
+    }
+
+    #[test]
+    fn test_id_ranges() {
+        let mut raycaster = cube();
+        raycaster.set_id_ranges(&[0, 2, 7, 2, 10, 3]).unwrap();
+
+        let hit = raycaster
+            .intersect_with_id(&[0.0, 0.0, 5.0], &[0.0, 0.0, -1.0], &IDENTITY)
+            .unwrap();
+        assert_eq!(hit.len(), 8);
+        assert_eq!(hit[7], 7.0);
+
+        // Right face (+X) is triangles 6 and 7
+        let id = raycaster.query_id_under_ray(&[5.0, 0.2, 0.1], &[-1.0, 0.0, 0.0], &IDENTITY);
+        assert_eq!(id, 3);
+        let miss = raycaster.query_id_under_ray(&[5.0, 5.0, 5.0], &[1.0, 0.0, 0.0], &IDENTITY);
+        assert_eq!(miss, -1);
+
+        // Unmapped triangles resolve to u32::MAX
+        raycaster.set_id_ranges(&[2, 4, 1]).unwrap();
+        let id = raycaster.query_id_under_ray(&[0.0, 0.0, 5.0], &[0.0, 0.0, -1.0], &IDENTITY);
+        assert_eq!(id, u32::MAX as i64);
+
+        // Overlaps are rejected and keep the previous mapping
+        assert!(raycaster.set_id_ranges(&[0, 4, 1, 3, 2, 2]).is_err());
+        assert_eq!(raycaster.resolve_id(3), 1);
+    }
+
+    #[test]
+    fn test_profiling_counters() {
+        let mut raycaster = cube();
+        let ray = ([0.0, 0.0, 5.0], [0.0, 0.0, -1.0]);
+
+        raycaster.intersect(&ray.0, &ray.1, &IDENTITY);
+        assert!(raycaster.get_node_visit_counts().is_empty());
+
+        raycaster.enable_profiling(true);
+        raycaster.intersect(&ray.0, &ray.1, &IDENTITY);
+        raycaster.intersect(&ray.0, &ray.1, &IDENTITY);
+
+        let nodes = raycaster.get_node_visit_counts();
+        assert_eq!(nodes.len(), raycaster.nodes.len());
+        assert_eq!(nodes[0], 2, "root is visited once per ray");
+
+        let tris = raycaster.get_triangle_test_counts();
+        assert_eq!(tris.len(), 12);
+        assert!(tris[0] + tris[1] >= 2, "front face triangles are tested");
+
+        let (_, indices) = cube_geometry();
+        let colors = BVHRaycaster::counts_to_vertex_colors(&indices, &tris);
+        assert_eq!(colors.len(), 8 * 3);
+        assert!(colors.iter().all(|c| (0.0..=1.0).contains(c)));
+
+        raycaster.reset_profiling();
+        assert!(raycaster.get_node_visit_counts().iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn test_export_debug_geometry() {
+        let bvh = cube();
+        let stats = bvh.get_stats();
+
+        let all = bvh.export_debug_geometry(u32::MAX, false);
+        assert_eq!(all.vertex_count(), stats[0] as usize * 24);
+        assert_eq!(all.colors().len(), all.vertex_count() * 3);
+        assert!(!all.is_truncated());
+
+        let leaves = bvh.export_debug_geometry(u32::MAX, true);
+        assert_eq!(leaves.vertex_count(), stats[1] as usize * 24);
+
+        // Root only, in the root color
+        let root = bvh.export_debug_geometry(0, false);
+        assert_eq!(root.vertex_count(), 24);
+        assert_eq!(root.colors()[..3], depth_color(0, 0));
+        let positions = root.positions();
+        let max_x = positions.chunks(3).map(|p| p[0]).fold(f32::MIN, f32::max);
+        assert_eq!(max_x, 1.0);
+
+        let empty = BVHRaycaster::from_geometry(&[], &[]);
+        assert_eq!(empty.export_debug_geometry(4, false).vertex_count(), 0);
+    }
+
+    #[test]
+    fn test_epsilon_scales_with_mesh_size() {
+        let (positions, indices) = cube_geometry();
+        for scale in [0.001f32, 100_000.0] {
+            let scaled: Vec<f32> = positions.iter().map(|p| p * scale).collect();
+            let bvh = BVHRaycaster::from_geometry(&scaled, &indices);
+
+            // Axis-aligned ray through the exact center (on the face's diagonal edge)
+            let hit = bvh
+                .intersect(&[0.0, 0.0, 10.0 * scale], &[0.0, 0.0, -1.0], &IDENTITY)
+                .unwrap_or_else(|| panic!("missed cube of scale {scale}"));
+            assert!((hit[2] - scale).abs() <= scale * 1e-5);
+            assert!((hit[6] - 9.0 * scale).abs() <= scale * 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_ray_on_box_face() {
+        let bvh = cube();
+        let root = &bvh.nodes[0];
+        // Ray lying in the x = 1 face plane, parallel to x
+        let on_face = Vec3::new(1.0, 0.0, 5.0);
+        let dir = Vec3::new(0.0, 0.0, -1.0);
+        assert!(BVHRaycaster::ray_aabb_intersect(
+            on_face,
+            dir.recip(),
+            root,
+            f32::MAX,
+            Vec3::ZERO
+        ));
+        let negative_zero = Vec3::new(-0.0, -0.0, -1.0);
+        assert!(BVHRaycaster::ray_aabb_intersect(
+            on_face,
+            negative_zero.recip(),
+            root,
+            f32::MAX,
+            Vec3::ZERO
+        ));
+
+        let outside = Vec3::new(1.0001, 0.0, 5.0);
+        assert!(!BVHRaycaster::ray_aabb_intersect(
+            outside,
+            dir.recip(),
+            root,
+            f32::MAX,
+            Vec3::ZERO
+        ));
+    }
+
+    #[test]
+    fn test_dispose() {
+        let mut bvh = cube();
+        bvh.dispose();
+        assert!(bvh.is_disposed());
+        assert_eq!(bvh.nodes.capacity() + bvh.triangles.capacity(), 0);
+
+        let ray = ([0.0, 0.0, 5.0], [0.0, 0.0, -1.0]);
+        assert!(bvh.intersect(&ray.0, &ray.1, &IDENTITY).is_none());
+        assert_eq!(bvh.query_id_under_ray(&ray.0, &ray.1, &IDENTITY), -1);
+        assert_eq!(bvh.get_stats(), vec![0; 6]);
+        assert!(bvh.set_id_ranges(&[0, 2, 7]).is_err());
+        bvh.enable_profiling(true);
+        assert!(bvh.get_node_visit_counts().is_empty());
+    }
+
+    #[test]
+    fn test_f64_huge_coordinates() {
+        // 2x2 quad facing +Z, centered around ECEF-like magnitudes
+        let base = DVec3::new(6_378_137.0, 1.0e7, -3_000_000.123);
+        let corners = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]];
+        let positions: Vec<f64> = corners
+            .iter()
+            .flat_map(|c| (base + DVec3::new(c[0], c[1], 0.0)).to_array())
+            .collect();
+        let bvh = BVHRaycaster::from_geometry_f64(&positions, &[0, 1, 2, 0, 2, 3]);
+        assert_eq!(bvh.get_origin(), base.to_array().to_vec());
+
+        let identity = DMat4::IDENTITY.to_cols_array();
+        let target = base + DVec3::new(0.123_456, -0.654_321, 0.0);
+        let ray_origin = (target + DVec3::new(0.0, 0.0, 5000.0)).to_array();
+        let hit = bvh
+            .intersect_f64(&ray_origin, &[0.0, 0.0, -1.0], &identity)
+            .unwrap();
+
+        // Millimeter accurate, and identical across repeated queries
+        assert!(DVec3::from_slice(&hit).distance(target) < 1e-3);
+        assert!((hit[6] - 5000.0).abs() < 1e-3);
+        assert_eq!(
+            bvh.intersect_f64(&ray_origin, &[0.0, 0.0, -1.0], &identity),
+            Some(hit)
+        );
+
+        // Non-uniform scale: the distance is still measured in world units
+        let model = DMat4::from_scale(DVec3::new(2.0, 0.5, 4.0));
+        let world_target = model.transform_point3(target);
+        let ray_origin = (world_target + DVec3::new(0.0, 0.0, 5000.0)).to_array();
+        let hit = bvh
+            .intersect_f64(&ray_origin, &[0.0, 0.0, -1.0], &model.to_cols_array())
+            .unwrap();
+        assert!(DVec3::from_slice(&hit).distance(world_target) < 1e-2);
+        assert!((hit[6] - 5000.0).abs() < 1e-2, "{}", hit[6]);
+    }
+
+    /// cube_geometry() copies shifted along x, indices offset per copy
+    fn cubes_along_x(offsets: &[f32], base_vertex: u32) -> (Vec<f32>, Vec<u32>) {
+        let (cube_positions, cube_indices) = cube_geometry();
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
+        for (n, &dx) in offsets.iter().enumerate() {
+            let first = base_vertex + (n * 8) as u32;
+            indices.extend(cube_indices.iter().map(|&i| first + i));
+            positions.extend(
+                cube_positions
+                    .chunks(3)
+                    .flat_map(|p| [p[0] + dx, p[1], p[2]]),
+            );
+        }
+        (positions, indices)
+    }
+
+    #[test]
+    fn test_append_geometry_and_compact() {
+        let ray_at = |x: f32| ([x, 0.0, 10.0], [0.0, 0.0, -1.0]);
+        let tests_for = |bvh: &mut BVHRaycaster, x: f32| {
+            bvh.reset_profiling();
+            let ray = ray_at(x);
+            bvh.intersect(&ray.0, &ray.1, &IDENTITY);
+            bvh.get_triangle_test_counts().iter().sum::<u32>()
+        };
+
+        // Large tree + small chunk: subtree beside the old root
+        let (positions, indices) = cubes_along_x(&[0.0, 3.0, 6.0, 9.0, 12.0], 0);
+        let mut bvh = BVHRaycaster::from_geometry(&positions, &indices);
+        bvh.enable_profiling(true);
+        let (chunk, chunk_indices) = cubes_along_x(&[20.0], 40);
+        let range = bvh.append_geometry(&chunk, &chunk_indices, 40).unwrap();
+        assert_eq!(range, vec![60, 12]);
+        assert_eq!(bvh.pending_triangle_count(), 12);
+
+        let ray = ray_at(20.0);
+        let before = bvh.intersect(&ray.0, &ray.1, &IDENTITY).unwrap();
+        assert!((before[2] - 1.0).abs() < 1e-6);
+        let tests_before = tests_for(&mut bvh, 20.0);
+        assert_eq!(tests_before, 12);
+
+        bvh.compact();
+        assert_eq!(bvh.pending_triangle_count(), 0);
+        assert_eq!(bvh.intersect(&ray.0, &ray.1, &IDENTITY), Some(before));
+        assert!(tests_for(&mut bvh, 20.0) < tests_before);
+        assert_eq!(bvh.get_stats()[4], 72);
+        // Old geometry is untouched
+        let ray = ray_at(6.0);
+        assert!(bvh.intersect(&ray.0, &ray.1, &IDENTITY).is_some());
+
+        // Comparable-size chunk: full rebuild; out-of-chunk triangles are skipped
+        let mut bvh = cube();
+        let (chunk, mut chunk_indices) = cubes_along_x(&[5.0], 8);
+        chunk_indices.extend([0, 1, 2]);
+        assert_eq!(
+            bvh.append_geometry(&chunk, &chunk_indices, 8).unwrap(),
+            vec![12, 13]
+        );
+        assert_eq!(bvh.pending_triangle_count(), 12);
+        let ray = ray_at(5.0);
+        let before = bvh.intersect_with_id(&ray.0, &ray.1, &IDENTITY).unwrap();
+        bvh.compact();
+        assert_eq!(
+            bvh.intersect_with_id(&ray.0, &ray.1, &IDENTITY),
+            Some(before)
+        );
+        assert!(bvh.get_stats()[3] > 0);
+
+        bvh.dispose();
+        assert!(bvh.append_geometry(&chunk, &chunk_indices, 8).is_err());
+    }
+
+    #[test]
+    fn test_cleanup_degenerate_and_duplicates() {
+        // 16x16 grid facing +z; every real triangle is followed by a sliver
+        // far below the area epsilon (but not collapsed)
+        const N: u32 = 16;
+        const SLIVER: f32 = 1e-4;
+        let mut positions: Vec<f32> = (0..=N)
+            .flat_map(|y| (0..=N).flat_map(move |x| [x as f32, y as f32, 0.0]))
+            .collect();
+        let mut indices = Vec::new();
+        for j in 0..N {
+            for i in 0..N {
+                let (a, c) = (j * (N + 1) + i, (j + 1) * (N + 1) + i);
+                let (b, d) = (a + 1, c + 1);
+                let e = positions.len() as u32 / 3;
+                let (x, y) = (i as f32, j as f32);
+                positions.extend([x + SLIVER, y, 0.0, x, y + SLIVER, 0.0]);
+                indices.extend([a, b, d, a, e, e + 1, a, d, c, a, e, e + 1]);
+            }
+        }
+        let real = (N * N * 2) as usize;
+        // Rotated copies of the first 10 quads' lower triangles
+        for q in 0..10 {
+            let t = &indices[q * 12..q * 12 + 3];
+            indices.extend([t[1], t[2], t[0]]);
+        }
+        let total = indices.len() / 3;
+        let depth_bound = |n: usize| (n / MAX_LEAF_TRIANGLES).max(1).ilog2() + 2;
+
+        // Without cleanup the pile of identical centroids still splits evenly
+        let plain = BVHRaycaster::from_geometry(&positions, &indices);
+        assert_eq!(plain.get_stats()[4] as usize, total);
+        assert!(plain.get_stats()[3] <= depth_bound(total));
+        assert_eq!(plain.get_cleanup_stats(), vec![0, 0]);
+        assert_eq!(plain.get_build_warnings(), vec![0, 0]);
+
+        let mut bvh = BVHRaycaster::from_geometry_with_cleanup(&positions, &indices, 3);
+        assert_eq!(bvh.get_cleanup_stats(), vec![real as u32, 10]);
+        assert_eq!(bvh.get_build_warnings(), vec![0, 0]);
+        assert_eq!(bvh.get_stats()[4] as usize, real);
+        assert!(bvh.get_stats()[3] <= depth_bound(real));
+
+        // Hits report original triangle indices: ID = index
+        let ranges: Vec<u32> = (0..total as u32).flat_map(|t| [t, 1, t]).collect();
+        bvh.set_id_ranges(&ranges).unwrap();
+        for (i, j) in [(0, 0), (5, 3), (15, 15)] {
+            let lower = [i as f32 + 0.75, j as f32 + 0.25, 10.0];
+            let upper = [i as f32 + 0.25, j as f32 + 0.75, 10.0];
+            let quad = (j * N + i) as i64;
+            assert_eq!(
+                bvh.query_id_under_ray(&lower, &[0.0, 0.0, -1.0], &IDENTITY),
+                quad * 4,
+                "quad ({i}, {j})"
+            );
+            assert_eq!(
+                bvh.query_id_under_ray(&upper, &[0.0, 0.0, -1.0], &IDENTITY),
+                quad * 4 + 2,
+                "quad ({i}, {j})"
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_warnings() {
+        let (positions, mut indices) = cube_geometry();
+        let vertex_count = positions.len() as u32 / 3;
+        // Collapsed onto an edge, repeated index, missing vertex
+        indices.extend([0, 1, 1, 2, 2, 2, 0, 1, vertex_count + 1]);
+        // Distinct indices but zero area (vertex 0 copied)
+        let mut positions = positions;
+        positions.extend_from_within(..3);
+        indices.extend([0, vertex_count, 3]);
+
+        let bvh = BVHRaycaster::from_geometry(&positions, &indices);
+        assert_eq!(bvh.get_build_warnings(), vec![3, 1]);
+        assert_eq!(bvh.get_stats()[4], 12);
+        assert_eq!(bvh.get_cleanup_stats(), vec![0, 0]);
+
+        // No hit, however grazing, comes back without a unit normal
+        for k in 0..200 {
+            let angle = k as f32 * 0.1;
+            let origin = [
+                angle.cos() * 3.0,
+                (k as f32 * 0.37).sin(),
+                angle.sin() * 3.0,
+            ];
+            let target = Vec3::from_slice(&positions[(k % 8) * 3..(k % 8) * 3 + 3]);
+            let dir = (target - Vec3::from(origin)).to_array();
+            if let Some(hit) = bvh.intersect(&origin, &dir, &IDENTITY) {
+                let normal = Vec3::from_slice(&hit[3..6]);
+                assert!(normal.is_finite() && (normal.length() - 1.0).abs() < 1e-5);
+            }
+        }
+
+        // Counts follow rebuilds, appends and serialization
+        let mut rebuilt = BVHRaycaster::from_geometry(&[], &[]);
+        rebuilt.update_geometry(&positions, &indices);
+        assert_eq!(rebuilt.get_build_warnings(), vec![3, 1]);
+        rebuilt
+            .append_geometry(&positions, &[0, 0, 1, 0, 1, 99], 0)
+            .unwrap();
+        assert_eq!(rebuilt.get_build_warnings(), vec![4, 2]);
+        let loaded = BVHRaycaster::from_bytes(&rebuilt.to_bytes()).unwrap();
+        assert_eq!(loaded.get_build_warnings(), vec![4, 2]);
+    }
+
+    #[test]
+    fn test_intersect_uv() {
+        // Unit quad in z = 0 with UVs stretched over [0, 2] x [0, 4]
+        let positions = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+        let indices = [0, 1, 2, 0, 2, 3];
+        let mut bvh = BVHRaycaster::from_geometry(&positions, &indices);
+        let down = [0.0, 0.0, -1.0];
+        let origin = [0.25, 0.6, 1.0];
+        assert_eq!(
+            bvh.intersect_uv(&origin, &down, &IDENTITY),
+            bvh.intersect(&origin, &down, &IDENTITY)
+        );
+
+        assert!(bvh.set_uvs(&[0.0; 6]).is_err());
+        bvh.set_uvs(&[0.0, 0.0, 2.0, 0.0, 2.0, 4.0, 0.0, 4.0])
+            .unwrap();
+        for (x, y) in [(0.25, 0.6), (0.75, 0.1), (0.5, 0.5)] {
+            let hit = bvh.intersect_uv(&[x, y, 1.0], &down, &IDENTITY).unwrap();
+            assert_eq!(hit.len(), 9);
+            assert!((hit[7] - x * 2.0).abs() < 1e-5 && (hit[8] - y * 4.0).abs() < 1e-5);
+        }
+
+        // Round-trips; appended triangles without UVs give plain hits
+        let loaded = BVHRaycaster::from_bytes(&bvh.to_bytes()).unwrap();
+        assert_eq!(
+            loaded.intersect_uv(&origin, &down, &IDENTITY),
+            bvh.intersect_uv(&origin, &down, &IDENTITY)
+        );
+        let far: Vec<f32> = positions
+            .chunks(3)
+            .flat_map(|p| [p[0] + 5.0, p[1], p[2]])
+            .collect();
+        bvh.append_geometry(&far, &[4, 5, 6, 4, 6, 7], 4).unwrap();
+        assert_eq!(
+            bvh.intersect_uv(&[5.5, 0.5, 1.0], &down, &IDENTITY)
+                .unwrap()
+                .len(),
+            7
+        );
+        assert!(bvh.set_uvs(&[0.0; 8]).is_err());
+        bvh.set_uvs(&[]).unwrap();
+        assert_eq!(
+            bvh.intersect_uv(&origin, &down, &IDENTITY).unwrap().len(),
+            7
+        );
+    }
+
+    #[test]
+    fn test_groups() {
+        // Cubes at x = 0, 3, 6: group = cube, except the last cube's faces
+        // alternate between groups 7 and 8
+        let (positions, indices) = cubes_along_x(&[0.0, 3.0, 6.0], 0);
+        let mut bvh = BVHRaycaster::from_geometry(&positions, &indices);
+        let mut groups: Vec<u32> = (0..36).map(|t| t / 12).collect();
+        for (t, group) in groups[24..].iter_mut().enumerate() {
+            *group = 7 + (t as u32 / 2) % 2;
+        }
+        assert!(bvh.set_groups(&groups[..35]).is_err());
+        bvh.set_groups(&groups).unwrap();
+
+        let from_left = ([-5.0, 0.2, 0.3], [1.0, 0.0, 0.0]);
+        let hit = bvh
+            .intersect_with_group(&from_left.0, &from_left.1, &IDENTITY)
+            .unwrap();
+        assert_eq!(hit[7], 0.0);
+        assert_eq!(
+            hit[..7],
+            bvh.intersect(&from_left.0, &from_left.1, &IDENTITY)
+                .unwrap()[..]
+        );
+
+        // Skipping groups makes the earlier cubes transparent
+        let only = |mask: &[u32]| {
+            bvh.intersect_filtered(&from_left.0, &from_left.1, &IDENTITY, Some(mask.to_vec()))
+                .map(|hit| (hit[0], hit[7]))
+        };
+        assert_eq!(only(&[1]), Some((2.0, 1.0)));
+        assert_eq!(only(&[2, 8]), None);
+        let last = only(&[7, 8]).unwrap();
+        assert_eq!(last.0, 5.0);
+        assert!(last.1 == 7.0 || last.1 == 8.0);
+        assert_eq!(only(&[]), None);
+
+        // Groups round-trip; appended triangles have none
+        let loaded = BVHRaycaster::from_bytes(&bvh.to_bytes()).unwrap();
+        assert_eq!(
+            loaded.intersect_filtered(&from_left.0, &from_left.1, &IDENTITY, Some(vec![1])),
+            bvh.intersect_filtered(&from_left.0, &from_left.1, &IDENTITY, Some(vec![1]))
+        );
+        let extra = cubes_along_x(&[-3.0], 24);
+        bvh.append_geometry(&extra.0, &extra.1, 24).unwrap();
+        let hit = bvh
+            .intersect_with_group(&from_left.0, &from_left.1, &IDENTITY)
+            .unwrap();
+        assert_eq!((hit[0], hit[7]), (-4.0, u32::MAX as f32));
+        let only = |mask: &[u32]| {
+            bvh.intersect_filtered(&from_left.0, &from_left.1, &IDENTITY, Some(mask.to_vec()))
+                .map(|hit| hit[0])
+        };
+        assert_eq!(only(&[u32::MAX]), Some(-4.0));
+        assert_eq!(only(&[0]), Some(-1.0));
+    }
+
+    #[test]
+    fn test_bounds_and_triangle_accessors() {
+        let mut bvh = cube();
+        assert_eq!(bvh.get_bounds(), vec![-1.0, -1.0, -1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(
+            BVHRaycaster::from_geometry(&[], &[]).get_bounds(),
+            vec![0.0; 6]
+        );
+
+        // Triangle 2 is the first of the back face (vertices 4, 6, 5)
+        assert_eq!(
+            bvh.get_triangle(2),
+            vec![-1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 0.0, 0.0, -1.0]
+        );
+        assert!(bvh.get_triangle(12).is_empty());
+        assert!(bvh.get_triangle(u32::MAX).is_empty());
+
+        // The hit triangle as reported by intersect_detailed
+        let hit = bvh
+            .intersect_detailed(&[0.2, 0.1, 5.0], &[0.0, 0.0, -1.0], &IDENTITY)
+            .unwrap();
+        let tri = bvh.get_triangle(hit[7] as u32);
+        assert_eq!(tri[9..], [0.0, 0.0, 1.0]);
+
+        // Skipped triangles read as empty, later ones keep their index
+        let (positions, mut indices) = cube_geometry();
+        indices.splice(0..0, [0, 0, 1]);
+        let skipped = BVHRaycaster::from_geometry(&positions, &indices);
+        assert!(skipped.get_triangle(0).is_empty());
+        assert_eq!(skipped.get_triangle(3), bvh.get_triangle(2));
+
+        // Appended geometry grows the bounds; floating origins are undone
+        let extra = cubes_along_x(&[5.0], 8);
+        bvh.append_geometry(&extra.0, &extra.1, 8).unwrap();
+        assert_eq!(bvh.get_bounds(), vec![-1.0, -1.0, -1.0, 6.0, 1.0, 1.0]);
+        let far: Vec<f64> = positions.iter().map(|&p| p as f64 + 1000.0).collect();
+        let shifted = BVHRaycaster::from_geometry_f64(&far, &cube_geometry().1);
+        let bounds = shifted.get_bounds();
+        assert!((bounds[0] - 999.0).abs() < 1e-3 && (bounds[5] - 1001.0).abs() < 1e-3);
+        assert!((shifted.get_triangle(0)[0] - 999.0).abs() < 1e-3);
+
+        bvh.dispose();
+        assert_eq!(bvh.get_bounds(), vec![0.0; 6]);
+        assert!(bvh.get_triangle(0).is_empty());
+    }
+
+    #[test]
+    fn test_stats_detailed() {
+        let (positions, indices) = cubes_along_x(&[0.0, 3.0, 6.0, 9.0, 12.0], 0);
+        for bvh in [
+            BVHRaycaster::from_geometry(&positions, &indices),
+            BVHRaycaster::from_geometry_sah(&positions, &indices, 8),
+            BVHRaycaster::from_geometry_fast(&positions, &indices),
+        ] {
+            let stats = bvh.get_stats();
+            let detailed = bvh.get_stats_detailed();
+            assert!((detailed[0] - bvh.get_sah_cost()).abs() < 1e-4 * detailed[0]);
+            assert_eq!(detailed[1], stats[1] as f32);
+            assert_eq!(detailed[2], stats[4] as f32);
+            assert_eq!(detailed[3], detailed[2] / detailed[1]);
+            assert!(detailed[4] >= detailed[3] && detailed[4] <= MAX_LEAF_TRIANGLES as f32);
+            assert_eq!(detailed[5], 0.0);
+            assert_eq!(detailed[6], stats[5] as f32);
+            assert_eq!(detailed[9], detailed[6] + detailed[7] + detailed[8]);
+        }
+
+        // Appended triangles add index and triangle bytes but no leaves
+        let mut bvh = cube();
+        let before = bvh.get_stats_detailed();
+        let extra = cubes_along_x(&[5.0], 8);
+        bvh.append_geometry(&extra.0, &extra.1, 8).unwrap();
+        let after = bvh.get_stats_detailed();
+        assert_eq!(after[..7], before[..7]);
+        assert!(after[7] > before[7] && after[8] > before[8]);
+
+        // The dummy root of empty geometry is one empty leaf
+        let empty = BVHRaycaster::from_geometry(&[], &[]).get_stats_detailed();
+        assert_eq!(empty[..6], [0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_cached_model_matrix() {
+        let mut bvh = cube();
+        let model = Mat4::from_scale_rotation_translation(
+            Vec3::splat(2.0),
+            glam::Quat::from_rotation_y(0.7),
+            Vec3::new(3.0, -1.0, 0.5),
+        )
+        .to_cols_array();
+        let rays = [
+            ([3.2, 6.0, 0.4], [0.0, -1.0, 0.0]),
+            ([-5.0, -0.8, 0.7], [1.0, 0.05, 0.0]),
+            ([20.0, 20.0, 20.0], [1.0, 0.0, 0.0]),
+        ];
+
+        // Identity until set, then the same as passing the matrix each time
+        assert_eq!(
+            bvh.intersect_cached(&[0.2, 0.1, 5.0], &[0.0, 0.0, -1.0]),
+            bvh.intersect(&[0.2, 0.1, 5.0], &[0.0, 0.0, -1.0], &IDENTITY)
+        );
+        bvh.set_model_matrix(&model).unwrap();
+        for (origin, dir) in &rays {
+            assert_eq!(
+                bvh.intersect_cached(origin, dir),
+                bvh.intersect(origin, dir, &model)
+            );
+        }
+        assert!(bvh.intersect_cached(&rays[0].0, &rays[0].1).is_some());
+
+        // Short matrices are an error / a miss instead of a panic
+        assert!(bvh.set_model_matrix(&model[..15]).is_err());
+        assert!(bvh
+            .intersect(&[0.2, 0.1, 5.0], &[0.0, 0.0, -1.0], &model[..15])
+            .is_none());
+        assert!(bvh.intersect_cached(&rays[0].0, &rays[0].1).is_some());
+    }
+
+    #[test]
+    fn test_intersect_into() {
+        let mut bvh = cube();
+        let model = Mat4::from_rotation_translation(
+            glam::Quat::from_rotation_x(0.4),
+            Vec3::new(0.0, 2.0, 0.0),
+        )
+        .to_cols_array();
+        let ptr = bvh.get_hit_ptr();
+
+        for (origin, dir) in [
+            ([0.2, 2.1, 5.0], [0.0, 0.0, -1.0]),
+            ([-4.0, 2.3, 0.2], [1.0, -0.1, 0.05]),
+        ] {
+            let expected = bvh.intersect_detailed(&origin, &dir, &model).unwrap();
+            assert!(bvh.intersect_into(&origin, &dir, &model));
+            let buffer = bvh.hit_buffer;
+            assert_eq!(buffer[..10], expected[..]);
+            assert!((buffer[8] + buffer[9] + buffer[10] - 1.0).abs() < 1e-6);
+            assert_eq!(buffer[11], 0.0);
+        }
+
+        // Misses overwrite the previous hit; the pointer never moves
+        assert!(!bvh.intersect_into(&[0.0, 10.0, 5.0], &[0.0, 0.0, -1.0], &model));
+        assert_eq!(bvh.get_hit_ptr(), ptr);
+        let buffer = bvh.hit_buffer;
+        assert_eq!((buffer[6], buffer[7]), (-1.0, -1.0));
+        assert!(!bvh.intersect_into(&[0.2, 2.1, 5.0], &[0.0, 0.0, -1.0], &model[..15]));
+    }
+
+    #[test]
+    fn test_intersect_segment() {
+        let mut bvh = cube();
+        let check = |hit: Option<Vec<f32>>, z: f32, distance: f32, fraction: f32| {
+            let hit = hit.expect("segment missed");
+            assert!((hit[2] - z).abs() < 1e-5, "{hit:?}");
+            assert!((hit[6] - distance).abs() < 1e-5, "{hit:?}");
+            assert!((hit[7] - fraction).abs() < 1e-5, "{hit:?}");
+        };
+
+        check(
+            bvh.intersect_segment(&[0.2, 0.1, 5.0], &[0.2, 0.1, -5.0], &IDENTITY),
+            1.0,
+            4.0,
+            0.4,
+        );
+        // Stops just short of the surface, and ends exactly on it
+        assert!(bvh
+            .intersect_segment(&[0.2, 0.1, 5.0], &[0.2, 0.1, 1.001], &IDENTITY)
+            .is_none());
+        check(
+            bvh.intersect_segment(&[0.2, 0.1, 5.0], &[0.2, 0.1, 1.0], &IDENTITY),
+            1.0,
+            4.0,
+            1.0,
+        );
+
+        // Scaled 2x: world distances and fractions, not object ones
+        let scaled = Mat4::from_scale(Vec3::splat(2.0)).to_cols_array();
+        check(
+            bvh.intersect_segment(&[0.2, 0.1, 6.0], &[0.2, 0.1, -2.0], &scaled),
+            2.0,
+            4.0,
+            0.5,
+        );
+        assert!(bvh
+            .intersect_segment(&[0.2, 0.1, 6.0], &[0.2, 0.1, 2.1], &scaled)
+            .is_none());
+
+        // Starting inside: back faces are culled unless culling is off
+        let inside = ([0.2, 0.1, 0.0], [0.2, 0.1, -5.0]);
+        assert!(bvh
+            .intersect_segment(&inside.0, &inside.1, &IDENTITY)
+            .is_none());
+        bvh.set_cull_mode(CULL_NONE).unwrap();
+        check(
+            bvh.intersect_segment(&inside.0, &inside.1, &IDENTITY),
+            -1.0,
+            1.0,
+            0.2,
+        );
+        assert!(bvh
+            .intersect_segment(&inside.0, &[0.2, 0.1, -0.5], &IDENTITY)
+            .is_none());
+        assert!(bvh
+            .intersect_segment(&inside.0, &inside.0, &IDENTITY)
+            .is_none());
+    }
+
+    #[test]
+    fn test_signed_distance() {
+        let bvh = cube();
+        let assert_near = |point: [f32; 3], expected: f32| {
+            let got = bvh.signed_distance(&point, &IDENTITY);
+            assert!((got - expected).abs() < 1e-5, "{point:?}: {got}");
+        };
+
+        // Just inside and just outside every face, off the face diagonals
+        for axis in 0..3 {
+            for side in [-1.0, 1.0] {
+                let mut point = [0.3, -0.2, 0.1];
+                for (depth, expected) in [(0.99, -0.01), (1.01, 0.01), (1.0, 0.0)] {
+                    point[axis] = side * depth;
+                    assert_near(point, expected);
+                }
+            }
+        }
+
+        // Near the corner, where the closest feature is the vertex or an edge
+        // and a single face normal can give either sign
+        assert_near([1.1, 1.1, 1.1], 0.1 * 3f32.sqrt());
+        assert_near([1.1, 1.1, 0.5], 0.1 * 2f32.sqrt());
+        assert_near([0.99, 0.99, 0.99], -0.01);
+        assert_near([0.98, 0.99, 0.995], -0.005);
+        for k in 0..50 {
+            let dir = Vec3::new(1.0 + (k % 5) as f32 * 0.3, 1.0 + (k / 5) as f32 * 0.2, 1.0);
+            let outside = Vec3::ONE + dir.normalize() * 0.05;
+            assert!(bvh.signed_distance(&outside.to_array(), &IDENTITY) > 0.0);
+        }
+
+        // Split vertices (flat-shaded export): the sign is still right
+        let (positions, indices) = cube_geometry();
+        let split: Vec<f32> = indices
+            .iter()
+            .flat_map(|&i| positions[i as usize * 3..i as usize * 3 + 3].to_vec())
+            .collect();
+        let flat = BVHRaycaster::from_geometry(&split, &(0..36).collect::<Vec<u32>>());
+        assert!(
+            (flat.signed_distance(&[1.1, 1.1, 1.1], &IDENTITY) - 0.1 * 3f32.sqrt()).abs() < 1e-5
+        );
+        assert!(flat.signed_distance(&[0.99, 0.999, 0.995], &IDENTITY) < 0.0);
+
+        // Thin spike: past its tip the far side's face normal points away
+        let spike_positions = [
+            0.1, 0.0, 0.0, -0.05, 0.087, 0.0, -0.05, -0.087, 0.0, 0.0, 0.0, 5.0,
+        ];
+        let spike =
+            BVHRaycaster::from_geometry(&spike_positions, &[0, 2, 1, 0, 1, 3, 1, 2, 3, 2, 0, 3]);
+        for k in 0..24 {
+            let angle = k as f32 * std::f32::consts::TAU / 24.0;
+            let beyond = [angle.cos() * 0.1, angle.sin() * 0.1, 5.1];
+            assert!(
+                spike.signed_distance(&beyond, &IDENTITY) > 0.0,
+                "{beyond:?}"
+            );
+        }
+        assert!(spike.signed_distance(&[0.0, 0.0, 4.5], &IDENTITY) < 0.0);
+
+        let scaled = Mat4::from_scale(Vec3::splat(2.0)).to_cols_array();
+        assert!((bvh.signed_distance(&[0.0, 1.5, 0.0], &scaled) + 0.5).abs() < 1e-5);
+        assert_eq!(
+            BVHRaycaster::from_geometry(&[], &[]).signed_distance(&[0.0; 3], &IDENTITY),
+            f32::INFINITY
+        );
+    }
+
+    #[test]
+    fn test_intersect_n() {
+        // Six cubes along x, listed out of order so the tree's left-first
+        // order is not the ray order
+        let offsets = [12.0, 0.0, 20.0, 4.0, 16.0, 8.0];
+        let (positions, indices) = cubes_along_x(&offsets, 0);
+        let mut bvh = BVHRaycaster::from_geometry(&positions, &indices);
+        bvh.set_cull_mode(1).unwrap();
+
+        let origins = [[-5.0, 0.3, 0.2], [30.0, 0.6, 0.7], [-5.0, 0.5, 0.5]];
+        let directions = [[1.0, 0.0, 0.0], [-1.0, 0.0, 0.0], [1.0, 0.01, 0.0]];
+        for (origin, direction) in origins.iter().zip(&directions) {
+            let all = bvh.intersect_all(origin, direction, &IDENTITY, 0);
+            let total = all.len() / ALL_HITS_STRIDE;
+            assert!(total >= 12, "{total}");
+            for n in [1, 3, total, total + 2] {
+                let hits = bvh.intersect_n(origin, direction, &IDENTITY, n);
+                assert_eq!(hits.len(), n * ALL_HITS_STRIDE);
+                let found = n.min(total) * ALL_HITS_STRIDE;
+                assert_eq!(hits[..found], all[..found], "n = {n}");
+                for miss in hits[found..].chunks(ALL_HITS_STRIDE) {
+                    assert_eq!(miss[6..], [-1.0, -1.0]);
+                }
+            }
+        }
+
+        assert!(bvh
+            .intersect_n(&[-5.0, 0.3, 0.2], &[1.0, 0.0, 0.0], &IDENTITY, 0)
+            .is_empty());
+        let miss = bvh.intersect_n(&[0.0, 5.0, 5.0], &[0.0, 0.0, -1.0], &IDENTITY, 2);
+        assert_eq!(miss.len(), 2 * ALL_HITS_STRIDE);
+        assert!(miss.chunks(ALL_HITS_STRIDE).all(|m| m[6] == -1.0));
+    }
+
+    #[test]
+    fn test_u16_and_soup_constructors() {
+        let (positions, indices) = cube_geometry();
+        let reference = BVHRaycaster::from_geometry(&positions, &indices);
+        let indices_u16: Vec<u16> = indices.iter().map(|&i| i as u16).collect();
+        let soup: Vec<f32> = indices
+            .iter()
+            .flat_map(|&i| positions[i as usize * 3..i as usize * 3 + 3].to_vec())
+            .collect();
+        let built = [
+            BVHRaycaster::from_geometry_u16(&positions, &indices_u16),
+            BVHRaycaster::from_soup(&soup),
+        ];
+
+        let rays = [
+            ([0.3, 0.2, 5.0], [0.0, 0.0, -1.0]),
+            ([-5.0, 0.4, -0.1], [1.0, 0.0, 0.0]),
+            ([3.0, 4.0, 2.0], [-0.5, -0.8, -0.4]),
+        ];
+        for bvh in &built {
+            assert_eq!(bvh.get_stats(), reference.get_stats());
+            for (origin, direction) in &rays {
+                assert_eq!(
+                    bvh.intersect_all(origin, direction, &IDENTITY, 0),
+                    reference.intersect_all(origin, direction, &IDENTITY, 0)
+                );
+            }
+        }
+
+        // Bounds checks match the u32 path; partial trailing triangles are ignored
+        let mut bad = indices_u16.clone();
+        bad[4] = 8;
+        bad.push(0);
+        let bvh = BVHRaycaster::from_geometry_u16(&positions, &bad);
+        assert_eq!(bvh.get_build_warnings(), [0, 1]);
+        assert!(bvh.get_triangle(1).is_empty());
+        let soup_bvh = BVHRaycaster::from_soup(&soup[..soup.len() - 3]);
+        assert_eq!(soup_bvh.get_stats()[4], 11);
+        assert!(BVHRaycaster::from_soup(&[])
+            .intersect(&[0.0; 3], &[1.0, 0.0, 0.0], &IDENTITY)
+            .is_none());
+    }
+
+    #[test]
+    fn test_non_uniform_scale_normals() {
+        // Slanted triangle in the plane x + z = 1, facing (1, 0, 1)
+        let corners = [[1.0, -1.0, 0.0], [0.5, 1.0, 0.5], [0.0, -1.0, 1.0]];
+        let bvh = BVHRaycaster::from_geometry(corners.as_flattened(), &[0, 1, 2]);
+        let scale = Mat4::from_scale(Vec3::new(1.0, 1.0, 3.0));
+        let model = scale.to_cols_array();
+
+        let [a, b, c] = corners.map(|p| scale.transform_point3(Vec3::from_array(p)));
+        let perpendicular = |hit: &[f32]| {
+            let n = Vec3::from_slice(&hit[3..6]);
+            assert!((n.length() - 1.0).abs() < 1e-5);
+            assert!(n.dot(b - a).abs() < 1e-5, "{n}");
+            assert!(n.dot(c - a).abs() < 1e-5, "{n}");
+            assert!(n.dot(Vec3::new(1.0, 0.0, 1.0)) > 0.0);
+        };
+        let target = (a + b + c) / 3.0;
+        let origin = target + Vec3::new(2.0, 0.1, 1.0);
+        let hit = bvh
+            .intersect(&origin.to_array(), &(target - origin).to_array(), &model)
+            .unwrap();
+        perpendicular(&hit);
+        assert!(Vec3::from_slice(&hit[..3]).distance(target) < 0.1);
+
+        perpendicular(&bvh.closest_point(&origin.to_array(), &model));
+        perpendicular(
+            &bvh.sphere_cast(
+                &origin.to_array(),
+                &(target - origin).to_array(),
+                0.1,
+                &model,
+            )
+            .unwrap(),
+        );
+        let model_f64 = model.map(f64::from);
+        let hit_f64 = bvh
+            .intersect_f64(
+                &origin.as_dvec3().to_array(),
+                &(target - origin).as_dvec3().to_array(),
+                &model_f64,
+            )
+            .unwrap();
+        perpendicular(&hit_f64.iter().map(|&x| x as f32).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_precision_high() {
+        // Slanted quad in the plane x + z = 1e5, facing the origin, picked
+        // from a camera near (0, 0, 0)
+        const X: f64 = 1e5;
+        let x = X as f32;
+        let positions = [
+            x - 5.0,
+            -5.0,
+            5.0,
+            x + 5.0,
+            -5.0,
+            -5.0,
+            x + 5.0,
+            5.0,
+            -5.0,
+            x - 5.0,
+            5.0,
+            5.0,
+        ];
+        let mut bvh = BVHRaycaster::from_geometry(&positions, &[0, 2, 1, 0, 3, 2]);
+        assert!(!bvh.get_precision_high());
+
+        let worst_error = |bvh: &mut BVHRaycaster| {
+            let mut worst = 0.0f64;
+            for i in 0..64 {
+                let origin = [0.37 * i as f32 - 9.0, 1.1, 4.1 - 0.13 * i as f32];
+                let target = [x + 0.03 * i as f32 - 0.9, 0.7 - 0.02 * i as f32, 0.0];
+                let direction: Vec<f32> = (0..3).map(|k| target[k] - origin[k]).collect();
+                let hit = bvh.intersect(&origin, &direction, &IDENTITY).unwrap();
+                assert!(bvh.intersect_into(&origin, &direction, &IDENTITY));
+                assert_eq!(bvh.hit_buffer[..7], hit[..]);
+
+                // Exact crossing of the plane by the same (f32) ray
+                let o = Vec3::from_array(origin).as_dvec3();
+                let d = Vec3::from_slice(&direction).as_dvec3().normalize();
+                let expected = o + d * ((X - o.x - o.z) / (d.x + d.z));
+                worst = worst.max(Vec3::from_slice(&hit[..3]).as_dvec3().distance(expected));
+            }
+            worst
+        };
+
+        let f32_error = worst_error(&mut bvh);
+        bvh.set_precision_high(true);
+        let f64_error = worst_error(&mut bvh);
+        assert!(f64_error < 0.01, "{f64_error}");
+        assert!(f32_error > 0.01, "{f32_error}");
+    }
+
+    #[test]
+    fn test_skewed_build_depth() {
+        // Centroids spaced geometrically along x, so SAH keeps peeling off the
+        // outermost few, plus a large pile of coincident triangles
+        const SPREAD: u32 = 1400;
+        const PILE: u32 = 20_000;
+        let mut positions = Vec::new();
+        for i in 0..SPREAD {
+            let x = 1.1f32.powi(i as i32 - 700);
+            positions.extend([x, 0.0, 0.0, x, 1.0, 0.0, x, 0.0, 1.0]);
+        }
+        for _ in 0..PILE {
+            positions.extend([-1.0, 0.0, 0.0, -1.0, 1.0, 0.0, -1.0, 0.0, 1.0]);
+        }
+        let indices: Vec<u32> = (0..(SPREAD + PILE) * 3).collect();
+
+        let bound = MAX_SAH_DEPTH + ((SPREAD + PILE) as f32).log2().ceil() as usize;
+        for bins in [2, 16] {
+            let mut bvh = BVHRaycaster::from_geometry_sah(&positions, &indices, bins);
+            let stats = bvh.get_stats();
+            assert_eq!(stats[4], SPREAD + PILE);
+            assert!(
+                (stats[3] as usize) <= bound,
+                "depth {} with {bins} bins",
+                stats[3]
+            );
+
+            // The spread triangles are still the first hit just past them
+            // (the mesh is 1e29 across; use epsilons for its unit-size end)
+            bvh.set_epsilon_scale(1.0);
+            for i in (600..SPREAD).step_by(7) {
+                let x = 1.1f32.powi(i as i32 - 700);
+                let hit = bvh
+                    .intersect(&[x * 1.05, 0.2, 0.2], &[-1.0, 0.0, 0.0], &IDENTITY)
+                    .unwrap();
+                assert!((hit[0] - x).abs() <= x * 1e-6, "{} vs {x}", hit[0]);
+            }
+            let hit = bvh
+                .intersect(&[-0.5, 0.2, 0.2], &[-1.0, 0.0, 0.0], &IDENTITY)
+                .unwrap();
+            assert_eq!(hit[0], -1.0);
+        }
+    }
+
+    #[test]
+    fn test_intersect_displaced() {
+        // 2x2 quad in y = 0 facing +y, vertices at the corners
+        let positions = [
+            -1.0, 0.0, -1.0, 1.0, 0.0, -1.0, 1.0, 0.0, 1.0, -1.0, 0.0, 1.0,
+        ];
+        let bvh = BVHRaycaster::from_geometry(&positions, &[0, 2, 1, 0, 3, 2]);
+        let down = |x: f32, z: f32, displacements: &[f32]| {
+            bvh.intersect_displaced(&[x, 5.0, z], &[0.0, -1.0, 0.0], &IDENTITY, displacements)
+                .unwrap()
+        };
+
+        let rest = [0.0; 12];
+        let hit = down(0.5, 0.5, &rest).unwrap();
+        assert!(hit[1].abs() < 1e-6);
+        assert_eq!(
+            hit,
+            bvh.intersect(&[0.5, 5.0, 0.5], &[0.0, -1.0, 0.0], &IDENTITY)
+                .unwrap()
+        );
+
+        // Everything lifted by 0.5
+        let lifted = [0.0, 0.5, 0.0].repeat(4);
+        let hit = down(0.5, 0.5, &lifted).unwrap();
+        assert!((hit[1] - 0.5).abs() < 1e-6);
+        assert!((hit[6] - 4.5).abs() < 1e-5);
+
+        // One corner raised: the hit follows the tilted triangle
+        let mut corner = rest;
+        corner[7] = 1.0; // vertex 2 (1, 0, 1) up by 1
+        let hit = down(0.6, 0.2, &corner).unwrap();
+        // Triangle (0, 2, 1) now lies in the plane y = (z + 1) / 2
+        assert!((hit[1] - 0.6).abs() < 1e-5, "{}", hit[1]);
+        let normal = Vec3::from_slice(&hit[3..6]);
+        assert!(normal.distance(Vec3::new(0.0, 2.0, -1.0).normalize()) < 1e-5);
+
+        // Moved out of the original bounds: still found through the grown nodes
+        let shifted = [2.0, 0.0, 0.0].repeat(4);
+        assert!(down(1.5, 0.0, &rest).is_none());
+        let hit = down(1.5, 0.0, &shifted).unwrap();
+        assert!(hit[1].abs() < 1e-6);
+
+        assert!(bvh
+            .intersect_displaced(&[0.0; 3], &[0.0, -1.0, 0.0], &IDENTITY, &[0.0; 9])
+            .is_err());
+    }
+
+    #[test]
+    fn test_triangle_mask() {
+        let mut bvh = cube();
+        bvh.set_cull_mode(1).unwrap();
+        let (origin, direction) = ([0.3, 0.2, 5.0], [0.0, 0.0, -1.0]);
+        let front = bvh
+            .intersect_detailed(&origin, &direction, &IDENTITY)
+            .unwrap();
+        assert!((front[6] - 4.0).abs() < 1e-5);
+        assert!([0.0, 1.0].contains(&front[7]));
+
+        assert!(bvh.set_triangle_mask(&[1; 11]).is_err());
+        // Front face (triangles 0 and 1) off: the ray goes through to the back
+        let mut mask = [1u8; 12];
+        mask[..2].fill(0);
+        bvh.set_triangle_mask(&mask).unwrap();
+        let back = bvh
+            .intersect_detailed(&origin, &direction, &IDENTITY)
+            .unwrap();
+        assert!((back[6] - 6.0).abs() < 1e-5);
+        assert!([2.0, 3.0].contains(&back[7]));
+        assert_eq!(
+            bvh.intersect_all(&origin, &direction, &IDENTITY, 0).len(),
+            ALL_HITS_STRIDE
+        );
+        let packet = bvh
+            .intersect_packet4(&origin.repeat(4), &direction.repeat(4), &IDENTITY)
+            .unwrap();
+        assert!((packet[6] - 6.0).abs() < 1e-5);
+        // Occlusion and shape casts skip the masked face too
+        assert!(!bvh.is_occluded(&origin, &[0.3, 0.2, 0.0], &IDENTITY));
+        let sphere = bvh
+            .sphere_cast(&origin, &direction, 0.1, &IDENTITY)
+            .unwrap();
+        assert!((sphere[6] - 5.9).abs() < 1e-4, "{sphere:?}");
+        let capsule = bvh
+            .capsule_cast(&origin, &[0.3, 0.4, 5.0], 0.1, &direction, 10.0, &IDENTITY)
+            .unwrap();
+        assert!((capsule[6] - 5.9).abs() < 1e-4, "{capsule:?}");
+
+        bvh.clear_triangle_mask();
+        assert!(bvh.is_occluded(&origin, &[0.3, 0.2, 0.0], &IDENTITY));
+        let sphere = bvh
+            .sphere_cast(&origin, &direction, 0.1, &IDENTITY)
+            .unwrap();
+        assert!((sphere[6] - 3.9).abs() < 1e-4, "{sphere:?}");
+        assert_eq!(
+            bvh.intersect_detailed(&origin, &direction, &IDENTITY),
+            Some(front)
+        );
+    }
+
+    #[test]
+    fn test_from_interleaved() {
+        let (positions, indices) = cube_geometry();
+        let reference = BVHRaycaster::from_geometry(&positions, &indices);
+        let rays = [
+            ([0.3, 0.2, 5.0], [0.0, 0.0, -1.0]),
+            ([-5.0, 0.4, -0.1], [1.0, 0.0, 0.0]),
+            ([3.0, 4.0, 2.0], [-0.5, -0.8, -0.4]),
+        ];
+
+        // 8 floats per vertex with the position at 0, 3 (after a normal) or 5
+        for offset in [0, 3, 5] {
+            let mut buffer = vec![9.0; 8 * 8];
+            for (vertex, p) in positions.chunks(3).enumerate() {
+                buffer[vertex * 8 + offset..][..3].copy_from_slice(p);
+            }
+            let bvh = BVHRaycaster::from_interleaved(&buffer, 8, offset as u32, &indices).unwrap();
+            assert_eq!(bvh.get_stats(), reference.get_stats());
+            assert_eq!(bvh.get_build_warnings(), [0, 0]);
+            for (origin, direction) in &rays {
+                assert_eq!(
+                    bvh.intersect_all(origin, direction, &IDENTITY, 0),
+                    reference.intersect_all(origin, direction, &IDENTITY, 0)
+                );
+            }
+        }
+
+        assert!(BVHRaycaster::from_interleaved(&[0.0; 64], 7, 5, &indices).is_err());
+        assert!(BVHRaycaster::from_interleaved(&[0.0; 63], 8, 0, &indices).is_err());
+        assert!(BVHRaycaster::from_interleaved(&[0.0; 64], 0, 0, &indices).is_err());
+        // Indices past the last whole vertex are skipped as usual (the rest
+        // collapse to a point here)
+        let short = BVHRaycaster::from_interleaved(&[0.0; 56], 8, 3, &indices).unwrap();
+        assert_eq!(short.get_build_warnings(), [8, 4]);
+    }
+
+    #[test]
+    fn test_update_region() {
+        // 32x32 quads in y = 0 facing +y, (x, z) in 0..32
+        const N: u32 = 32;
+        let mut positions = Vec::new();
+        for z in 0..=N {
+            for x in 0..=N {
+                positions.extend([x as f32, 0.0, z as f32]);
+            }
+        }
+        let mut indices = Vec::new();
+        for z in 0..N {
+            for x in 0..N {
+                let v = z * (N + 1) + x;
+                indices.extend([v, v + N + 1, v + 1, v + 1, v + N + 1, v + N + 2]);
+            }
+        }
+        let mut bvh = BVHRaycaster::from_geometry_sah(&positions, &indices, 8);
+        let node_limit = 2 * indices.len() / 3;
+
+        // Strokes pulling up the 6x6 vertex corner at (0, 0) to changing heights
+        let corner: Vec<u32> = (0..36)
+            .map(|q| 2 * ((q / 6) * N + q % 6))
+            .flat_map(|t| [t, t + 1])
+            .collect();
+        for stroke in 0..20 {
+            let height = 1.0 + (stroke % 5) as f32;
+            for z in 0..=6 {
+                for x in 0..=6 {
+                    positions[((z * (N + 1) + x) * 3 + 1) as usize] =
+                        height * (6 - x.max(z)) as f32 / 6.0;
+                }
+            }
+            bvh.update_region(&corner, &positions).unwrap();
+
+            let fresh = BVHRaycaster::from_geometry(&positions, &indices);
+            for i in 0..40 {
+                let origin = [0.17 * i as f32, 10.0, 0.23 * i as f32 % 9.0];
+                let direction = [0.05, -1.0, 0.02];
+                assert_eq!(
+                    bvh.intersect(&origin, &direction, &IDENTITY),
+                    fresh.intersect(&origin, &direction, &IDENTITY),
+                    "stroke {stroke}, ray {i}"
+                );
+            }
+            let stats = bvh.get_stats();
+            assert!((stats[0] as usize) < node_limit, "{stats:?}");
+            assert_eq!(stats[0], stats[1] + stats[2]);
+            assert_eq!(stats[4] as usize, indices.len() / 3);
+            assert!(BVHRaycaster::from_bytes(&bvh.to_bytes()).is_ok());
+        }
+
+        assert!(bvh.update_region(&[2 * N * N], &positions).is_err());
+        assert!(bvh.update_region(&[0], &positions[3..]).is_err());
+    }
+
+    #[test]
+    fn test_grid_raycaster() {
+        fn assert_same(grid: Option<Vec<f32>>, bvh: Option<Vec<f32>>) {
+            match (grid, bvh) {
+                (None, None) => {}
+                (Some(g), Some(b)) => {
+                    for (x, y) in g.iter().zip(&b) {
+                        assert!((x - y).abs() < 1e-4, "grid {g:?} vs bvh {b:?}");
+                    }
+                }
+                (g, b) => panic!("grid {g:?} vs bvh {b:?}"),
+            }
+        }
+
+        // Cube: every face, a diagonal, misses, from inside and transformed
+        let (positions, indices) = cube_geometry();
+        let grid = GridRaycaster::from_geometry(&positions, &indices);
+        let bvh = cube();
+        let rays: [([f32; 3], [f32; 3]); 10] = [
+            ([0.0, 0.0, 5.0], [0.0, 0.0, -1.0]),
+            ([0.0, 0.0, -5.0], [0.0, 0.0, 1.0]),
+            ([5.0, 0.3, 0.0], [-1.0, 0.0, 0.0]),
+            ([-5.0, 0.0, 0.2], [1.0, 0.0, 0.0]),
+            ([0.1, 5.0, 0.0], [0.0, -1.0, 0.0]),
+            ([0.0, -5.0, -0.4], [0.0, 1.0, 0.0]),
+            ([4.0, 3.0, 5.0], [-4.0, -3.0, -5.0]),
+            ([0.0, 0.0, 5.0], [0.0, 0.0, 1.0]),
+            ([3.0, 0.0, 5.0], [0.0, 0.0, -1.0]),
+            ([0.0, 0.0, 0.0], [0.3, 0.2, 1.0]),
+        ];
+        let scaled = Mat4::from_scale_rotation_translation(
+            Vec3::new(2.0, 0.5, 1.0),
+            glam::Quat::from_rotation_y(0.4),
+            Vec3::new(0.5, 0.0, -1.0),
+        )
+        .to_cols_array();
+        for (origin, dir) in rays {
+            for model in [&IDENTITY, &scaled] {
+                assert_same(
+                    grid.intersect(&origin, &dir, model),
+                    bvh.intersect(&origin, &dir, model),
+                );
+            }
+        }
+        assert!(grid
+            .intersect(&[0.0, 0.0, 5.0], &[0.0, 0.0, -1.0], &[0.0; 3])
+            .is_none());
+
+        // Heightfield: long oblique rays cross many cells
+        let n = 24;
+        let mut positions = Vec::new();
+        for z in 0..=n {
+            for x in 0..=n {
+                let (fx, fz) = (x as f32, z as f32);
+                positions.extend([fx, (fx * 0.7).sin() + (fz * 0.4).cos(), fz]);
+            }
+        }
+        let mut indices = Vec::new();
+        for z in 0..n {
+            for x in 0..n {
+                let i = z * (n + 1) + x;
+                indices.extend([i, i + n + 1, i + 1, i + 1, i + n + 1, i + n + 2]);
+            }
+        }
+        let bvh = BVHRaycaster::from_geometry(&positions, &indices);
+        let auto = GridRaycaster::from_geometry(&positions, &indices);
+        let coarse = GridRaycaster::with_resolution(&positions, &indices, 3);
+        // 1152 triangles over a square: 24 x 24 cells of 2, tiling x and z
+        assert_eq!(auto.get_resolution(), vec![24, 24, 0, 2]);
+        assert_eq!(coarse.get_resolution(), vec![3, 3, 0, 2]);
+
+        let mut hits = 0;
+        for i in 0..50 {
+            let f = i as f32;
+            let origin = [f * 0.47 % 24.0, 5.0, f * 0.31 % 24.0];
+            let dirs = [
+                [0.0, -1.0, 0.0],
+                [(f * 1.3).cos(), -0.5, (f * 1.3).sin()],
+                [1.0, -0.3, 0.5],
+            ];
+            for dir in dirs {
+                let expected = bvh.intersect(&origin, &dir, &IDENTITY);
+                hits += expected.is_some() as usize;
+                assert_same(auto.intersect(&origin, &dir, &IDENTITY), expected.clone());
+                assert_same(coarse.intersect(&origin, &dir, &IDENTITY), expected);
+            }
+        }
+        assert!(hits > 75, "only {hits} rays hit the terrain");
+    }
+
+    #[test]
+    fn test_short_model_matrix() {
+        let bvh = cube();
+        let short = &IDENTITY[..15];
+        let (origin, dir) = ([0.0, 0.0, 5.0], [0.0, 0.0, -1.0]);
+
+        // Option: None, like a miss
+        assert!(bvh.intersect_within(&origin, &dir, short, 10.0).is_none());
+        assert!(bvh.intersect_detailed(&origin, &dir, short).is_none());
+        assert!(bvh.sphere_cast(&origin, &dir, 0.1, short).is_none());
+        let identity = DMat4::IDENTITY.to_cols_array();
+        let (origin_f64, dir_f64) = ([0.0, 0.0, 5.0], [0.0, 0.0, -1.0]);
+        assert!(bvh
+            .intersect_f64(&origin_f64, &dir_f64, &identity)
+            .is_some());
+        assert!(bvh
+            .intersect_f64(&origin_f64, &dir_f64, &identity[..15])
+            .is_none());
+        assert!(bvh
+            .intersect_f64(&origin_f64[..2], &dir_f64, &identity)
+            .is_none());
+        assert!(bvh
+            .intersect_f64(&origin_f64, &dir_f64[..2], &identity)
+            .is_none());
+
+        // Result: an error naming the length
+        let mut out = [0.0; BATCH_STRIDE];
+        let err = bvh
+            .intersect_batch(&origin, &dir, short, &mut out)
+            .unwrap_err();
+        assert!(err.contains("16 floats"), "{err}");
+        assert!(bvh
+            .intersect_packet4(&[0.0; 12], &[1.0; 12], short)
+            .is_err());
+        assert!(bvh.query_frustum(&[0.0; 24], short, false).is_err());
+
+        // Plain values: the miss value of each query
+        assert!(bvh.intersect_all(&origin, &dir, short, 0).is_empty());
+        assert_eq!(
+            bvh.intersect_n(&origin, &dir, short, 1),
+            [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0, -1.0]
+        );
+        assert_eq!(bvh.query_id_under_ray(&origin, &dir, short), -1);
+        assert!(!bvh.is_occluded(&origin, &[0.0, 0.0, -5.0], short));
+        assert!(!bvh.contains_point(&[0.0; 3], short));
+        assert!(bvh.closest_point(&[0.0; 3], short).is_empty());
+        assert_eq!(bvh.signed_distance(&[0.0; 3], short), f32::INFINITY);
+        assert!(bvh.nearest_triangles(&[0.0; 3], 3, short).is_empty());
+        assert!(bvh
+            .query_aabb(&[-1.0, -1.0, -1.0], &[1.0, 1.0, 1.0], short)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_sah_build() {
+        let rays: Vec<([f32; 3], [f32; 3])> = vec![
+            ([0.0, 0.0, 5.0], [0.0, 0.0, -1.0]),
+            ([0.3, -0.2, -5.0], [0.0, 0.0, 1.0]),
+            ([5.0, 0.5, 0.5], [-1.0, 0.0, 0.0]),
+            ([3.0, 4.0, 5.0], [-0.5, -0.7, -1.0]),
+            ([0.0, 5.0, 0.0], [0.0, -1.0, 0.0]),
+            ([5.0, 5.0, 5.0], [1.0, 1.0, 1.0]),
+        ];
+        let (positions, indices) = cube_geometry();
+        let median = cube();
+        let sah = BVHRaycaster::from_geometry_sah(&positions, &indices, 8);
+        for (origin, direction) in &rays {
+            assert_eq!(
+                sah.intersect(origin, direction, &IDENTITY),
+                median.intersect(origin, direction, &IDENTITY)
+            );
+        }
+
+        // Plane of 2x2 cells with the corner cell subdivided 32x32
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
+        let mut quad = |x: f32, y: f32, size: f32| {
+            let base = (positions.len() / 3) as u32;
+            positions.extend([
+                x,
+                y,
+                0.0,
+                x + size,
+                y,
+                0.0,
+                x,
+                y + size,
+                0.0,
+                x + size,
+                y + size,
+                0.0,
+            ]);
+            indices.extend([base, base + 1, base + 3, base, base + 3, base + 2]);
+        };
+        for j in 0..8 {
+            for i in 0..8 {
+                if i + j > 0 {
+                    quad(i as f32 * 2.0, j as f32 * 2.0, 2.0);
+                }
+            }
+        }
+        for j in 0..32 {
+            for i in 0..32 {
+                quad(i as f32 / 16.0, j as f32 / 16.0, 1.0 / 16.0);
+            }
+        }
+        let median = BVHRaycaster::from_geometry(&positions, &indices);
+        let sah = BVHRaycaster::from_geometry_sah(&positions, &indices, 16);
+        assert_eq!(sah.get_stats()[4], median.get_stats()[4]);
+        assert!(
+            sah.get_sah_cost() < median.get_sah_cost(),
+            "{} vs {}",
+            sah.get_sah_cost(),
+            median.get_sah_cost()
+        );
+
+        // Same hits, including inside the dense corner
+        let mut seed = 12345u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f32 / (1 << 24) as f32
+        };
+        for n in 0..200 {
+            let extent = if n % 2 == 0 { 2.0 } else { 16.0 };
+            let origin = [next() * extent, next() * extent, 3.0];
+            let hit = sah.intersect(&origin, &[0.0, 0.0, -1.0], &IDENTITY);
+            assert_eq!(hit, median.intersect(&origin, &[0.0, 0.0, -1.0], &IDENTITY));
+        }
+    }
+
+    #[test]
+    fn test_refit() {
+        let (positions, indices) = cube_geometry();
+        let mut bvh = BVHRaycaster::from_geometry(&positions, &indices);
+        let stats = bvh.get_stats();
+        let down = [0.0, 0.0, -1.0];
+
+        // Scale by 2 and move 10 along x
+        let moved: Vec<f32> = positions
+            .chunks(3)
+            .flat_map(|p| [p[0] * 2.0 + 10.0, p[1] * 2.0, p[2] * 2.0])
+            .collect();
+        bvh.refit(&moved).unwrap();
+        assert_eq!(bvh.get_stats(), stats);
+        assert!(bvh.intersect(&[0.0, 0.0, 5.0], &down, &IDENTITY).is_none());
+        let hit = bvh.intersect(&[11.5, 1.5, 5.0], &down, &IDENTITY).unwrap();
+        assert!((hit[6] - 3.0).abs() < 1e-5);
+        assert!((hit[5] - 1.0).abs() < 1e-5);
+
+        // Appended chunks and compacted subtrees refit too
+        let (chunk, chunk_indices) = cubes_along_x(&[5.0], 8);
+        bvh.append_geometry(&chunk, &chunk_indices, 8).unwrap();
+        bvh.compact();
+        let mut all = moved.clone();
+        all.extend(chunk.chunks(3).flat_map(|p| [p[0], p[1] + 20.0, p[2]]));
+        bvh.refit(&all).unwrap();
+        assert!(bvh.intersect(&[5.0, 20.0, 5.0], &down, &IDENTITY).is_some());
+        assert!(bvh.intersect(&[5.0, 0.0, 5.0], &down, &IDENTITY).is_none());
+
+        assert!(bvh.refit(&moved).is_err());
+        bvh.dispose();
+        assert!(bvh.refit(&[]).is_err());
+    }
 
-        if node.triangle_count > 0 {
-            // Leaf node
-            return current_depth;
-        }
+    #[test]
+    fn test_intersect_all() {
+        // Two cubes along x: a ray down the axis enters each once (backfaces culled)
+        let (positions, indices) = cubes_along_x(&[0.0, 4.0], 0);
+        let bvh = BVHRaycaster::from_geometry(&positions, &indices);
+        let hits = bvh.intersect_all(&[-5.0, 0.3, 0.2], &[1.0, 0.0, 0.0], &IDENTITY, 0);
+        assert_eq!(hits.len(), 2 * ALL_HITS_STRIDE);
+        assert!((hits[6] - 4.0).abs() < 1e-5);
+        assert!((hits[ALL_HITS_STRIDE + 6] - 8.0).abs() < 1e-5);
+        // Left faces are triangles 4 and 5 of each cube
+        assert!([4.0, 5.0].contains(&hits[7]));
+        assert!([16.0, 17.0].contains(&hits[ALL_HITS_STRIDE + 7]));
 
-        let left_depth = node.left.map_or(current_depth, |l| {
-            self.compute_max_depth(l, current_depth + 1)
-        });
-        let right_depth = node.right.map_or(current_depth, |r| {
-            self.compute_max_depth(r, current_depth + 1)
-        });
+        let capped = bvh.intersect_all(&[-5.0, 0.3, 0.2], &[1.0, 0.0, 0.0], &IDENTITY, 1);
+        assert_eq!(capped, hits[..ALL_HITS_STRIDE]);
+        // A cap too large to multiply by the stride is just no cap
+        let uncapped =
+            bvh.intersect_all(&[-5.0, 0.3, 0.2], &[1.0, 0.0, 0.0], &IDENTITY, usize::MAX);
+        assert_eq!(uncapped, hits);
+
+        // Through the diagonal shared by the two front-face triangles: one hit
+        let hits = bvh.intersect_all(&[0.25, 0.25, 5.0], &[0.0, 0.0, -1.0], &IDENTITY, 0);
+        assert_eq!(hits.len(), ALL_HITS_STRIDE);
+        assert!((hits[6] - 4.0).abs() < 1e-5);
 
-        left_depth.max(right_depth)
+        assert!(bvh
+            .intersect_all(&[0.0, 5.0, 5.0], &[0.0, 0.0, -1.0], &IDENTITY, 0)
+            .is_empty());
     }
 
-    /// Iterative BVH traversal (faster than recursive for WASM)
-    fn traverse_bvh(&self, origin: Vec3, direction: Vec3, inv_dir: Vec3) -> Option<RayHit> {
-        if self.nodes.is_empty() {
-            return None;
-        }
+    #[test]
+    fn test_cull_modes() {
+        // One triangle in z = 0 facing +z
+        let mut bvh =
+            BVHRaycaster::from_geometry(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0], &[0, 1, 2]);
+        let from_front =
+            |bvh: &BVHRaycaster| bvh.intersect(&[0.2, 0.2, 1.0], &[0.0, 0.0, -1.0], &IDENTITY);
+        let from_back =
+            |bvh: &BVHRaycaster| bvh.intersect(&[0.2, 0.2, -1.0], &[0.0, 0.0, 1.0], &IDENTITY);
 
-        let mut stack = Vec::with_capacity(64); // Max depth we expect
-        stack.push(0usize); // Root node
+        assert_eq!(bvh.get_cull_mode(), 0);
+        assert_eq!(from_front(&bvh).unwrap()[5], 1.0);
+        assert!(from_back(&bvh).is_none());
 
-        let mut closest_hit: Option<RayHit> = None;
-        let mut closest_t = f32::MAX;
+        bvh.set_cull_mode(1).unwrap();
+        assert_eq!(from_front(&bvh).unwrap()[5], 1.0);
+        let back = from_back(&bvh).unwrap();
+        assert_eq!(back[5], -1.0);
+        assert!((back[6] - 1.0).abs() < 1e-6);
 
-        while let Some(node_idx) = stack.pop() {
-            let node = &self.nodes[node_idx];
+        bvh.set_cull_mode(2).unwrap();
+        assert!(from_front(&bvh).is_none());
+        assert_eq!(from_back(&bvh).unwrap()[5], -1.0);
 
-            // AABB intersection test with early termination
-            if !self.ray_aabb_intersect(origin, inv_dir, node, closest_t) {
-                continue;
-            }
+        assert!(bvh.set_cull_mode(3).is_err());
+        assert_eq!(bvh.get_cull_mode(), 2);
+    }
 
-            if node.triangle_count > 0 {
-                // Leaf node - test triangles
-                for i in 0..node.triangle_count {
-                    let tri_idx = self.triangle_indices[node.triangle_start + i];
-                    if let Some(hit) =
-                        self.ray_triangle_intersect(origin, direction, &self.triangles[tri_idx])
-                    {
-                        if hit.distance < closest_t {
-                            closest_t = hit.distance;
-                            closest_hit = Some(hit);
-                        }
+    #[test]
+    fn test_intersect_detailed() {
+        // Second cube's front face, after a skipped (out of range) triangle
+        let (positions, mut indices) = cubes_along_x(&[0.0, 4.0], 0);
+        indices.splice(0..0, [99, 99, 99]);
+        let bvh = BVHRaycaster::from_geometry(&positions, &indices);
+
+        let hit = bvh
+            .intersect_detailed(&[4.5, -0.5, 5.0], &[0.0, 0.0, -1.0], &IDENTITY)
+            .unwrap();
+        assert_eq!(hit.len(), 10);
+        assert_eq!(
+            hit[..7],
+            bvh.intersect(&[4.5, -0.5, 5.0], &[0.0, 0.0, -1.0], &IDENTITY)
+                .unwrap()[..]
+        );
+        // Triangle (0, 1, 2) of cube 2 is input triangle 1 + 12
+        assert_eq!(hit[7], 13.0);
+
+        // Barycentrics reproduce the hit point from the triangle's vertices
+        let tri = &indices[13 * 3..13 * 3 + 3];
+        let vertex = |i: u32| Vec3::from_slice(&positions[i as usize * 3..]);
+        let (u, v) = (hit[8], hit[9]);
+        let point = vertex(tri[0]) * (1.0 - u - v) + vertex(tri[1]) * u + vertex(tri[2]) * v;
+        assert!(point.distance(Vec3::from_slice(&hit[..3])) < 1e-5);
+    }
+
+    #[test]
+    fn test_smooth_normals() {
+        // Icosahedron: faces are the vertex triples 2 apart, wound outwards
+        let phi = (1.0 + 5f32.sqrt()) / 2.0;
+        let mut vertices = Vec::new();
+        for a in [-1.0, 1.0] {
+            for b in [-phi, phi] {
+                vertices.extend([
+                    Vec3::new(0.0, a, b),
+                    Vec3::new(a, b, 0.0),
+                    Vec3::new(b, 0.0, a),
+                ]);
+            }
+        }
+        let mut indices = Vec::new();
+        for i in 0..12u32 {
+            for j in i + 1..12 {
+                for k in j + 1..12 {
+                    let [a, b, c] = [i, j, k].map(|n| vertices[n as usize]);
+                    let edge = |p: Vec3, q: Vec3| (p.distance(q) - 2.0).abs() < 1e-4;
+                    if edge(a, b) && edge(b, c) && edge(a, c) {
+                        let outward = (b - a).cross(c - a).dot(a + b + c) > 0.0;
+                        indices.extend(if outward { [i, j, k] } else { [i, k, j] });
                     }
                 }
-            } else {
-                // Internal node - push children onto stack
-                // Push in reverse order so left is processed first (front-to-back)
-                if let Some(right) = node.right {
-                    stack.push(right);
-                }
-                if let Some(left) = node.left {
-                    stack.push(left);
+            }
+        }
+        assert_eq!(indices.len(), 60);
+        let positions: Vec<f32> = vertices.iter().flat_map(|v| v.to_array()).collect();
+        let normals: Vec<f32> = vertices
+            .iter()
+            .flat_map(|v| v.normalize().to_array())
+            .collect();
+
+        let flat = BVHRaycaster::from_geometry(&positions, &indices);
+        let smooth =
+            BVHRaycaster::from_geometry_with_normals(&positions, &indices, &normals).unwrap();
+        assert!(
+            BVHRaycaster::from_geometry_with_normals(&positions, &indices, &normals[3..]).is_err()
+        );
+
+        // Aim at the centre, off the face centres
+        for direction in [Vec3::new(0.3, 0.8, 0.5), Vec3::new(-0.7, 0.1, 0.4)] {
+            let origin = (direction.normalize() * 5.0).to_array();
+            let ray = (-direction).to_array();
+            let flat_hit = flat.intersect(&origin, &ray, &IDENTITY).unwrap();
+            let smooth_hit = smooth.intersect(&origin, &ray, &IDENTITY).unwrap();
+            assert_eq!(flat_hit[..3], smooth_hit[..3]);
+
+            let radial = Vec3::from_slice(&smooth_hit[..3]).normalize();
+            let flat_normal = Vec3::from_slice(&flat_hit[3..6]);
+            let smooth_normal = Vec3::from_slice(&smooth_hit[3..6]);
+            assert!(
+                smooth_normal.dot(radial) > 0.999,
+                "{smooth_normal} vs {radial}"
+            );
+            assert!(smooth_normal.dot(radial) > flat_normal.dot(radial));
+        }
+
+        let mut cleared = smooth;
+        cleared.set_vertex_normals(&[]).unwrap();
+        let ray = (Vec3::new(0.3, 0.8, 0.5).normalize() * 5.0).to_array();
+        assert_eq!(
+            cleared.intersect(&ray, &[-0.3, -0.8, -0.5], &IDENTITY),
+            flat.intersect(&ray, &[-0.3, -0.8, -0.5], &IDENTITY)
+        );
+    }
+
+    #[test]
+    fn test_intersect_batch() {
+        let offsets: Vec<f32> = (0..32).map(|i| i as f32 * 3.0).collect();
+        let (positions, indices) = cubes_along_x(&offsets, 0);
+        let bvh = BVHRaycaster::from_geometry(&positions, &indices);
+        let model = Mat4::from_scale_rotation_translation(
+            Vec3::splat(1.5),
+            glam::Quat::from_rotation_y(0.3),
+            Vec3::new(1.0, 2.0, 3.0),
+        )
+        .to_cols_array();
+
+        // Rays fanning down over the row, every third one missing
+        let rays = 3000;
+        let mut origins = Vec::new();
+        let mut directions = Vec::new();
+        for n in 0..rays {
+            let x = n as f32 * 0.05;
+            origins.extend([x, if n % 3 == 0 { 20.0 } else { 2.0 }, 10.0]);
+            directions.extend([0.01 * (n % 7) as f32, 0.0, -1.0]);
+        }
+
+        let mut out = vec![0.0; rays * BATCH_STRIDE];
+        let mut best = [f64::MAX; 2];
+        for _ in 0..3 {
+            let start = std::time::Instant::now();
+            let mut individual = Vec::with_capacity(rays);
+            for (o, d) in origins.chunks(3).zip(directions.chunks(3)) {
+                individual.push(bvh.intersect_detailed(o, d, &model));
+            }
+            best[0] = best[0].min(start.elapsed().as_secs_f64());
+
+            let start = std::time::Instant::now();
+            let hits = bvh
+                .intersect_batch(&origins, &directions, &model, &mut out)
+                .unwrap();
+            best[1] = best[1].min(start.elapsed().as_secs_f64());
+
+            assert_eq!(hits, individual.iter().flatten().count());
+            assert!(hits > 0 && hits < rays);
+            for (slot, single) in out.chunks(BATCH_STRIDE).zip(&individual) {
+                match single {
+                    Some(hit) => assert_eq!(slot, &hit[..8]),
+                    None => assert_eq!(slot[6..], [-1.0, -1.0]),
                 }
             }
         }
+        assert!(
+            best[1] < best[0],
+            "batch {:?} vs individual {:?}",
+            best[1],
+            best[0]
+        );
 
-        closest_hit
+        assert!(bvh
+            .intersect_batch(&origins, &directions[3..], &model, &mut out)
+            .is_err());
+        assert!(bvh
+            .intersect_batch(&origins, &directions, &model, &mut out[1..])
+            .is_err());
     }
 
-    /// Ray-AABB intersection using slab method
-    ///
-    /// Uses pre-computed inverse direction for efficiency
-    #[inline]
-    fn ray_aabb_intersect(&self, origin: Vec3, inv_dir: Vec3, node: &BVHNode, max_t: f32) -> bool {
-        let t1 = (node.bounds_min - origin) * inv_dir;
-        let t2 = (node.bounds_max - origin) * inv_dir;
+    #[test]
+    fn test_closest_point() {
+        let bvh = cube();
+        let query = |p: [f32; 3]| bvh.closest_point(&p, &IDENTITY);
+        let assert_near = |got: &[f32], want: [f32; 3], distance: f32| {
+            assert!(
+                Vec3::from_slice(got).distance(Vec3::from(want)) < 1e-5,
+                "{got:?}"
+            );
+            assert!((got[6] - distance).abs() < 1e-5, "{got:?}");
+        };
 
-        let t_min_v = t1.min(t2);
-        let t_max_v = t1.max(t2);
+        // Above the front face (triangle 1 covers y > x)
+        let hit = query([0.2, 0.3, 5.0]);
+        assert_near(&hit, [0.2, 0.3, 1.0], 4.0);
+        assert_eq!(hit[3..6], [0.0, 0.0, 1.0]);
+        assert_eq!(hit[7], 1.0);
 
-        let t_enter = t_min_v.x.max(t_min_v.y).max(t_min_v.z);
-        let t_exit = t_max_v.x.min(t_max_v.y).min(t_max_v.z);
+        // Near an edge and a corner
+        assert_near(&query([2.0, 0.5, 2.0]), [1.0, 0.5, 1.0], 2f32.sqrt());
+        assert_near(&query([2.0, 2.0, 2.0]), [1.0, 1.0, 1.0], 3f32.sqrt());
 
-        // Ray intersects if entry < exit, exit >= 0, and entry < current best
-        t_enter <= t_exit && t_exit >= 0.0 && t_enter < max_t
+        // On the surface, inside, and far outside the root bounds
+        assert_near(&query([0.5, -0.5, 1.0]), [0.5, -0.5, 1.0], 0.0);
+        assert_near(&query([0.0, 0.1, 0.9]), [0.0, 0.1, 1.0], 0.1);
+        assert_near(&query([0.0, 0.0, -1000.0]), [0.0, 0.0, -1.0], 999.0);
+
+        // Scaled and moved model
+        let model = Mat4::from_scale_rotation_translation(
+            Vec3::splat(2.0),
+            glam::Quat::IDENTITY,
+            Vec3::new(10.0, 0.0, 0.0),
+        );
+        let hit = bvh.closest_point(&[10.5, 0.0, 5.0], &model.to_cols_array());
+        assert_near(&hit, [10.5, 0.0, 2.0], 3.0);
+
+        assert!(BVHRaycaster::from_geometry(&[], &[])
+            .closest_point(&[0.0; 3], &IDENTITY)
+            .is_empty());
     }
 
-    /// Möller–Trumbore ray-triangle intersection
-    ///
-    /// Fast algorithm that computes barycentric coordinates directly.
-    /// Uses front-face culling (a < 0 = backface hit, rejected to match Three.js)
-    #[inline]
-    fn ray_triangle_intersect(&self, origin: Vec3, dir: Vec3, tri: &Triangle) -> Option<RayHit> {
-        const EPSILON: f32 = 1e-7;
+    #[test]
+    fn test_sphere_cast() {
+        let bvh = cube();
+        let down = [0.0, -1.0, 0.0];
+        let check = |hit: Option<Vec<f32>>, contact: [f32; 3], distance: f32| {
+            let hit = hit.expect("sphere missed");
+            assert!(
+                Vec3::from_slice(&hit).distance(Vec3::from(contact)) < 1e-4,
+                "{hit:?}"
+            );
+            assert!((hit[6] - distance).abs() < 1e-4, "{hit:?}");
+            hit
+        };
 
-        let edge1 = tri.v1 - tri.v0;
-        let edge2 = tri.v2 - tri.v0;
-        let h = dir.cross(edge2);
-        let a = edge1.dot(h);
+        // Face: lands flat on the top
+        let hit = check(
+            bvh.sphere_cast(&[0.2, 5.0, 0.1], &down, 0.5, &IDENTITY),
+            [0.2, 1.0, 0.1],
+            3.5,
+        );
+        assert!((Vec3::from_slice(&hit[3..6]) - Vec3::Y).length() < 1e-5);
 
-        // Backface culling: a > 0 = front face, a < 0 = back face
-        // Also rejects parallel rays (a ≈ 0)
-        if a < EPSILON {
-            return None;
+        // Edge: beside the cube, where a plain ray misses
+        assert!(bvh.intersect(&[1.3, 5.0, 0.0], &down, &IDENTITY).is_none());
+        check(
+            bvh.sphere_cast(&[1.3, 5.0, 0.0], &down, 0.5, &IDENTITY),
+            [1.0, 1.0, 0.0],
+            3.6,
+        );
+
+        // Vertex: diagonal to the corner, 0.3 * sqrt(2) out
+        let drop = (0.25f32 - 0.18).sqrt();
+        check(
+            bvh.sphere_cast(&[1.3, 5.0, 1.3], &down, 0.5, &IDENTITY),
+            [1.0, 1.0, 1.0],
+            4.0 - drop,
+        );
+
+        // Too far out, and already touching
+        assert!(bvh
+            .sphere_cast(&[1.6, 5.0, 0.0], &down, 0.5, &IDENTITY)
+            .is_none());
+        check(
+            bvh.sphere_cast(&[0.0, 1.2, 0.0], &down, 0.5, &IDENTITY),
+            [0.0, 1.0, 0.0],
+            0.0,
+        );
+
+        // Uniform scale: cube becomes [-2, 2]^3
+        let scaled = Mat4::from_scale(Vec3::splat(2.0)).to_cols_array();
+        check(
+            bvh.sphere_cast(&[0.0, 10.0, 0.0], &down, 0.5, &scaled),
+            [0.0, 2.0, 0.0],
+            7.5,
+        );
+    }
+
+    #[test]
+    fn test_capsule_cast() {
+        let bvh = cube();
+        let down = [0.0, -1.0, 0.0];
+        let check = |hit: Option<Vec<f32>>, normal: Vec3, distance: f32| {
+            let hit = hit.expect("capsule missed");
+            assert!((hit[6] - distance).abs() < 1e-4, "{hit:?}");
+            assert!(
+                (Vec3::from_slice(&hit[3..6]) - normal).length() < 1e-4,
+                "{hit:?}"
+            );
+            hit
+        };
+
+        // Lying flat, ends past the cube: lands on the top face
+        let hit = check(
+            bvh.capsule_cast(
+                &[-3.0, 5.0, 0.0],
+                &[3.0, 5.0, 0.0],
+                0.5,
+                &down,
+                10.0,
+                &IDENTITY,
+            ),
+            Vec3::Y,
+            3.5,
+        );
+        assert!((hit[1] - 1.0).abs() < 1e-5 && hit[0].abs() <= 1.0);
+        assert!(bvh
+            .capsule_cast(
+                &[-3.0, 5.0, 0.0],
+                &[3.0, 5.0, 0.0],
+                0.5,
+                &down,
+                3.4,
+                &IDENTITY
+            )
+            .is_none());
+
+        // Upright, sliding into the x = 1 face
+        check(
+            bvh.capsule_cast(
+                &[3.0, -0.5, 0.0],
+                &[3.0, 0.5, 0.0],
+                0.5,
+                &[-2.0, 0.0, 0.0],
+                10.0,
+                &IDENTITY,
+            ),
+            Vec3::X,
+            1.5,
+        );
+
+        // Side onto the top corners beside the cube, where the ends miss
+        let drop = (0.25f32 - 0.09).sqrt();
+        let hit = check(
+            bvh.capsule_cast(
+                &[1.3, 5.0, -3.0],
+                &[1.3, 5.0, 3.0],
+                0.5,
+                &down,
+                10.0,
+                &IDENTITY,
+            ),
+            Vec3::new(0.3, drop, 0.0) / 0.5,
+            4.0 - drop,
+        );
+        assert!(
+            (Vec3::from_slice(&hit[..3]) - Vec3::ONE)
+                .abs()
+                .max_element()
+                < 1e-4
+                || (Vec3::from_slice(&hit[..3]) - Vec3::new(1.0, 1.0, -1.0))
+                    .abs()
+                    .max_element()
+                    < 1e-4
+        );
+
+        // Already overlapping: zero distance, pushed out of the top face
+        check(
+            bvh.capsule_cast(
+                &[0.0, 1.2, -3.0],
+                &[0.0, 1.2, 3.0],
+                0.5,
+                &down,
+                10.0,
+                &IDENTITY,
+            ),
+            Vec3::Y,
+            0.0,
+        );
+        let through = bvh
+            .capsule_cast(
+                &[0.0, 0.3, -3.0],
+                &[0.0, 0.3, 3.0],
+                0.1,
+                &down,
+                10.0,
+                &IDENTITY,
+            )
+            .unwrap();
+        assert_eq!(through[6], 0.0);
+        assert!((Vec3::from_slice(&through[3..6]).length() - 1.0).abs() < 1e-5);
+
+        // Random sweeps: the capsule touches at the reported distance and is
+        // clear just before it
+        let mut seed = 99u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0
+        };
+        let gap = |a: Vec3, b: Vec3| {
+            bvh.triangles
+                .iter()
+                .map(|tri| {
+                    let (axis, nearest) = BVHRaycaster::closest_points_segment_triangle(a, b, tri);
+                    axis.distance(nearest)
+                })
+                .fold(f32::MAX, f32::min)
+        };
+        let mut hits = 0;
+        for _ in 0..300 {
+            let a = Vec3::new(next(), next(), next()) * 4.0;
+            let b = a + Vec3::new(next(), next(), next()) * 2.0;
+            let dir = (-a + Vec3::new(next(), next(), next())).normalize();
+            let radius = 0.1 + next().abs() * 0.4;
+            let Some(hit) = bvh.capsule_cast(
+                &a.to_array(),
+                &b.to_array(),
+                radius,
+                &dir.to_array(),
+                20.0,
+                &IDENTITY,
+            ) else {
+                // No contact anywhere along the way
+                assert!((0..=400).all(|i| {
+                    let t = i as f32 * 0.05;
+                    gap(a + dir * t, b + dir * t) > radius - 1e-3
+                }));
+                continue;
+            };
+            hits += 1;
+            let t = hit[6];
+            assert!((gap(a + dir * t, b + dir * t) - radius).abs() < 1e-3 || t == 0.0);
+            if t > 1e-2 {
+                let before = t - 1e-2;
+                assert!(gap(a + dir * before, b + dir * before) > radius);
+            }
         }
+        assert!(hits > 75, "{hits}");
+    }
 
-        let f = 1.0 / a;
-        let s = origin - tri.v0;
-        let u = f * s.dot(h);
+    #[test]
+    fn test_is_occluded() {
+        let offsets: Vec<f32> = (0..16).map(|i| i as f32 * 3.0).collect();
+        let (positions, indices) = cubes_along_x(&offsets, 0);
+        let mut bvh = BVHRaycaster::from_geometry(&positions, &indices);
+        let tests = |bvh: &BVHRaycaster| bvh.get_triangle_test_counts().iter().sum::<u32>();
 
-        // Outside triangle (u coordinate)
-        if !(0.0..=1.0).contains(&u) {
-            return None;
+        // Along the row, cube 0 first: any-hit stops at the first blocker,
+        // closest-hit keeps testing triangles that might be nearer
+        let (from, to) = ([-5.0, 0.2, 0.3], [50.0, 0.2, 0.3]);
+        bvh.enable_profiling(true);
+        assert!(bvh.is_occluded(&from, &to, &IDENTITY));
+        let any_hit = tests(&bvh);
+        bvh.reset_profiling();
+        assert!(bvh.intersect(&from, &[1.0, 0.0, 0.0], &IDENTITY).is_some());
+        let closest = tests(&bvh);
+        assert!(any_hit < closest, "{any_hit} vs {closest}");
+
+        // Segment ending short of the mesh, passing beside it, and from inside
+        // a cube (only back faces in the way) to the gap before the next one
+        assert!(!bvh.is_occluded(&[-5.0, 0.0, 0.0], &[-1.5, 0.0, 0.0], &IDENTITY));
+        assert!(!bvh.is_occluded(&[-5.0, 5.0, 0.0], &[50.0, 5.0, 0.0], &IDENTITY));
+        assert!(bvh.is_occluded(&[0.0, 0.0, 0.0], &[1.5, 0.0, 0.0], &IDENTITY));
+        // (intersect culls that back face and only finds the next cube)
+        let hit = bvh.intersect(&[0.0, 0.0, 0.0], &[1.0, 0.0, 0.0], &IDENTITY);
+        assert_eq!(hit.unwrap()[6], 2.0);
+    }
+
+    #[test]
+    fn test_intersect_within() {
+        let bvh = cube();
+        let (origin, down) = ([0.2, 0.3, 5.0], [0.0, 0.0, -1.0]);
+        let full = bvh.intersect(&origin, &down, &IDENTITY);
+
+        assert_eq!(bvh.intersect_within(&origin, &down, &IDENTITY, 10.0), full);
+        assert_eq!(bvh.intersect_within(&origin, &down, &IDENTITY, 4.0), full);
+        assert!(bvh
+            .intersect_within(&origin, &down, &IDENTITY, 3.99)
+            .is_none());
+
+        // The limit is in world units: doubled cube, front face 8 away
+        let scaled = Mat4::from_scale(Vec3::splat(2.0)).to_cols_array();
+        let origin = [0.2, 0.3, 10.0];
+        assert!(bvh.intersect_within(&origin, &down, &scaled, 8.0).is_some());
+        assert!(bvh.intersect_within(&origin, &down, &scaled, 7.9).is_none());
+    }
+
+    #[test]
+    fn test_update_geometry() {
+        let mut bvh = cube();
+        let old = cubes_along_x(&[0.0], 0);
+        let new = cubes_along_x(&[10.0], 0);
+        let right = [1.0, 0.0, 0.0];
+        let at_old = [-5.0, 0.2, 0.3];
+        let at_new = [5.0, 0.2, 0.3];
+        assert!(bvh.intersect(&at_old, &right, &IDENTITY).is_some());
+
+        bvh.update_geometry(&new.0, &new.1);
+        assert!(bvh.intersect(&at_old, &right, &IDENTITY).unwrap()[0] > 8.0);
+        let hit = bvh.intersect(&at_new, &right, &IDENTITY).unwrap();
+        assert!((hit[0] - 9.0).abs() < 1e-5);
+        assert_eq!(bvh.get_stats()[4], 12);
+
+        // Empty geometry is a valid raycaster that never hits
+        bvh.update_geometry(&[], &[]);
+        assert!(bvh.intersect(&at_new, &right, &IDENTITY).is_none());
+        assert!(bvh.closest_point(&[0.0; 3], &IDENTITY).is_empty());
+
+        bvh.update_geometry(&old.0, &old.1);
+        let hit = bvh
+            .intersect(&at_new, &[-1.0, 0.0, 0.0], &IDENTITY)
+            .unwrap();
+        assert!((hit[0] - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_to_from_bytes() {
+        let (positions, indices) = cubes_along_x(&[0.0, 3.0, 6.0, 9.0], 0);
+        let mut bvh = BVHRaycaster::from_geometry_sah(&positions, &indices, 8);
+        bvh.set_id_ranges(&[0, 12, 7]).unwrap();
+        bvh.set_cull_mode(CULL_NONE).unwrap();
+        let extra = cubes_along_x(&[12.0], 32);
+        bvh.append_geometry(&extra.0, &extra.1, 32).unwrap();
+
+        let bytes = bvh.to_bytes();
+        let loaded = BVHRaycaster::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.get_stats(), bvh.get_stats());
+        assert_eq!(loaded.to_bytes(), bytes);
+        for (origin, dir) in [
+            ([-5.0, 0.2, 0.3], [1.0, 0.0, 0.0]),
+            ([20.0, 0.2, 0.3], [-1.0, 0.0, 0.0]),
+            ([9.5, 0.1, 5.0], [0.0, 0.0, -1.0]),
+            ([0.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ] {
+            assert_eq!(
+                loaded.intersect_with_id(&origin, &dir, &IDENTITY),
+                bvh.intersect_with_id(&origin, &dir, &IDENTITY)
+            );
         }
 
-        let q = s.cross(edge1);
-        let v = f * dir.dot(q);
+        // Empty trees round-trip too
+        let empty = BVHRaycaster::from_geometry(&[], &[]);
+        assert!(BVHRaycaster::from_bytes(&empty.to_bytes()).is_ok());
 
-        // Outside triangle (v coordinate or u+v > 1)
-        if v < 0.0 || u + v > 1.0 {
-            return None;
+        // Foreign, newer and truncated data
+        assert!(BVHRaycaster::from_bytes(b"CJPS").is_err());
+        let mut newer = bytes.clone();
+        newer[4] += 1;
+        assert!(BVHRaycaster::from_bytes(&newer).is_err());
+        for len in 0..bytes.len() {
+            assert!(BVHRaycaster::from_bytes(&bytes[..len]).is_err());
         }
 
-        let t = f * edge2.dot(q);
+        // Corrupting any single byte must never panic
+        for pos in 0..bytes.len() {
+            for value in [0x00, 0x7f, 0xff] {
+                let mut corrupt = bytes.clone();
+                corrupt[pos] = value;
+                if let Ok(loaded) = BVHRaycaster::from_bytes(&corrupt) {
+                    loaded.intersect(&[-5.0, 0.2, 0.3], &[1.0, 0.0, 0.0], &IDENTITY);
+                    loaded.closest_point(&[0.0; 3], &IDENTITY);
+                }
+            }
+        }
+    }
 
-        // Intersection in front of ray origin
-        if t > EPSILON {
-            Some(RayHit {
-                point: origin + dir * t,
-                normal: tri.normal,
-                distance: t,
-            })
-        } else {
-            None
+    #[test]
+    fn test_flat_node_layout() {
+        // Bumpy 60x60 grid, 7200 triangles
+        let n = 60;
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
+        for j in 0..=n {
+            for i in 0..=n {
+                let (x, y) = (i as f32 / n as f32, j as f32 / n as f32);
+                positions.extend([x, y, 0.05 * (x * 40.0).sin() * (y * 37.0).cos()]);
+            }
+        }
+        for j in 0..n {
+            for i in 0..n {
+                let a = (j * (n + 1) + i) as u32;
+                let c = a + n as u32 + 1;
+                indices.extend([a, a + 1, c + 1, a, c + 1, c]);
+            }
+        }
+        let mut bvh = BVHRaycaster::from_geometry(&positions, &indices);
+        let extra = cubes_along_x(&[0.5], positions.len() as u32 / 3);
+        bvh.append_geometry(&extra.0, &extra.1, positions.len() as u32 / 3)
+            .unwrap();
+        bvh.compact();
+
+        let stats = bvh.get_stats();
+        assert_eq!(std::mem::size_of::<BVHNode>(), 32);
+        assert_eq!(stats[5], stats[0] * 32);
+        // Left children are adjacent and every child follows its parent,
+        // including after compact() put a new root over the old tree
+        for (idx, node) in bvh.nodes.iter().enumerate() {
+            if let Some((left, right)) = node.children(idx) {
+                assert_eq!(left, idx + 1);
+                assert!(right > left && right < bvh.nodes.len());
+            }
+        }
+
+        // Same hits as testing every triangle
+        let mut seed = 7u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f32 / (1 << 24) as f32
+        };
+        for _ in 0..300 {
+            let origin = Vec3::new(next(), next(), 2.0);
+            let dir = Vec3::new(next() - 0.5, next() - 0.5, -1.0).normalize();
+            let expected = bvh
+                .triangles
+                .iter()
+                .enumerate()
+                .filter_map(|(i, tri)| bvh.ray_triangle_intersect(origin, dir, tri, i))
+                .map(|hit| hit.distance)
+                .min_by(f32::total_cmp);
+            let hit = bvh.traverse_bvh(origin, dir, dir.recip());
+            assert_eq!(hit.map(|h| h.distance), expected);
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_scene_raycaster() {
+        let mut scene = SceneRaycaster::new();
+        let single = scene.add_mesh(cube());
+        let (positions, indices) = cubes_along_x(&[0.0, 3.0], 0);
+        let pair = scene.add_mesh(BVHRaycaster::from_geometry(&positions, &indices));
+        assert!(scene.add_instance(7, &IDENTITY).is_err());
+        assert!(scene.add_instance(single, &IDENTITY[..15]).is_err());
+
+        let at = |x: f32, scale: f32| {
+            Mat4::from_scale_rotation_translation(
+                Vec3::splat(scale),
+                glam::Quat::IDENTITY,
+                Vec3::new(x, 0.0, 0.0),
+            )
+            .to_cols_array()
+        };
+        let near = scene.add_instance(single, &at(10.0, 2.0)).unwrap();
+        let far = scene.add_instance(pair, &at(20.0, 1.0)).unwrap();
+        let behind = scene.add_instance(single, &at(-10.0, 1.0)).unwrap();
+        assert_eq!(scene.instance_count(), 3);
+
+        // Doubled cube at x = 10: left face (triangles 4, 5) 8 world units away
+        let (origin, right) = ([0.0, 0.2, 0.3], [1.0, 0.0, 0.0]);
+        let hit = scene.intersect(&origin, &right).unwrap();
+        assert_eq!(hit.len(), SCENE_HIT_STRIDE);
+        assert!((hit[0] - 8.0).abs() < 1e-4 && (hit[6] - 8.0).abs() < 1e-4);
+        assert_eq!(&hit[3..6], &[-1.0, 0.0, 0.0]);
+        assert_eq!(hit[7], near as f32);
+        assert!(hit[8] == 4.0 || hit[8] == 5.0);
+
+        let hit = scene.intersect(&origin, &[-1.0, 0.0, 0.0]).unwrap();
+        assert_eq!(hit[7], behind as f32);
+        assert!((hit[6] - 9.0).abs() < 1e-4);
+        assert!(scene.intersect(&origin, &[0.0, 1.0, 0.0]).is_none());
+
+        // Moving the near instance out of the way exposes the second mesh
+        scene
+            .set_instance_matrix(
+                near,
+                &Mat4::from_translation(Vec3::Y * 50.0).to_cols_array(),
+            )
+            .unwrap();
+        let hit = scene.intersect(&origin, &right).unwrap();
+        assert_eq!(hit[7], far as f32);
+        assert!((hit[0] - 19.0).abs() < 1e-4);
+        assert!(scene.set_instance_matrix(9, &IDENTITY).is_err());
+
+        // Second cube of the pair, behind the first along -x
+        let hit = scene
+            .intersect(&[30.0, 0.2, 0.3], &[-1.0, 0.0, 0.0])
+            .unwrap();
+        assert_eq!(hit[7], far as f32);
+        assert!((hit[0] - 24.0).abs() < 1e-4);
+        assert!(hit[8] >= 12.0);
+    }
 
     #[test]
-    fn test_bvh_build() {
-        // Simple cube geometry (8 vertices, 12 triangles)
-        let positions: Vec<f32> = vec![
-            // Front face
-            -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0, // Back face
-            -1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0,
-        ];
+    fn test_query_aabb() {
+        let bvh = cube();
+        // Slab through the right face (triangles 6 and 7) only
+        assert_eq!(
+            bvh.query_aabb(&[0.9, -0.5, -0.5], &[1.5, 0.5, 0.5], &IDENTITY),
+            vec![6, 7]
+        );
+        // Both triangles' bounds cover the whole face; only 7 reaches (y + z > 0)
+        assert_eq!(
+            bvh.query_aabb(&[0.9, 0.6, 0.6], &[1.1, 0.8, 0.8], &IDENTITY),
+            vec![7]
+        );
+        // Inside the cube without touching it, and enclosing it
+        assert!(bvh.query_aabb(&[-0.5; 3], &[0.5; 3], &IDENTITY).is_empty());
+        assert_eq!(
+            bvh.query_aabb(&[-2.0; 3], &[2.0; 3], &IDENTITY),
+            (0..12).collect::<Vec<u32>>()
+        );
 
-        let indices: Vec<u32> = vec![
-            0, 1, 2, 0, 2, 3, // Front
-            4, 6, 5, 4, 7, 6, // Back
-            0, 3, 7, 0, 7, 4, // Left
-            1, 5, 6, 1, 6, 2, // Right
-            3, 2, 6, 3, 6, 7, // Top
-            0, 4, 5, 0, 5, 1, // Bottom
-        ];
+        // The box is in world space: the doubled cube's right face is at x = 2
+        let scaled = Mat4::from_scale(Vec3::splat(2.0)).to_cols_array();
+        assert_eq!(
+            bvh.query_aabb(&[1.9, -0.5, -0.5], &[2.5, 0.5, 0.5], &scaled),
+            vec![6, 7]
+        );
+    }
 
-        let raycaster = BVHRaycaster::from_geometry(&positions, &indices);
+    /// Frustum planes for query_frustum from an axis-aligned box
+    fn box_frustum(min: [f32; 3], max: [f32; 3]) -> Vec<f32> {
+        let mut planes = Vec::new();
+        for axis in 0..3 {
+            let n = Vec3::AXES[axis];
+            planes.extend([n.x, n.y, n.z, -min[axis]]);
+            planes.extend([-n.x, -n.y, -n.z, max[axis]]);
+        }
+        planes
+    }
 
-        assert_eq!(raycaster.triangles.len(), 12);
-        assert!(raycaster.nodes.len() > 1, "BVH should have multiple nodes");
+    #[test]
+    fn test_query_frustum() {
+        let bvh = cube();
+        let query = |planes: &[f32]| bvh.query_frustum(planes, &IDENTITY, false).unwrap();
 
-        let stats = raycaster.get_stats();
-        println!(
-            "BVH Stats: nodes={}, leaves={}, internal={}, depth={}, tris={}",
-            stats[0], stats[1], stats[2], stats[3], stats[4]
+        // Region at the (1, 1, 1) corner: both front face triangles, and the
+        // right and top face halves that reach it (6 and 9 pass every plane
+        // test but are separated from the region)
+        let corner = box_frustum([0.5; 3], [3.0; 3]);
+        assert_eq!(query(&corner), vec![0, 1, 7, 8]);
+        assert_eq!(
+            query(&box_frustum([-3.0; 3], [3.0; 3])),
+            (0..12).collect::<Vec<_>>()
         );
+        assert!(query(&box_frustum([1.5, -3.0, -3.0], [3.0; 3])).is_empty());
+        // Zero-volume and invalid frustums
+        assert!(query(&box_frustum([0.5, -3.0, -3.0], [0.5, 3.0, 3.0])).is_empty());
+        assert!(query(&[0.0; 24]).is_empty());
+        assert!(bvh.query_frustum(&corner[..20], &IDENTITY, false).is_err());
+
+        // Leaf IDs cover at least the triangles found
+        let leaves = bvh.query_frustum(&corner, &IDENTITY, true).unwrap();
+        let mut covered: Vec<u32> = leaves
+            .iter()
+            .flat_map(|&leaf| bvh.get_leaf_triangles(leaf))
+            .collect();
+        covered.sort_unstable();
+        assert!(query(&corner).iter().all(|t| covered.contains(t)));
+
+        // Camera frustum (Gribb-Hartmann) from z = 5, looking at and away from the cube
+        let camera_planes = |target: Vec3| {
+            let view = Mat4::look_at_rh(Vec3::Z * 5.0, target, Vec3::Y);
+            let clip = Mat4::perspective_rh_gl(0.5, 1.0, 0.1, 100.0) * view;
+            let (x, y, z, w) = (clip.row(0), clip.row(1), clip.row(2), clip.row(3));
+            [w + x, w - x, w + y, w - y, w + z, w - z]
+                .iter()
+                .flat_map(|p| p.to_array())
+                .collect::<Vec<f32>>()
+        };
+        assert_eq!(query(&camera_planes(Vec3::ZERO)).len(), 12);
+        assert!(query(&camera_planes(Vec3::Z * 10.0)).is_empty());
+        let moved = Mat4::from_translation(Vec3::X * 50.0).to_cols_array();
+        assert!(bvh
+            .query_frustum(&camera_planes(Vec3::ZERO), &moved, false)
+            .unwrap()
+            .is_empty());
     }
 
     #[test]
-    fn test_ray_hit() {
-        // Unit cube centered at origin
-        let positions: Vec<f32> = vec![
-            -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0, -1.0, -1.0, -1.0, 1.0,
-            -1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0,
-        ];
-        let indices: Vec<u32> = vec![
-            0, 1, 2, 0, 2, 3, 4, 6, 5, 4, 7, 6, 0, 3, 7, 0, 7, 4, 1, 5, 6, 1, 6, 2, 3, 2, 6, 3, 6,
-            7, 0, 4, 5, 0, 5, 1,
-        ];
+    fn test_nearest_triangles() {
+        let bvh = cube();
+        // Inside, near the (1, 1, 1) corner: 0.1 from the right face, 0.2 from
+        // the top, 0.3 from the front; each face's half nearest the corner
+        let near = bvh.nearest_triangles(&[0.9, 0.8, 0.7], 3, &IDENTITY);
+        assert_eq!(near.len(), 3 * NEAREST_STRIDE);
+        let entries: Vec<&[f32]> = near.chunks(NEAREST_STRIDE).collect();
+        assert_eq!(entries[0][0], 7.0);
+        assert_eq!(entries[1][0], 8.0);
+        assert_eq!(entries[2][0], 0.0);
+        for (entry, dist) in entries.iter().zip([0.1, 0.2, 0.3]) {
+            assert!((entry[1] - dist).abs() < 1e-5, "{entry:?}");
+        }
+        assert!((entries[0][2] - 1.0).abs() < 1e-6);
 
-        let raycaster = BVHRaycaster::from_geometry(&positions, &indices);
+        // Agrees with closest_point, and k beyond the mesh returns everything
+        let closest = bvh.closest_point(&[3.0, 0.5, 0.2], &IDENTITY);
+        let single = bvh.nearest_triangles(&[3.0, 0.5, 0.2], 1, &IDENTITY);
+        assert_eq!(single[..2], [closest[7], closest[6]]);
+        let all = bvh.nearest_triangles(&[0.9, 0.8, 0.7], 50, &IDENTITY);
+        assert_eq!(all.len(), 12 * NEAREST_STRIDE);
+        assert!(all
+            .chunks(NEAREST_STRIDE)
+            .zip(all.chunks(NEAREST_STRIDE).skip(1))
+            .all(|(a, b)| a[1] <= b[1]));
+        assert!(bvh.nearest_triangles(&[0.0; 3], 0, &IDENTITY).is_empty());
+        // A huge k is clamped to the mesh instead of sizing the heap
+        let huge = bvh.nearest_triangles(&[0.9, 0.8, 0.7], u32::MAX as usize, &IDENTITY);
+        assert_eq!(huge, all);
+        assert!(BVHRaycaster::from_geometry(&[], &[])
+            .nearest_triangles(&[0.0; 3], 3, &IDENTITY)
+            .is_empty());
+    }
 
-        // Ray from z=5 toward origin should hit front face at z=1
-        let identity: [f32; 16] = [
-            1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+    #[test]
+    fn test_contains_point() {
+        let bvh = cube();
+        assert!(bvh.contains_point(&[0.1, 0.2, 0.3], &IDENTITY));
+        assert!(bvh.contains_point(&[0.0; 3], &IDENTITY));
+        assert!(!bvh.contains_point(&[3.0, 0.2, 0.3], &IDENTITY));
+        assert!(!bvh.contains_point(&[0.5, 1.5, 0.5], &IDENTITY));
+        // On a face, an edge and a vertex
+        assert!(bvh.contains_point(&[1.0, 0.2, 0.3], &IDENTITY));
+        assert!(bvh.contains_point(&[1.0, 1.0, 0.3], &IDENTITY));
+        assert!(bvh.contains_point(&[-1.0, -1.0, -1.0], &IDENTITY));
+
+        // First ray exits exactly through the right face's diagonal (1, 0, 0)
+        // (counted by neither or both triangles), so another direction decides
+        let dir = BVHRaycaster::containment_direction(0);
+        let through_edge = |t: f32| (Vec3::X - dir * t).to_array();
+        assert!(bvh.contains_point(&through_edge(0.5), &IDENTITY));
+        assert!(!bvh.contains_point(&through_edge(10.0), &IDENTITY));
+
+        // World space: the doubled cube contains x = 1.5
+        let scaled = Mat4::from_scale(Vec3::splat(2.0)).to_cols_array();
+        assert!(bvh.contains_point(&[1.5, 0.0, 0.0], &scaled));
+        assert!(!BVHRaycaster::from_geometry(&[], &[]).contains_point(&[0.0; 3], &IDENTITY));
+    }
+
+    #[test]
+    fn test_intersect_packet4() {
+        let offsets: Vec<f32> = (0..16).map(|i| i as f32 * 3.0).collect();
+        let (positions, indices) = cubes_along_x(&offsets, 0);
+        let mut bvh = BVHRaycaster::from_geometry(&positions, &indices);
+        let extra = cubes_along_x(&[0.0], 128);
+        bvh.append_geometry(&extra.0, &extra.1, 128).unwrap();
+        let model = Mat4::from_scale_rotation_translation(
+            Vec3::splat(1.5),
+            glam::Quat::from_rotation_y(0.3),
+            Vec3::new(1.0, 2.0, 3.0),
+        )
+        .to_cols_array();
+
+        // 2x2 bundles sweeping the row, some missing, some axis-parallel
+        let mut seed = 99u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f32 / (1 << 24) as f32
+        };
+        for packet in 0..200 {
+            let base = [next() * 60.0, next() * 8.0 - 2.0, 12.0];
+            let mut origins = Vec::new();
+            let mut directions = Vec::new();
+            for lane in 0..PACKET_RAYS {
+                let offset = 0.05 * (lane % 2) as f32;
+                origins.extend([
+                    base[0] + offset,
+                    base[1] + 0.05 * (lane / 2) as f32,
+                    base[2],
+                ]);
+                if packet % 10 == 0 {
+                    directions.extend([0.0, 0.0, -1.0]);
+                } else {
+                    directions.extend([next() - 0.5, next() - 0.5, -1.0]);
+                }
+            }
+
+            let packed = bvh
+                .intersect_packet4(&origins, &directions, &model)
+                .unwrap();
+            for lane in 0..PACKET_RAYS {
+                let range = lane * 3..lane * 3 + 3;
+                let single = bvh.intersect(&origins[range.clone()], &directions[range], &model);
+                let record = &packed[lane * PACKET_HIT_STRIDE..(lane + 1) * PACKET_HIT_STRIDE];
+                match single {
+                    Some(hit) => assert_eq!(record, &hit[..]),
+                    None => assert_eq!(record, &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0]),
+                }
+            }
+        }
+        assert!(bvh
+            .intersect_packet4(&[0.0; 9], &[0.0; 12], &model)
+            .is_err());
+    }
+
+    #[test]
+    fn test_from_geometry_fast() {
+        let rays: Vec<([f32; 3], [f32; 3])> = vec![
+            ([0.2, 0.1, 5.0], [0.0, 0.0, -1.0]),
+            ([0.3, -0.2, -5.0], [0.0, 0.0, 1.0]),
+            ([5.0, 0.5, 0.3], [-1.0, 0.0, 0.0]),
+            ([3.0, 4.0, 5.0], [-0.5, -0.7, -1.0]),
+            ([5.0, 5.0, 5.0], [1.0, 1.0, 1.0]),
         ];
+        let (positions, indices) = cube_geometry();
+        let fast = BVHRaycaster::from_geometry_fast(&positions, &indices);
+        for (origin, direction) in &rays {
+            assert_eq!(
+                fast.intersect(origin, direction, &IDENTITY),
+                cube().intersect(origin, direction, &IDENTITY)
+            );
+        }
 
-        let hit = raycaster.intersect(&[0.0, 0.0, 5.0], &[0.0, 0.0, -1.0], &identity);
-        assert!(hit.is_some(), "Should hit cube");
+        // Random triangle soup
+        let mut seed = 4242u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f32 / (1 << 24) as f32
+        };
+        let mut positions = Vec::new();
+        for _ in 0..3000 {
+            let center = [next() * 20.0, next() * 20.0, next() * 20.0];
+            for _ in 0..3 {
+                positions.extend(center.map(|c| c + next() - 0.5));
+            }
+        }
+        let indices: Vec<u32> = (0..positions.len() as u32 / 3).collect();
+        let median = BVHRaycaster::from_geometry(&positions, &indices);
+        let fast = BVHRaycaster::from_geometry_fast(&positions, &indices);
+        assert_eq!(fast.get_stats()[4], median.get_stats()[4]);
+        for (idx, node) in fast.nodes.iter().enumerate() {
+            if let Some((left, right)) = node.children(idx) {
+                assert_eq!(left, idx + 1);
+                assert!(right > left);
+            }
+        }
+        let mut hits = 0;
+        for _ in 0..500 {
+            let origin = [next() * 20.0, next() * 20.0, -5.0];
+            let direction = [next() - 0.5, next() - 0.5, 1.0];
+            let hit = fast.intersect(&origin, &direction, &IDENTITY);
+            hits += hit.is_some() as usize;
+            assert_eq!(hit, median.intersect(&origin, &direction, &IDENTITY));
+        }
+        assert!(hits > 100);
 
-        let hit = hit.unwrap();
-        assert!(
-            (hit[2] - 1.0).abs() < 0.01,
-            "Should hit at z=1, got z={}",
-            hit[2]
-        );
+        // Same-position triangles still split (equal codes)
+        let pile: Vec<f32> = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0].repeat(40);
+        let pile_indices: Vec<u32> = (0..120).collect();
+        let stacked = BVHRaycaster::from_geometry_fast(&pile, &pile_indices);
+        assert!(stacked.get_stats()[3] <= 5);
+        assert!(BVHRaycaster::from_geometry_fast(&[], &[])
+            .intersect(&[0.0; 3], &[0.0, 0.0, 1.0], &IDENTITY)
+            .is_none());
     }
 }
@@ -0,0 +1,317 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::thread::LocalKey;
+
+use wasm_bindgen::prelude::*;
+
+use crate::raycast::BVHRaycaster;
+use crate::ripple_physics::{RippleSimulator, RippleType};
+use crate::texture_gen::TextureGenerator;
+
+/// Handle 0 is never issued so JS can use it as "none"
+const FIRST_HANDLE: u32 = 1;
+
+type Slots<T> = HashMap<u32, Rc<RefCell<T>>>;
+
+// Crate-wide object table. WASM is single threaded; thread-local keeps native
+// tests isolated. Handles are never reused, so a released handle stays stale.
+thread_local! {
+    static NEXT_HANDLE: Cell<u32> = const { Cell::new(FIRST_HANDLE) };
+    static RIPPLE_SIMULATORS: RefCell<Slots<RippleSimulator>> = RefCell::new(HashMap::new());
+    static RAYCASTERS: RefCell<Slots<BVHRaycaster>> = RefCell::new(HashMap::new());
+    static TEXTURE_GENERATORS: RefCell<Slots<TextureGenerator>> = RefCell::new(HashMap::new());
+}
+
+/// A type whose instances can live in the registry
+pub(crate) trait Registered: Sized + 'static {
+    /// Type name used in error messages
+    const NAME: &'static str;
+
+    fn slots() -> &'static LocalKey<RefCell<Slots<Self>>>;
+}
+
+impl Registered for RippleSimulator {
+    const NAME: &'static str = "RippleSimulator";
+
+    fn slots() -> &'static LocalKey<RefCell<Slots<Self>>> {
+        &RIPPLE_SIMULATORS
+    }
+}
+
+impl Registered for BVHRaycaster {
+    const NAME: &'static str = "BVHRaycaster";
+
+    fn slots() -> &'static LocalKey<RefCell<Slots<Self>>> {
+        &RAYCASTERS
+    }
+}
+
+impl Registered for TextureGenerator {
+    const NAME: &'static str = "TextureGenerator";
+
+    fn slots() -> &'static LocalKey<RefCell<Slots<Self>>> {
+        &TEXTURE_GENERATORS
+    }
+}
+
+/// Move an object into the registry, returns its handle
+pub(crate) fn register<T: Registered>(value: T) -> u32 {
+    let handle = NEXT_HANDLE.with(|next| {
+        let handle = next.get();
+        next.set(handle.checked_add(1).expect("registry handles exhausted"));
+        handle
+    });
+    T::slots().with(|slots| {
+        slots
+            .borrow_mut()
+            .insert(handle, Rc::new(RefCell::new(value)))
+    });
+    handle
+}
+
+/// Shared cell behind a handle
+///
+/// The table itself is not borrowed once this returns, so callers may
+/// register or resolve other handles while holding the object.
+fn resolve<T: Registered>(handle: u32) -> Result<Rc<RefCell<T>>, String> {
+    T::slots()
+        .with(|slots| slots.borrow().get(&handle).cloned())
+        .ok_or_else(|| format!("no {} registered under handle {handle}", T::NAME))
+}
+
+/// Run `f` with shared access to a registered object
+///
+/// Fails instead of panicking on stale handles or while the object is
+/// mutably borrowed further up the stack.
+pub(crate) fn with<T: Registered, R>(handle: u32, f: impl FnOnce(&T) -> R) -> Result<R, String> {
+    let cell = resolve::<T>(handle)?;
+    let value = cell
+        .try_borrow()
+        .map_err(|_| format!("{} {handle} is already mutably borrowed", T::NAME))?;
+    Ok(f(&value))
+}
+
+/// Run `f` with exclusive access to a registered object
+pub(crate) fn with_mut<T: Registered, R>(
+    handle: u32,
+    f: impl FnOnce(&mut T) -> R,
+) -> Result<R, String> {
+    let cell = resolve::<T>(handle)?;
+    let mut value = cell
+        .try_borrow_mut()
+        .map_err(|_| format!("{} {handle} is already borrowed", T::NAME))?;
+    Ok(f(&mut value))
+}
+
+/// Remove an object from the registry and hand it back
+///
+/// Refused while the object is borrowed; the handle stays valid in that case.
+pub(crate) fn take<T: Registered>(handle: u32) -> Result<T, String> {
+    let cell = T::slots()
+        .with(|slots| slots.borrow_mut().remove(&handle))
+        .ok_or_else(|| format!("no {} registered under handle {handle}", T::NAME))?;
+    match Rc::try_unwrap(cell) {
+        Ok(cell) => Ok(cell.into_inner()),
+        Err(cell) => {
+            T::slots().with(|slots| slots.borrow_mut().insert(handle, cell));
+            Err(format!("{} {handle} is in use", T::NAME))
+        }
+    }
+}
+
+/// Drop whatever is registered under `handle`
+fn release_any(handle: u32) -> bool {
+    // An object borrowed further up the stack is kept alive by that borrow
+    // and freed when it ends
+    RIPPLE_SIMULATORS.with(|slots| slots.borrow_mut().remove(&handle).is_some())
+        || RAYCASTERS.with(|slots| slots.borrow_mut().remove(&handle).is_some())
+        || TEXTURE_GENERATORS.with(|slots| slots.borrow_mut().remove(&handle).is_some())
+}
+
+fn contains_any(handle: u32) -> bool {
+    RIPPLE_SIMULATORS.with(|slots| slots.borrow().contains_key(&handle))
+        || RAYCASTERS.with(|slots| slots.borrow().contains_key(&handle))
+        || TEXTURE_GENERATORS.with(|slots| slots.borrow().contains_key(&handle))
+}
+
+/// JS view of the crate-wide handle registry
+///
+/// Heavy objects that other objects need to reach (a simulator fed by a
+/// raycaster, a scheduler stepping a simulator, ...) are moved in with
+/// `register_self()` and referenced by handle from then on. Resolving a stale
+/// handle, or an object that is already borrowed, returns an error instead of
+/// trapping.
+#[wasm_bindgen]
+pub struct Registry;
+
+#[wasm_bindgen]
+impl Registry {
+    /// Drop the object behind a handle; false if the handle was stale
+    #[wasm_bindgen]
+    pub fn release(handle: u32) -> bool {
+        release_any(handle)
+    }
+
+    /// True while `handle` refers to a registered object
+    #[wasm_bindgen]
+    pub fn contains(handle: u32) -> bool {
+        contains_any(handle)
+    }
+
+    /// Move a simulator back out of the registry (the handle becomes stale)
+    #[wasm_bindgen]
+    pub fn take_ripple_simulator(handle: u32) -> Result<RippleSimulator, String> {
+        take(handle)
+    }
+
+    /// Move a raycaster back out of the registry (the handle becomes stale)
+    #[wasm_bindgen]
+    pub fn take_raycaster(handle: u32) -> Result<BVHRaycaster, String> {
+        take(handle)
+    }
+
+    /// Move a texture generator back out of the registry (the handle becomes stale)
+    #[wasm_bindgen]
+    pub fn take_texture_generator(handle: u32) -> Result<TextureGenerator, String> {
+        take(handle)
+    }
+
+    /// Step a registered simulator, see RippleSimulator::update
+    #[wasm_bindgen]
+    pub fn update_ripple_simulator(handle: u32, delta_time: f32) -> Result<(), String> {
+        with_mut(handle, |sim: &mut RippleSimulator| sim.update(delta_time))
+    }
+
+    /// Shader uniforms of a registered simulator, see RippleSimulator::get_uniforms
+    #[wasm_bindgen]
+    pub fn ripple_uniforms(handle: u32) -> Result<Vec<f32>, String> {
+        with(handle, |sim: &RippleSimulator| sim.get_uniforms())
+    }
+}
+
+#[wasm_bindgen]
+impl RippleSimulator {
+    /// Move this simulator into the Registry, returns its handle
+    #[wasm_bindgen]
+    pub fn register_self(self) -> u32 {
+        register(self)
+    }
+
+    /// spawn_ripple_occluded with a registered picker
+    ///
+    /// # Arguments
+    /// * `picker` - Registry handle of the BVHRaycaster
+    ///
+    /// # Returns
+    /// Same codes as spawn_ripple_occluded, or an error for a stale handle
+    #[allow(clippy::too_many_arguments)]
+    #[wasm_bindgen]
+    pub fn spawn_ripple_occluded_by_handle(
+        &mut self,
+        picker: u32,
+        surface_id: u32,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+        amplitude: f32,
+        speed: f32,
+        decay: f32,
+        ripple_type: RippleType,
+        mode: u8,
+    ) -> Result<i32, String> {
+        with(picker, |picker: &BVHRaycaster| {
+            self.spawn_ripple_occluded(
+                picker,
+                surface_id,
+                ray_origin,
+                ray_direction,
+                model_matrix,
+                amplitude,
+                speed,
+                decay,
+                ripple_type,
+                mode,
+            )
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl BVHRaycaster {
+    /// Move this raycaster into the Registry, returns its handle
+    #[wasm_bindgen]
+    pub fn register_self(self) -> u32 {
+        register(self)
+    }
+}
+
+#[wasm_bindgen]
+impl TextureGenerator {
+    /// Move this generator into the Registry, returns its handle
+    #[wasm_bindgen]
+    pub fn register_self(self) -> u32 {
+        register(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_handles() {
+        let sim = RippleSimulator::new().register_self();
+        assert!(Registry::contains(sim));
+        assert!(Registry::update_ripple_simulator(sim, 0.1).is_ok());
+
+        // Wrong type behind a live handle
+        assert!(Registry::take_raycaster(sim).is_err());
+
+        let taken = Registry::take_ripple_simulator(sim);
+        assert!(taken.is_ok());
+        assert!(!Registry::contains(sim));
+        let err = Registry::ripple_uniforms(sim).unwrap_err();
+        assert!(err.contains("no RippleSimulator"), "{err}");
+
+        // Re-registering never revives the old handle
+        let again = taken.unwrap().register_self();
+        assert_ne!(again, sim);
+        assert!(Registry::release(again));
+        assert!(!Registry::release(again));
+        assert!(Registry::update_ripple_simulator(again, 0.1).is_err());
+    }
+
+    #[test]
+    fn test_double_borrow_is_an_error() {
+        let handle = RippleSimulator::new().register_self();
+
+        let nested = with_mut(handle, |_: &mut RippleSimulator| {
+            (
+                with(handle, |_: &RippleSimulator| ()),
+                with_mut(handle, |_: &mut RippleSimulator| ()),
+                Registry::take_ripple_simulator(handle).map(|_| ()),
+            )
+        })
+        .unwrap();
+        assert!(nested.0.unwrap_err().contains("borrowed"));
+        assert!(nested.1.unwrap_err().contains("borrowed"));
+        assert!(nested.2.unwrap_err().contains("in use"));
+
+        // Shared borrows nest fine, and the failed take left the handle alive
+        assert!(with(handle, |_: &RippleSimulator| with(
+            handle,
+            |_: &RippleSimulator| ()
+        ))
+        .is_ok());
+        assert!(Registry::contains(handle));
+
+        // Released while borrowed: the borrow finishes, then the handle is stale
+        let released = with_mut(handle, |sim: &mut RippleSimulator| {
+            sim.update(0.1);
+            Registry::release(handle)
+        });
+        assert_eq!(released, Ok(true));
+        assert!(Registry::ripple_uniforms(handle).is_err());
+    }
+}
@@ -1,7 +1,79 @@
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+use crate::motion;
+use crate::options::{self, JsOptions};
+use crate::raycast::BVHRaycaster;
+
 const MAX_RIPPLES: usize = 2;
+/// Low bits of a ripple handle hold the slot, the rest its generation
+const HANDLE_SLOT_BITS: u32 = 8;
+/// Generations count 1..=MAX_GENERATION, so no handle is ever 0
+const MAX_GENERATION: u32 = u32::MAX >> HANDLE_SLOT_BITS;
 const AMPLITUDE_THRESHOLD: f32 = 0.001;
+/// Longest haptic envelope generated for a single ripple (seconds)
+const MAX_HAPTIC_SECONDS: f32 = 5.0;
+/// Half-width of the wave crest around the expanding front (world units)
+const WAVEFRONT_WIDTH: f32 = 0.5;
+/// Blockers exported by get_blocker_uniforms (extra blockers are CPU-only)
+const MAX_BLOCKER_UNIFORMS: usize = 8;
+/// Default fraction of a wave that leaks past a blocker
+const DEFAULT_BLOCKER_LEAKAGE: f32 = 0.2;
+/// Reach of the pull kernel (matches the shader's smoothstep(3.5, 0.0, dist))
+const PULL_RADIUS: f32 = 3.5;
+/// Share of the way to a pull's centre a vertex moves at full weight (the shader's stiffness)
+const PULL_STIFFNESS: f32 = 0.6;
+/// Frequency components a dispersive wave ripple can carry
+const MAX_WAVE_COMPONENTS: usize = 3;
+/// get_uniforms layouts: 1 = 8 floats per ripple, 2 = same plus a trailing dispersion block
+const UNIFORM_LAYOUT_V1: u8 = 1;
+const UNIFORM_LAYOUT_V2: u8 = 2;
+
+// --- Occlusion-aware spawn ---
+/// spawn_ripple_occluded modes: occluded clicks spawn nothing, or a muffled ripple
+const OCCLUDED_SPAWN_REJECT: u8 = 0;
+const OCCLUDED_SPAWN_MUFFLE: u8 = 1;
+/// spawn_ripple_occluded results (non-negative results are ripple slots)
+const SPAWN_MISS: i32 = -2;
+const SPAWN_OCCLUDED: i32 = -3;
+/// Amplitude kept per occluding surface crossed in muffle mode
+const MUFFLE_PER_OCCLUDER: f32 = 0.5;
+/// Occluding surfaces walked past before giving up on reaching the target
+const MAX_OCCLUDER_LAYERS: u32 = 8;
+/// Ray restart offset past each occluder hit, relative to the distance travelled
+const OCCLUDER_STEP_EPSILON: f32 = 1e-4;
+
+// --- Sonification ---
+/// Time-scale applied to the wave sine term so the shader's few-Hz ripple becomes audible
+const SONIFY_PITCH: f32 = 40.0;
+/// Pull release oscillator frequency (Hz) and damping (1/s)
+const THUMP_HZ: f32 = 55.0;
+const THUMP_DAMPING: f32 = 6.0;
+/// Gain applied before the soft clip
+const SONIFY_GAIN: f32 = 8.0;
+/// DC blocker cutoff (Hz)
+const DC_CUTOFF_HZ: f32 = 20.0;
+
+// --- Surface particles ---
+/// Age after which the shader stops drawing a wave ripple (seconds)
+const WAVE_LIFETIME: f32 = 0.8;
+/// Central-difference step for the wave height gradient (world units)
+const GRADIENT_STEP: f32 = 0.01;
+/// Longest step integrated at once; larger frame gaps are clamped
+const MAX_PARTICLE_DT: f32 = 0.05;
+/// Default particle dynamics
+const DEFAULT_PARTICLE_SLIDE: f32 = 4.0;
+const DEFAULT_PARTICLE_STIFFNESS: f32 = 20.0;
+const DEFAULT_PARTICLE_DAMPING: f32 = 6.0;
+const DEFAULT_PARTICLE_MAX_DISTANCE: f32 = 0.25;
+
+// --- Phase speed map ---
+/// Phase speed of vertices the map doesn't cover
+const DEFAULT_PHASE_SPEED: f32 = 1.0;
+/// Smallest accepted phase speed (keeps dist / speed finite)
+const MIN_PHASE_SPEED: f32 = 0.05;
+/// Floats per vertex returned by evaluate_vertices
+const VERTEX_EVAL_STRIDE: usize = 3;
 
 #[wasm_bindgen]
 #[derive(Clone, Copy)]
@@ -10,6 +82,69 @@ pub enum RippleType {
     Pull = 1,
 }
 
+/// Direction that wave displacement is applied along
+#[derive(Clone, Copy, PartialEq)]
+enum DisplacementSpace {
+    /// Along the vertex normal (default)
+    Normal = 0,
+    /// Along the fixed world axis given by the normalized axis mask
+    WorldAxis = 1,
+    /// Vertex normal projected onto the masked axes, renormalized
+    ProjectedNormal = 2,
+}
+
+/// RippleSimulator settings as a JS object (set_params_with_options)
+///
+/// Keys are camelCase; every field is optional:
+/// `axisMask` ([1, 1, 1]), `displacementSpace` (0 = normal), `blockerLeakage` (0.2)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RippleParams {
+    pub axis_mask: [f32; 3],
+    pub displacement_space: u8,
+    pub blocker_leakage: f32,
+}
+
+impl Default for RippleParams {
+    fn default() -> Self {
+        Self {
+            axis_mask: [1.0, 1.0, 1.0],
+            displacement_space: DisplacementSpace::Normal as u8,
+            blocker_leakage: DEFAULT_BLOCKER_LEAKAGE,
+        }
+    }
+}
+
+impl JsOptions for RippleParams {
+    const FIELDS: &'static [&'static str] = &["axisMask", "displacementSpace", "blockerLeakage"];
+
+    fn validate(&self) -> Result<(), String> {
+        if self.axis_mask.iter().any(|m| !m.is_finite()) {
+            return Err("axisMask entries must be finite".into());
+        }
+        if self.displacement_space > DisplacementSpace::ProjectedNormal as u8 {
+            return Err(format!(
+                "displacementSpace {} is not a displacement space",
+                self.displacement_space
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.blocker_leakage) {
+            return Err(format!(
+                "blockerLeakage must be within 0..1, got {}",
+                self.blocker_leakage
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Static obstacle that shadows ripples
+#[derive(Clone, Copy)]
+enum Blocker {
+    Sphere { center: [f32; 3], radius: f32 },
+    Box { min: [f32; 3], max: [f32; 3] },
+}
+
 #[derive(Clone, Copy)]
 struct Ripple {
     position: [f32; 3],
@@ -20,11 +155,68 @@ struct Ripple {
     decay: f32,
     ripple_type: RippleType,
     active: bool,
+    /// Front radius of each dispersion component (radius is the leading one)
+    component_radii: [f32; MAX_WAVE_COMPONENTS],
+    /// Crest band width, scales the wavelength (default WAVEFRONT_WIDTH)
+    width: f32,
+    /// Reach of the pull kernel (default PULL_RADIUS)
+    falloff: f32,
+    /// Age after which the wave is no longer drawn (default WAVE_LIFETIME)
+    lifetime: f32,
+}
+
+/// One frequency component of a dispersive wave packet
+#[derive(Clone, Copy)]
+struct WaveComponent {
+    /// Spatial frequency (cycles per world unit)
+    frequency: f32,
+    amplitude: f32,
+    /// Propagation speed relative to the ripple's base speed: f^speed_exponent
+    speed_scale: f32,
 }
 
 #[wasm_bindgen]
 pub struct RippleSimulator {
     ripples: [Ripple; MAX_RIPPLES],
+    /// Times each slot has been filled, packed into ripple handles
+    generations: [u32; MAX_RIPPLES],
+    /// Componentwise multiplier on the final displacement
+    axis_mask: [f32; 3],
+    displacement_space: DisplacementSpace,
+    /// Camera-distance LOD: (camera position, near, far), None = disabled
+    lod: Option<([f32; 3], f32, f32)>,
+    /// Set by dispose(); ripples can no longer be added
+    disposed: bool,
+    /// Registered obstacles as (id, blocker)
+    blockers: Vec<(u32, Blocker)>,
+    next_blocker_id: u32,
+    /// Fraction of the wave that passes a blocker (0 = hard shadow)
+    blocker_leakage: f32,
+    /// Dispersive wave packet components, empty = single-front shader wave
+    wave_components: Vec<WaveComponent>,
+    speed_exponent: f32,
+    /// get_uniforms layout version
+    uniform_layout: u8,
+    /// Spawn energy budget: current, capacity (infinite = unlimited), regen per second
+    energy: f32,
+    energy_capacity: f32,
+    energy_regen: f32,
+    /// Refuse spawns the budget can't fully pay for instead of weakening them
+    energy_reject: bool,
+    /// Per-vertex wave speed multiplier, empty = 1.0 everywhere
+    phase_speed_map: Vec<f32>,
+    /// Soft cap on the summed displacement magnitude (infinite = off)
+    displacement_limit: f32,
+    /// Fraction of the limit (0..1) over which the cap is eased in
+    limit_softness: f32,
+    /// Vertices the limiter changed in the last evaluate_displacements
+    limited_vertices: u32,
+}
+
+impl Default for RippleSimulator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[wasm_bindgen]
@@ -33,6 +225,25 @@ impl RippleSimulator {
     pub fn new() -> RippleSimulator {
         RippleSimulator {
             ripples: [RippleSimulator::inactive_ripple(); MAX_RIPPLES],
+            generations: [0; MAX_RIPPLES],
+            axis_mask: [1.0, 1.0, 1.0],
+            displacement_space: DisplacementSpace::Normal,
+            lod: None,
+            disposed: false,
+            blockers: Vec::new(),
+            next_blocker_id: 0,
+            blocker_leakage: DEFAULT_BLOCKER_LEAKAGE,
+            wave_components: Vec::new(),
+            speed_exponent: 0.0,
+            uniform_layout: UNIFORM_LAYOUT_V1,
+            energy: f32::INFINITY,
+            energy_capacity: f32::INFINITY,
+            energy_regen: 0.0,
+            energy_reject: false,
+            phase_speed_map: Vec::new(),
+            displacement_limit: f32::INFINITY,
+            limit_softness: 0.0,
+            limited_vertices: 0,
         }
     }
 
@@ -46,6 +257,10 @@ impl RippleSimulator {
             decay: 1.0,
             ripple_type: RippleType::Wave,
             active: false,
+            component_radii: [0.0; MAX_WAVE_COMPONENTS],
+            width: WAVEFRONT_WIDTH,
+            falloff: PULL_RADIUS,
+            lifetime: WAVE_LIFETIME,
         }
     }
 
@@ -65,6 +280,17 @@ impl RippleSimulator {
     }
 
     /// Add or replace the weakest ripple
+    ///
+    /// With an energy budget (set_energy_budget) the spawn costs |amplitude|;
+    /// when the budget can't cover it the ripple is weakened to what is left
+    /// (or refused, see set_energy_reject).
+    ///
+    /// # Returns
+    /// The ripple slot, or -1 if the position is inside a blocker, the budget
+    /// refused the spawn, or the simulator is disposed
+    /// (slots are recycled; add_ripple_tracked returns a handle that can't
+    /// silently name another ripple)
+    #[allow(clippy::too_many_arguments)]
     pub fn add_ripple(
         &mut self,
         x: f32,
@@ -74,8 +300,15 @@ impl RippleSimulator {
         speed: f32,
         decay: f32,
         ripple_type: RippleType,
-    ) {
+    ) -> i32 {
+        if self.disposed || self.blockers.iter().any(|(_, b)| b.contains([x, y, z])) {
+            return -1;
+        }
+        let Some(amplitude) = self.spend_energy(amplitude) else {
+            return -1;
+        };
         let idx = self.find_slot();
+        self.generations[idx] = self.generations[idx] % MAX_GENERATION + 1;
 
         self.ripples[idx] = Ripple {
             position: [x, y, z],
@@ -86,19 +319,347 @@ impl RippleSimulator {
             decay,
             ripple_type,
             active: true,
+            component_radii: [0.0; MAX_WAVE_COMPONENTS],
+            width: WAVEFRONT_WIDTH,
+            falloff: PULL_RADIUS,
+            lifetime: WAVE_LIFETIME,
+        };
+        idx as i32
+    }
+
+    /// add_ripple returning a generation-tagged handle instead of the slot
+    ///
+    /// Slots are recycled when the simulator is full, so a raw slot kept by
+    /// JS can end up naming a different ripple; a handle goes stale instead.
+    ///
+    /// # Returns
+    /// The handle (see ripple_handle), or 0 if add_ripple refused the spawn
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_ripple_tracked(
+        &mut self,
+        x: f32,
+        y: f32,
+        z: f32,
+        amplitude: f32,
+        speed: f32,
+        decay: f32,
+        ripple_type: RippleType,
+    ) -> u32 {
+        match self.add_ripple(x, y, z, amplitude, speed, decay, ripple_type) {
+            slot if slot >= 0 => self.ripple_handle(slot as usize),
+            _ => 0,
+        }
+    }
+
+    /// Handle of the ripple currently in a slot (e.g. one returned by
+    /// spawn_ripple_occluded), 0 if the slot is empty
+    pub fn ripple_handle(&self, slot: usize) -> u32 {
+        match self.ripples.get(slot) {
+            Some(ripple) if ripple.active => {
+                (self.generations[slot] << HANDLE_SLOT_BITS) | slot as u32
+            }
+            _ => 0,
+        }
+    }
+
+    /// add_ripple with the shape parameters of set_ripple_params
+    ///
+    /// # Returns
+    /// Same as add_ripple; -1 also for an invalid shape parameter
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_ripple_ex(
+        &mut self,
+        x: f32,
+        y: f32,
+        z: f32,
+        amplitude: f32,
+        ripple_type: RippleType,
+        wave_speed: f32,
+        wave_width: f32,
+        decay_rate: f32,
+        falloff: f32,
+        lifetime: f32,
+    ) -> i32 {
+        if !Self::valid_shape(wave_width, falloff, lifetime) {
+            return -1;
+        }
+        let slot = self.add_ripple(x, y, z, amplitude, wave_speed, decay_rate, ripple_type);
+        if slot >= 0 {
+            self.set_ripple_params(
+                slot as usize,
+                wave_speed,
+                wave_width,
+                decay_rate,
+                falloff,
+                lifetime,
+            );
+        }
+        slot
+    }
+
+    /// Retune a live ripple, so tight ripples and slow swells can coexist
+    ///
+    /// # Arguments
+    /// * `index` - Ripple slot
+    /// * `wave_speed` - Front speed (world units per second)
+    /// * `wave_width` - Crest band width; wider bands mean longer waves (default 0.5)
+    /// * `decay_rate` - Amplitude factor per second
+    /// * `falloff` - Reach of a pull ripple (default 3.5)
+    /// * `lifetime` - Seconds a wave is drawn for (default 0.8, scaled under
+    ///   reduced motion)
+    ///
+    /// # Returns
+    /// false if the slot is empty or width, falloff or lifetime is not a
+    /// positive number
+    pub fn set_ripple_params(
+        &mut self,
+        index: usize,
+        wave_speed: f32,
+        wave_width: f32,
+        decay_rate: f32,
+        falloff: f32,
+        lifetime: f32,
+    ) -> bool {
+        if !Self::valid_shape(wave_width, falloff, lifetime) {
+            return false;
+        }
+        match self.ripples.get_mut(index) {
+            Some(ripple) if ripple.active => {
+                ripple.speed = wave_speed;
+                ripple.width = wave_width;
+                ripple.decay = decay_rate;
+                ripple.falloff = falloff;
+                ripple.lifetime = lifetime;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Per-ripple shape parameters for shaders that match the CPU side
+    ///
+    /// Layout per ripple (same order as get_uniforms):
+    /// [wave width, pull falloff, lifetime, speed]
+    ///
+    /// Lifetime is scaled for reduced motion like the CPU evaluation.
+    pub fn get_ripple_param_uniforms(&self) -> Vec<f32> {
+        let lifetime_scale = motion::lifetime_scale();
+        self.ripples
+            .iter()
+            .flat_map(|r| [r.width, r.falloff, r.lifetime * lifetime_scale, r.speed])
+            .collect()
+    }
+
+    /// Move a live ripple's center
+    ///
+    /// # Returns
+    /// false if the handle is stale: its ripple expired, was removed, or
+    /// its slot was reused
+    pub fn update_ripple_position(&mut self, handle: u32, x: f32, y: f32, z: f32) -> bool {
+        let Some(slot) = self.resolve_handle(handle) else {
+            return false;
+        };
+        self.ripples[slot].position = [x, y, z];
+        true
+    }
+
+    /// remove_ripple by handle; false (and nothing removed) if it is stale
+    pub fn remove_ripple_tracked(&mut self, handle: u32) -> bool {
+        self.resolve_handle(handle)
+            .is_some_and(|slot| self.remove_ripple(slot))
+    }
+
+    /// add_ripple_ex returning a handle (see add_ripple_tracked)
+    ///
+    /// # Returns
+    /// The handle, or 0 if add_ripple_ex refused the spawn
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_ripple_ex_tracked(
+        &mut self,
+        x: f32,
+        y: f32,
+        z: f32,
+        amplitude: f32,
+        ripple_type: RippleType,
+        wave_speed: f32,
+        wave_width: f32,
+        decay_rate: f32,
+        falloff: f32,
+        lifetime: f32,
+    ) -> u32 {
+        match self.add_ripple_ex(
+            x,
+            y,
+            z,
+            amplitude,
+            ripple_type,
+            wave_speed,
+            wave_width,
+            decay_rate,
+            falloff,
+            lifetime,
+        ) {
+            slot if slot >= 0 => self.ripple_handle(slot as usize),
+            _ => 0,
+        }
+    }
+
+    /// set_ripple_params by handle; false (and nothing changed) if it is stale
+    pub fn set_ripple_params_tracked(
+        &mut self,
+        handle: u32,
+        wave_speed: f32,
+        wave_width: f32,
+        decay_rate: f32,
+        falloff: f32,
+        lifetime: f32,
+    ) -> bool {
+        self.resolve_handle(handle).is_some_and(|slot| {
+            self.set_ripple_params(slot, wave_speed, wave_width, decay_rate, falloff, lifetime)
+        })
+    }
+
+    /// Spawn a ripple where a pick ray hits the surface, unless something is in front
+    ///
+    /// `picker` is a merged-geometry raycaster with ID ranges (see
+    /// BVHRaycaster::set_id_ranges); triangles tagged `surface_id` belong to this
+    /// simulator's surface, everything else counts as an occluder.
+    ///
+    /// # Arguments
+    /// * `mode` - 0 = reject occluded clicks, 1 = muffle: spawn behind occluders
+    ///   with amplitude scaled by 0.5 per occluding surface crossed
+    ///
+    /// # Returns
+    /// The ripple slot, -1 if add_ripple refused the spawn (blocker / disposed),
+    /// -2 if the ray never reaches the surface, or -3 if it does but something
+    /// is in front (reject mode)
+    #[allow(clippy::too_many_arguments)]
+    #[wasm_bindgen]
+    pub fn spawn_ripple_occluded(
+        &mut self,
+        picker: &BVHRaycaster,
+        surface_id: u32,
+        ray_origin: &[f32],
+        ray_direction: &[f32],
+        model_matrix: &[f32],
+        amplitude: f32,
+        speed: f32,
+        decay: f32,
+        ripple_type: RippleType,
+        mode: u8,
+    ) -> i32 {
+        if ray_origin.len() < 3 || ray_direction.len() < 3 {
+            return SPAWN_MISS;
+        }
+        let direction = normalize([ray_direction[0], ray_direction[1], ray_direction[2]]);
+        let mut origin = [ray_origin[0], ray_origin[1], ray_origin[2]];
+        let mut travelled = 0.0f32;
+        let mut occluders = 0u32;
+
+        // Walk front to back until the surface is reached
+        let surface_point = loop {
+            let Some(hit) = picker.intersect_with_id(&origin, &direction, model_matrix) else {
+                return if occluders > 0 {
+                    SPAWN_OCCLUDED
+                } else {
+                    SPAWN_MISS
+                };
+            };
+            let point = [hit[0], hit[1], hit[2]];
+            if hit[7] as u32 == surface_id {
+                break point;
+            }
+
+            occluders += 1;
+            if occluders > MAX_OCCLUDER_LAYERS {
+                return SPAWN_OCCLUDED;
+            }
+            travelled += distance(origin, point);
+            let step = OCCLUDER_STEP_EPSILON * (1.0 + travelled);
+            travelled += step;
+            origin = [
+                point[0] + direction[0] * step,
+                point[1] + direction[1] * step,
+                point[2] + direction[2] * step,
+            ];
+        };
+
+        let amplitude = match mode {
+            _ if occluders == 0 => amplitude,
+            OCCLUDED_SPAWN_REJECT => return SPAWN_OCCLUDED,
+            OCCLUDED_SPAWN_MUFFLE => amplitude * MUFFLE_PER_OCCLUDER.powi(occluders as i32),
+            _ => return SPAWN_OCCLUDED,
         };
+        let [x, y, z] = surface_point;
+        self.add_ripple(x, y, z, amplitude, speed, decay, ripple_type)
+    }
+
+    /// Cancel one ripple (e.g. the pointer left mid-drag)
+    ///
+    /// The slot is free for the next add_ripple; other slots keep their
+    /// ripples, so handles returned earlier stay valid.
+    ///
+    /// # Returns
+    /// false if the slot does not exist or holds no active ripple
+    pub fn remove_ripple(&mut self, index: usize) -> bool {
+        match self.ripples.get_mut(index) {
+            Some(ripple) if ripple.active => {
+                *ripple = RippleSimulator::inactive_ripple();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Deactivate all ripples (e.g. on a scene change); unlike dispose the
+    /// simulator keeps accepting new ones
+    pub fn clear(&mut self) {
+        self.ripples = [RippleSimulator::inactive_ripple(); MAX_RIPPLES];
+    }
+
+    /// Deactivate all ripples and stop accepting new ones
+    ///
+    /// The simulator keeps producing (inactive) uniforms so a render loop that
+    /// outlives the view does not break.
+    pub fn dispose(&mut self) {
+        self.ripples = [RippleSimulator::inactive_ripple(); MAX_RIPPLES];
+        self.lod = None;
+        self.disposed = true;
+    }
+
+    pub fn is_disposed(&self) -> bool {
+        self.disposed
     }
 
     /// Advance simulation (call once per frame)
+    ///
+    /// Under reduced motion (see motion::set_reduced_motion) amplitudes decay
+    /// faster so ripples expire after their shortened lifetime.
     pub fn update(&mut self, delta_time: f32) {
+        self.energy =
+            (self.energy + self.energy_regen * delta_time.max(0.0)).min(self.energy_capacity);
+
+        let decay_time = delta_time / motion::lifetime_scale();
         for ripple in &mut self.ripples {
             if !ripple.active {
                 continue;
             }
 
-            ripple.radius += ripple.speed * delta_time;
+            if matches!(ripple.ripple_type, RippleType::Wave) && !self.wave_components.is_empty() {
+                // Per-component fronts are advanced here once per frame, not per vertex
+                for (radius, component) in
+                    ripple.component_radii.iter_mut().zip(&self.wave_components)
+                {
+                    *radius += ripple.speed * component.speed_scale * delta_time;
+                }
+                ripple.radius = ripple.component_radii[..self.wave_components.len()]
+                    .iter()
+                    .fold(0.0, |a, &b| a.max(b));
+            } else {
+                ripple.radius += ripple.speed * delta_time;
+            }
             ripple.phase += delta_time;
-            ripple.amplitude *= ripple.decay.powf(delta_time);
+            ripple.amplitude *= ripple.decay.powf(decay_time);
 
             if ripple.amplitude < AMPLITUDE_THRESHOLD {
                 ripple.active = false;
@@ -106,26 +667,2115 @@ impl RippleSimulator {
         }
     }
 
-    /// Flat uniform buffer for Three.js
+    /// Scale the final displacement per world axis (default 1, 1, 1)
+    pub fn set_displacement_axis_mask(&mut self, x: f32, y: f32, z: f32) {
+        self.axis_mask = [x, y, z];
+    }
+
+    /// Choose the displacement direction
+    /// mode: 0 = vertex normal (default), 1 = fixed world axis (normalized axis mask),
+    ///       2 = normal projected onto the masked axes and renormalized
+    pub fn set_displacement_space(&mut self, mode: u8) {
+        self.displacement_space = match mode {
+            1 => DisplacementSpace::WorldAxis,
+            2 => DisplacementSpace::ProjectedNormal,
+            _ => DisplacementSpace::Normal,
+        };
+    }
+
+    // --- Spawn energy budget ---
+
+    /// Limit the total strength spawned in bursts (default: unlimited)
     ///
-    /// Layout per ripple:
-    /// [pos.x, pos.y, pos.z, radius,
-    ///  amplitude, phase, type, active]
-    pub fn get_uniforms(&self) -> Vec<f32> {
-        self.ripples
+    /// Each add_ripple spends |amplitude| from a pool of `max_total_strength`
+    /// that refills at `regen_per_second` in update(). The pool starts full.
+    /// A non-finite or non-positive maximum removes the limit.
+    pub fn set_energy_budget(&mut self, max_total_strength: f32, regen_per_second: f32) {
+        self.energy_capacity = if max_total_strength.is_finite() && max_total_strength > 0.0 {
+            max_total_strength
+        } else {
+            f32::INFINITY
+        };
+        self.energy = self.energy_capacity;
+        self.energy_regen = regen_per_second.max(0.0);
+    }
+
+    /// Refuse (true) or weaken (false, default) spawns the budget can't fully cover
+    pub fn set_energy_reject(&mut self, reject: bool) {
+        self.energy_reject = reject;
+    }
+
+    /// Energy left for spawning (Infinity when unlimited)
+    pub fn available_energy(&self) -> f32 {
+        self.energy
+    }
+
+    // --- Phase speed map ---
+
+    /// Per-vertex material stiffness (one value per vertex, default 1.0)
+    ///
+    /// A vertex with speed `s` sees the wave front arrive `s` times sooner with
+    /// a wavelength `s` times longer, and only feels pulls within
+    /// the ripple's falloff / s, so stiff (fast) regions deform less. Values are clamped
+    /// to >= 0.05; non-finite values count as 1.0. An empty slice clears the map.
+    /// See sample_map_to_vertices for building the map from a mask texture.
+    pub fn set_phase_speed_map(&mut self, values: &[f32]) {
+        self.phase_speed_map = values
             .iter()
-            .flat_map(|r| {
-                vec![
-                    r.position[0],
-                    r.position[1],
-                    r.position[2],
-                    r.radius,
-                    r.amplitude,
-                    r.phase,
-                    r.ripple_type as i32 as f32,
-                    if r.active { 1.0 } else { 0.0 },
-                ]
+            .map(|&v| {
+                if v.is_finite() {
+                    v.max(MIN_PHASE_SPEED)
+                } else {
+                    DEFAULT_PHASE_SPEED
+                }
             })
-            .collect()
+            .collect();
+    }
+
+    /// Phase speed of a vertex (1.0 past the end of the map)
+    pub fn get_phase_speed(&self, vertex: usize) -> f32 {
+        self.phase_speed_map
+            .get(vertex)
+            .copied()
+            .unwrap_or(DEFAULT_PHASE_SPEED)
+    }
+
+    /// Pointer to the map for uploading as a vertex attribute
+    pub fn phase_speed_map_ptr(&self) -> *const f32 {
+        self.phase_speed_map.as_ptr()
+    }
+
+    /// CPU evaluation of the surface at each vertex, phase speed map applied
+    ///
+    /// Height and pull are scaled by the camera LOD weight; vertices beyond
+    /// the LOD's far distance are skipped and evaluate to [0, 0, Infinity].
+    ///
+    /// # Arguments
+    /// * `positions` - World-space vertex positions [x, y, z, ...], indexed like the map
+    ///
+    /// # Returns
+    /// Per vertex: [wave height along the normal, summed pull weight (falloff *
+    /// amplitude), signed distance to the nearest wave front in the vertex's
+    /// scaled units (Infinity if no wave is active)]
+    pub fn evaluate_vertices(&self, positions: &[f32]) -> Vec<f32> {
+        let amplitude_scale = motion::amplitude_scale();
+        let mut out = Vec::with_capacity(positions.len() / 3 * VERTEX_EVAL_STRIDE);
+        for (vertex, p) in positions.chunks_exact(3).enumerate() {
+            let point = [p[0], p[1], p[2]];
+            let lod = self.lod_weight(p[0], p[1], p[2]);
+            if lod == 0.0 {
+                out.extend([0.0, 0.0, f32::INFINITY]);
+                continue;
+            }
+            let speed = self.get_phase_speed(vertex);
+
+            let mut pull = 0.0f32;
+            let mut front = f32::INFINITY;
+            for r in self.ripples.iter().filter(|r| r.active) {
+                let dist = distance(r.position, point);
+                match r.ripple_type {
+                    RippleType::Pull => {
+                        pull += Self::pull_weight(r, dist, speed, amplitude_scale);
+                    }
+                    RippleType::Wave => {
+                        let offset = dist / speed - r.radius;
+                        if offset.abs() < front.abs() {
+                            front = offset;
+                        }
+                    }
+                }
+            }
+            out.extend([
+                self.wave_height_scaled(point, speed) * lod,
+                pull * lod,
+                front,
+            ]);
+        }
+        out
+    }
+
+    /// Turn wave ripples into dispersive packets of up to 3 frequency components
+    ///
+    /// Component k travels at `speed * frequencies[k]^speed_exponent`, so with a
+    /// negative exponent long wavelengths outrun short ones (deep water is -0.5).
+    /// Empty arrays restore the single-front shader wave. Active ripples restart
+    /// every component from their current front.
+    pub fn set_wave_components(
+        &mut self,
+        frequencies: &[f32],
+        amplitudes: &[f32],
+        speed_exponent: f32,
+    ) -> Result<(), String> {
+        if frequencies.len() != amplitudes.len() {
+            return Err("frequencies and amplitudes must have the same length".into());
+        }
+        if frequencies.len() > MAX_WAVE_COMPONENTS {
+            return Err(format!("at most {MAX_WAVE_COMPONENTS} wave components"));
+        }
+        if frequencies.iter().any(|&f| f.is_nan() || f <= 0.0) {
+            return Err("wave component frequencies must be positive".into());
+        }
+
+        self.speed_exponent = speed_exponent;
+        self.wave_components = frequencies
+            .iter()
+            .zip(amplitudes)
+            .map(|(&frequency, &amplitude)| WaveComponent {
+                frequency,
+                amplitude,
+                speed_scale: frequency.powf(speed_exponent),
+            })
+            .collect();
+        for ripple in &mut self.ripples {
+            ripple.component_radii = [ripple.radius; MAX_WAVE_COMPONENTS];
+        }
+        Ok(())
+    }
+
+    /// Select the get_uniforms layout (1 = legacy, 2 = with dispersion block)
+    pub fn set_uniform_layout(&mut self, version: u8) -> Result<(), String> {
+        match version {
+            UNIFORM_LAYOUT_V1 | UNIFORM_LAYOUT_V2 => {
+                self.uniform_layout = version;
+                Ok(())
+            }
+            _ => Err(format!("unknown uniform layout version {version}")),
+        }
+    }
+
+    pub fn get_uniform_layout(&self) -> u8 {
+        self.uniform_layout
+    }
+
+    // --- Presets ---
+
+    /// Tunable settings as a flat list (see presets):
+    /// [mask.x, mask.y, mask.z, displacement_space, blocker_leakage]
+    pub fn get_params(&self) -> Vec<f32> {
+        vec![
+            self.axis_mask[0],
+            self.axis_mask[1],
+            self.axis_mask[2],
+            self.displacement_space as u8 as f32,
+            self.blocker_leakage,
+        ]
+    }
+
+    /// Apply a list from get_params(); missing trailing entries keep their
+    /// current values and extra entries are ignored
+    pub fn set_params(&mut self, params: &[f32]) {
+        let mut mask = self.axis_mask;
+        for (m, &p) in mask.iter_mut().zip(params) {
+            *m = p;
+        }
+        self.set_displacement_axis_mask(mask[0], mask[1], mask[2]);
+        if let Some(&mode) = params.get(3) {
+            self.set_displacement_space(mode as u8);
+        }
+        if let Some(&leakage) = params.get(4) {
+            self.set_blocker_leakage(leakage);
+        }
+    }
+
+    /// Apply settings from a plain JS object (see RippleParams)
+    ///
+    /// Missing fields take the RippleParams defaults. Unknown keys are an error
+    /// when `strict`, otherwise a console warning.
+    pub fn set_params_with_options(
+        &mut self,
+        options: JsValue,
+        strict: bool,
+    ) -> Result<(), String> {
+        let params: RippleParams = options::from_js(options, strict)?;
+        self.apply_params(&params);
+        Ok(())
+    }
+
+    /// Current settings as a plain JS object (see RippleParams)
+    pub fn get_params_object(&self) -> Result<JsValue, String> {
+        options::to_js(&RippleParams {
+            axis_mask: self.axis_mask,
+            displacement_space: self.displacement_space as u8,
+            blocker_leakage: self.blocker_leakage,
+        })
+    }
+
+    /// Unit-amplitude displacement for a vertex with normal (nx, ny, nz)
+    ///
+    /// This is the direction the shader should scale by the wave height, with the
+    /// axis mask already applied. Returns [dx, dy, dz].
+    pub fn displacement_direction(&self, nx: f32, ny: f32, nz: f32) -> Vec<f32> {
+        let [mx, my, mz] = self.axis_mask;
+        let normalize = |v: [f32; 3]| -> [f32; 3] {
+            let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+            if len > f32::EPSILON {
+                [v[0] / len, v[1] / len, v[2] / len]
+            } else {
+                [0.0; 3]
+            }
+        };
+
+        let direction = match self.displacement_space {
+            DisplacementSpace::Normal => [nx, ny, nz],
+            DisplacementSpace::WorldAxis => normalize([mx, my, mz]),
+            DisplacementSpace::ProjectedNormal => normalize([nx * mx, ny * my, nz * mz]),
+        };
+
+        vec![direction[0] * mx, direction[1] * my, direction[2] * mz]
+    }
+
+    /// Displacement settings for the shader: [mask.x, mask.y, mask.z, space mode]
+    pub fn get_displacement_uniforms(&self) -> Vec<f32> {
+        vec![
+            self.axis_mask[0],
+            self.axis_mask[1],
+            self.axis_mask[2],
+            self.displacement_space as u8 as f32,
+        ]
+    }
+
+    /// Cap the summed displacement of each vertex (default: off)
+    ///
+    /// Applied once to the displacement accumulated over all ripples, never
+    /// per ripple, so crossing waves saturate instead of turning the mesh
+    /// inside out. Magnitudes up to `limit * (1 - softness)` pass unchanged;
+    /// above that they are compressed with tanh towards `limit`, direction
+    /// preserved. Softness is clamped to 0..1, 0 being a hard clamp. A
+    /// non-finite or non-positive limit turns the limiter off (exact identity).
+    pub fn set_displacement_limit(&mut self, limit: f32, softness: f32) {
+        self.displacement_limit = if limit.is_finite() && limit > 0.0 {
+            limit
+        } else {
+            f32::INFINITY
+        };
+        self.limit_softness = if softness.is_nan() {
+            0.0
+        } else {
+            softness.clamp(0.0, 1.0)
+        };
+    }
+
+    /// Limiter settings for the shader: [limit, softness] (limit = -1 when off)
+    ///
+    /// The shader should pass its summed displacement through the same curve
+    /// as limit_displacement after the ripple loop.
+    pub fn get_displacement_limit_uniforms(&self) -> Vec<f32> {
+        let limit = if self.displacement_limit.is_finite() {
+            self.displacement_limit
+        } else {
+            -1.0
+        };
+        vec![limit, self.limit_softness]
+    }
+
+    /// A summed displacement [dx, dy, dz] after the limiter
+    /// (see set_displacement_limit)
+    pub fn limit_displacement(&self, dx: f32, dy: f32, dz: f32) -> Vec<f32> {
+        self.limited([dx, dy, dz]).0.to_vec()
+    }
+
+    /// CPU evaluation of each vertex's final displacement, limiter applied
+    ///
+    /// Sums every ripple as the shader does: wave height along
+    /// displacement_direction, pulls towards their centres at the shader's
+    /// stiffness (axis mask applied), scales by the camera LOD weight, then
+    /// limits the total.
+    ///
+    /// # Arguments
+    /// * `positions` - World-space vertex positions [x, y, z, ...], indexed like the map
+    /// * `normals` - Vertex normals, same layout
+    ///
+    /// # Returns
+    /// Per vertex: [dx, dy, dz]
+    pub fn evaluate_displacements(
+        &mut self,
+        positions: &[f32],
+        normals: &[f32],
+    ) -> Result<Vec<f32>, String> {
+        if positions.len() != normals.len() || !positions.len().is_multiple_of(3) {
+            return Err("positions and normals must be the same number of [x, y, z]".into());
+        }
+        let amplitude_scale = motion::amplitude_scale();
+        let mut limited_vertices = 0;
+        let mut out = Vec::with_capacity(positions.len());
+        for (vertex, (p, n)) in positions
+            .chunks_exact(3)
+            .zip(normals.chunks_exact(3))
+            .enumerate()
+        {
+            let point = [p[0], p[1], p[2]];
+            let lod = self.lod_weight(p[0], p[1], p[2]);
+            if lod == 0.0 {
+                out.extend([0.0; 3]);
+                continue;
+            }
+            let speed = self.get_phase_speed(vertex);
+
+            let height = self.wave_height_scaled(point, speed);
+            let direction = self.displacement_direction(n[0], n[1], n[2]);
+            let mut sum = [0, 1, 2].map(|i| direction[i] * height);
+            for r in self
+                .ripples
+                .iter()
+                .filter(|r| r.active && matches!(r.ripple_type, RippleType::Pull))
+            {
+                let weight =
+                    Self::pull_weight(r, distance(r.position, point), speed, amplitude_scale)
+                        * PULL_STIFFNESS;
+                for i in 0..3 {
+                    sum[i] += (r.position[i] - point[i]) * weight * self.axis_mask[i];
+                }
+            }
+
+            let (displacement, limited) = self.limited(sum.map(|d| d * lod));
+            limited_vertices += limited as u32;
+            out.extend(displacement);
+        }
+        self.limited_vertices = limited_vertices;
+        Ok(out)
+    }
+
+    /// Vertices the limiter changed in the last evaluate_displacements, for tuning
+    pub fn limited_vertex_count(&self) -> u32 {
+        self.limited_vertices
+    }
+
+    /// Enable camera-distance LOD for ripple evaluation
+    ///
+    /// Vertices closer than `lod_near` (world units) get full ripples, beyond
+    /// `lod_far` none, with a smooth fade in between. Pass an empty `view_pos`
+    /// to disable.
+    pub fn set_camera(&mut self, view_pos: &[f32], lod_near: f32, lod_far: f32) {
+        self.lod = match view_pos {
+            [x, y, z, ..] => Some(([*x, *y, *z], lod_near.max(0.0), lod_far.max(0.0))),
+            _ => None,
+        };
+    }
+
+    /// Ripple weight (0..1) for a vertex at (x, y, z) under the camera LOD
+    ///
+    /// 0 means ripple evaluation can be skipped and the displacement zeroed.
+    pub fn lod_weight(&self, x: f32, y: f32, z: f32) -> f32 {
+        let Some((camera, near, far)) = self.lod else {
+            return 1.0;
+        };
+        let (dx, dy, dz) = (x - camera[0], y - camera[1], z - camera[2]);
+        let dist = (dx * dx + dy * dy + dz * dz).sqrt();
+
+        if dist >= far {
+            0.0
+        } else if dist <= near {
+            1.0
+        } else {
+            let t = (dist - near) / (far - near);
+            1.0 - t * t * (3.0 - 2.0 * t)
+        }
+    }
+
+    /// LOD settings for the shader: [camera.x, camera.y, camera.z, near, far]
+    /// (far = -1 when the LOD is disabled)
+    pub fn get_lod_uniforms(&self) -> Vec<f32> {
+        match self.lod {
+            Some((c, near, far)) => vec![c[0], c[1], c[2], near, far],
+            None => vec![0.0, 0.0, 0.0, 0.0, -1.0],
+        }
+    }
+
+    /// Wave front proximity for every active wave ripple
+    ///
+    /// Layout per wave ripple:
+    /// [signed distance to front (dist_to_center - radius), local height, ripple index]
+    /// The height is the ripple's share of the rendered wave (see wave_height_at),
+    /// blocker attenuation included, so the heights of all fronts sum to it.
+    /// Pull ripples and inactive slots are skipped.
+    pub fn query_wavefront(&self, x: f32, y: f32, z: f32) -> Vec<f32> {
+        let point = [x, y, z];
+        self.ripples
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.active && matches!(r.ripple_type, RippleType::Wave))
+            .flat_map(|(idx, r)| {
+                let offset = distance(r.position, point) - r.radius;
+                let height = self.wave_contribution(r, point, DEFAULT_PHASE_SPEED);
+                [offset, height, idx as f32]
+            })
+            .collect()
+    }
+
+    /// Closest wave front to a point, same triple as query_wavefront (empty if none)
+    pub fn nearest_wavefront(&self, x: f32, y: f32, z: f32) -> Vec<f32> {
+        self.query_wavefront(x, y, z)
+            .chunks(3)
+            .min_by(|a, b| a[0].abs().partial_cmp(&b[0].abs()).unwrap())
+            .map(|c| c.to_vec())
+            .unwrap_or_default()
+    }
+
+    // --- Blockers ---
+
+    /// Register a static sphere obstacle, returns its id
+    pub fn add_blocker_sphere(&mut self, x: f32, y: f32, z: f32, radius: f32) -> u32 {
+        self.push_blocker(Blocker::Sphere {
+            center: [x, y, z],
+            radius: radius.max(0.0),
+        })
+    }
+
+    /// Register a static axis-aligned box obstacle, returns its id
+    ///
+    /// `min` and `max` are [x, y, z] corners (swapped per axis if needed).
+    pub fn add_blocker_box(&mut self, min: &[f32], max: &[f32]) -> Result<u32, String> {
+        let (Ok(a), Ok(b)) = (<[f32; 3]>::try_from(min), <[f32; 3]>::try_from(max)) else {
+            return Err("blocker box corners must be 3 floats each".into());
+        };
+        Ok(self.push_blocker(Blocker::Box {
+            min: [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])],
+            max: [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
+        }))
+    }
+
+    /// Remove a blocker by id, returns false if it did not exist
+    pub fn remove_blocker(&mut self, id: u32) -> bool {
+        let before = self.blockers.len();
+        self.blockers.retain(|(blocker_id, _)| *blocker_id != id);
+        self.blockers.len() != before
+    }
+
+    /// Fraction of a wave that passes behind a blocker (default 0.2, 0 = hard shadow)
+    pub fn set_blocker_leakage(&mut self, leakage: f32) {
+        self.blocker_leakage = leakage.clamp(0.0, 1.0);
+    }
+
+    /// Attenuation (leakage..1) of ripple `index` at a point due to blockers
+    ///
+    /// A wave is shadowed when the straight segment from the ripple center to
+    /// the point passes through a blocker; sphere shadows soften towards the rim.
+    /// Returns 1 without any blocker tests when there are no blockers or the point
+    /// is outside the ripple's influence radius.
+    pub fn blocker_attenuation(&self, index: usize, x: f32, y: f32, z: f32) -> f32 {
+        let Some(ripple) = self.ripples.get(index).filter(|r| r.active) else {
+            return 1.0;
+        };
+        self.attenuation_for(ripple, [x, y, z])
+    }
+
+    /// Blockers for the shader, first 8, 8 floats each:
+    /// sphere [0, cx, cy, cz, radius, 0, 0, 1], box [1, min.xyz, max.xyz, 1];
+    /// unused slots are all zero. Leakage is get_blocker_leakage().
+    pub fn get_blocker_uniforms(&self) -> Vec<f32> {
+        let mut uniforms = vec![0.0; MAX_BLOCKER_UNIFORMS * 8];
+        for ((_, blocker), slot) in self.blockers.iter().zip(uniforms.chunks_exact_mut(8)) {
+            let values = match *blocker {
+                Blocker::Sphere { center: c, radius } => {
+                    [0.0, c[0], c[1], c[2], radius, 0.0, 0.0, 1.0]
+                }
+                Blocker::Box { min, max } => {
+                    [1.0, min[0], min[1], min[2], max[0], max[1], max[2], 1.0]
+                }
+            };
+            slot.copy_from_slice(&values);
+        }
+        uniforms
+    }
+
+    pub fn get_blocker_leakage(&self) -> f32 {
+        self.blocker_leakage
+    }
+
+    /// Sample a ripple's amplitude decay as a haptic intensity envelope
+    ///
+    /// Follows the same model as update() (amplitude *= decay^dt) from the ripple's
+    /// current state, clamped to 0..1, and stops once the amplitude drops below the
+    /// deactivation threshold. Inactive or out-of-range ripples give an empty envelope.
+    pub fn haptic_envelope_for_ripple(&self, index: usize, sample_rate_hz: f32) -> Vec<f32> {
+        let Some(ripple) = self.ripples.get(index).filter(|r| r.active) else {
+            return Vec::new();
+        };
+        if sample_rate_hz <= 0.0 {
+            return Vec::new();
+        }
+
+        let step_decay = ripple.decay.powf(1.0 / sample_rate_hz);
+        let max_samples = (MAX_HAPTIC_SECONDS * sample_rate_hz) as usize;
+        let mut amplitude = ripple.amplitude;
+        let mut envelope = Vec::new();
+
+        while amplitude >= AMPLITUDE_THRESHOLD && envelope.len() < max_samples {
+            envelope.push(amplitude.clamp(0.0, 1.0));
+            amplitude *= step_decay;
+        }
+
+        envelope
+    }
+
+    /// Flat uniform buffer for Three.js
+    ///
+    /// Layout per ripple:
+    /// [pos.x, pos.y, pos.z, radius,
+    ///  amplitude, phase, type, active]
+    ///
+    /// Layout 2 (set_uniform_layout) appends a dispersion block after the
+    /// ripples, so layout-1 shaders can keep reading the prefix:
+    /// [layout, component count, speed_exponent, 0],
+    /// 3 x [frequency, amplitude, speed scale, 0] (unused components are zero),
+    /// then per ripple [front radius 0, 1, 2, 0]
+    ///
+    /// Amplitudes are scaled for reduced motion (0 when motion is off).
+    pub fn get_uniforms(&self) -> Vec<f32> {
+        let amplitude_scale = motion::amplitude_scale();
+        let mut uniforms: Vec<f32> = self
+            .ripples
+            .iter()
+            .flat_map(|r| {
+                vec![
+                    r.position[0],
+                    r.position[1],
+                    r.position[2],
+                    r.radius,
+                    r.amplitude * amplitude_scale,
+                    r.phase,
+                    r.ripple_type as i32 as f32,
+                    if r.active { 1.0 } else { 0.0 },
+                ]
+            })
+            .collect();
+
+        if self.uniform_layout == UNIFORM_LAYOUT_V2 {
+            uniforms.extend([
+                UNIFORM_LAYOUT_V2 as f32,
+                self.wave_components.len() as f32,
+                self.speed_exponent,
+                0.0,
+            ]);
+            for k in 0..MAX_WAVE_COMPONENTS {
+                match self.wave_components.get(k) {
+                    Some(c) => uniforms.extend([c.frequency, c.amplitude, c.speed_scale, 0.0]),
+                    None => uniforms.extend([0.0; 4]),
+                }
+            }
+            for r in &self.ripples {
+                uniforms.extend(r.component_radii);
+                uniforms.push(0.0);
+            }
+        }
+        uniforms
+    }
+}
+
+/// Sample a mask texture at each vertex's UV (bilinear, clamped to the edges)
+///
+/// # Arguments
+/// * `texture` - Row-major texels, top row first (canvas getImageData order);
+///   1 to 4 bytes per texel, only the first channel is read
+/// * `tex_w`, `tex_h` - Texture size in texels
+/// * `uvs` - Per-vertex [u, v, ...] with v = 0 at the bottom (three.js convention)
+///
+/// # Returns
+/// One value in 0..1 per vertex. Remap before set_phase_speed_map,
+/// e.g. `0.5 + value` for a mid-gray-is-neutral mask.
+#[wasm_bindgen]
+pub fn sample_map_to_vertices(
+    texture: &[u8],
+    tex_w: u32,
+    tex_h: u32,
+    uvs: &[f32],
+) -> Result<Vec<f32>, String> {
+    let (w, h) = (tex_w as usize, tex_h as usize);
+    if w == 0 || h == 0 {
+        return Err("texture size must be non-zero".to_string());
+    }
+    let channels = texture.len() / (w * h);
+    if !(1..=4).contains(&channels) || texture.len() != w * h * channels {
+        return Err(format!(
+            "texture of {} bytes does not match {w}x{h} with 1-4 channels",
+            texture.len()
+        ));
+    }
+
+    let texel = |x: usize, y: usize| texture[(y * w + x) * channels] as f32 / 255.0;
+    Ok(uvs
+        .chunks_exact(2)
+        .map(|uv| {
+            // Texel centres sit at half-integer coordinates
+            let x = (uv[0] * w as f32 - 0.5).clamp(0.0, (w - 1) as f32);
+            let y = ((1.0 - uv[1]) * h as f32 - 0.5).clamp(0.0, (h - 1) as f32);
+            let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+            let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+            let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+            let top = texel(x0, y0) + (texel(x1, y0) - texel(x0, y0)) * fx;
+            let bottom = texel(x0, y1) + (texel(x1, y1) - texel(x0, y1)) * fx;
+            top + (bottom - top) * fy
+        })
+        .collect())
+}
+
+/// Convert an intensity envelope into a `navigator.vibrate` on/off pattern
+///
+/// # Arguments
+/// * `envelope` - Intensity samples (e.g. from haptic_envelope_for_ripple)
+/// * `sample_rate_hz` - Rate the envelope was sampled at
+/// * `threshold` - Samples at or above this vibrate
+///
+/// # Returns
+/// Alternating [on_ms, off_ms, on_ms, ...]. Starts with 0 if the envelope starts
+/// below threshold, never ends with an off segment, and has no other zero entries.
+#[wasm_bindgen]
+pub fn envelope_to_vibrate_pattern(
+    envelope: &[f32],
+    sample_rate_hz: f32,
+    threshold: f32,
+) -> Vec<u32> {
+    let mut pattern: Vec<u32> = Vec::new();
+    if sample_rate_hz <= 0.0 {
+        return pattern;
+    }
+
+    let ms_per_sample = 1000.0 / sample_rate_hz;
+    let mut on = true;
+    let mut run = 0usize;
+
+    let flush = |pattern: &mut Vec<u32>, on: bool, run: usize| {
+        if run == 0 {
+            return;
+        }
+        let ms = ((run as f32 * ms_per_sample).round() as u32).max(1);
+        // Patterns alternate on/off starting with on
+        if pattern.len() % 2 == usize::from(on) {
+            pattern.push(0);
+        }
+        pattern.push(ms);
+    };
+
+    for &sample in envelope {
+        let sample_on = sample >= threshold;
+        if sample_on != on {
+            flush(&mut pattern, on, run);
+            on = sample_on;
+            run = 0;
+        }
+        run += 1;
+    }
+    // Trailing off time is dropped, so the pattern always ends vibrating
+    if on {
+        flush(&mut pattern, on, run);
+    }
+    pattern
+}
+
+impl RippleSimulator {
+    /// Pay for a spawn; the (possibly weakened) amplitude, or None if refused
+    fn spend_energy(&mut self, amplitude: f32) -> Option<f32> {
+        let cost = amplitude.abs();
+        if cost <= self.energy {
+            self.energy -= cost;
+            return Some(amplitude);
+        }
+        if self.energy_reject || self.energy < AMPLITUDE_THRESHOLD {
+            return None;
+        }
+        let scaled = amplitude * (self.energy / cost);
+        self.energy = 0.0;
+        Some(scaled)
+    }
+
+    fn apply_params(&mut self, params: &RippleParams) {
+        let [x, y, z] = params.axis_mask;
+        self.set_displacement_axis_mask(x, y, z);
+        self.set_displacement_space(params.displacement_space);
+        self.set_blocker_leakage(params.blocker_leakage);
+    }
+
+    /// Slot of a handle that still names the ripple it was issued for
+    fn resolve_handle(&self, handle: u32) -> Option<usize> {
+        let slot = (handle & ((1 << HANDLE_SLOT_BITS) - 1)) as usize;
+        (handle != 0 && self.ripple_handle(slot) == handle).then_some(slot)
+    }
+
+    /// Pull weight (falloff * amplitude) of a ripple `dist` from a vertex of phase speed `speed`
+    fn pull_weight(r: &Ripple, dist: f32, speed: f32, amplitude_scale: f32) -> f32 {
+        smoothstep(r.falloff, 0.0, dist * speed) * r.amplitude * amplitude_scale
+    }
+
+    /// Displacement after the limiter, and whether the limiter changed it
+    fn limited(&self, displacement: [f32; 3]) -> ([f32; 3], bool) {
+        let limit = self.displacement_limit;
+        let magnitude = distance(displacement, [0.0; 3]);
+        let knee = limit * (1.0 - self.limit_softness);
+        if !limit.is_finite() || magnitude <= knee {
+            return (displacement, false);
+        }
+        let capped = if knee < limit {
+            knee + (limit - knee) * ((magnitude - knee) / (limit - knee)).tanh()
+        } else {
+            limit
+        };
+        (displacement.map(|d| d * (capped / magnitude)), true)
+    }
+
+    /// Shape parameters set_ripple_params accepts
+    fn valid_shape(wave_width: f32, falloff: f32, lifetime: f32) -> bool {
+        [wave_width, falloff, lifetime]
+            .iter()
+            .all(|v| v.is_finite() && *v > 0.0)
+    }
+
+    fn push_blocker(&mut self, blocker: Blocker) -> u32 {
+        let id = self.next_blocker_id;
+        self.next_blocker_id = self.next_blocker_id.wrapping_add(1);
+        self.blockers.push((id, blocker));
+        id
+    }
+
+    /// Distance beyond which a ripple's kernel is negligible
+    fn influence_radius(ripple: &Ripple) -> f32 {
+        match ripple.ripple_type {
+            // Shader wave falls off as exp(-2 * dist)
+            RippleType::Wave => (ripple.amplitude.abs() / AMPLITUDE_THRESHOLD).max(1.0).ln() * 0.5,
+            RippleType::Pull => ripple.falloff,
+        }
+    }
+
+    fn attenuation_for(&self, ripple: &Ripple, point: [f32; 3]) -> f32 {
+        if self.blockers.is_empty() {
+            return 1.0;
+        }
+        let from = ripple.position;
+        let influence = Self::influence_radius(ripple);
+        if distance(from, point) > influence {
+            return 1.0;
+        }
+
+        let mut shadow = 0.0f32;
+        for (_, blocker) in &self.blockers {
+            // Skip blockers entirely outside the ripple's sphere of influence
+            let (center, reach) = blocker.bounding_sphere();
+            if distance(from, center) > influence + reach {
+                continue;
+            }
+            shadow = shadow.max(blocker.segment_shadow(from, point));
+            if shadow >= 1.0 {
+                break;
+            }
+        }
+        1.0 - (1.0 - self.blocker_leakage) * shadow
+    }
+
+    /// Summed wave displacement (along the normal) at a world point, as the shader draws it
+    ///
+    /// Pull ripples are not included; blockers attenuate as in query_wavefront.
+    /// With wave components set, each ripple is instead a sum of Gaussian-windowed
+    /// sine packets centred on the per-component fronts.
+    fn wave_height_at(&self, point: [f32; 3]) -> f32 {
+        self.wave_height_scaled(point, DEFAULT_PHASE_SPEED)
+    }
+
+    /// wave_height_at for a vertex whose phase speed is `phase_speed`
+    ///
+    /// Distances to the ripple centres are divided by the speed, which
+    /// stretches both the front's travel and the wavelength.
+    fn wave_height_scaled(&self, point: [f32; 3], phase_speed: f32) -> f32 {
+        if motion::amplitude_scale() == 0.0 {
+            return 0.0;
+        }
+        self.ripples
+            .iter()
+            .filter(|r| r.active && matches!(r.ripple_type, RippleType::Wave))
+            .map(|r| self.wave_contribution(r, point, phase_speed))
+            .sum()
+    }
+
+    /// One wave ripple's term of wave_height_scaled
+    fn wave_contribution(&self, r: &Ripple, point: [f32; 3], phase_speed: f32) -> f32 {
+        let dispersive = !self.wave_components.is_empty();
+        if !dispersive && r.phase >= r.lifetime * motion::lifetime_scale() {
+            return 0.0;
+        }
+        let dist = distance(r.position, point) / phase_speed;
+        let height = if dispersive {
+            self.wave_components
+                .iter()
+                .zip(r.component_radii)
+                .map(|(c, radius)| {
+                    let offset = dist - radius;
+                    let envelope = (-(offset / r.width).powi(2)).exp();
+                    c.amplitude * (std::f32::consts::TAU * c.frequency * offset).sin() * envelope
+                })
+                .sum()
+        } else {
+            wave_kernel(dist, r.phase, r.speed, r.width, 1.0)
+        };
+        height * r.amplitude * motion::amplitude_scale() * self.attenuation_for(r, point)
+    }
+}
+
+impl Blocker {
+    fn contains(&self, p: [f32; 3]) -> bool {
+        match *self {
+            Blocker::Sphere { center, radius } => distance(center, p) < radius,
+            Blocker::Box { min, max } => (0..3).all(|i| p[i] > min[i] && p[i] < max[i]),
+        }
+    }
+
+    fn bounding_sphere(&self) -> ([f32; 3], f32) {
+        match *self {
+            Blocker::Sphere { center, radius } => (center, radius),
+            Blocker::Box { min, max } => {
+                let center = [0, 1, 2].map(|i| (min[i] + max[i]) * 0.5);
+                (center, distance(center, max))
+            }
+        }
+    }
+
+    /// How strongly the segment a -> b is shadowed: 0 = clear, 1 = fully blocked
+    fn segment_shadow(&self, a: [f32; 3], b: [f32; 3]) -> f32 {
+        let d = [0, 1, 2].map(|i| b[i] - a[i]);
+        match *self {
+            Blocker::Sphere { center, radius } => {
+                // Closest point on the segment to the sphere center
+                let len_sq = d[0] * d[0] + d[1] * d[1] + d[2] * d[2];
+                let t = if len_sq > 0.0 {
+                    ((0..3).map(|i| (center[i] - a[i]) * d[i]).sum::<f32>() / len_sq)
+                        .clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let closest = [0, 1, 2].map(|i| a[i] + d[i] * t);
+                let miss = distance(closest, center);
+                if miss >= radius {
+                    return 0.0;
+                }
+                // Full shadow through the core, soft towards the rim
+                let edge = ((radius - miss) / (radius * 0.5)).min(1.0);
+                edge * edge * (3.0 - 2.0 * edge)
+            }
+            Blocker::Box { min, max } => {
+                // Slab test restricted to the segment (t in 0..1)
+                let (mut t_enter, mut t_exit) = (0.0f32, 1.0f32);
+                for i in 0..3 {
+                    if d[i] == 0.0 {
+                        if a[i] < min[i] || a[i] > max[i] {
+                            return 0.0;
+                        }
+                        continue;
+                    }
+                    let inv = 1.0 / d[i];
+                    let (t0, t1) = ((min[i] - a[i]) * inv, (max[i] - a[i]) * inv);
+                    t_enter = t_enter.max(t0.min(t1));
+                    t_exit = t_exit.min(t0.max(t1));
+                }
+                if t_enter < t_exit {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let (dx, dy, dz) = (a[0] - b[0], a[1] - b[1], a[2] - b[2]);
+    (dx * dx + dy * dy + dz * dz).sqrt()
+}
+
+/// Shader wave + secondary ripple height at `dist` from the center, per unit strength
+///
+/// The front travels at `speed` and the wavelength scales with `width`;
+/// Speed 2 (the shader's waveSpeed) and WAVEFRONT_WIDTH reproduce the shader exactly.
+/// `pitch` scales the time term (1 = shader speed).
+fn wave_kernel(dist: f32, age: f32, speed: f32, width: f32, pitch: f32) -> f32 {
+    let front = age * speed * pitch;
+    let band = WAVEFRONT_WIDTH / width;
+    let wave =
+        ((dist * 8.0 - front * 10.0) * band).sin() * (-dist * 2.0).exp() * (-age * 3.0).exp();
+    let ripple2 =
+        ((dist - front * 8.0) * band).sin() * (-dist * 3.0).exp() * (-age * 4.0).exp() * 0.1;
+    wave * 0.15 + ripple2 * 0.01
+}
+
+/// GLSL smoothstep
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = distance(v, [0.0; 3]);
+    if len > 0.0 {
+        [v[0] / len, v[1] / len, v[2] / len]
+    } else {
+        v
+    }
+}
+
+/// Audio-rate copy of one ripple, resynced from the simulator each block
+#[derive(Clone, Copy)]
+struct SonifierVoice {
+    position: [f32; 3],
+    amplitude: f32,
+    age: f32,
+    decay: f32,
+    ripple_type: RippleType,
+    /// Front speed and band width of a wave voice (Ripple::speed, Ripple::width)
+    speed: f32,
+    width: f32,
+    /// Reach of a pull voice (Ripple::falloff)
+    falloff: f32,
+    /// Seconds a wave voice sounds for (Ripple::lifetime, unscaled)
+    lifetime: f32,
+}
+
+/// Renders the ripple displacement at a probe point as an audio signal
+///
+/// Evaluates the same analytic kernels as the vertex shader at audio rate,
+/// using only the ripple parameters (no vertex buffers).
+#[wasm_bindgen]
+pub struct RippleSonifier {
+    sample_rate: f32,
+    probe_position: [f32; 3],
+    probe_normal: [f32; 3],
+    voices: [Option<SonifierVoice>; MAX_RIPPLES],
+    /// One-pole highpass state (previous input, previous output)
+    dc_x1: f32,
+    dc_y1: f32,
+}
+
+#[wasm_bindgen]
+impl RippleSonifier {
+    #[wasm_bindgen(constructor)]
+    pub fn new(sample_rate: f32) -> RippleSonifier {
+        RippleSonifier {
+            sample_rate: sample_rate.max(1.0),
+            probe_position: [0.0, 0.0, 0.0],
+            probe_normal: [0.0, 1.0, 0.0],
+            voices: [None; MAX_RIPPLES],
+            dc_x1: 0.0,
+            dc_y1: 0.0,
+        }
+    }
+
+    /// Set the listening point and the surface normal there (world space)
+    pub fn set_probe(&mut self, x: f32, y: f32, z: f32, nx: f32, ny: f32, nz: f32) {
+        self.probe_position = [x, y, z];
+        let len = (nx * nx + ny * ny + nz * nz).sqrt();
+        self.probe_normal = if len > 0.0 {
+            [nx / len, ny / len, nz / len]
+        } else {
+            [0.0, 1.0, 0.0]
+        };
+    }
+
+    /// Render one audio block, bounded to -1..1
+    ///
+    /// # Arguments
+    /// * `block` - Output samples, overwritten
+    /// * `sim` - Simulator the ripple parameters are read from
+    /// * `visual_dt` - Frame step the simulator is advanced by; local voices that
+    ///   drift further than this from the simulator are resynced
+    pub fn render(&mut self, block: &mut [f32], sim: &RippleSimulator, visual_dt: f32) {
+        self.sync_voices(sim, visual_dt.max(block.len() as f32 / self.sample_rate));
+
+        let dt = 1.0 / self.sample_rate;
+        let r = 1.0 - std::f32::consts::TAU * DC_CUTOFF_HZ / self.sample_rate;
+
+        for out in block.iter_mut() {
+            let mut x = 0.0;
+            for voice in self.voices.iter_mut().flatten() {
+                x += Self::voice_sample(voice, self.probe_position, self.probe_normal);
+                voice.age += dt;
+                voice.amplitude *= voice.decay.powf(dt);
+            }
+
+            // DC block, then soft clip
+            let y = x * SONIFY_GAIN - self.dc_x1 + r * self.dc_y1;
+            self.dc_x1 = x * SONIFY_GAIN;
+            self.dc_y1 = y;
+            *out = y.tanh();
+        }
+
+        for voice in &mut self.voices {
+            if voice.is_some_and(|v| v.amplitude < AMPLITUDE_THRESHOLD) {
+                *voice = None;
+            }
+        }
+    }
+}
+
+impl RippleSonifier {
+    /// Pick up new/replaced ripples and correct voices that drifted from the simulator
+    fn sync_voices(&mut self, sim: &RippleSimulator, tolerance: f32) {
+        for (voice, ripple) in self.voices.iter_mut().zip(&sim.ripples) {
+            if !ripple.active {
+                *voice = None;
+                continue;
+            }
+            let in_sync = voice.is_some_and(|v| {
+                v.position == ripple.position
+                    && v.speed == ripple.speed
+                    && v.width == ripple.width
+                    && v.falloff == ripple.falloff
+                    && v.lifetime == ripple.lifetime
+                    && (v.age - ripple.phase).abs() <= tolerance
+            });
+            if !in_sync {
+                *voice = Some(SonifierVoice {
+                    position: ripple.position,
+                    amplitude: ripple.amplitude,
+                    age: ripple.phase,
+                    decay: ripple.decay,
+                    ripple_type: ripple.ripple_type,
+                    speed: ripple.speed,
+                    width: ripple.width,
+                    falloff: ripple.falloff,
+                    lifetime: ripple.lifetime,
+                });
+            }
+        }
+    }
+
+    /// Displacement of one voice at the probe, projected on the probe normal
+    fn voice_sample(voice: &SonifierVoice, probe: [f32; 3], normal: [f32; 3]) -> f32 {
+        let to_probe = [
+            probe[0] - voice.position[0],
+            probe[1] - voice.position[1],
+            probe[2] - voice.position[2],
+        ];
+        let dist =
+            (to_probe[0] * to_probe[0] + to_probe[1] * to_probe[1] + to_probe[2] * to_probe[2])
+                .sqrt();
+        let age = voice.age;
+
+        match voice.ripple_type {
+            RippleType::Wave => {
+                // Silent once the wave is no longer drawn
+                if age >= voice.lifetime * motion::lifetime_scale() {
+                    return 0.0;
+                }
+                // Sine terms sped up into the audible range
+                wave_kernel(dist, age, voice.speed, voice.width, SONIFY_PITCH) * voice.amplitude
+            }
+            RippleType::Pull => {
+                // Release oscillator: damped low sine scaled by the shader's pull falloff
+                let t = ((voice.falloff - dist) / voice.falloff).clamp(0.0, 1.0);
+                let falloff = t * t * (3.0 - 2.0 * t);
+                let facing = if dist > 0.0 {
+                    -(to_probe[0] * normal[0] + to_probe[1] * normal[1] + to_probe[2] * normal[2])
+                        / dist
+                } else {
+                    1.0
+                };
+                let thump =
+                    (std::f32::consts::TAU * THUMP_HZ * age).sin() * (-age * THUMP_DAMPING).exp();
+                thump * falloff * facing.abs().max(0.25) * PULL_STIFFNESS * voice.amplitude
+            }
+        }
+    }
+}
+
+/// Decorative particles resting on the rippling surface
+///
+/// Each particle bobs along the displacement direction with the wave height at
+/// its position and slides down the height gradient in its tangent plane, while
+/// a damped spring pulls it back to its rest point. Positions are exposed as a
+/// count*3 buffer for instancing.
+#[wasm_bindgen]
+pub struct SurfaceParticles {
+    count: usize,
+    rest_positions: Vec<f32>,
+    rest_normals: Vec<f32>,
+    /// Tangential offset from the rest point and its velocity, count*3 each
+    offsets: Vec<f32>,
+    velocities: Vec<f32>,
+    /// Output positions, count*3
+    positions: Vec<f32>,
+    slide: f32,
+    stiffness: f32,
+    damping: f32,
+    /// Hard limit on the distance from the rest point
+    max_distance: f32,
+}
+
+#[wasm_bindgen]
+impl SurfaceParticles {
+    /// `count` particles at the origin with +Y normals
+    #[wasm_bindgen(constructor)]
+    pub fn new(count: usize) -> SurfaceParticles {
+        SurfaceParticles {
+            count,
+            rest_positions: vec![0.0; count * 3],
+            rest_normals: [0.0, 1.0, 0.0].repeat(count),
+            offsets: vec![0.0; count * 3],
+            velocities: vec![0.0; count * 3],
+            positions: vec![0.0; count * 3],
+            slide: DEFAULT_PARTICLE_SLIDE,
+            stiffness: DEFAULT_PARTICLE_STIFFNESS,
+            damping: DEFAULT_PARTICLE_DAMPING,
+            max_distance: DEFAULT_PARTICLE_MAX_DISTANCE,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Set rest positions (count*3 floats, world space) and snap particles to them
+    pub fn set_rest_positions(&mut self, positions: &[f32]) -> Result<(), String> {
+        if positions.len() != self.count * 3 {
+            return Err(format!(
+                "expected {} rest position floats, got {}",
+                self.count * 3,
+                positions.len()
+            ));
+        }
+        self.rest_positions.copy_from_slice(positions);
+        self.reset();
+        Ok(())
+    }
+
+    /// Set rest normals (count*3 floats, normalized here)
+    pub fn set_rest_normals(&mut self, normals: &[f32]) -> Result<(), String> {
+        if normals.len() != self.count * 3 {
+            return Err(format!(
+                "expected {} rest normal floats, got {}",
+                self.count * 3,
+                normals.len()
+            ));
+        }
+        for (dst, n) in self.rest_normals.chunks_mut(3).zip(normals.chunks(3)) {
+            let n = normalize([n[0], n[1], n[2]]);
+            dst.copy_from_slice(if n == [0.0; 3] { &[0.0, 1.0, 0.0] } else { &n });
+        }
+        self.reset();
+        Ok(())
+    }
+
+    /// Tune the coupling
+    ///
+    /// # Arguments
+    /// * `slide` - Tangential acceleration per unit of wave slope
+    /// * `stiffness` - Spring back to the rest point (1/s^2)
+    /// * `damping` - Velocity damping (1/s)
+    /// * `max_distance` - Hard clamp on the distance from the rest point
+    pub fn set_dynamics(&mut self, slide: f32, stiffness: f32, damping: f32, max_distance: f32) {
+        self.slide = slide.max(0.0);
+        self.stiffness = stiffness.max(0.0);
+        self.damping = damping.max(0.0);
+        self.max_distance = max_distance.max(0.0);
+    }
+
+    /// Put every particle back on its rest point, at rest
+    pub fn reset(&mut self) {
+        self.offsets.fill(0.0);
+        self.velocities.fill(0.0);
+        self.positions.copy_from_slice(&self.rest_positions);
+    }
+
+    /// Advance the particles against the simulator's current wave field
+    pub fn update(&mut self, sim: &RippleSimulator, dt: f32) {
+        let dt = dt.clamp(0.0, MAX_PARTICLE_DT);
+
+        for i in 0..self.count {
+            let range = i * 3..i * 3 + 3;
+            let rest = to_vec3(&self.rest_positions[range.clone()]);
+            let normal = to_vec3(&self.rest_normals[range.clone()]);
+            let mut offset = to_vec3(&self.offsets[range.clone()]);
+            let mut velocity = to_vec3(&self.velocities[range.clone()]);
+
+            // Height gradient at the displaced sample point, flattened into the tangent plane
+            let at = add(rest, offset);
+            let height = sim.wave_height_at(at);
+            let h = GRADIENT_STEP;
+            let gradient = [
+                sim.wave_height_at(add(at, [h, 0.0, 0.0]))
+                    - sim.wave_height_at(add(at, [-h, 0.0, 0.0])),
+                sim.wave_height_at(add(at, [0.0, h, 0.0]))
+                    - sim.wave_height_at(add(at, [0.0, -h, 0.0])),
+                sim.wave_height_at(add(at, [0.0, 0.0, h]))
+                    - sim.wave_height_at(add(at, [0.0, 0.0, -h])),
+            ];
+            let gradient = tangential(scale(gradient, 0.5 / h), normal);
+
+            // Slide downhill, spring home, damp (semi-implicit Euler)
+            for k in 0..3 {
+                let accel = -self.slide * gradient[k]
+                    - self.stiffness * offset[k]
+                    - self.damping * velocity[k];
+                velocity[k] += accel * dt;
+                offset[k] += velocity[k] * dt;
+            }
+            offset = tangential(offset, normal);
+            velocity = tangential(velocity, normal);
+
+            // Bob along the displacement direction, then hard-clamp to the rest radius
+            let direction = sim.displacement_direction(normal[0], normal[1], normal[2]);
+            let mut displacement = add(offset, scale(to_vec3(&direction), height));
+            let length = distance(displacement, [0.0; 3]);
+            if length > self.max_distance {
+                let fit = self.max_distance / length;
+                displacement = scale(displacement, fit);
+                offset = scale(offset, fit);
+                velocity = scale(velocity, fit);
+            }
+
+            self.offsets[range.clone()].copy_from_slice(&offset);
+            self.velocities[range.clone()].copy_from_slice(&velocity);
+            self.positions[range].copy_from_slice(&add(rest, displacement));
+        }
+    }
+
+    /// Get pointer to particle positions (count*3 floats)
+    pub fn positions_ptr(&self) -> *const f32 {
+        self.positions.as_ptr()
+    }
+
+    /// Particle positions, count*3 floats
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.clone()
+    }
+}
+
+fn to_vec3(v: &[f32]) -> [f32; 3] {
+    [v[0], v[1], v[2]]
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn scale(v: [f32; 3], s: f32) -> [f32; 3] {
+    [v[0] * s, v[1] * s, v[2] * s]
+}
+
+/// Component of `v` perpendicular to the unit `normal`
+fn tangential(v: [f32; 3], normal: [f32; 3]) -> [f32; 3] {
+    let d = v[0] * normal[0] + v[1] * normal[1] + v[2] * normal[2];
+    [
+        v[0] - normal[0] * d,
+        v[1] - normal[1] * d,
+        v[2] - normal[2] * d,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Surface quad (id 1) at z = 0 and a smaller occluder quad (id 2) at z = 2
+    fn occluded_picker() -> BVHRaycaster {
+        let quad = |half: f32, z: f32| {
+            vec![
+                -half, -half, z, half, -half, z, half, half, z, -half, half, z,
+            ]
+        };
+        let positions = [quad(4.0, 0.0), quad(1.0, 2.0)].concat();
+        let indices = [0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7];
+        let mut picker = BVHRaycaster::from_geometry(&positions, &indices);
+        picker.set_id_ranges(&[0, 2, 1, 2, 2, 2]).unwrap();
+        picker
+    }
+
+    #[test]
+    fn test_spawn_ripple_occluded() {
+        let picker = occluded_picker();
+        let identity = glam::Mat4::IDENTITY.to_cols_array();
+        let down = [0.0, 0.0, -1.0];
+        let mut sim = RippleSimulator::new();
+        let spawn = |sim: &mut RippleSimulator, x: f32, mode: u8| {
+            sim.spawn_ripple_occluded(
+                &picker,
+                1,
+                &[x, 0.0, 5.0],
+                &down,
+                &identity,
+                1.0,
+                2.0,
+                0.5,
+                RippleType::Wave,
+                mode,
+            )
+        };
+
+        // Blocker in front suppresses the spawn; past its edge the surface is hit
+        assert_eq!(spawn(&mut sim, 0.0, OCCLUDED_SPAWN_REJECT), SPAWN_OCCLUDED);
+        assert!(sim.ripples.iter().all(|r| !r.active));
+        let slot = spawn(&mut sim, 1.5, OCCLUDED_SPAWN_REJECT);
+        assert!(slot >= 0);
+        assert_eq!(sim.ripples[slot as usize].position, [1.5, 0.0, 0.0]);
+
+        // Missing everything is distinguishable from being occluded
+        assert_eq!(spawn(&mut sim, 10.0, OCCLUDED_SPAWN_REJECT), SPAWN_MISS);
+
+        // Muffle mode spawns behind the occluder at reduced strength
+        let slot = spawn(&mut sim, 0.0, OCCLUDED_SPAWN_MUFFLE);
+        assert!(slot >= 0);
+        let ripple = sim.ripples[slot as usize];
+        assert_eq!(ripple.position, [0.0, 0.0, 0.0]);
+        assert!((ripple.amplitude - MUFFLE_PER_OCCLUDER).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_query_wavefront() {
+        let mut sim = RippleSimulator::new();
+        sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave);
+        sim.add_ripple(5.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Pull);
+        sim.update(0.5);
+
+        // Front has travelled 1 unit; pull ripple is excluded
+        let fronts = sim.query_wavefront(3.0, 0.0, 0.0);
+        assert_eq!(fronts.len(), 3);
+        assert!((fronts[0] - 2.0).abs() < 1e-5);
+        assert_eq!(fronts[2], 0.0);
+
+        let on_front = sim.nearest_wavefront(1.0, 0.0, 0.0);
+        assert!(on_front[0].abs() < 1e-5);
+
+        // Heights are the rendered wave, ripple by ripple
+        sim.add_ripple(-1.0, 0.5, 0.0, 0.7, 2.0, 0.5, RippleType::Wave);
+        for point in [[0.3, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.2, 0.4]] {
+            let heights: f32 = sim
+                .query_wavefront(point[0], point[1], point[2])
+                .chunks(3)
+                .map(|f| f[1])
+                .sum();
+            let rendered = sim.wave_height_at(point);
+            assert!(rendered.abs() > 1e-4);
+            assert!((heights - rendered).abs() < 1e-6, "{heights} vs {rendered}");
+        }
+
+        assert!(RippleSimulator::new()
+            .nearest_wavefront(0.0, 0.0, 0.0)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_displacement_axis_mask() {
+        let mut sim = RippleSimulator::new();
+        let normals = [[0.6, 0.8, 0.0], [0.0, 0.0, 1.0], [-0.48, 0.6, 0.64]];
+
+        // Defaults reproduce the plain normal
+        for n in normals {
+            assert_eq!(sim.displacement_direction(n[0], n[1], n[2]), n.to_vec());
+        }
+        assert_eq!(sim.get_displacement_uniforms(), vec![1.0, 1.0, 1.0, 0.0]);
+
+        sim.set_displacement_axis_mask(0.0, 1.0, 0.0);
+        for mode in 0..3 {
+            sim.set_displacement_space(mode);
+            for n in normals {
+                let d = sim.displacement_direction(n[0], n[1], n[2]);
+                assert_eq!((d[0], d[2]), (0.0, 0.0), "mode {mode}");
+            }
+        }
+
+        // World axis mode moves every vertex straight up; projected keeps the sign
+        sim.set_displacement_space(1);
+        assert_eq!(
+            sim.displacement_direction(0.0, 0.0, 1.0),
+            vec![0.0, 1.0, 0.0]
+        );
+        sim.set_displacement_space(2);
+        assert_eq!(
+            sim.displacement_direction(0.0, -0.5, 0.5),
+            vec![0.0, -1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_displacement_limit() {
+        let mut sim = RippleSimulator::new();
+        // Strongest waves the simulator holds, all centred on one point
+        for _ in 0..MAX_RIPPLES {
+            sim.add_ripple(0.0, 0.0, 0.0, 50.0, 2.0, 0.5, RippleType::Wave);
+        }
+        sim.update(0.05);
+        let positions: Vec<f32> = (0..40).flat_map(|i| [i as f32 * 0.02, 0.0, 0.0]).collect();
+        let normals = [0.0, 1.0, 0.0].repeat(40);
+
+        // Off by default: exactly the summed displacement, nothing limited
+        let raw = sim.evaluate_displacements(&positions, &normals).unwrap();
+        for (d, p) in raw.chunks(3).zip(positions.chunks(3)) {
+            assert_eq!(d, [0.0, sim.wave_height_at([p[0], p[1], p[2]]), 0.0]);
+        }
+        assert_eq!(sim.limited_vertex_count(), 0);
+        assert_eq!(sim.get_displacement_limit_uniforms(), vec![-1.0, 0.0]);
+        let peak = raw.iter().fold(0.0f32, |m, d| m.max(d.abs()));
+        assert!(peak > 1.0);
+
+        for softness in [0.0, 0.5, 1.0] {
+            sim.set_displacement_limit(0.5, softness);
+            let limited = sim.evaluate_displacements(&positions, &normals).unwrap();
+            assert!(sim.limited_vertex_count() > 0);
+            for (d, r) in limited.chunks(3).zip(raw.chunks(3)) {
+                // Never past the limit, same direction, small ones untouched
+                assert!(d[1].abs() <= 0.5, "softness {softness}: {d:?}");
+                assert!(d[1] * r[1] >= 0.0);
+                if r[1].abs() <= 0.5 * (1.0 - softness) {
+                    assert_eq!(d[1], r[1]);
+                }
+            }
+        }
+        let capped = sim.limit_displacement(3.0, 4.0, 0.0);
+        for (c, expected) in capped.iter().zip([0.3, 0.4, 0.0]) {
+            assert!((c - expected).abs() < 1e-6, "{capped:?}");
+        }
+
+        sim.set_displacement_limit(f32::INFINITY, 0.5);
+        assert_eq!(sim.limit_displacement(3.0, 4.0, 0.0), vec![3.0, 4.0, 0.0]);
+        assert!(sim
+            .evaluate_displacements(&positions, &normals[3..])
+            .is_err());
+    }
+
+    #[test]
+    fn test_evaluate_displacements_matches_shader() {
+        let mut sim = RippleSimulator::new();
+        sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave);
+        sim.add_ripple(1.0, 0.5, 0.0, 0.8, 2.0, 0.5, RippleType::Pull);
+        sim.update(0.2);
+        let positions = [0.4, 0.0, 0.0, -0.5, 0.3, 0.2, 2.5, 0.0, 1.0];
+        let normals = [0.0, 1.0, 0.0, 0.6, 0.8, 0.0, 0.0, 0.0, 1.0];
+        sim.set_camera(&[0.0, 0.0, 4.0], 1.0, 6.0);
+
+        // Shader terms: wave along the normal, pull towards the click at
+        // smoothstep(falloff, 0, dist) * 0.6 * strength, all scaled by the LOD
+        let pull = sim.ripples[1];
+        let displacements = sim.evaluate_displacements(&positions, &normals).unwrap();
+        for ((d, p), n) in displacements
+            .chunks(3)
+            .zip(positions.chunks(3))
+            .zip(normals.chunks(3))
+        {
+            let point = [p[0], p[1], p[2]];
+            let height = sim.wave_height_at(point);
+            let to_click = [0, 1, 2].map(|i| pull.position[i] - point[i]);
+            let weight =
+                smoothstep(PULL_RADIUS, 0.0, distance(pull.position, point)) * 0.6 * pull.amplitude;
+            let lod = sim.lod_weight(p[0], p[1], p[2]);
+            assert!(lod > 0.0 && lod < 1.0);
+            for i in 0..3 {
+                let expected = (n[i] * height + to_click[i] * weight) * lod;
+                assert!((d[i] - expected).abs() < 1e-6, "{d:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_camera_lod_weight() {
+        let mut sim = RippleSimulator::new();
+        assert_eq!(sim.lod_weight(1000.0, 0.0, 0.0), 1.0);
+
+        sim.set_camera(&[0.0, 0.0, 10.0], 5.0, 20.0);
+        assert_eq!(sim.lod_weight(0.0, 0.0, 8.0), 1.0);
+        assert_eq!(sim.lod_weight(0.0, 0.0, -10.0), 0.0);
+        assert_eq!(sim.lod_weight(0.0, 25.0, 10.0), 0.0);
+
+        // Transition band fades monotonically
+        let mut last = 1.0;
+        for step in 0..=40 {
+            let w = sim.lod_weight(0.0, 0.0, 10.0 - 5.0 - step as f32 * 0.4);
+            assert!(w <= last && (0.0..=1.0).contains(&w));
+            last = w;
+        }
+
+        // CPU evaluation: zero beyond far, fading through the band
+        sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave);
+        sim.add_ripple(0.0, 0.0, 1.0, 1.0, 2.0, 0.5, RippleType::Pull);
+        sim.update(0.2);
+        let far = [0.3, 0.0, -12.0];
+        assert_eq!(sim.evaluate_vertices(&far), vec![0.0, 0.0, f32::INFINITY]);
+        assert_eq!(
+            sim.evaluate_displacements(&far, &[0.0, 0.0, 1.0]).unwrap(),
+            vec![0.0; 3]
+        );
+        let near = [0.3, 0.0, 0.5];
+        sim.set_camera(&[], 0.0, 0.0);
+        let full = sim.evaluate_vertices(&near);
+        sim.set_camera(&[0.0, 0.0, 10.0], 5.0, 12.0);
+        let faded = sim.evaluate_vertices(&near);
+        let lod = sim.lod_weight(near[0], near[1], near[2]);
+        assert!(lod > 0.0 && lod < 1.0);
+        assert!((faded[0] - full[0] * lod).abs() < 1e-6);
+        assert!((faded[1] - full[1] * lod).abs() < 1e-6);
+
+        sim.set_camera(&[], 0.0, 0.0);
+        assert_eq!(sim.get_lod_uniforms()[4], -1.0);
+        assert_eq!(sim.evaluate_vertices(&near), full);
+    }
+
+    #[test]
+    fn test_haptic_envelope() {
+        let mut sim = RippleSimulator::new();
+        assert!(sim.haptic_envelope_for_ripple(0, 100.0).is_empty());
+
+        sim.add_ripple(0.0, 0.0, 0.0, 0.8, 1.0, 0.05, RippleType::Wave);
+        let envelope = sim.haptic_envelope_for_ripple(0, 100.0);
+        assert_eq!(envelope, sim.haptic_envelope_for_ripple(0, 100.0));
+        assert!((envelope[0] - 0.8).abs() < 1e-6);
+        assert!(envelope.windows(2).all(|w| w[1] < w[0]));
+        // Trimmed right where update() would deactivate the ripple
+        assert!(*envelope.last().unwrap() >= AMPLITUDE_THRESHOLD);
+        let next = envelope.last().unwrap() * 0.05f32.powf(0.01);
+        assert!(next < AMPLITUDE_THRESHOLD);
+    }
+
+    #[test]
+    fn test_vibrate_pattern() {
+        let envelope = [0.9, 0.9, 0.1, 0.1, 0.1, 0.8, 0.0, 0.0];
+        assert_eq!(
+            envelope_to_vibrate_pattern(&envelope, 100.0, 0.5),
+            vec![20, 30, 10]
+        );
+
+        let late_start = [0.0, 0.0, 1.0, 1.0];
+        assert_eq!(
+            envelope_to_vibrate_pattern(&late_start, 100.0, 0.5),
+            vec![0, 20, 20]
+        );
+
+        assert!(envelope_to_vibrate_pattern(&[0.1, 0.2], 100.0, 0.5).is_empty());
+        assert!(envelope_to_vibrate_pattern(&[], 100.0, 0.5).is_empty());
+    }
+
+    #[test]
+    fn test_sonifier_bounded() {
+        let mut sim = RippleSimulator::new();
+        let mut sonifier = RippleSonifier::new(48_000.0);
+        sonifier.set_probe(0.2, 0.0, 0.0, 0.0, 1.0, 0.0);
+
+        let mut block = [1.0f32; 512];
+        sonifier.render(&mut block, &sim, 1.0 / 60.0);
+        assert!(block.iter().all(|&s| s == 0.0));
+
+        sim.add_ripple(0.0, 0.0, 0.0, 50.0, 2.0, 0.5, RippleType::Wave);
+        sim.add_ripple(0.5, 0.0, 0.0, 50.0, 2.0, 0.5, RippleType::Pull);
+        let mut peak = 0.0f32;
+        let mut sum = 0.0f32;
+        for _ in 0..20 {
+            sonifier.render(&mut block, &sim, 1.0 / 60.0);
+            sim.update(block.len() as f32 / 48_000.0);
+            for &s in &block {
+                assert!(s.is_finite() && s.abs() <= 1.0);
+                peak = peak.max(s.abs());
+                sum += s;
+            }
+        }
+        assert!(peak > 0.1);
+        // DC blocked: the mean stays near zero
+        assert!((sum / (20.0 * 512.0)).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_blockers() {
+        let mut sim = RippleSimulator::new();
+        let logo = sim.add_blocker_sphere(2.0, 0.0, 0.0, 0.5);
+        let wall = sim
+            .add_blocker_box(&[0.0, 2.0, -1.0], &[0.2, 3.0, 1.0])
+            .unwrap();
+        assert!(sim.add_blocker_box(&[0.0, 2.0], &[0.2, 3.0, 1.0]).is_err());
+        assert!(sim.add_blocker_box(&[0.0, 2.0, -1.0], &[]).is_err());
+        sim.set_blocker_leakage(0.25);
+
+        // Cannot spawn inside a blocker
+        assert_eq!(
+            sim.add_ripple(2.1, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave),
+            -1
+        );
+        assert_eq!(
+            sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave),
+            0
+        );
+
+        // Behind the sphere: shadowed down to the leakage; beside it: clear
+        assert!((sim.blocker_attenuation(0, 3.0, 0.0, 0.0) - 0.25).abs() < 1e-6);
+        assert_eq!(sim.blocker_attenuation(0, 0.0, -1.0, 0.0), 1.0);
+        // Through the box
+        assert!((sim.blocker_attenuation(0, 0.1, 3.2, 0.0) - 0.25).abs() < 1e-6);
+        // Outside the influence radius nothing is tested
+        assert_eq!(sim.blocker_attenuation(0, 50.0, 0.0, 0.0), 1.0);
+
+        let fronts = sim.query_wavefront(3.0, 0.0, 0.0);
+        sim.set_blocker_leakage(1.0);
+        let unblocked = sim.query_wavefront(3.0, 0.0, 0.0)[1];
+        sim.set_blocker_leakage(0.25);
+        assert!(unblocked != 0.0);
+        assert!((fronts[1] - unblocked * 0.25).abs() < 1e-6);
+
+        let uniforms = sim.get_blocker_uniforms();
+        assert_eq!(uniforms.len(), 64);
+        assert_eq!(uniforms[..8], [0.0, 2.0, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0]);
+        assert_eq!(uniforms[8], 1.0);
+
+        assert!(sim.remove_blocker(logo));
+        assert!(!sim.remove_blocker(logo));
+        assert!(sim.remove_blocker(wall));
+        assert_eq!(sim.blocker_attenuation(0, 3.0, 0.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn test_surface_particle_bobs_and_settles() {
+        let mut sim = RippleSimulator::new();
+        sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave);
+
+        let mut particles = SurfaceParticles::new(1);
+        particles.set_rest_positions(&[0.3, 0.0, 0.0]).unwrap();
+        assert!(particles.set_rest_normals(&[0.0, 1.0]).is_err());
+
+        let dt = 1.0 / 60.0;
+        let mut max_bob = 0.0f32;
+        for _ in 0..180 {
+            sim.update(dt);
+            particles.update(&sim, dt);
+            let p = particles.positions();
+            max_bob = max_bob.max(p[1]);
+            // Never further than the clamp from the rest point
+            assert!(
+                distance([p[0], p[1], p[2]], [0.3, 0.0, 0.0])
+                    <= DEFAULT_PARTICLE_MAX_DISTANCE + 1e-6
+            );
+        }
+
+        assert!(max_bob > 0.005, "particle never bobbed up ({max_bob})");
+        let p = particles.positions();
+        assert!(
+            distance([p[0], p[1], p[2]], [0.3, 0.0, 0.0]) < 1e-3,
+            "did not settle: {p:?}"
+        );
+    }
+
+    #[test]
+    fn test_wave_dispersion_leading_edge() {
+        let mut sim = RippleSimulator::new();
+        assert!(sim.set_wave_components(&[1.0, 2.0], &[1.0], 0.5).is_err());
+        sim.set_wave_components(&[1.0, 2.0, 4.0], &[1.0, 0.6, 0.3], 0.5)
+            .unwrap();
+        sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.9, RippleType::Wave);
+        for _ in 0..60 {
+            sim.update(1.0 / 60.0);
+        }
+
+        // Fastest component (f = 4, speed 2 * 4^0.5) leads, slowest lags
+        let ripple = sim.ripples[0];
+        assert!((ripple.radius - 4.0).abs() < 1e-3);
+        assert!((ripple.component_radii[0] - 2.0).abs() < 1e-3);
+
+        // Nothing ahead of the leading front, signal right at it
+        let leading_edge = (0..2000)
+            .map(|i| i as f32 * 0.005)
+            .filter(|&d| sim.wave_height_at([d, 0.0, 0.0]).abs() > 1e-4)
+            .fold(0.0f32, f32::max);
+        assert!(
+            leading_edge > 4.0 && leading_edge < 4.0 + 3.0 * WAVEFRONT_WIDTH,
+            "leading edge at {leading_edge}"
+        );
+
+        // Layout 2 keeps the legacy prefix and appends the dispersion block
+        let legacy = sim.get_uniforms();
+        sim.set_uniform_layout(2).unwrap();
+        let v2 = sim.get_uniforms();
+        assert_eq!(&v2[..legacy.len()], &legacy[..]);
+        assert_eq!(
+            v2.len(),
+            legacy.len() + 4 + 4 * MAX_WAVE_COMPONENTS + 4 * MAX_RIPPLES
+        );
+        assert_eq!(v2[legacy.len() + 1], 3.0);
+        assert!(sim.set_uniform_layout(7).is_err());
+    }
+
+    #[test]
+    fn test_reduced_motion() {
+        let run = |level: u8| {
+            motion::set_reduced_motion(level).unwrap();
+            let mut sim = RippleSimulator::new();
+            sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave);
+            let mut particles = SurfaceParticles::new(1);
+            particles.set_rest_positions(&[0.3, 0.0, 0.0]).unwrap();
+
+            let mut frames = 0;
+            let mut max_bob = 0.0f32;
+            while sim.ripples[0].active {
+                sim.update(1.0 / 60.0);
+                particles.update(&sim, 1.0 / 60.0);
+                max_bob = max_bob.max(particles.positions()[1].abs());
+                if level == 2 {
+                    assert_eq!(sim.wave_height_at([0.3, 0.0, 0.0]), 0.0);
+                    assert_eq!(sim.get_uniforms()[4], 0.0);
+                }
+                frames += 1;
+            }
+            (frames, max_bob)
+        };
+
+        let (full_frames, full_bob) = run(0);
+        let (reduced_frames, reduced_bob) = run(1);
+        let (none_frames, none_bob) = run(2);
+        assert!(reduced_bob < full_bob && reduced_frames < full_frames);
+        // Displacement is exactly zero, but ripples still expire
+        assert_eq!(none_bob, 0.0);
+        assert_eq!(none_frames, full_frames);
+
+        // Back to full motion without residue
+        assert!(motion::set_reduced_motion(3).is_err());
+        assert_eq!(run(0), (full_frames, full_bob));
+        assert_eq!(motion::get_reduced_motion(), 0);
+    }
+
+    #[test]
+    fn test_remove_and_clear_ripples() {
+        let mut sim = RippleSimulator::new();
+        let add = |sim: &mut RippleSimulator, x: f32| {
+            sim.add_ripple(x, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave)
+        };
+        let mut handles: Vec<i32> = (0..MAX_RIPPLES).map(|i| add(&mut sim, i as f32)).collect();
+        assert_eq!(handles, (0..MAX_RIPPLES as i32).collect::<Vec<_>>());
+
+        // The freed slot is reused instead of evicting a live ripple
+        let middle = MAX_RIPPLES / 2;
+        assert!(sim.remove_ripple(middle));
+        assert!(!sim.ripples[middle].active);
+        assert!(!sim.remove_ripple(middle));
+        assert!(!sim.remove_ripple(MAX_RIPPLES));
+        handles[middle] = add(&mut sim, 10.0);
+        assert_eq!(handles[middle], middle as i32);
+        for (i, &handle) in handles.iter().enumerate() {
+            let ripple = sim.ripples[handle as usize];
+            assert!(ripple.active);
+            let x = if i == middle { 10.0 } else { i as f32 };
+            assert_eq!(ripple.position, [x, 0.0, 0.0]);
+        }
+
+        // Clearing keeps the simulator usable
+        sim.clear();
+        assert!(sim.get_uniforms().chunks(8).all(|r| r[7] == 0.0));
+        assert_eq!(add(&mut sim, 0.0), 0);
+    }
+
+    #[test]
+    fn test_stale_ripple_handles() {
+        let mut sim = RippleSimulator::new();
+        let add = |sim: &mut RippleSimulator, x: f32| {
+            sim.add_ripple_tracked(x, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave)
+        };
+        let handles: Vec<u32> = (0..MAX_RIPPLES)
+            .map(|i| {
+                sim.update(0.1);
+                add(&mut sim, i as f32)
+            })
+            .collect();
+        assert!(handles.iter().all(|&h| h != 0));
+
+        // Overflow: one slot is recycled, and the handle it had goes stale
+        let newest = add(&mut sim, 10.0);
+        let slot = sim.resolve_handle(newest).unwrap();
+        let stale = handles
+            .iter()
+            .position(|&h| h & ((1 << HANDLE_SLOT_BITS) - 1) == slot as u32)
+            .unwrap();
+        assert_ne!(handles[stale], newest);
+        assert!(!sim.update_ripple_position(handles[stale], 5.0, 5.0, 5.0));
+        assert!(!sim.remove_ripple_tracked(handles[stale]));
+        assert_eq!(sim.ripples[slot].position, [10.0, 0.0, 0.0]);
+        assert!(sim.ripples[slot].active);
+
+        // Live handles still work
+        for (i, &handle) in handles.iter().enumerate() {
+            if i != stale {
+                assert!(sim.update_ripple_position(handle, 0.0, 1.0, 0.0));
+            }
+        }
+        assert!(sim.update_ripple_position(newest, 0.0, 2.0, 0.0));
+        assert_eq!(sim.ripples[slot].position, [0.0, 2.0, 0.0]);
+
+        // Removed is stale too, even before the slot is refilled
+        assert!(sim.remove_ripple_tracked(newest));
+        assert!(!sim.update_ripple_position(newest, 0.0, 0.0, 0.0));
+        assert_eq!(sim.ripple_handle(slot), 0);
+        assert!(!sim.remove_ripple_tracked(0));
+    }
+
+    #[test]
+    fn test_update_is_frame_rate_independent() {
+        let run = |steps: usize, dispersive: bool| {
+            let mut sim = RippleSimulator::new();
+            if dispersive {
+                sim.set_wave_components(&[1.0, 2.0], &[1.0, 0.5], 0.5)
+                    .unwrap();
+            }
+            sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave);
+            sim.add_ripple(1.0, 0.0, 0.0, 1.0, 1.5, 0.3, RippleType::Pull);
+            for _ in 0..steps {
+                sim.update(1.0 / steps as f32);
+            }
+            let probes = [0.5, 0.0, 0.0, 1.2, 0.0, 0.4, 2.0, 0.0, 0.1];
+            [sim.get_uniforms(), sim.evaluate_vertices(&probes)].concat()
+        };
+
+        // One simulated second at 60 and 120 fps
+        for dispersive in [false, true] {
+            let (slow, fast) = (run(60, dispersive), run(120, dispersive));
+            for (a, b) in slow.iter().zip(&fast) {
+                assert!((a - b).abs() < 1e-3, "{slow:?} vs {fast:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_ripple_params() {
+        let probes = [0.3, 0.0, 0.0, 2.0, 0.0, 0.0, 3.0, 0.0, 0.0];
+        let run = |tuned: Option<(f32, f32, f32)>| {
+            let mut sim = RippleSimulator::new();
+            let [wave, pull] = match tuned {
+                None => [
+                    sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave),
+                    sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Pull),
+                ],
+                Some((width, falloff, lifetime)) => [RippleType::Wave, RippleType::Pull].map(|t| {
+                    sim.add_ripple_ex(0.0, 0.0, 0.0, 1.0, t, 2.0, width, 0.5, falloff, lifetime)
+                }),
+            };
+            assert!(wave >= 0 && pull >= 0);
+            sim.update(0.5);
+            (sim.evaluate_vertices(&probes), sim)
+        };
+
+        // Defaults reproduce the constants exactly
+        let (plain, sim) = run(None);
+        let (same, _) = run(Some((WAVEFRONT_WIDTH, PULL_RADIUS, WAVE_LIFETIME)));
+        assert_eq!(plain, same);
+        assert_eq!(
+            sim.get_ripple_param_uniforms()[..4],
+            [WAVEFRONT_WIDTH, PULL_RADIUS, WAVE_LIFETIME, 2.0]
+        );
+
+        // A tight pull stops short of far probes, a short life ends the wave
+        let (tight, mut sim) = run(Some((0.2, 1.0, 0.4)));
+        assert!(plain[VERTEX_EVAL_STRIDE + 1] > 0.0);
+        assert_eq!(tight[VERTEX_EVAL_STRIDE + 1], 0.0);
+        assert!(plain[0] != 0.0);
+        assert_eq!(tight[0], 0.0);
+        assert_eq!(sim.get_ripple_param_uniforms()[0], 0.2);
+
+        // Retuning after spawn, refused for empty slots and bad shapes
+        assert!(sim.set_ripple_params(0, 2.0, 2.0, 0.5, 1.0, 0.4));
+        assert_eq!(sim.get_ripple_param_uniforms()[..3], [2.0, 1.0, 0.4]);
+        assert!(!sim.set_ripple_params(0, 2.0, 0.0, 0.5, 1.0, 0.4));
+        assert!(!sim.set_ripple_params(0, 2.0, 1.0, 0.5, f32::NAN, 0.4));
+        sim.clear();
+        assert!(!sim.set_ripple_params(0, 2.0, 1.0, 0.5, 1.0, 0.4));
+
+        // Handle-checked variants refuse stale handles
+        let handle = sim.add_ripple_ex_tracked(
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+            RippleType::Pull,
+            2.0,
+            0.5,
+            0.5,
+            1.0,
+            0.8,
+        );
+        assert_ne!(handle, 0);
+        assert!(sim.set_ripple_params_tracked(handle, 2.0, 0.5, 0.5, 2.0, 0.8));
+        assert_eq!(sim.ripples[0].falloff, 2.0);
+        assert!(sim.remove_ripple_tracked(handle));
+        assert!(!sim.set_ripple_params_tracked(handle, 2.0, 0.5, 0.5, 3.0, 0.8));
+        assert_eq!(
+            sim.add_ripple_ex_tracked(
+                0.0,
+                0.0,
+                0.0,
+                1.0,
+                RippleType::Wave,
+                2.0,
+                0.0,
+                0.5,
+                1.0,
+                0.8
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn test_sonifier_uses_ripple_params() {
+        // Probe 2 units out: inside the default pull reach, outside a tight one
+        let render = |falloff: f32| {
+            let mut sim = RippleSimulator::new();
+            sim.add_ripple_ex(
+                0.0,
+                0.0,
+                0.0,
+                5.0,
+                RippleType::Pull,
+                2.0,
+                0.5,
+                0.5,
+                falloff,
+                0.8,
+            );
+            let mut sonifier = RippleSonifier::new(48_000.0);
+            sonifier.set_probe(2.0, 0.0, 0.0, 0.0, 1.0, 0.0);
+            let mut block = [0.0f32; 512];
+            sonifier.render(&mut block, &sim, 1.0 / 60.0);
+            block.iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+        };
+        assert!(render(PULL_RADIUS) > 0.01);
+        assert_eq!(render(1.0), 0.0);
+
+        // A wave voice stops sounding at its lifetime
+        let mut sim = RippleSimulator::new();
+        sim.add_ripple_ex(
+            0.0,
+            0.0,
+            0.0,
+            5.0,
+            RippleType::Wave,
+            2.0,
+            0.5,
+            0.9,
+            3.5,
+            0.01,
+        );
+        let mut sonifier = RippleSonifier::new(48_000.0);
+        sonifier.set_probe(0.2, 0.0, 0.0, 0.0, 1.0, 0.0);
+        let mut block = [0.0f32; 9600];
+        sonifier.render(&mut block, &sim, 1.0);
+        assert!(block[..480].iter().any(|s| s.abs() > 0.0));
+        // Past the DC blocker's settling time only silence is left
+        assert!(block[4800..].iter().all(|s| s.abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_wave_speed_and_width() {
+        // Shader defaults reproduce the shader's formula exactly
+        for (dist, age) in [(0.0f32, 0.1f32), (0.7, 0.3), (2.5, 0.75)] {
+            let front = age * 2.0;
+            let wave = (dist * 8.0 - front * 10.0).sin() * (-dist * 2.0).exp() * (-age * 3.0).exp();
+            let ripple2 =
+                (dist - front * 8.0).sin() * (-dist * 3.0).exp() * (-age * 4.0).exp() * 0.1;
+            assert_eq!(
+                wave_kernel(dist, age, 2.0, WAVEFRONT_WIDTH, 1.0),
+                wave * 0.15 + ripple2 * 0.01
+            );
+        }
+
+        // Rendered height and queried front height both follow speed and width
+        let sample = |speed: f32, width: f32| {
+            let mut sim = RippleSimulator::new();
+            sim.add_ripple_ex(
+                0.0,
+                0.0,
+                0.0,
+                1.0,
+                RippleType::Wave,
+                speed,
+                width,
+                0.5,
+                PULL_RADIUS,
+                WAVE_LIFETIME,
+            );
+            sim.update(0.3);
+            let height = sim.wave_height_at([0.7, 0.0, 0.0]);
+            assert_eq!(sim.query_wavefront(0.7, 0.0, 0.0)[1], height);
+            height
+        };
+        let base = sample(2.0, WAVEFRONT_WIDTH);
+        let fast = sample(4.0, WAVEFRONT_WIDTH);
+        let wide = sample(2.0, 1.5);
+        assert!((base - fast).abs() > 1e-3, "{base} vs {fast}");
+        assert!((base - wide).abs() > 1e-3, "{base} vs {wide}");
+    }
+
+    #[test]
+    fn test_energy_budget() {
+        let mut sim = RippleSimulator::new();
+        sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave);
+        assert_eq!(sim.available_energy(), f32::INFINITY);
+
+        sim.set_energy_budget(2.5, 1.0);
+        let mut spawned = Vec::new();
+        for _ in 0..6 {
+            let slot = sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave);
+            spawned.push(sim.ripples[slot as usize].amplitude);
+            sim.update(0.1);
+        }
+        // Two full spawns, a partial one, then only what regenerates per frame
+        assert_eq!(&spawned[..2], &[1.0, 1.0]);
+        assert!(spawned.windows(2).all(|w| w[1] <= w[0] + 1e-6));
+        assert!(spawned[5] < 0.2);
+
+        // Refused outright when rejecting
+        sim.set_energy_reject(true);
+        assert_eq!(
+            sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave),
+            -1
+        );
+
+        // Full strength once the budget has regenerated
+        for _ in 0..30 {
+            sim.update(0.1);
+        }
+        assert_eq!(sim.available_energy(), 2.5);
+        let slot = sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave);
+        assert_eq!(sim.ripples[slot as usize].amplitude, 1.0);
+    }
+
+    #[test]
+    fn test_phase_speed_map() {
+        let mut sim = RippleSimulator::new();
+        sim.add_ripple(0.0, 0.0, 0.0, 1.0, 1.0, 0.9, RippleType::Wave);
+        let positions = [
+            [2.0, 0.0, 0.0],
+            [0.0, 2.0, 0.0],
+            [0.0, 0.0, 2.0],
+            [-2.0, 0.0, 0.0],
+        ]
+        .concat();
+
+        // Default map evaluates exactly like the shader-matching CPU wave
+        sim.update(0.1);
+        let eval = sim.evaluate_vertices(&positions);
+        assert_eq!(eval[0], sim.wave_height_at([2.0, 0.0, 0.0]));
+
+        // Second half of the vertices is twice as stiff
+        sim.set_phase_speed_map(&[1.0, 1.0, 2.0, 2.0]);
+        let mut arrival = [None; 4];
+        let dt = 0.01;
+        for step in 1..=300 {
+            sim.update(dt);
+            let eval = sim.evaluate_vertices(&positions);
+            for (v, t) in arrival.iter_mut().enumerate() {
+                if t.is_none() && eval[v * VERTEX_EVAL_STRIDE + 2] <= 0.0 {
+                    *t = Some(0.1 + step as f32 * dt);
+                }
+            }
+        }
+        let [slow, _, fast, _] = arrival.map(|t| t.expect("front never arrived"));
+        assert_eq!(arrival[0], arrival[1]);
+        assert_eq!(arrival[2], arrival[3]);
+        assert!((slow - 2.0).abs() < 0.02, "{slow}");
+        assert!((fast - 1.0).abs() < 0.02, "{fast}");
+
+        // Stiff vertices feel a pull over a shorter reach
+        let mut sim = RippleSimulator::new();
+        sim.add_ripple(0.0, 0.0, 0.0, 1.0, 0.0, 0.9, RippleType::Pull);
+        sim.set_phase_speed_map(&[1.0, 2.0]);
+        let eval = sim.evaluate_vertices(&[2.0, 0.0, 0.0, 2.0, 0.0, 0.0]);
+        assert!(eval[1] > 0.0);
+        assert_eq!(eval[VERTEX_EVAL_STRIDE + 1], 0.0);
+    }
+
+    #[test]
+    fn test_sample_map_to_vertices() {
+        // 2x2 RGBA: top row black/white, bottom row white/black (red channel)
+        let mut texture = vec![0u8; 16];
+        texture[4] = 255;
+        texture[8] = 255;
+        let uvs = [0.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.5, 0.5];
+        let values = sample_map_to_vertices(&texture, 2, 2, &uvs).unwrap();
+        assert_eq!(values[..3], [0.0, 1.0, 1.0]);
+        assert!((values[3] - 0.5).abs() < 1e-6);
+        assert!(sample_map_to_vertices(&texture, 3, 2, &uvs).is_err());
     }
 }
@@ -0,0 +1,556 @@
+use wasm_bindgen::prelude::*;
+
+use crate::raycast::BVHRaycaster;
+use crate::registry::{self, Registered};
+use crate::ripple_physics::RippleSimulator;
+use crate::texture_gen::TextureGenerator;
+
+/// Weight of the newest measurement when a job's cost estimate comes down
+/// (overruns raise the estimate to the measured cost immediately)
+const COST_SMOOTHING: f64 = 0.25;
+/// Safety cap on steps per run() in case the clock stops advancing
+const MAX_STEPS_PER_RUN: u32 = 10_000;
+/// Rows generated per scheduled TextureGenerator step
+const SCHEDULED_TEXTURE_ROWS: u32 = 8;
+
+/// A unit of per-frame work that can be split into small steps
+pub(crate) trait IncrementalWork {
+    /// Do one slice of work; returns true when there is nothing left to do
+    /// (for this frame, for recurring jobs)
+    fn step(&mut self) -> bool;
+}
+
+/// Crate-side job on a registered object. The object stays in the Registry,
+/// so its results remain reachable; a stale handle finishes the job.
+struct RegistryWork<T, F> {
+    handle: u32,
+    step: F,
+    target: std::marker::PhantomData<T>,
+}
+
+impl<T: Registered, F: FnMut(&mut T) -> bool> IncrementalWork for RegistryWork<T, F> {
+    fn step(&mut self) -> bool {
+        registry::with_mut(self.handle, &mut self.step).unwrap_or(true)
+    }
+}
+
+/// JS callback job: called with no arguments, a truthy return means done.
+/// A throwing callback counts as done so it cannot stall the frame.
+struct JsWork(js_sys::Function);
+
+impl IncrementalWork for JsWork {
+    fn step(&mut self) -> bool {
+        self.0
+            .call0(&JsValue::NULL)
+            .map_or(true, |done| done.is_truthy())
+    }
+}
+
+struct Job {
+    id: u32,
+    priority: i32,
+    /// Running estimate of one step's cost (ms)
+    estimate_ms: f64,
+    /// Recurring jobs restart every frame; one-shot jobs are dropped when done
+    recurring: bool,
+    done_this_frame: bool,
+    depends_on: Vec<u32>,
+    work: Box<dyn IncrementalWork>,
+}
+
+/// Cooperative per-frame scheduler for WASM-side work
+///
+/// Jobs run highest priority first (registration order breaks ties), one step
+/// at a time, while the step's estimated cost still fits in the frame budget,
+/// so the budget holds as long as the estimates do.
+/// Unfinished jobs carry over to the next run(). A job only runs once all of
+/// its dependencies have finished in the current frame (or no longer exist).
+/// Besides JS callbacks (add_job), registered crate objects can be stepped
+/// directly by handle: add_ripple_step, add_texture_job, add_refit, add_compact.
+#[wasm_bindgen]
+pub struct FrameScheduler {
+    jobs: Vec<Job>,
+    next_id: u32,
+    /// [elapsed_ms, steps, jobs still waiting] from the last run()
+    last_run: [f64; 3],
+}
+
+impl Default for FrameScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl FrameScheduler {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            jobs: Vec::new(),
+            next_id: 0,
+            last_run: [0.0; 3],
+        }
+    }
+
+    /// Register a JS step callback, returns its handle
+    ///
+    /// # Arguments
+    /// * `step` - Does one slice of work; return true when finished
+    /// * `priority` - Higher runs first
+    /// * `estimated_cost_ms` - Initial per-step cost guess, refined from measurements
+    /// * `recurring` - Run every frame (e.g. ripple step) instead of once to completion
+    #[wasm_bindgen]
+    pub fn add_job(
+        &mut self,
+        step: js_sys::Function,
+        priority: i32,
+        estimated_cost_ms: f64,
+        recurring: bool,
+    ) -> u32 {
+        self.add_work(
+            Box::new(JsWork(step)),
+            priority,
+            estimated_cost_ms,
+            recurring,
+        )
+    }
+
+    /// Step a registered RippleSimulator by a fixed delta_time every frame
+    ///
+    /// # Arguments
+    /// * `simulator` - Registry handle of the RippleSimulator
+    ///
+    /// # Returns
+    /// The job handle (recurring), or an error for a stale handle
+    #[wasm_bindgen]
+    pub fn add_ripple_step(
+        &mut self,
+        simulator: u32,
+        delta_time: f32,
+        priority: i32,
+        estimated_cost_ms: f64,
+    ) -> Result<u32, String> {
+        self.add_registered(
+            simulator,
+            move |sim: &mut RippleSimulator| {
+                sim.update(delta_time);
+                true
+            },
+            priority,
+            estimated_cost_ms,
+            true,
+        )
+    }
+
+    /// Run the chunked job of a registered TextureGenerator (see
+    /// TextureGenerator::begin) to completion, a few rows per step
+    ///
+    /// The generator stays registered; read its output once is_pending turns
+    /// false, e.g. after Registry.take_texture_generator.
+    ///
+    /// # Returns
+    /// The job handle (one-shot), or an error for a stale handle
+    #[wasm_bindgen]
+    pub fn add_texture_job(
+        &mut self,
+        generator: u32,
+        priority: i32,
+        estimated_cost_ms: f64,
+    ) -> Result<u32, String> {
+        self.add_registered(
+            generator,
+            |generator: &mut TextureGenerator| {
+                generator.generate_chunk(SCHEDULED_TEXTURE_ROWS) >= 1.0
+            },
+            priority,
+            estimated_cost_ms,
+            false,
+        )
+    }
+
+    /// Refit a registered BVHRaycaster to new positions when the frame has
+    /// room (see BVHRaycaster::refit)
+    ///
+    /// A refit the raycaster rejects (wrong vertex count, disposed) is
+    /// dropped.
+    ///
+    /// # Returns
+    /// The job handle (one-shot), or an error for a stale handle
+    #[wasm_bindgen]
+    pub fn add_refit(
+        &mut self,
+        raycaster: u32,
+        positions: Vec<f32>,
+        priority: i32,
+        estimated_cost_ms: f64,
+    ) -> Result<u32, String> {
+        self.add_registered(
+            raycaster,
+            move |raycaster: &mut BVHRaycaster| {
+                let _ = raycaster.refit(&positions);
+                true
+            },
+            priority,
+            estimated_cost_ms,
+            false,
+        )
+    }
+
+    /// Merge a registered BVHRaycaster's appended triangles into its tree
+    /// when the frame has room (see BVHRaycaster::compact)
+    ///
+    /// # Returns
+    /// The job handle (one-shot), or an error for a stale handle
+    #[wasm_bindgen]
+    pub fn add_compact(
+        &mut self,
+        raycaster: u32,
+        priority: i32,
+        estimated_cost_ms: f64,
+    ) -> Result<u32, String> {
+        self.add_registered(
+            raycaster,
+            |raycaster: &mut BVHRaycaster| {
+                raycaster.compact();
+                true
+            },
+            priority,
+            estimated_cost_ms,
+            false,
+        )
+    }
+
+    /// Make `job` wait each frame until `depends_on` has finished
+    ///
+    /// Rejects unknown handles and dependency cycles.
+    #[wasm_bindgen]
+    pub fn add_dependency(&mut self, job: u32, depends_on: u32) -> Result<(), String> {
+        if self.find(depends_on).is_none() {
+            return Err(format!("unknown job {depends_on}"));
+        }
+        if job == depends_on || self.reaches(depends_on, job) {
+            return Err(format!(
+                "job {job} depending on {depends_on} creates a cycle"
+            ));
+        }
+        let idx = self.find(job).ok_or_else(|| format!("unknown job {job}"))?;
+        if !self.jobs[idx].depends_on.contains(&depends_on) {
+            self.jobs[idx].depends_on.push(depends_on);
+        }
+        Ok(())
+    }
+
+    /// Unregister a job; jobs depending on it stop waiting for it
+    #[wasm_bindgen]
+    pub fn remove_job(&mut self, job: u32) -> bool {
+        let Some(idx) = self.find(job) else {
+            return false;
+        };
+        self.jobs.remove(idx);
+        true
+    }
+
+    /// True while a job is registered (one-shot jobs unregister when done)
+    #[wasm_bindgen]
+    pub fn is_pending(&self, job: u32) -> bool {
+        self.find(job).is_some()
+    }
+
+    /// Current per-step cost estimate (ms) of a job
+    #[wasm_bindgen]
+    pub fn estimated_cost(&self, job: u32) -> Option<f64> {
+        self.find(job).map(|idx| self.jobs[idx].estimate_ms)
+    }
+
+    #[wasm_bindgen]
+    pub fn job_count(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// Run jobs until the frame budget is used up
+    ///
+    /// # Arguments
+    /// * `budget_ms` - Time available this frame
+    /// * `now` - Clock callback returning milliseconds (e.g. `() => performance.now()`)
+    ///
+    /// A job whose estimate alone exceeds the budget still runs one step when
+    /// it is the first thing picked, so it cannot starve.
+    ///
+    /// # Returns
+    /// Number of steps executed
+    #[wasm_bindgen]
+    pub fn run(&mut self, budget_ms: f64, now: &js_sys::Function) -> u32 {
+        self.run_with_clock(budget_ms, || {
+            now.call0(&JsValue::NULL)
+                .ok()
+                .and_then(|t| t.as_f64())
+                .unwrap_or(0.0)
+        })
+    }
+
+    /// Last run(): [elapsed_ms, steps, jobs still waiting to finish this frame]
+    #[wasm_bindgen]
+    pub fn get_last_run_stats(&self) -> Vec<f64> {
+        self.last_run.to_vec()
+    }
+}
+
+impl FrameScheduler {
+    pub(crate) fn add_work(
+        &mut self,
+        work: Box<dyn IncrementalWork>,
+        priority: i32,
+        estimated_cost_ms: f64,
+        recurring: bool,
+    ) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.jobs.push(Job {
+            id,
+            priority,
+            estimate_ms: estimated_cost_ms.max(0.0),
+            recurring,
+            done_this_frame: false,
+            depends_on: Vec::new(),
+            work,
+        });
+        id
+    }
+
+    /// add_work for a step on a registered object, checking the handle first
+    fn add_registered<T: Registered>(
+        &mut self,
+        handle: u32,
+        step: impl FnMut(&mut T) -> bool + 'static,
+        priority: i32,
+        estimated_cost_ms: f64,
+        recurring: bool,
+    ) -> Result<u32, String> {
+        registry::with(handle, |_: &T| ())?;
+        let work = RegistryWork {
+            handle,
+            step,
+            target: std::marker::PhantomData,
+        };
+        Ok(self.add_work(Box::new(work), priority, estimated_cost_ms, recurring))
+    }
+
+    /// run() against any millisecond clock
+    pub(crate) fn run_with_clock(&mut self, budget_ms: f64, mut now: impl FnMut() -> f64) -> u32 {
+        // New frame: recurring jobs that finished last frame are due again
+        for job in &mut self.jobs {
+            job.done_this_frame = false;
+        }
+
+        let start = now();
+        let mut steps = 0u32;
+        while steps < MAX_STEPS_PER_RUN {
+            let remaining = budget_ms - (now() - start);
+            let Some(idx) = self.next_job(remaining, budget_ms, steps == 0) else {
+                break;
+            };
+
+            let t0 = now();
+            let done = self.jobs[idx].work.step();
+            let cost = (now() - t0).max(0.0);
+            steps += 1;
+
+            let job = &mut self.jobs[idx];
+            job.estimate_ms = if cost > job.estimate_ms {
+                cost
+            } else {
+                job.estimate_ms + (cost - job.estimate_ms) * COST_SMOOTHING
+            };
+            if done {
+                if job.recurring {
+                    job.done_this_frame = true;
+                } else {
+                    self.jobs.remove(idx);
+                }
+            }
+        }
+
+        let waiting = self.jobs.iter().filter(|j| !j.done_this_frame).count();
+        self.last_run = [now() - start, steps as f64, waiting as f64];
+        steps
+    }
+
+    fn find(&self, id: u32) -> Option<usize> {
+        self.jobs.iter().position(|j| j.id == id)
+    }
+
+    /// Highest-priority ready job whose estimate fits in `remaining`
+    fn next_job(&self, remaining: f64, budget: f64, first: bool) -> Option<usize> {
+        self.jobs
+            .iter()
+            .enumerate()
+            .filter(|(_, j)| !j.done_this_frame && self.dependencies_done(j))
+            .filter(|(_, j)| j.estimate_ms <= remaining || (first && j.estimate_ms > budget))
+            .max_by_key(|(_, j)| (j.priority, std::cmp::Reverse(j.id)))
+            .map(|(idx, _)| idx)
+    }
+
+    fn dependencies_done(&self, job: &Job) -> bool {
+        job.depends_on.iter().all(|&dep| {
+            self.find(dep)
+                .is_none_or(|idx| self.jobs[idx].done_this_frame)
+        })
+    }
+
+    /// True if `from` (transitively) depends on `to`
+    fn reaches(&self, from: u32, to: u32) -> bool {
+        let mut stack = vec![from];
+        let mut seen = Vec::new();
+        while let Some(id) = stack.pop() {
+            if id == to {
+                return true;
+            }
+            if seen.contains(&id) {
+                continue;
+            }
+            seen.push(id);
+            if let Some(idx) = self.find(id) {
+                stack.extend(&self.jobs[idx].depends_on);
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    /// Fake job: each step advances the shared clock by `cost` ms
+    struct Timed<W> {
+        name: &'static str,
+        cost: f64,
+        clock: Rc<Cell<f64>>,
+        log: Rc<RefCell<Vec<&'static str>>>,
+        work: W,
+    }
+
+    impl<W: FnMut() -> bool> IncrementalWork for Timed<W> {
+        fn step(&mut self) -> bool {
+            self.clock.set(self.clock.get() + self.cost);
+            self.log.borrow_mut().push(self.name);
+            (self.work)()
+        }
+    }
+
+    #[test]
+    fn test_scheduler_budget_and_dependencies() {
+        let clock = Rc::new(Cell::new(0.0));
+        let log = Rc::new(RefCell::new(Vec::new()));
+        type Work = Box<dyn FnMut() -> bool>;
+        let timed = |name: &'static str, cost: f64, work: Work| {
+            Box::new(Timed {
+                name,
+                cost,
+                clock: clock.clone(),
+                log: log.clone(),
+                work,
+            })
+        };
+        let chunks = |n: u32| -> Work {
+            let mut left = n;
+            Box::new(move || {
+                left -= 1;
+                left == 0
+            })
+        };
+
+        let mut scheduler = FrameScheduler::new();
+        let refit = scheduler.add_work(timed("refit", 0.5, Box::new(|| true)), 5, 0.5, true);
+        let sim = scheduler.add_work(timed("sim", 1.0, Box::new(|| true)), 10, 1.0, true);
+        let texture = scheduler.add_work(timed("texture", 0.8, chunks(40)), 1, 0.8, false);
+        // Deliberately overestimated; the measured history corrects it
+        let bake = scheduler.add_work(timed("bake", 1.2, chunks(30)), 0, 3.0, false);
+        scheduler.add_dependency(refit, sim).unwrap();
+        assert!(scheduler.add_dependency(sim, refit).is_err());
+
+        let budget = 8.0;
+        let mut frames = 0;
+        while scheduler.is_pending(texture) || scheduler.is_pending(bake) {
+            log.borrow_mut().clear();
+            scheduler.run_with_clock(budget, || clock.get());
+            frames += 1;
+
+            let stats = scheduler.get_last_run_stats();
+            assert!(stats[0] <= budget, "frame {frames} took {} ms", stats[0]);
+            // Refit runs every frame, after the sim step
+            let log = log.borrow();
+            let sim_at = log.iter().position(|&n| n == "sim").unwrap();
+            let refit_at = log.iter().position(|&n| n == "refit").unwrap();
+            assert!(sim_at < refit_at);
+            assert!(frames < 20, "jobs did not converge");
+        }
+
+        // 40 * 0.8 + 30 * 1.2 ms of chunked work at ~6.5 ms per frame
+        assert!(frames >= 10);
+        assert!((scheduler.estimated_cost(sim).unwrap() - 1.0).abs() < 1e-9);
+        assert_eq!(scheduler.estimated_cost(bake), None);
+        assert_eq!(scheduler.job_count(), 2);
+    }
+
+    #[test]
+    fn test_scheduler_runs_registered_jobs() {
+        use crate::registry::Registry;
+        use crate::ripple_physics::RippleType;
+
+        let mut expected = TextureGenerator::new(7);
+        expected.begin(0, &[16.0]).unwrap();
+        expected.finish();
+        let mut generator = TextureGenerator::new(7);
+        generator.begin(0, &[16.0]).unwrap();
+        let generator = generator.register_self();
+
+        let mut sim = RippleSimulator::new();
+        sim.add_ripple(0.0, 0.0, 0.0, 1.0, 2.0, 0.5, RippleType::Wave);
+        let sim = sim.register_self();
+
+        let positions = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let raycaster = BVHRaycaster::from_geometry(&positions, &[0, 1, 2]).register_self();
+        let moved = positions.map(|p| p * 2.0).to_vec();
+        let identity = glam::Mat4::IDENTITY.to_cols_array();
+
+        let mut scheduler = FrameScheduler::new();
+        let texture = scheduler.add_texture_job(generator, 0, 0.0).unwrap();
+        let step = scheduler.add_ripple_step(sim, 0.25, 10, 0.0).unwrap();
+        let refit = scheduler.add_refit(raycaster, moved, 5, 0.0).unwrap();
+        scheduler.add_dependency(refit, step).unwrap();
+        // Handles are typed: a simulator is not a raycaster
+        assert!(scheduler.add_compact(sim, 0, 0.0).is_err());
+
+        let mut ticks = 0.0;
+        for _ in 0..2 {
+            scheduler.run_with_clock(1.0, || {
+                ticks += 0.001;
+                ticks
+            });
+        }
+        assert!(!scheduler.is_pending(texture));
+        assert!(!scheduler.is_pending(refit));
+        assert!(scheduler.is_pending(step));
+
+        // Results stay with the registered objects
+        let generator = Registry::take_texture_generator(generator).unwrap();
+        assert_eq!(generator.output(), expected.output());
+        assert_eq!(Registry::ripple_uniforms(sim).unwrap()[5], 0.5);
+        let raycaster = Registry::take_raycaster(raycaster).unwrap();
+        let hit = raycaster
+            .intersect(&[0.5, 0.5, 1.0], &[0.0, 0.0, -1.0], &identity)
+            .unwrap();
+        assert!(hit[0] > 0.4 && hit[1] > 0.4);
+        assert!(raycaster
+            .intersect(&[1.5, 0.2, 1.0], &[0.0, 0.0, -1.0], &identity)
+            .is_some());
+
+        // A released target ends its job instead of failing every frame
+        assert!(Registry::release(sim));
+        scheduler.run_with_clock(1.0, || 0.0);
+        assert_eq!(scheduler.get_last_run_stats()[2], 0.0);
+    }
+}
@@ -0,0 +1,329 @@
+use wasm_bindgen::prelude::*;
+
+use crate::texture_gen::TextureGenerator;
+
+/// Alpha values at or above this count as "inside" the glyph
+const INSIDE_THRESHOLD: u8 = 128;
+
+/// Squared distance used as "infinitely far" by the distance transform
+const EDT_INF: f32 = 1e20;
+
+/// Packed signed distance field atlas built from glyph bitmaps
+///
+/// The atlas is single channel (one byte per texel, upload as R8/Alpha).
+/// 0.5 (byte 127/128) is the glyph edge, higher values are inside.
+#[wasm_bindgen]
+pub struct SdfAtlas {
+    size: u32,
+    padding: u32,
+    data: Vec<u8>,
+    /// Per glyph: [u0, v0, u1, v1] in atlas UV space (0..1), padding included
+    rects: Vec<f32>,
+    /// Indices of glyphs that did not fit or had invalid input
+    missing: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl SdfAtlas {
+    /// Atlas width/height in texels
+    #[wasm_bindgen]
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Padding (in texels) around each glyph cell
+    #[wasm_bindgen]
+    pub fn padding(&self) -> u32 {
+        self.padding
+    }
+
+    /// Atlas texels, size*size bytes
+    #[wasm_bindgen]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    /// Get pointer to atlas texels (size*size bytes)
+    #[wasm_bindgen]
+    pub fn data_ptr(&self) -> *const u8 {
+        self.data.as_ptr()
+    }
+
+    /// Per-glyph UV rectangles: [u0, v0, u1, v1] per glyph, in input order.
+    /// Glyphs listed by missing() have an all-zero rectangle.
+    #[wasm_bindgen]
+    pub fn rects(&self) -> Vec<f32> {
+        self.rects.clone()
+    }
+
+    /// Indices of glyphs that could not be packed
+    #[wasm_bindgen]
+    pub fn missing(&self) -> Vec<u32> {
+        self.missing.clone()
+    }
+}
+
+#[wasm_bindgen]
+impl TextureGenerator {
+    /// Build a packed SDF atlas from tightly packed alpha bitmaps
+    ///
+    /// # Arguments
+    /// * `glyph_bitmaps` - All glyph bitmaps back to back, one byte per texel
+    /// * `glyph_widths` / `glyph_heights` - Bitmap size of each glyph
+    /// * `count` - Number of glyphs
+    /// * `atlas_size` - Atlas width/height in texels
+    /// * `spread` - Distance (texels) mapped to the full 0..1 range; also the cell padding
+    #[wasm_bindgen]
+    pub fn build_sdf_atlas(
+        &self,
+        glyph_bitmaps: &[u8],
+        glyph_widths: &[u32],
+        glyph_heights: &[u32],
+        count: u32,
+        atlas_size: u32,
+        spread: f32,
+    ) -> SdfAtlas {
+        let count = count as usize;
+        let spread = spread.max(1.0);
+        let padding = spread.ceil() as u32;
+        let atlas = atlas_size as usize;
+
+        let mut atlas_data = vec![0u8; atlas * atlas];
+        let mut rects = vec![0.0f32; count * 4];
+        let mut missing = Vec::new();
+
+        // Locate each glyph in the input buffer, rejecting malformed entries
+        let mut offsets = Vec::with_capacity(count);
+        let mut offset = 0usize;
+        for i in 0..count {
+            let (Some(&w), Some(&h)) = (glyph_widths.get(i), glyph_heights.get(i)) else {
+                offsets.push(None);
+                continue;
+            };
+            let len = (w * h) as usize;
+            if offset + len > glyph_bitmaps.len() {
+                offsets.push(None);
+            } else {
+                offsets.push(Some((offset, w, h)));
+            }
+            offset += len;
+        }
+
+        // Shelf packing, tallest cells first for tighter shelves
+        let mut order: Vec<usize> = (0..count).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(glyph_heights.get(i).copied().unwrap_or(0)));
+
+        let mut shelf_x = 0u32;
+        let mut shelf_y = 0u32;
+        let mut shelf_height = 0u32;
+
+        for i in order {
+            let Some((start, w, h)) = offsets[i] else {
+                missing.push(i as u32);
+                continue;
+            };
+            let cell_w = w + padding * 2;
+            let cell_h = h + padding * 2;
+
+            if shelf_x + cell_w > atlas_size {
+                shelf_y += shelf_height;
+                shelf_x = 0;
+                shelf_height = 0;
+            }
+            if cell_w > atlas_size || shelf_y + cell_h > atlas_size {
+                missing.push(i as u32);
+                continue;
+            }
+
+            let bitmap = &glyph_bitmaps[start..start + (w * h) as usize];
+            let cell = Self::glyph_sdf(bitmap, w as usize, h as usize, padding as usize, spread);
+            for row in 0..cell_h as usize {
+                let dst = (shelf_y as usize + row) * atlas + shelf_x as usize;
+                let src = row * cell_w as usize;
+                atlas_data[dst..dst + cell_w as usize]
+                    .copy_from_slice(&cell[src..src + cell_w as usize]);
+            }
+
+            let inv = 1.0 / atlas_size as f32;
+            rects[i * 4] = shelf_x as f32 * inv;
+            rects[i * 4 + 1] = shelf_y as f32 * inv;
+            rects[i * 4 + 2] = (shelf_x + cell_w) as f32 * inv;
+            rects[i * 4 + 3] = (shelf_y + cell_h) as f32 * inv;
+
+            shelf_x += cell_w;
+            shelf_height = shelf_height.max(cell_h);
+        }
+
+        missing.sort_unstable();
+
+        SdfAtlas {
+            size: atlas_size,
+            padding,
+            data: atlas_data,
+            rects,
+            missing,
+        }
+    }
+}
+
+impl TextureGenerator {
+    /// Compute the encoded SDF of one glyph into a padded cell
+    fn glyph_sdf(bitmap: &[u8], w: usize, h: usize, padding: usize, spread: f32) -> Vec<u8> {
+        let cell_w = w + padding * 2;
+        let cell_h = h + padding * 2;
+        let inside = |x: usize, y: usize| -> bool {
+            if x < padding || y < padding || x >= padding + w || y >= padding + h {
+                return false;
+            }
+            bitmap[(y - padding) * w + (x - padding)] >= INSIDE_THRESHOLD
+        };
+
+        // Squared distance to the nearest inside / outside texel
+        let mut to_inside = vec![0.0f32; cell_w * cell_h];
+        let mut to_outside = vec![0.0f32; cell_w * cell_h];
+        for y in 0..cell_h {
+            for x in 0..cell_w {
+                let i = y * cell_w + x;
+                if inside(x, y) {
+                    to_outside[i] = EDT_INF;
+                } else {
+                    to_inside[i] = EDT_INF;
+                }
+            }
+        }
+        edt_2d(&mut to_inside, cell_w, cell_h);
+        edt_2d(&mut to_outside, cell_w, cell_h);
+
+        to_inside
+            .iter()
+            .zip(&to_outside)
+            .map(|(&d_in, &d_out)| {
+                // Positive inside the glyph, negative outside
+                let distance = d_out.sqrt() - d_in.sqrt();
+                let value = 0.5 + distance / (2.0 * spread);
+                (value.clamp(0.0, 1.0) * 255.0).round() as u8
+            })
+            .collect()
+    }
+}
+
+/// Exact 2D squared Euclidean distance transform (Felzenszwalb & Huttenlocher)
+///
+/// `grid` holds 0 for feature texels and EDT_INF elsewhere; it is overwritten
+/// with the squared distance to the nearest feature texel.
+fn edt_2d(grid: &mut [f32], width: usize, height: usize) {
+    let n = width.max(height);
+    let mut f = vec![0.0f32; n];
+    let mut d = vec![0.0f32; n];
+    let mut v = vec![0usize; n];
+    let mut z = vec![0.0f32; n + 1];
+
+    for x in 0..width {
+        for y in 0..height {
+            f[y] = grid[y * width + x];
+        }
+        edt_1d(&f[..height], &mut d[..height], &mut v, &mut z);
+        for y in 0..height {
+            grid[y * width + x] = d[y];
+        }
+    }
+
+    for y in 0..height {
+        let row = &mut grid[y * width..(y + 1) * width];
+        f[..width].copy_from_slice(row);
+        edt_1d(&f[..width], &mut d[..width], &mut v, &mut z);
+        row.copy_from_slice(&d[..width]);
+    }
+}
+
+/// 1D squared distance transform of sampled function `f` (lower envelope of parabolas)
+fn edt_1d(f: &[f32], d: &mut [f32], v: &mut [usize], z: &mut [f32]) {
+    let n = f.len();
+    if n == 0 {
+        return;
+    }
+
+    let mut k = 0usize;
+    v[0] = 0;
+    z[0] = f32::NEG_INFINITY;
+    z[1] = f32::INFINITY;
+
+    for q in 1..n {
+        let mut s = parabola_intersection(f, q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = parabola_intersection(f, q, v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = f32::INFINITY;
+    }
+
+    k = 0;
+    for (q, out) in d.iter_mut().enumerate() {
+        while z[k + 1] < q as f32 {
+            k += 1;
+        }
+        let diff = q as f32 - v[k] as f32;
+        *out = diff * diff + f[v[k]];
+    }
+}
+
+/// Horizontal position where the parabolas rooted at q and p intersect
+#[inline]
+fn parabola_intersection(f: &[f32], q: usize, p: usize) -> f32 {
+    let (qf, pf) = (q as f32, p as f32);
+    ((f[q] + qf * qf) - (f[p] + pf * pf)) / (2.0 * qf - 2.0 * pf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square_glyph(size: u32) -> Vec<u8> {
+        vec![255u8; (size * size) as usize]
+    }
+
+    #[test]
+    fn test_sdf_atlas_packing() {
+        let gen = TextureGenerator::new(1);
+        let mut bitmaps = square_glyph(8);
+        bitmaps.extend(square_glyph(4));
+        let atlas = gen.build_sdf_atlas(&bitmaps, &[8, 4], &[8, 4], 2, 64, 4.0);
+
+        assert_eq!(atlas.data().len(), 64 * 64);
+        assert!(atlas.missing().is_empty());
+
+        let rects = atlas.rects();
+        // 8x8 glyph + 4 texels padding on each side
+        assert!((rects[2] - rects[0] - 16.0 / 64.0).abs() < 1e-6);
+        assert!((rects[3] - rects[1] - 16.0 / 64.0).abs() < 1e-6);
+
+        // Cells must not overlap
+        let overlap_x = rects[0] < rects[6] && rects[4] < rects[2];
+        let overlap_y = rects[1] < rects[7] && rects[5] < rects[3];
+        assert!(!(overlap_x && overlap_y));
+
+        // Glyph center is inside, cell corner is fully outside
+        let x0 = (rects[0] * 64.0) as usize;
+        let y0 = (rects[1] * 64.0) as usize;
+        let data = atlas.data();
+        assert!(data[(y0 + 8) * 64 + x0 + 8] > 128);
+        assert_eq!(data[y0 * 64 + x0], 0);
+    }
+
+    #[test]
+    fn test_sdf_atlas_reports_overflow() {
+        let gen = TextureGenerator::new(1);
+        let bitmaps = [square_glyph(8), square_glyph(8), square_glyph(8)].concat();
+        let atlas = gen.build_sdf_atlas(&bitmaps, &[8, 8, 8], &[8, 8, 8], 3, 32, 4.0);
+
+        // Each cell is 16x16, so only 4 fit; all 3 fit here
+        assert!(atlas.missing().is_empty());
+
+        let atlas = gen.build_sdf_atlas(&bitmaps, &[8, 8, 8], &[8, 8, 8], 3, 20, 4.0);
+        assert_eq!(atlas.missing(), vec![1, 2]);
+        assert_eq!(&atlas.rects()[4..], &[0.0; 8]);
+    }
+}
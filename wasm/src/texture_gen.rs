@@ -1,16 +1,838 @@
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+use crate::options::{self, JsOptions};
+
+/// LUT layout: 2D strip, width = size*size, height = size (blue selects the slice)
+const LUT_LAYOUT_STRIP: u8 = 0;
+/// LUT layout: Unity-style strip as in its LUT images, same size and tiles as
+/// the strip but green increasing up the image (first row = full green)
+const LUT_LAYOUT_STRIP_UNITY: u8 = 1;
+/// LUT layout: raw 3D texture data, r fastest, then g, then b
+const LUT_LAYOUT_3D: u8 = 2;
+
+/// Texel ordering of a color LUT in memory
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LutLayout {
+    Strip,
+    StripUnity,
+    Volume,
+}
+
+impl LutLayout {
+    fn from_u8(layout: u8) -> Self {
+        match layout {
+            LUT_LAYOUT_STRIP_UNITY => LutLayout::StripUnity,
+            LUT_LAYOUT_3D => LutLayout::Volume,
+            _ => LutLayout::Strip,
+        }
+    }
+
+    /// Texel index (not byte offset) of LUT coordinate (r, g, b)
+    #[inline]
+    fn texel_index(self, size: usize, r: usize, g: usize, b: usize) -> usize {
+        match self {
+            // x = b * size + r, y = g
+            LutLayout::Strip => g * size * size + b * size + r,
+            // x = b * size + r, y = size - 1 - g
+            LutLayout::StripUnity => (size - 1 - g) * size * size + b * size + r,
+            LutLayout::Volume => (b * size + g) * size + r,
+        }
+    }
+
+    /// LUT coordinate (r, g, b) stored at texel index `index` (inverse of texel_index)
+    #[inline]
+    fn texel_coords(self, size: usize, index: usize) -> (usize, usize, usize) {
+        let (lo, mid, hi) = (index % size, (index / size) % size, index / (size * size));
+        match self {
+            LutLayout::Strip => (lo, hi, mid),
+            LutLayout::StripUnity => (lo, size - 1 - hi, mid),
+            LutLayout::Volume => (lo, mid, hi),
+        }
+    }
+}
+
+/// Chunked generation kind: tileable noise, params [size]
+const TEXTURE_KIND_NOISE: u8 = 0;
+/// Chunked generation kind: blue noise, params [size]
+const TEXTURE_KIND_BLUE_NOISE: u8 = 1;
+/// Chunked generation kind: color LUT,
+/// params [size, contrast, saturation, layout, tone_curve, exposure_ev, linearize_input]
+const TEXTURE_KIND_COLOR_LUT: u8 = 2;
+/// Chunked generation kind: shaped noise, params [size, spectral_tilt]
+const TEXTURE_KIND_SHAPED_NOISE: u8 = 3;
+/// Chunked generation kind: splat decal, params [size, seed, arms, irregularity, falloff]
+const TEXTURE_KIND_SPLAT: u8 = 4;
+/// Chunked generation kind: crack decal, params [size, seed, branches, depth]
+const TEXTURE_KIND_CRACK: u8 = 5;
+/// Chunked generation kind: lens distortion map,
+/// params [width, height, k1, k2, chromatic_shift, mode]
+const TEXTURE_KIND_LENS_DISTORTION: u8 = 6;
+/// Chunked generation kind: starfield,
+/// params [width, height, density_per_kpx, seed, size_distribution, twinkle_frames]
+const TEXTURE_KIND_STARFIELD: u8 = 7;
+/// Chunked generation kind: texture bombing,
+/// params [size, count, seed, rotation_random, blend_mode], stamp from set_bombing_stamp
+const TEXTURE_KIND_BOMBING: u8 = 8;
+/// Chunked generation kind: curves LUT (strip layout), params [size], curves from set_lut_curves
+const TEXTURE_KIND_COLOR_LUT_CURVES: u8 = 9;
+
+/// Lens distortion map flag: three RG layers (R, G, B channel offsets) instead of one
+const LENS_MAP_CHROMATIC: u8 = 1;
+/// Lens distortion map flag: allow offsets that sample outside [0, 1]
+const LENS_MAP_OVERSCAN: u8 = 2;
+
+/// Starfield size distribution: radii uniform in [STAR_MIN_RADIUS, STAR_MAX_RADIUS]
+const STAR_SIZE_UNIFORM: u8 = 0;
+/// Starfield size distribution: power law, many faint stars and a few bright ones
+const STAR_SIZE_POWER_LAW: u8 = 1;
+/// Star radius range in texels (Gaussian sigma is half the radius)
+const STAR_MIN_RADIUS: f32 = 0.6;
+const STAR_MAX_RADIUS: f32 = 3.0;
+
+/// Texture bombing blend: per-channel max
+const BOMB_BLEND_MAX: u8 = 0;
+/// Texture bombing blend: per-channel add, clamped to 1
+const BOMB_BLEND_ADD: u8 = 1;
+/// Texture bombing blend: straight-alpha "over"
+const BOMB_BLEND_OVER: u8 = 2;
+/// Placement attempts per stamp when a density mask rejects positions
+const BOMB_MASK_ATTEMPTS: u32 = 32;
+
+/// One star of a generated starfield
+struct Star {
+    x: f32,
+    y: f32,
+    radius: f32,
+    /// RGB tint from the star's color temperature
+    tint: [f32; 3],
+    /// Twinkle: whole cycles per animation loop, phase offset (0..1), depth (0..1)
+    twinkle_cycles: u32,
+    twinkle_phase: f32,
+    twinkle_depth: f32,
+}
+
+impl Star {
+    /// Splat half-extent in texels (3 sigma, with sigma half the radius)
+    fn reach(&self) -> i32 {
+        (self.radius * 0.5 * 3.0).ceil() as i32
+    }
+}
+
+/// LUT tone curve: none
+const TONE_CURVE_NONE: u8 = 0;
+/// LUT tone curve: Reinhard, x / (1 + x)
+const TONE_CURVE_REINHARD: u8 = 1;
+/// LUT tone curve: Hable / Uncharted 2 filmic (see set_hable_params)
+const TONE_CURVE_HABLE: u8 = 2;
+/// LUT tone curve: Narkowicz ACES fit
+const TONE_CURVE_ACES: u8 = 3;
+/// Hable curve constants: linear strength/angle, toe numerator/denominator, white point
+const HABLE_B: f32 = 0.50;
+const HABLE_C: f32 = 0.10;
+const HABLE_E: f32 = 0.02;
+const HABLE_F: f32 = 0.30;
+const HABLE_WHITE: f32 = 11.2;
+/// Default Hable shoulder (A) and toe (D) strengths
+const HABLE_DEFAULT_SHOULDER: f32 = 0.15;
+const HABLE_DEFAULT_TOE: f32 = 0.20;
+
+/// Full color grade baked into a LUT texel
+#[derive(Clone, Copy)]
+struct LutGrade {
+    contrast: f32,
+    saturation: f32,
+    tone_curve: u8,
+    /// Linear multiplier, 2^exposure_ev
+    exposure: f32,
+    /// Convert sRGB input to linear before exposure/tone, and back after
+    linearize: bool,
+    /// Hable shoulder and toe strengths
+    hable: [f32; 2],
+}
+
+impl LutGrade {
+    fn basic(contrast: f32, saturation: f32) -> Self {
+        Self {
+            contrast,
+            saturation,
+            tone_curve: TONE_CURVE_NONE,
+            exposure: 1.0,
+            linearize: false,
+            hable: [HABLE_DEFAULT_SHOULDER, HABLE_DEFAULT_TOE],
+        }
+    }
+
+    /// No exposure or tone step, so the linear round trip can be skipped exactly
+    fn is_tone_neutral(&self) -> bool {
+        self.tone_curve == TONE_CURVE_NONE && self.exposure == 1.0
+    }
+
+    /// Exposure + tone curve on one linear-light channel value
+    fn tone_map(&self, x: f32) -> f32 {
+        let x = x * self.exposure;
+        match self.tone_curve {
+            TONE_CURVE_REINHARD => x / (1.0 + x),
+            TONE_CURVE_HABLE => {
+                let [a, d] = self.hable;
+                let curve = |v: f32| {
+                    ((v * (a * v + HABLE_C * HABLE_B) + d * HABLE_E)
+                        / (v * (a * v + HABLE_B) + d * HABLE_F))
+                        - HABLE_E / HABLE_F
+                };
+                // Standard exposure bias of 2, normalized so the white point maps to 1
+                curve(x * 2.0) / curve(HABLE_WHITE)
+            }
+            TONE_CURVE_ACES => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+            _ => x,
+        }
+    }
+}
+
+/// Monotone cubic (Fritsch-Carlson) curve through (x, y) control points
+///
+/// Never overshoots between points, so monotone control points give a
+/// monotone curve. Inputs outside the first/last point hold the end value.
+#[derive(Clone)]
+struct ToneCurve {
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+    slopes: Vec<f32>,
+}
+
+impl ToneCurve {
+    /// Parse flat [x0, y0, x1, y1, ...]; None for an empty (identity) curve
+    ///
+    /// x must be strictly increasing within 0..1. (0, 0) and (1, 1) are added
+    /// when the points do not reach the ends, so one point is a simple lift.
+    fn parse(name: &str, points: &[f32]) -> Result<Option<Self>, String> {
+        if points.is_empty() {
+            return Ok(None);
+        }
+        if !points.len().is_multiple_of(2) {
+            return Err(format!("{name} curve must be (x, y) pairs"));
+        }
+
+        let (mut xs, mut ys): (Vec<f32>, Vec<f32>) =
+            points.chunks_exact(2).map(|p| (p[0], p[1])).unzip();
+        if xs.iter().chain(&ys).any(|v| !v.is_finite()) {
+            return Err(format!("{name} curve has non-finite control points"));
+        }
+        if xs.iter().any(|x| !(0.0..=1.0).contains(x)) {
+            return Err(format!("{name} curve x values must be within 0..1"));
+        }
+        if xs.windows(2).any(|w| w[1] <= w[0]) {
+            return Err(format!("{name} curve x values must be strictly increasing"));
+        }
+        if xs[0] > 0.0 {
+            xs.insert(0, 0.0);
+            ys.insert(0, 0.0);
+        }
+        if xs[xs.len() - 1] < 1.0 {
+            xs.push(1.0);
+            ys.push(1.0);
+        }
+
+        // Secant slopes, then Fritsch-Carlson tangents
+        let deltas: Vec<f32> = (0..xs.len() - 1)
+            .map(|k| (ys[k + 1] - ys[k]) / (xs[k + 1] - xs[k]))
+            .collect();
+        let mut slopes = vec![0.0f32; xs.len()];
+        slopes[0] = deltas[0];
+        slopes[xs.len() - 1] = deltas[deltas.len() - 1];
+        for k in 1..xs.len() - 1 {
+            if deltas[k - 1] * deltas[k] > 0.0 {
+                slopes[k] = (deltas[k - 1] + deltas[k]) * 0.5;
+            }
+        }
+        for (k, &delta) in deltas.iter().enumerate() {
+            if delta == 0.0 {
+                slopes[k] = 0.0;
+                slopes[k + 1] = 0.0;
+                continue;
+            }
+            let (a, b) = (slopes[k] / delta, slopes[k + 1] / delta);
+            let length_sq = a * a + b * b;
+            if length_sq > 9.0 {
+                let t = 3.0 / length_sq.sqrt();
+                slopes[k] = t * a * delta;
+                slopes[k + 1] = t * b * delta;
+            }
+        }
+
+        Ok(Some(Self { xs, ys, slopes }))
+    }
+
+    fn eval(&self, x: f32) -> f32 {
+        let last = self.xs.len() - 1;
+        if x <= self.xs[0] {
+            return self.ys[0];
+        }
+        if x >= self.xs[last] {
+            return self.ys[last];
+        }
+
+        let k = self.xs.partition_point(|&xk| xk <= x) - 1;
+        let h = self.xs[k + 1] - self.xs[k];
+        let t = (x - self.xs[k]) / h;
+        let (t2, t3) = (t * t, t * t * t);
+        (2.0 * t3 - 3.0 * t2 + 1.0) * self.ys[k]
+            + (t3 - 2.0 * t2 + t) * h * self.slopes[k]
+            + (-2.0 * t3 + 3.0 * t2) * self.ys[k + 1]
+            + (t3 - t2) * h * self.slopes[k + 1]
+    }
+}
+
+/// Parsed Curves-style adjustment (see TextureGenerator::generate_color_lut_from_curves)
+#[derive(Clone)]
+struct LutCurves {
+    master: Option<ToneCurve>,
+    /// Red, green and blue curves, applied after the master curve
+    channels: [Option<ToneCurve>; 3],
+    linear_space: bool,
+}
+
+impl LutCurves {
+    fn parse(
+        master: &[f32],
+        red: &[f32],
+        green: &[f32],
+        blue: &[f32],
+        linear_space: bool,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            master: ToneCurve::parse("master", master)?,
+            channels: [
+                ToneCurve::parse("red", red)?,
+                ToneCurve::parse("green", green)?,
+                ToneCurve::parse("blue", blue)?,
+            ],
+            linear_space,
+        })
+    }
+
+    /// Texel of a size^3 LUT at integer coordinate [r, g, b]
+    fn texel(&self, size: u32, rgb: [usize; 3]) -> [u8; 4] {
+        let identity = self.master.is_none() && self.channels.iter().all(Option::is_none);
+        let apply = |curve: &Option<ToneCurve>, c: f32| curve.as_ref().map_or(c, |t| t.eval(c));
+
+        let scale = 1.0 / (size - 1) as f32;
+        let mut texel = [255u8; 4];
+        for (channel, out) in texel[..3].iter_mut().enumerate() {
+            let mut c = rgb[channel] as f32 * scale;
+            if !identity {
+                if self.linear_space {
+                    c = srgb_to_linear(c);
+                }
+                c = apply(&self.channels[channel], apply(&self.master, c));
+                if self.linear_space {
+                    c = linear_to_srgb(c);
+                }
+            }
+            *out = (c.clamp(0.0, 1.0) * 255.0) as u8;
+        }
+        texel
+    }
+}
+
+/// Lens distortion map settings (see TextureGenerator::generate_lens_distortion_map)
+///
+/// The map is one RG layer of width * height per channel scale, stacked
+/// vertically, so its rows run layer by layer.
+struct LensMap {
+    width: u32,
+    height: u32,
+    k1: f32,
+    k2: f32,
+    channel_scales: Vec<f32>,
+    overscan: bool,
+    /// Largest |offset| over the rows scanned so far (chunked jobs)
+    max_offset: f32,
+}
+
+impl LensMap {
+    fn new(width: u32, height: u32, k1: f32, k2: f32, chromatic_shift: f32, mode: u8) -> Self {
+        let channel_scales = if mode & LENS_MAP_CHROMATIC != 0 {
+            vec![1.0 - chromatic_shift, 1.0, 1.0 + chromatic_shift]
+        } else {
+            vec![1.0]
+        };
+        Self {
+            width,
+            height,
+            k1,
+            k2,
+            channel_scales,
+            overscan: mode & LENS_MAP_OVERSCAN != 0,
+            max_offset: 0.0,
+        }
+    }
+
+    fn rows(&self) -> u32 {
+        self.channel_scales.len() as u32 * self.height
+    }
+
+    /// Raw (du, dv) UV offsets of one map row
+    fn row_offsets(&self, row: u32) -> impl Iterator<Item = f32> + '_ {
+        let channel_scale = self.channel_scales[(row / self.height) as usize];
+        let (width, height) = (self.width as f32, self.height as f32);
+        let aspect = width / self.height.max(1) as f32;
+        let v = (row % self.height) as f32 + 0.5;
+
+        (0..self.width).flat_map(move |x| {
+            let u = (x as f32 + 0.5) / width;
+            let v = v / height;
+            // Radius in aspect-corrected space so the distortion is circular
+            let px = (u - 0.5) * 2.0 * aspect;
+            let py = (v - 0.5) * 2.0;
+            let r2 = px * px + py * py;
+            let factor = (1.0 + self.k1 * r2 + self.k2 * r2 * r2) * channel_scale - 1.0;
+
+            let mut du = (u - 0.5) * factor;
+            let mut dv = (v - 0.5) * factor;
+            if !self.overscan {
+                du = (u + du).clamp(0.0, 1.0) - u;
+                dv = (v + dv).clamp(0.0, 1.0) - v;
+            }
+            [du, dv]
+        })
+    }
+
+    /// Encoding scale that maps `max_offset` to byte 255
+    fn inv_scale(max_offset: f32) -> f32 {
+        if max_offset > 0.0 {
+            127.0 / max_offset
+        } else {
+            0.0
+        }
+    }
+
+    fn encode(offset: f32, inv_scale: f32) -> u8 {
+        (128.0 + (offset * inv_scale).round()).clamp(1.0, 255.0) as u8
+    }
+}
+
+/// Placed starfield, rendered row by row (see TextureGenerator::generate_starfield)
+struct Starfield {
+    width: u32,
+    height: u32,
+    frames: u32,
+    stars: Vec<Star>,
+    /// Indices of the stars whose splat reaches each image row, in star order
+    row_stars: Vec<Vec<u32>>,
+}
+
+impl Starfield {
+    fn new(
+        width: u32,
+        height: u32,
+        density_per_kpx: f32,
+        seed: u32,
+        size_distribution: u8,
+        twinkle_frames: u32,
+    ) -> Self {
+        let stars =
+            TextureGenerator::place_stars(width, height, density_per_kpx, seed, size_distribution);
+        let mut row_stars = vec![Vec::new(); height as usize];
+        for (index, star) in stars.iter().enumerate() {
+            let reach = star.reach();
+            let cy = star.y.floor() as i32;
+            for dy in -reach..=reach {
+                let rows: &mut Vec<u32> =
+                    &mut row_stars[(cy + dy).rem_euclid(height as i32) as usize];
+                // A splat taller than the image wraps onto the same row twice
+                if rows.last() != Some(&(index as u32)) {
+                    rows.push(index as u32);
+                }
+            }
+        }
+
+        Self {
+            width,
+            height,
+            frames: twinkle_frames.max(1),
+            stars,
+            row_stars,
+        }
+    }
+
+    /// Image rows across all frames
+    fn rows(&self) -> u32 {
+        self.height * self.frames
+    }
+
+    /// Render one RGBA row (width * 4 bytes); rows run frame by frame
+    fn render_row(&self, row: u32, out: &mut [u8]) {
+        let (frame, y) = (row / self.height, row % self.height);
+        let t = frame as f32 / self.frames as f32;
+        let mut light = vec![[0.0f32; 3]; self.width as usize];
+
+        for &index in &self.row_stars[y as usize] {
+            let star = &self.stars[index as usize];
+            let brightness = if self.frames > 1 {
+                let phase =
+                    (star.twinkle_cycles as f32 * t + star.twinkle_phase) * std::f32::consts::TAU;
+                1.0 - star.twinkle_depth * 0.5 * (1.0 - phase.cos())
+            } else {
+                1.0
+            };
+            self.splat_row(&mut light, star, brightness, y);
+        }
+
+        for (texel, rgb) in out.chunks_exact_mut(4).zip(&light) {
+            let [r, g, b] = rgb.map(|c| (c.min(1.0) * 255.0).round() as u8);
+            texel.copy_from_slice(&[r, g, b, r.max(g).max(b)]);
+        }
+    }
+
+    // Internal: add the part of one star's Gaussian splat that lands on row y, wrapping
+    // at the edges. Per texel the sums run in the same order as a whole-image splat.
+    fn splat_row(&self, light: &mut [[f32; 3]], star: &Star, brightness: f32, y: u32) {
+        let sigma = star.radius * 0.5;
+        let reach = star.reach();
+        let inv_two_sigma_sq = 1.0 / (2.0 * sigma * sigma);
+        // Bigger stars are brighter at the core
+        let peak = brightness * (star.radius / STAR_MAX_RADIUS).sqrt().max(0.35);
+        let (cx, cy) = (star.x.floor() as i32, star.y.floor() as i32);
+
+        for dy in -reach..=reach {
+            if (cy + dy).rem_euclid(self.height as i32) != y as i32 {
+                continue;
+            }
+            for dx in -reach..=reach {
+                // Distance from the texel center to the star center
+                let px = (cx + dx) as f32 + 0.5 - star.x;
+                let py = (cy + dy) as f32 + 0.5 - star.y;
+                let weight = peak * (-(px * px + py * py) * inv_two_sigma_sq).exp();
+
+                let x = (cx + dx).rem_euclid(self.width as i32) as usize;
+                for (channel, tint) in light[x].iter_mut().zip(star.tint) {
+                    *channel += tint * weight;
+                }
+            }
+        }
+    }
+}
+
+/// Stamp image and scale range for texture bombing (see TextureGenerator::set_bombing_stamp)
+#[derive(Clone)]
+struct BombingStamp {
+    /// RGBA, size * size * 4 bytes
+    pixels: Vec<u8>,
+    size: u32,
+    scale_range: (f32, f32),
+    /// Placement density per output texel; empty = uniform
+    mask: Vec<u8>,
+}
+
+impl BombingStamp {
+    fn new(
+        stamp: &[u8],
+        stamp_size: u32,
+        scale_range: &[f32],
+        mask: &[u8],
+    ) -> Result<Self, String> {
+        if stamp_size == 0 || stamp.len() != (stamp_size * stamp_size * 4) as usize {
+            return Err(format!(
+                "stamp must be stamp_size * stamp_size * 4 = {} bytes, got {}",
+                stamp_size * stamp_size * 4,
+                stamp.len()
+            ));
+        }
+        let scale_range = match scale_range {
+            [min, max, ..] => (*min, *max),
+            [scale] => (*scale, *scale),
+            [] => (1.0, 1.0),
+        };
+        Ok(Self {
+            pixels: stamp.to_vec(),
+            size: stamp_size,
+            scale_range,
+            mask: mask.to_vec(),
+        })
+    }
+}
+
+/// Texture bombing in progress: stamps land one at a time on a float canvas
+struct Bombing {
+    size: u32,
+    stamp: BombingStamp,
+    count: u32,
+    rotation_random: bool,
+    blend_mode: u8,
+    rng: SeededRng,
+    canvas: Vec<[f32; 4]>,
+}
+
+impl Bombing {
+    fn new(
+        size: u32,
+        stamp: BombingStamp,
+        count: u32,
+        seed: u32,
+        rotation_random: bool,
+        blend_mode: u8,
+    ) -> Result<Self, String> {
+        let texels = (size * size) as usize;
+        if !stamp.mask.is_empty() && stamp.mask.len() != texels {
+            return Err(format!(
+                "bombing mask must be size * size = {texels} bytes, got {}",
+                stamp.mask.len()
+            ));
+        }
+        Ok(Self {
+            size,
+            stamp,
+            count,
+            rotation_random,
+            blend_mode,
+            rng: SeededRng::new(seed),
+            canvas: vec![[0.0f32; 4]; texels],
+        })
+    }
+
+    /// Place the next stamp (call `count` times)
+    fn place_next(&mut self) {
+        let (size, mask) = (self.size, &self.stamp.mask);
+        let rng = &mut self.rng;
+
+        let mut position = None;
+        for _ in 0..BOMB_MASK_ATTEMPTS {
+            let x = rng.range(0.0, size as f32);
+            let y = rng.range(0.0, size as f32);
+            let density = if mask.is_empty() {
+                1.0
+            } else {
+                mask[y as usize * size as usize + x as usize] as f32 / 255.0
+            };
+            if rng.next_f32() < density {
+                position = Some((x, y));
+                break;
+            }
+        }
+        let (min_scale, max_scale) = self.stamp.scale_range;
+        let scale = rng.range(min_scale, max_scale);
+        let angle = if self.rotation_random {
+            rng.range(0.0, std::f32::consts::TAU)
+        } else {
+            0.0
+        };
+
+        if let Some((x, y)) = position {
+            TextureGenerator::stamp_wrapped(
+                &mut self.canvas,
+                size,
+                &self.stamp.pixels,
+                self.stamp.size,
+                [x, y],
+                scale,
+                angle,
+                self.blend_mode,
+            );
+        }
+    }
+
+    /// Quantize canvas row y to RGBA bytes (size * 4)
+    fn write_row(&self, y: u32, out: &mut [u8]) {
+        let row = &self.canvas[(y * self.size) as usize..][..self.size as usize];
+        for (texel, rgba) in out.chunks_exact_mut(4).zip(row) {
+            texel.copy_from_slice(&rgba.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+        }
+    }
+}
+
+/// Color LUT settings (see TextureGenerator::generate_color_lut_options)
+///
+/// As a JS object (from_object / generate_color_lut_with_options) the keys are
+/// camelCase and every field is optional, defaulting to LutOptions::new().
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LutOptions {
+    /// LUT resolution per axis (>= 2)
+    pub size: u32,
+    pub contrast: f32,
+    pub saturation: f32,
+    /// Texel layout, see generate_color_lut_ex
+    pub layout: u8,
+    /// Tone curve, see generate_color_lut_tone
+    pub tone_curve: u8,
+    pub exposure_ev: f32,
+    pub linearize_input: bool,
+    /// Hable shoulder (A) and toe (D) strengths
+    pub hable_shoulder: f32,
+    pub hable_toe: f32,
+}
+
+impl Default for LutOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl LutOptions {
+    /// Neutral 32^3 strip LUT
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            size: 32,
+            contrast: 1.0,
+            saturation: 1.0,
+            layout: LUT_LAYOUT_STRIP,
+            tone_curve: TONE_CURVE_NONE,
+            exposure_ev: 0.0,
+            linearize_input: false,
+            hable_shoulder: HABLE_DEFAULT_SHOULDER,
+            hable_toe: HABLE_DEFAULT_TOE,
+        }
+    }
+
+    /// Settings as a flat list, same order as the chunked LUT params:
+    /// [size, contrast, saturation, layout, tone_curve, exposure_ev,
+    ///  linearize_input, hable_shoulder, hable_toe]
+    pub fn get_params(&self) -> Vec<f32> {
+        vec![
+            self.size as f32,
+            self.contrast,
+            self.saturation,
+            self.layout as f32,
+            self.tone_curve as f32,
+            self.exposure_ev,
+            if self.linearize_input { 1.0 } else { 0.0 },
+            self.hable_shoulder,
+            self.hable_toe,
+        ]
+    }
+
+    /// Apply a list from get_params(); missing trailing entries keep their
+    /// current values and extra entries are ignored
+    pub fn set_params(&mut self, params: &[f32]) {
+        let param = |i: usize| params.get(i).copied();
+        if let Some(v) = param(0) {
+            self.size = v.max(2.0) as u32;
+        }
+        self.contrast = param(1).unwrap_or(self.contrast);
+        self.saturation = param(2).unwrap_or(self.saturation);
+        self.layout = param(3).map_or(self.layout, |v| v as u8);
+        self.tone_curve = param(4).map_or(self.tone_curve, |v| v as u8);
+        self.exposure_ev = param(5).unwrap_or(self.exposure_ev);
+        self.linearize_input = param(6).map_or(self.linearize_input, |v| v != 0.0);
+        self.hable_shoulder = param(7).unwrap_or(self.hable_shoulder);
+        self.hable_toe = param(8).unwrap_or(self.hable_toe);
+    }
+
+    /// Build from a plain JS object (camelCase keys, all optional)
+    ///
+    /// Unknown keys are an error when `strict`, otherwise a console warning.
+    pub fn from_object(options: JsValue, strict: bool) -> Result<LutOptions, String> {
+        options::from_js(options, strict)
+    }
+
+    /// Settings as a plain JS object (inverse of from_object)
+    pub fn to_object(&self) -> Result<JsValue, String> {
+        options::to_js(self)
+    }
+}
+
+impl JsOptions for LutOptions {
+    const FIELDS: &'static [&'static str] = &[
+        "size",
+        "contrast",
+        "saturation",
+        "layout",
+        "toneCurve",
+        "exposureEv",
+        "linearizeInput",
+        "hableShoulder",
+        "hableToe",
+    ];
+
+    fn validate(&self) -> Result<(), String> {
+        if self.size < 2 {
+            return Err(format!("size must be >= 2, got {}", self.size));
+        }
+        if !matches!(
+            self.layout,
+            LUT_LAYOUT_STRIP | LUT_LAYOUT_STRIP_UNITY | LUT_LAYOUT_3D
+        ) {
+            return Err(format!("layout {} is not a LUT layout", self.layout));
+        }
+        if self.tone_curve > TONE_CURVE_ACES {
+            return Err(format!("toneCurve {} is not a tone curve", self.tone_curve));
+        }
+        if self.hable_shoulder <= 0.0 || self.hable_toe <= 0.0 {
+            return Err("hableShoulder and hableToe must be > 0".into());
+        }
+        Ok(())
+    }
+}
+
+/// What an in-progress chunked job produces
+enum TextureKind {
+    Noise,
+    BlueNoise,
+    ColorLut {
+        grade: LutGrade,
+        layout: LutLayout,
+    },
+    /// Strip layout
+    CurvesLut(LutCurves),
+    /// The first rows() steps scan for the largest offset, the next rows()
+    /// encode against it
+    LensMap(LensMap),
+    Starfield(Starfield),
+    /// One step per stamp, then one per canvas row
+    Bombing(Bombing),
+    /// Rendered whole by begin() (the generator needs the full image);
+    /// chunks only report progress
+    Buffered,
+}
+
+/// In-progress chunked generation (see TextureGenerator::begin)
+struct TextureJob {
+    kind: TextureKind,
+    size: u32,
+    /// Texels per output row (per-texel kinds; 0 for the others)
+    row_texels: usize,
+    total_rows: u32,
+    next_row: u32,
+}
+
 /// Generates optimized textures for shader effects
 #[wasm_bindgen]
 pub struct TextureGenerator {
     seed: u32,
+    /// Active chunked job, if any
+    job: Option<TextureJob>,
+    /// Output of the chunked job, filled row by row
+    output: Vec<u8>,
+    /// Offset magnitude encoded by byte 255 in the last lens distortion map
+    lens_offset_scale: f32,
+    /// Set by dispose(); chunked jobs can no longer be started
+    disposed: bool,
+    /// Hable shoulder and toe strengths for TONE_CURVE_HABLE LUTs
+    hable: [f32; 2],
+    /// Input of chunked bombing jobs (set_bombing_stamp)
+    bombing_stamp: Option<BombingStamp>,
+    /// Input of chunked curves LUT jobs (set_lut_curves)
+    lut_curves: Option<LutCurves>,
 }
 
 #[wasm_bindgen]
 impl TextureGenerator {
     #[wasm_bindgen(constructor)]
     pub fn new(seed: u32) -> Self {
-        Self { seed }
+        Self {
+            seed,
+            job: None,
+            output: Vec::new(),
+            lens_offset_scale: 0.0,
+            disposed: false,
+            hable: [HABLE_DEFAULT_SHOULDER, HABLE_DEFAULT_TOE],
+            bombing_stamp: None,
+            lut_curves: None,
+        }
     }
 
     /// Generate tileable noise texture (RGBA, single channel duplicated)
@@ -22,8 +844,7 @@ impl TextureGenerator {
 
         for y in 0..size {
             for x in 0..size {
-                let value = self.tileable_noise(x, y, size);
-                let byte = (value * 255.0) as u8;
+                let byte = self.noise_texel(x, y, size);
                 data.push(byte); // R
                 data.push(byte); // G
                 data.push(byte); // B
@@ -40,27 +861,9 @@ impl TextureGenerator {
         let total = (size * size) as usize;
         let mut data = Vec::with_capacity(total * 4);
 
-        // Simple blue noise approximation using multiple octaves with offset sampling
         for y in 0..size {
             for x in 0..size {
-                let mut value = 0.0;
-                let mut amplitude = 1.0;
-                let mut total_amplitude = 0.0;
-
-                for octave in 0..4 {
-                    let freq = 1 << octave;
-                    let offset = octave * 17;
-                    value += self.tileable_noise(
-                        (x * freq + offset) % size,
-                        (y * freq + offset * 3) % size,
-                        size,
-                    ) * amplitude;
-                    total_amplitude += amplitude;
-                    amplitude *= 0.5;
-                }
-
-                value /= total_amplitude;
-                let byte = (value * 255.0) as u8;
+                let byte = self.blue_noise_texel(x, y, size);
                 data.push(byte);
                 data.push(byte);
                 data.push(byte);
@@ -71,81 +874,1755 @@ impl TextureGenerator {
         data
     }
 
+    /// Generate tileable noise with a shaped spatial spectrum (RGBA, single channel duplicated)
+    ///
+    /// # Arguments
+    /// * `spectral_tilt` - Power change in dB per octave of spatial frequency.
+    ///   0 = white (same as generate_noise), negative = low frequencies dominate
+    ///   (-6 is roughly 1/f "pink", good for dust), positive = finer, sharper grain.
+    ///
+    /// The white noise field is split into octave bands (a wrapped Laplacian pyramid)
+    /// and the bands are re-weighted, so the result still tiles. Output is
+    /// renormalized to the full 0-255 range.
+    #[wasm_bindgen]
+    pub fn generate_shaped_noise(&self, size: u32, spectral_tilt: f32) -> Vec<u8> {
+        if spectral_tilt == 0.0 || size < 2 {
+            return self.generate_noise(size);
+        }
+
+        let white: Vec<f32> = (0..size * size)
+            .map(|i| self.tileable_noise(i % size, i / size, size))
+            .collect();
+
+        // Low-passed copies of the white field, finest (= white) to coarsest (= mean)
+        let levels: Vec<Vec<f32>> = (0..u32::BITS)
+            .map(|j| size >> j)
+            .take_while(|&cells| cells >= 1)
+            .map(|cells| Self::wrapped_lowpass(&white, size, cells))
+            .collect();
+
+        // Band j = level j - level j+1; each coarser band is one octave lower,
+        // so it gets `-spectral_tilt` dB relative to the band above it
+        let band_gain = 10f32.powf(-spectral_tilt / 20.0);
+        let mut field = vec![0.0f32; white.len()];
+        let mut weight = 1.0;
+        for pair in levels.windows(2) {
+            for (out, (fine, coarse)) in field.iter_mut().zip(pair[0].iter().zip(&pair[1])) {
+                *out += (fine - coarse) * weight;
+            }
+            weight *= band_gain;
+        }
+
+        let min = field.iter().copied().fold(f32::MAX, f32::min);
+        let max = field.iter().copied().fold(f32::MIN, f32::max);
+        let scale = if max > min { 255.0 / (max - min) } else { 0.0 };
+
+        field
+            .iter()
+            .flat_map(|&v| {
+                let byte = ((v - min) * scale).round() as u8;
+                [byte, byte, byte, 255]
+            })
+            .collect()
+    }
+
     /// Generate a 3D color grading LUT as a 2D strip
     /// size: LUT resolution (e.g., 32 for 32x32x32)
     /// Returns: 2D texture of size (size*size, size)
     #[wasm_bindgen]
     pub fn generate_color_lut(&self, size: u32, contrast: f32, saturation: f32) -> Vec<u8> {
-        let width = size * size;
-        let height = size;
-        let mut data = Vec::with_capacity((width * height * 4) as usize);
-
-        for y in 0..height {
-            for x in 0..width {
-                // Convert 2D coords to 3D LUT coords
-                let b = x / size;
-                let r = x % size;
-                let g = y;
-
-                // Normalize to 0-1
-                let mut rf = r as f32 / (size - 1) as f32;
-                let mut gf = g as f32 / (size - 1) as f32;
-                let mut bf = b as f32 / (size - 1) as f32;
-
-                // Apply contrast
-                rf = ((rf - 0.5) * contrast + 0.5).clamp(0.0, 1.0);
-                gf = ((gf - 0.5) * contrast + 0.5).clamp(0.0, 1.0);
-                bf = ((bf - 0.5) * contrast + 0.5).clamp(0.0, 1.0);
-
-                // Apply saturation
-                let luma = rf * 0.299 + gf * 0.587 + bf * 0.114;
-                rf = luma + (rf - luma) * saturation;
-                gf = luma + (gf - luma) * saturation;
-                bf = luma + (bf - luma) * saturation;
-
-                data.push((rf.clamp(0.0, 1.0) * 255.0) as u8);
-                data.push((gf.clamp(0.0, 1.0) * 255.0) as u8);
-                data.push((bf.clamp(0.0, 1.0) * 255.0) as u8);
-                data.push(255);
-            }
+        self.generate_color_lut_ex(size, contrast, saturation, LUT_LAYOUT_STRIP)
+    }
+
+    /// Generate a 3D color grading LUT in the requested texel layout
+    /// layout: 0 = strip (size*size, size), 1 = Unity-style strip (size*size, size,
+    ///         rows flipped so green increases upwards),
+    ///         2 = raw 3D (r fastest, then g, then b) for texImage3D / Data3DTexture
+    /// Unknown layouts fall back to the strip layout.
+    #[wasm_bindgen]
+    pub fn generate_color_lut_ex(
+        &self,
+        size: u32,
+        contrast: f32,
+        saturation: f32,
+        layout: u8,
+    ) -> Vec<u8> {
+        Self::build_color_lut(size, &LutGrade::basic(contrast, saturation), layout)
+    }
+
+    /// Generate a color LUT with exposure and a filmic tone curve baked in
+    ///
+    /// Per texel, in order:
+    /// 1. un-gamma sRGB -> linear (only if `linearize_input`)
+    /// 2. exposure: multiply by 2^exposure_ev
+    /// 3. tone curve: 0 = none, 1 = Reinhard, 2 = Hable (set_hable_params), 3 = ACES fit
+    /// 4. linear -> sRGB (only if `linearize_input`)
+    /// 5. contrast, then saturation, as in generate_color_lut
+    ///
+    /// LUT inputs are display-referred 0..1, so there is no scene data above
+    /// white: the curves only compress highlights that a positive exposure
+    /// lifts past 1. With no curve and 0 EV the output equals generate_color_lut_ex.
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_color_lut_tone(
+        &self,
+        size: u32,
+        contrast: f32,
+        saturation: f32,
+        layout: u8,
+        tone_curve: u8,
+        exposure_ev: f32,
+        linearize_input: bool,
+    ) -> Vec<u8> {
+        let grade = self.lut_grade(
+            contrast,
+            saturation,
+            tone_curve,
+            exposure_ev,
+            linearize_input,
+        );
+        Self::build_color_lut(size, &grade, layout)
+    }
+
+    /// Generate a color LUT (strip layout) from Curves-style control points
+    ///
+    /// Each curve is flat [x0, y0, x1, y1, ...] with x strictly increasing in
+    /// 0..1, interpolated with a monotone cubic (no overshoot). (0, 0) and (1, 1)
+    /// are implied when the points don't reach the ends; an empty curve is the
+    /// identity. The master curve is applied first, then the channel curve.
+    ///
+    /// # Arguments
+    /// * `linear_space` - Apply the curves to linear light (sRGB decoded first,
+    ///   re-encoded after) instead of to the sRGB values
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_color_lut_from_curves(
+        &self,
+        size: u32,
+        master: &[f32],
+        red: &[f32],
+        green: &[f32],
+        blue: &[f32],
+        linear_space: bool,
+    ) -> Result<Vec<u8>, String> {
+        if size < 2 {
+            return Err("color LUT size must be >= 2".into());
         }
+        let curves = LutCurves::parse(master, red, green, blue, linear_space)?;
+        Ok(Self::fill_lut(size, LUT_LAYOUT_STRIP, |rgb| {
+            curves.texel(size, rgb)
+        }))
+    }
 
-        data
+    /// Curves for chunked curves LUTs (begin() kind 9), same arguments as
+    /// generate_color_lut_from_curves
+    ///
+    /// Kept until replaced, so later begin() calls can reuse them.
+    #[wasm_bindgen]
+    pub fn set_lut_curves(
+        &mut self,
+        master: &[f32],
+        red: &[f32],
+        green: &[f32],
+        blue: &[f32],
+        linear_space: bool,
+    ) -> Result<(), String> {
+        self.lut_curves = Some(LutCurves::parse(master, red, green, blue, linear_space)?);
+        Ok(())
     }
 
-    // Internal: tileable noise using hash
-    fn tileable_noise(&self, x: u32, y: u32, size: u32) -> f32 {
-        let hash = self.hash2d(x % size, y % size);
-        hash as f32 / u32::MAX as f32
+    /// Generate a color LUT from an options object (see generate_color_lut_tone)
+    #[wasm_bindgen]
+    pub fn generate_color_lut_options(&self, options: &LutOptions) -> Vec<u8> {
+        let grade = LutGrade {
+            tone_curve: options.tone_curve,
+            exposure: options.exposure_ev.exp2(),
+            linearize: options.linearize_input,
+            hable: [
+                options.hable_shoulder.max(1e-4),
+                options.hable_toe.max(1e-4),
+            ],
+            ..LutGrade::basic(options.contrast, options.saturation)
+        };
+        Self::build_color_lut(options.size.max(2), &grade, options.layout)
     }
 
-    // Fast integer hash
-    fn hash2d(&self, x: u32, y: u32) -> u32 {
-        let mut h = self.seed;
-        h ^= x.wrapping_mul(0x45d9f3b);
-        h = h.wrapping_mul(0x45d9f3b);
-        h ^= y.wrapping_mul(0x119de1f3);
-        h = h.wrapping_mul(0x119de1f3);
-        h ^= h >> 16;
-        h
+    /// Generate a color LUT from a plain JS options object (see LutOptions::from_object)
+    #[wasm_bindgen]
+    pub fn generate_color_lut_with_options(
+        &self,
+        options: JsValue,
+        strict: bool,
+    ) -> Result<Vec<u8>, String> {
+        Ok(self.generate_color_lut_options(&LutOptions::from_object(options, strict)?))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Shoulder (A, default 0.15) and toe (D, default 0.20) strength of the Hable curve
+    #[wasm_bindgen]
+    pub fn set_hable_params(&mut self, shoulder_strength: f32, toe_strength: f32) {
+        self.hable = [shoulder_strength.max(1e-4), toe_strength.max(1e-4)];
+    }
 
-    #[test]
-    fn test_noise_generation() {
-        let gen = TextureGenerator::new(42);
-        let data = gen.generate_noise(64);
-        assert_eq!(data.len(), 64 * 64 * 4);
+    /// Re-order RGBA LUT data from one layout to another (see generate_color_lut_ex)
+    /// Returns an empty array if `data` doesn't hold size^3 RGBA texels.
+    #[wasm_bindgen]
+    pub fn convert_lut_layout(data: &[u8], size: u32, from: u8, to: u8) -> Vec<u8> {
+        let n = size as usize;
+        if data.len() != n * n * n * 4 {
+            return Vec::new();
+        }
+
+        let from = LutLayout::from_u8(from);
+        let to = LutLayout::from_u8(to);
+        if from == to {
+            return data.to_vec();
+        }
+
+        let mut out = vec![0u8; data.len()];
+        for b in 0..n {
+            for g in 0..n {
+                for r in 0..n {
+                    let src = from.texel_index(n, r, g, b) * 4;
+                    let dst = to.texel_index(n, r, g, b) * 4;
+                    out[dst..dst + 4].copy_from_slice(&data[src..src + 4]);
+                }
+            }
+        }
+
+        out
     }
 
-    #[test]
-    fn test_lut_generation() {
-        let gen = TextureGenerator::new(42);
-        let data = gen.generate_color_lut(16, 1.1, 1.0);
-        assert_eq!(data.len(), 16 * 16 * 16 * 4);
+    // --- Chunked generation (progress-friendly, same output as the single calls) ---
+
+    /// Start a chunked generation job, replacing any job in progress
+    ///
+    /// # Arguments
+    /// * `kind` - 0 = noise [size], 1 = blue noise [size],
+    ///   2 = color LUT [size, contrast, saturation, layout?, tone_curve?, exposure_ev?,
+    ///   linearize_input?] (see generate_color_lut_tone),
+    ///   3 = shaped noise [size, spectral_tilt], 4 = splat [size, seed, arms,
+    ///   irregularity, falloff], 5 = crack [size, seed, branches, depth],
+    ///   6 = lens distortion [width, height, k1, k2, chromatic_shift, mode],
+    ///   7 = starfield [width, height, density_per_kpx, seed, size_distribution,
+    ///   twinkle_frames], 8 = bombing [size, count, seed, rotation_random,
+    ///   blend_mode] with the stamp from set_bombing_stamp(), 9 = curves LUT
+    ///   [size] with the curves from set_lut_curves()
+    /// * `params` - Kind-specific parameters (see above)
+    ///
+    /// The output buffer is allocated up front; read it via get_output_ptr().
+    /// Kinds 3 to 5 need the whole image at once, so begin() renders them
+    /// and the chunks only report progress. A lens distortion map takes two
+    /// passes over its rows (the encoding depends on the largest offset);
+    /// bombing places one stamp per row step, then converts the canvas.
+    #[wasm_bindgen]
+    pub fn begin(&mut self, kind: u8, params: &[f32]) -> Result<(), String> {
+        if self.disposed {
+            return Err("texture generator is disposed".into());
+        }
+        let size = params.first().copied().unwrap_or(0.0) as u32;
+        if size == 0 {
+            return Err("texture size must be > 0".into());
+        }
+
+        let param = |i: usize| params.get(i).copied().unwrap_or(0.0);
+        let buffered = match kind {
+            TEXTURE_KIND_SHAPED_NOISE => Some(self.generate_shaped_noise(size, param(1))),
+            TEXTURE_KIND_SPLAT => Some(self.generate_splat(
+                size,
+                param(1) as u32,
+                param(2) as u32,
+                param(3),
+                param(4),
+            )),
+            TEXTURE_KIND_CRACK => {
+                Some(self.generate_crack(size, param(1) as u32, param(2) as u32, param(3) as u32))
+            }
+            _ => None,
+        };
+        if let Some(data) = buffered {
+            self.output = data;
+            self.job = Some(TextureJob {
+                kind: TextureKind::Buffered,
+                size,
+                row_texels: 0,
+                total_rows: 1,
+                next_row: 0,
+            });
+            return Ok(());
+        }
+
+        let (kind, row_texels, total_rows, output_len) = match kind {
+            TEXTURE_KIND_NOISE => (TextureKind::Noise, size as usize, size, size * size * 4),
+            TEXTURE_KIND_BLUE_NOISE => {
+                (TextureKind::BlueNoise, size as usize, size, size * size * 4)
+            }
+            TEXTURE_KIND_COLOR_LUT => {
+                if params.len() < 3 {
+                    return Err("color LUT needs [size, contrast, saturation, layout?]".into());
+                }
+                if size < 2 {
+                    return Err("color LUT size must be >= 2".into());
+                }
+                let kind = TextureKind::ColorLut {
+                    grade: self.lut_grade(
+                        params[1],
+                        params[2],
+                        param(4) as u8,
+                        param(5),
+                        param(6) != 0.0,
+                    ),
+                    layout: LutLayout::from_u8(param(3) as u8),
+                };
+                (kind, (size * size) as usize, size, size * size * size * 4)
+            }
+            TEXTURE_KIND_LENS_DISTORTION | TEXTURE_KIND_STARFIELD if param(1) < 1.0 => {
+                return Err("texture height must be > 0".into());
+            }
+            TEXTURE_KIND_LENS_DISTORTION => {
+                let lens = LensMap::new(
+                    size,
+                    param(1) as u32,
+                    param(2),
+                    param(3),
+                    param(4),
+                    param(5) as u8,
+                );
+                let rows = lens.rows();
+                (TextureKind::LensMap(lens), 0, rows * 2, rows * size * 2)
+            }
+            TEXTURE_KIND_STARFIELD => {
+                let field = Starfield::new(
+                    size,
+                    param(1) as u32,
+                    param(2),
+                    param(3) as u32,
+                    param(4) as u8,
+                    param(5) as u32,
+                );
+                let rows = field.rows();
+                (TextureKind::Starfield(field), 0, rows, rows * size * 4)
+            }
+            TEXTURE_KIND_BOMBING => {
+                let stamp = self.bombing_stamp.clone().ok_or_else(|| {
+                    format!("texture kind {kind} (bombing) needs a stamp, call set_bombing_stamp")
+                })?;
+                let count = param(1) as u32;
+                let bombing = Bombing::new(
+                    size,
+                    stamp,
+                    count,
+                    param(2) as u32,
+                    param(3) != 0.0,
+                    param(4) as u8,
+                )?;
+                (
+                    TextureKind::Bombing(bombing),
+                    0,
+                    count + size,
+                    size * size * 4,
+                )
+            }
+            TEXTURE_KIND_COLOR_LUT_CURVES => {
+                if size < 2 {
+                    return Err("color LUT size must be >= 2".into());
+                }
+                let curves = self.lut_curves.clone().ok_or_else(|| {
+                    format!("texture kind {kind} (curves LUT) needs curves, call set_lut_curves")
+                })?;
+                let kind = TextureKind::CurvesLut(curves);
+                (kind, (size * size) as usize, size, size * size * size * 4)
+            }
+            _ => return Err(format!("unknown texture kind {kind}")),
+        };
+
+        self.output.clear();
+        self.output.resize(output_len as usize, 0);
+        self.job = Some(TextureJob {
+            kind,
+            size,
+            row_texels,
+            total_rows,
+            next_row: 0,
+        });
+        Ok(())
+    }
+
+    /// Generate up to `max_rows` more rows of the current job
+    ///
+    /// # Returns
+    /// Progress in 0..1 (1.0 when done or when no job is active)
+    #[wasm_bindgen]
+    pub fn generate_chunk(&mut self, max_rows: u32) -> f32 {
+        let Some(mut job) = self.job.take() else {
+            return 1.0;
+        };
+
+        let end = job.next_row.saturating_add(max_rows).min(job.total_rows);
+        for row in job.next_row..end {
+            self.job_row(&mut job, row);
+        }
+        job.next_row = end;
+        let progress = end as f32 / job.total_rows as f32;
+        self.job = Some(job);
+
+        progress
+    }
+
+    /// Complete any remaining rows and end the job
+    ///
+    /// # Returns
+    /// Output length in bytes (data stays readable via get_output_ptr())
+    #[wasm_bindgen]
+    pub fn finish(&mut self) -> usize {
+        self.generate_chunk(u32::MAX);
+        self.job = None;
+        self.output.len()
+    }
+
+    /// Get pointer to the chunked output buffer (get_output_len() bytes)
+    #[wasm_bindgen]
+    pub fn get_output_ptr(&self) -> *const u8 {
+        self.output.as_ptr()
+    }
+
+    #[wasm_bindgen]
+    pub fn get_output_len(&self) -> usize {
+        self.output.len()
+    }
+
+    /// Drop the chunked job and its output buffer now instead of waiting for JS GC
+    ///
+    /// One-shot generators keep working (they allocate per call); begin()
+    /// returns an error afterwards.
+    #[wasm_bindgen]
+    pub fn dispose(&mut self) {
+        self.job = None;
+        self.output = Vec::new();
+        self.disposed = true;
+    }
+
+    #[wasm_bindgen]
+    pub fn is_disposed(&self) -> bool {
+        self.disposed
+    }
+
+    /// Generate a radial splash decal (RGBA, white with shaped alpha)
+    ///
+    /// # Arguments
+    /// * `arms` - Number of main lobes around the center
+    /// * `irregularity` - 0 = clean lobes, 1 = strongly noise-perturbed outline
+    /// * `falloff` - Power applied to the alpha ramp from center to outline
+    ///
+    /// Deterministic from `seed`; alpha is exactly 0 on the texture border.
+    #[wasm_bindgen]
+    pub fn generate_splat(
+        &self,
+        size: u32,
+        seed: u32,
+        arms: u32,
+        irregularity: f32,
+        falloff: f32,
+    ) -> Vec<u8> {
+        let mut rng = SeededRng::new(seed);
+        let arms = arms.max(1) as f32;
+        let irregularity = irregularity.clamp(0.0, 1.0);
+        let falloff = falloff.max(0.01);
+        let lobe_phase = rng.range(0.0, std::f32::consts::TAU);
+
+        // Random low-order harmonics: periodic in angle, so the outline closes seamlessly
+        let harmonics: Vec<(f32, f32, f32)> = (1..=6)
+            .map(|k| {
+                let amp = rng.range(0.0, 1.0) / k as f32;
+                (k as f32 + arms, amp, rng.range(0.0, std::f32::consts::TAU))
+            })
+            .collect();
+        let harmonic_norm: f32 = harmonics.iter().map(|h| h.1).sum::<f32>().max(1e-6);
+
+        let half = size as f32 * 0.5;
+        let mut data = vec![0u8; (size * size * 4) as usize];
+
+        for y in 0..size {
+            for x in 0..size {
+                let dx = (x as f32 + 0.5 - half) / half;
+                let dy = (y as f32 + 0.5 - half) / half;
+                let r = (dx * dx + dy * dy).sqrt();
+                let theta = dy.atan2(dx);
+
+                let lobes = 0.5 + 0.5 * (arms * theta + lobe_phase).cos();
+                let noise = harmonics
+                    .iter()
+                    .map(|&(freq, amp, phase)| amp * (freq * theta + phase).sin())
+                    .sum::<f32>()
+                    / harmonic_norm;
+
+                // Outline radius, kept inside the unit circle so the border stays clear
+                let outline =
+                    (0.45 + 0.3 * lobes * (1.0 - 0.5 * irregularity) + 0.15 * irregularity * noise)
+                        .clamp(0.05, 0.9);
+
+                let border = x == 0 || y == 0 || x == size - 1 || y == size - 1;
+                let alpha = if border {
+                    0.0
+                } else {
+                    (1.0 - r / outline).clamp(0.0, 1.0).powf(falloff)
+                };
+
+                let i = ((y * size + x) * 4) as usize;
+                data[i] = 255;
+                data[i + 1] = 255;
+                data[i + 2] = 255;
+                data[i + 3] = (alpha * 255.0) as u8;
+            }
+        }
+
+        data
+    }
+
+    /// Generate a branching crack decal (RGBA, white with shaped alpha)
+    ///
+    /// # Arguments
+    /// * `branches` - Number of cracks leaving the impact point
+    /// * `depth` - How many levels of sub-branches each crack may spawn
+    ///
+    /// Deterministic from `seed`; alpha is exactly 0 on the texture border.
+    #[wasm_bindgen]
+    pub fn generate_crack(&self, size: u32, seed: u32, branches: u32, depth: u32) -> Vec<u8> {
+        const MAX_SEGMENTS: usize = 4096;
+
+        let mut rng = SeededRng::new(seed);
+        let half = size as f32 * 0.5;
+        let step = (size as f32 / 32.0).max(1.0);
+        let max_radius = half * 0.9;
+        let mut alpha = vec![0.0f32; (size * size) as usize];
+
+        // Walkers: (x, y, angle, thickness, remaining depth)
+        let mut walkers: Vec<(f32, f32, f32, f32, u32)> = (0..branches.max(1))
+            .map(|i| {
+                let base = i as f32 / branches.max(1) as f32 * std::f32::consts::TAU;
+                let angle = base + rng.range(-0.3, 0.3);
+                (half, half, angle, (size as f32 / 48.0).max(1.0), depth)
+            })
+            .collect();
+
+        let mut segments = 0;
+        while let Some((mut x, mut y, mut angle, mut thickness, level)) = walkers.pop() {
+            while thickness > 0.35 && segments < MAX_SEGMENTS {
+                angle += rng.range(-0.45, 0.45);
+                let nx = x + angle.cos() * step;
+                let ny = y + angle.sin() * step;
+
+                let (cx, cy) = (nx - half, ny - half);
+                if (cx * cx + cy * cy).sqrt() > max_radius {
+                    break;
+                }
+
+                Self::stamp_segment(&mut alpha, size, [x, y], [nx, ny], thickness);
+                segments += 1;
+
+                if level > 0 && rng.next_f32() < 0.12 {
+                    let fork =
+                        angle + rng.range(0.4, 0.9) * if rng.next_f32() < 0.5 { -1.0 } else { 1.0 };
+                    walkers.push((nx, ny, fork, thickness * 0.6, level - 1));
+                }
+
+                x = nx;
+                y = ny;
+                thickness *= 0.94;
+            }
+        }
+
+        let mut data = vec![0u8; (size * size * 4) as usize];
+        for y in 0..size {
+            for x in 0..size {
+                let idx = (y * size + x) as usize;
+                let border = x == 0 || y == 0 || x == size - 1 || y == size - 1;
+                let a = if border { 0.0 } else { alpha[idx] };
+                data[idx * 4] = 255;
+                data[idx * 4 + 1] = 255;
+                data[idx * 4 + 2] = 255;
+                data[idx * 4 + 3] = (a.clamp(0.0, 1.0) * 255.0) as u8;
+            }
+        }
+
+        data
+    }
+
+    /// Generate a UV-offset map for Brown-Conrady lens distortion (RG8 per layer)
+    ///
+    /// # Arguments
+    /// * `k1` / `k2` - Radial terms; positive = pincushion, negative = barrel
+    /// * `chromatic_shift` - Per-channel distortion spread (R: 1 - shift, B: 1 + shift)
+    /// * `mode` - Bit flags: 1 = chromatic (three layers), 2 = overscan (no clamping)
+    ///
+    /// # Returns
+    /// One RG layer (width*height*2 bytes), or R, G, B layers back to back when
+    /// chromatic. Offsets are stored as 128 + 127 * offset / scale, so byte 128
+    /// is neutral; read the scale with get_lens_offset_scale(). Without overscan,
+    /// offsets are clamped so uv + offset stays inside [0, 1].
+    #[wasm_bindgen]
+    pub fn generate_lens_distortion_map(
+        &mut self,
+        width: u32,
+        height: u32,
+        k1: f32,
+        k2: f32,
+        chromatic_shift: f32,
+        mode: u8,
+    ) -> Vec<u8> {
+        let lens = LensMap::new(width, height, k1, k2, chromatic_shift, mode);
+        let offsets: Vec<f32> = (0..lens.rows())
+            .flat_map(|row| lens.row_offsets(row))
+            .collect();
+
+        let max_offset = offsets.iter().fold(0.0f32, |m, o| m.max(o.abs()));
+        self.lens_offset_scale = max_offset;
+        let inv_scale = LensMap::inv_scale(max_offset);
+
+        offsets
+            .iter()
+            .map(|&o| LensMap::encode(o, inv_scale))
+            .collect()
+    }
+
+    /// Offset (in UV units) encoded by byte 255 in the last lens distortion map
+    ///
+    /// Decode with: offset = (byte - 128) / 127 * scale
+    #[wasm_bindgen]
+    pub fn get_lens_offset_scale(&self) -> f32 {
+        self.lens_offset_scale
+    }
+
+    /// Generate a tileable starfield, optionally as a vertical twinkle sprite sheet (RGBA)
+    ///
+    /// # Arguments
+    /// * `density_per_kpx` - Stars per 1000 texels
+    /// * `size_distribution` - 0 = uniform radii, 1 = power law
+    /// * `twinkle_frames` - Frames stacked vertically (0 or 1 = a single still frame);
+    ///   every star completes whole brightness cycles so the animation loops
+    ///
+    /// Splats wrap around the edges, so the texture tiles without clipped stars.
+    /// Alpha is the brightest channel. Deterministic from `seed`.
+    #[wasm_bindgen]
+    pub fn generate_starfield(
+        &self,
+        width: u32,
+        height: u32,
+        density_per_kpx: f32,
+        seed: u32,
+        size_distribution: u8,
+        twinkle_frames: u32,
+    ) -> Vec<u8> {
+        if width == 0 || height == 0 {
+            return Vec::new();
+        }
+
+        let field = Starfield::new(
+            width,
+            height,
+            density_per_kpx,
+            seed,
+            size_distribution,
+            twinkle_frames,
+        );
+        let row_len = width as usize * 4;
+        let mut data = vec![0u8; field.rows() as usize * row_len];
+        for (row, out) in data.chunks_exact_mut(row_len).enumerate() {
+            field.render_row(row as u32, out);
+        }
+
+        data
+    }
+
+    /// Scatter rotated, scaled copies of an RGBA stamp over a tileable texture (RGBA)
+    ///
+    /// # Arguments
+    /// * `stamp` - RGBA stamp, stamp_size * stamp_size * 4 bytes
+    /// * `count` - Number of stamps
+    /// * `scale_range` - [min, max] stamp scale (1 = one stamp texel per output texel);
+    ///   empty = 1
+    /// * `rotation_random` - Give each stamp a random rotation
+    /// * `blend_mode` - 0 = max, 1 = add (clamped), 2 = alpha over
+    ///
+    /// Stamps wrap around the edges so the result tiles. Deterministic from
+    /// `seed`; returns an empty Vec if the stamp size doesn't match.
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_bombing(
+        &self,
+        size: u32,
+        stamp: &[u8],
+        stamp_size: u32,
+        count: u32,
+        seed: u32,
+        scale_range: &[f32],
+        rotation_random: bool,
+        blend_mode: u8,
+    ) -> Vec<u8> {
+        self.generate_bombing_masked(
+            size,
+            stamp,
+            stamp_size,
+            count,
+            seed,
+            scale_range,
+            rotation_random,
+            blend_mode,
+            &[],
+        )
+    }
+
+    /// generate_bombing with density-weighted placement
+    ///
+    /// `mask` is one byte per output texel (size * size); stamps land on a
+    /// texel with probability mask / 255, so a curvature map concentrates wear
+    /// on edges. An empty mask places uniformly; any other size returns an
+    /// empty Vec.
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_bombing_masked(
+        &self,
+        size: u32,
+        stamp: &[u8],
+        stamp_size: u32,
+        count: u32,
+        seed: u32,
+        scale_range: &[f32],
+        rotation_random: bool,
+        blend_mode: u8,
+        mask: &[u8],
+    ) -> Vec<u8> {
+        let bombing = BombingStamp::new(stamp, stamp_size, scale_range, mask)
+            .and_then(|stamp| Bombing::new(size, stamp, count, seed, rotation_random, blend_mode));
+        let Ok(mut bombing) = bombing else {
+            return Vec::new();
+        };
+
+        for _ in 0..count {
+            bombing.place_next();
+        }
+        let mut data = vec![0u8; (size * size * 4) as usize];
+        for (y, out) in data.chunks_exact_mut(size as usize * 4).enumerate() {
+            bombing.write_row(y as u32, out);
+        }
+        data
+    }
+
+    /// Stamp for chunked texture bombing (begin() kind 8); see generate_bombing_masked
+    ///
+    /// Kept until replaced, so later begin() calls can reuse it. The mask
+    /// (empty = uniform placement) is checked against the size in begin().
+    #[wasm_bindgen]
+    pub fn set_bombing_stamp(
+        &mut self,
+        stamp: &[u8],
+        stamp_size: u32,
+        scale_range: &[f32],
+        mask: &[u8],
+    ) -> Result<(), String> {
+        self.bombing_stamp = Some(BombingStamp::new(stamp, stamp_size, scale_range, mask)?);
+        Ok(())
+    }
+
+    // Internal: the chunked output buffer (what get_output_ptr points at)
+    #[cfg(test)]
+    pub(crate) fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    // Internal: run one row step of a chunked job, writing its bytes into the output
+    fn job_row(&mut self, job: &mut TextureJob, row: u32) {
+        let size = job.size;
+        match &mut job.kind {
+            TextureKind::LensMap(lens) => {
+                let rows = lens.rows();
+                if row < rows {
+                    lens.max_offset = lens
+                        .row_offsets(row)
+                        .fold(lens.max_offset, |m, o| m.max(o.abs()));
+                    if row + 1 == rows {
+                        self.lens_offset_scale = lens.max_offset;
+                    }
+                } else {
+                    let inv_scale = LensMap::inv_scale(lens.max_offset);
+                    let row_len = size as usize * 2;
+                    let out = &mut self.output[(row - rows) as usize * row_len..][..row_len];
+                    for (byte, offset) in out.iter_mut().zip(lens.row_offsets(row - rows)) {
+                        *byte = LensMap::encode(offset, inv_scale);
+                    }
+                }
+            }
+            TextureKind::Starfield(field) => {
+                let row_len = size as usize * 4;
+                field.render_row(row, &mut self.output[row as usize * row_len..][..row_len]);
+            }
+            TextureKind::Bombing(bombing) => {
+                if row < bombing.count {
+                    bombing.place_next();
+                } else {
+                    let y = row - bombing.count;
+                    let row_len = size as usize * 4;
+                    bombing.write_row(y, &mut self.output[y as usize * row_len..][..row_len]);
+                }
+            }
+            TextureKind::Buffered => {}
+            kind => {
+                let row_texels = job.row_texels;
+                for texel in row as usize * row_texels..(row as usize + 1) * row_texels {
+                    let rgba = self.job_texel(kind, size, texel);
+                    self.output[texel * 4..texel * 4 + 4].copy_from_slice(&rgba);
+                }
+            }
+        }
+    }
+
+    // Internal: one RGBA texel of a chunked job, by texel index in the output
+    fn job_texel(&self, kind: &TextureKind, size: u32, texel: usize) -> [u8; 4] {
+        match kind {
+            TextureKind::Noise | TextureKind::BlueNoise => {
+                let x = (texel % size as usize) as u32;
+                let y = (texel / size as usize) as u32;
+                let byte = if matches!(kind, TextureKind::Noise) {
+                    self.noise_texel(x, y, size)
+                } else {
+                    self.blue_noise_texel(x, y, size)
+                };
+                [byte, byte, byte, 255]
+            }
+            TextureKind::ColorLut { grade, layout } => {
+                let (r, g, b) = layout.texel_coords(size as usize, texel);
+                Self::lut_texel(size, [r, g, b], grade)
+            }
+            TextureKind::CurvesLut(curves) => {
+                let (r, g, b) = LutLayout::Strip.texel_coords(size as usize, texel);
+                curves.texel(size, [r, g, b])
+            }
+            _ => unreachable!("only per-texel kinds have row_texels"),
+        }
+    }
+
+    // Internal: seeded star placement, round(density * area / 1000) stars
+    fn place_stars(
+        width: u32,
+        height: u32,
+        density_per_kpx: f32,
+        seed: u32,
+        size_distribution: u8,
+    ) -> Vec<Star> {
+        let count = (density_per_kpx.max(0.0) * (width * height) as f32 / 1000.0).round() as usize;
+        let mut rng = SeededRng::new(seed);
+
+        (0..count)
+            .map(|_| {
+                let x = rng.range(0.0, width as f32);
+                let y = rng.range(0.0, height as f32);
+                let radius = match size_distribution {
+                    STAR_SIZE_POWER_LAW => {
+                        // Pareto (alpha = 2.5) tail, clamped to the max radius
+                        let u = 1.0 - rng.next_f32();
+                        (STAR_MIN_RADIUS * u.powf(-1.0 / 2.5)).min(STAR_MAX_RADIUS)
+                    }
+                    STAR_SIZE_UNIFORM => rng.range(STAR_MIN_RADIUS, STAR_MAX_RADIUS),
+                    // Unknown distributions fall back to uniform
+                    _ => rng.range(STAR_MIN_RADIUS, STAR_MAX_RADIUS),
+                };
+
+                // Color temperature: -1 = cool blue-white, 1 = warm orange
+                let temperature = rng.range(-1.0, 1.0);
+                let tint = [
+                    0.9 + 0.1 * temperature,
+                    0.9 - 0.05 * temperature.abs(),
+                    0.9 - 0.1 * temperature,
+                ];
+
+                Star {
+                    x,
+                    y,
+                    radius,
+                    tint,
+                    twinkle_cycles: 1 + rng.next_u32() % 3,
+                    twinkle_phase: rng.next_f32(),
+                    twinkle_depth: rng.range(0.2, 0.8),
+                }
+            })
+            .collect()
+    }
+
+    // Internal: composite one transformed stamp centered at `center`, wrapping at the edges
+    #[allow(clippy::too_many_arguments)]
+    fn stamp_wrapped(
+        canvas: &mut [[f32; 4]],
+        size: u32,
+        stamp: &[u8],
+        stamp_size: u32,
+        center: [f32; 2],
+        scale: f32,
+        angle: f32,
+        blend_mode: u8,
+    ) {
+        if scale <= 0.0 || size == 0 {
+            return;
+        }
+        let half = stamp_size as f32 * 0.5;
+        let reach = (half * scale * std::f32::consts::SQRT_2).ceil() as i32;
+        let (sin, cos) = angle.sin_cos();
+        let inv_scale = 1.0 / scale;
+        let (cx, cy) = (center[0].floor() as i32, center[1].floor() as i32);
+        let blend_max = |dst: &mut [f32; 4], src: [f32; 4]| {
+            for (d, s) in dst.iter_mut().zip(src) {
+                *d = d.max(s);
+            }
+        };
+
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                // Output texel center relative to the stamp center, into stamp space
+                let px = (cx + dx) as f32 + 0.5 - center[0];
+                let py = (cy + dy) as f32 + 0.5 - center[1];
+                let sx = (cos * px + sin * py) * inv_scale + half;
+                let sy = (-sin * px + cos * py) * inv_scale + half;
+                let Some(src) = Self::sample_stamp_bilinear(stamp, stamp_size, sx, sy) else {
+                    continue;
+                };
+
+                let x = (cx + dx).rem_euclid(size as i32) as usize;
+                let y = (cy + dy).rem_euclid(size as i32) as usize;
+                let dst = &mut canvas[y * size as usize + x];
+                match blend_mode {
+                    BOMB_BLEND_ADD => {
+                        for (d, s) in dst.iter_mut().zip(src) {
+                            *d = (*d + s).min(1.0);
+                        }
+                    }
+                    BOMB_BLEND_OVER => {
+                        let a = src[3];
+                        for c in 0..3 {
+                            dst[c] = src[c] * a + dst[c] * (1.0 - a);
+                        }
+                        dst[3] = a + dst[3] * (1.0 - a);
+                    }
+                    BOMB_BLEND_MAX => blend_max(dst, src),
+                    // Unknown modes fall back to max
+                    _ => blend_max(dst, src),
+                }
+            }
+        }
+    }
+
+    // Internal: bilinear RGBA stamp lookup at texel coordinates (x, y), None outside the stamp
+    fn sample_stamp_bilinear(stamp: &[u8], stamp_size: u32, x: f32, y: f32) -> Option<[f32; 4]> {
+        let n = stamp_size as f32;
+        if !(0.0..n).contains(&x) || !(0.0..n).contains(&y) {
+            return None;
+        }
+        // Texel centers sit at +0.5; clamp the taps at the stamp edge
+        let fx = (x - 0.5).max(0.0);
+        let fy = (y - 0.5).max(0.0);
+        let x0 = (fx as u32).min(stamp_size - 1);
+        let y0 = (fy as u32).min(stamp_size - 1);
+        let x1 = (x0 + 1).min(stamp_size - 1);
+        let y1 = (y0 + 1).min(stamp_size - 1);
+        let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+
+        let tap = |x: u32, y: u32, c: usize| stamp[((y * stamp_size + x) * 4) as usize + c] as f32;
+        Some(std::array::from_fn(|c| {
+            let top = tap(x0, y0, c) + (tap(x1, y0, c) - tap(x0, y0, c)) * tx;
+            let bottom = tap(x0, y1, c) + (tap(x1, y1, c) - tap(x0, y1, c)) * tx;
+            (top + (bottom - top) * ty) / 255.0
+        }))
+    }
+
+    // Internal: grade for a tone-mapped LUT, using this generator's Hable params
+    fn lut_grade(
+        &self,
+        contrast: f32,
+        saturation: f32,
+        tone_curve: u8,
+        exposure_ev: f32,
+        linearize_input: bool,
+    ) -> LutGrade {
+        LutGrade {
+            tone_curve,
+            exposure: exposure_ev.exp2(),
+            linearize: linearize_input,
+            hable: self.hable,
+            ..LutGrade::basic(contrast, saturation)
+        }
+    }
+
+    // Internal: full LUT in the given layout
+    fn build_color_lut(size: u32, grade: &LutGrade, layout: u8) -> Vec<u8> {
+        Self::fill_lut(size, layout, |rgb| Self::lut_texel(size, rgb, grade))
+    }
+
+    // Internal: LUT of size^3 texels, each computed from its [r, g, b] coordinate
+    fn fill_lut(size: u32, layout: u8, texel: impl Fn([usize; 3]) -> [u8; 4]) -> Vec<u8> {
+        let layout = LutLayout::from_u8(layout);
+        let n = size as usize;
+        let mut data = vec![0u8; n * n * n * 4];
+
+        for b in 0..n {
+            for g in 0..n {
+                for r in 0..n {
+                    let i = layout.texel_index(n, r, g, b) * 4;
+                    data[i..i + 4].copy_from_slice(&texel([r, g, b]));
+                }
+            }
+        }
+
+        data
+    }
+
+    // Internal: white noise value at (x, y)
+    fn noise_texel(&self, x: u32, y: u32, size: u32) -> u8 {
+        (self.tileable_noise(x, y, size) * 255.0) as u8
+    }
+
+    // Internal: blue noise approximation using multiple octaves with offset sampling
+    fn blue_noise_texel(&self, x: u32, y: u32, size: u32) -> u8 {
+        let mut value = 0.0;
+        let mut amplitude = 1.0;
+        let mut total_amplitude = 0.0;
+
+        for octave in 0..4 {
+            let freq = 1 << octave;
+            let offset = octave * 17;
+            value += self.tileable_noise(
+                (x * freq + offset) % size,
+                (y * freq + offset * 3) % size,
+                size,
+            ) * amplitude;
+            total_amplitude += amplitude;
+            amplitude *= 0.5;
+        }
+
+        value /= total_amplitude;
+        (value * 255.0) as u8
+    }
+
+    // Internal: graded RGBA value of LUT coordinate rgb
+    fn lut_texel(size: u32, rgb: [usize; 3], grade: &LutGrade) -> [u8; 4] {
+        let (contrast, saturation) = (grade.contrast, grade.saturation);
+        // Normalize to 0-1
+        let [mut rf, mut gf, mut bf] = rgb.map(|c| c as f32 / (size - 1) as f32);
+
+        // Exposure and tone curve, in linear light when requested
+        if !grade.is_tone_neutral() {
+            let tone = |c: f32| {
+                if grade.linearize {
+                    linear_to_srgb(grade.tone_map(srgb_to_linear(c)))
+                } else {
+                    grade.tone_map(c)
+                }
+            };
+            [rf, gf, bf] = [rf, gf, bf].map(tone);
+        }
+
+        // Apply contrast
+        rf = ((rf - 0.5) * contrast + 0.5).clamp(0.0, 1.0);
+        gf = ((gf - 0.5) * contrast + 0.5).clamp(0.0, 1.0);
+        bf = ((bf - 0.5) * contrast + 0.5).clamp(0.0, 1.0);
+
+        // Apply saturation
+        let luma = rf * 0.299 + gf * 0.587 + bf * 0.114;
+        rf = luma + (rf - luma) * saturation;
+        gf = luma + (gf - luma) * saturation;
+        bf = luma + (bf - luma) * saturation;
+
+        [
+            (rf.clamp(0.0, 1.0) * 255.0) as u8,
+            (gf.clamp(0.0, 1.0) * 255.0) as u8,
+            (bf.clamp(0.0, 1.0) * 255.0) as u8,
+            255,
+        ]
+    }
+
+    // Internal: average `field` into `cells` x `cells` blocks and bilinearly upsample
+    // them back to `size` x `size`, wrapping at the borders (cells == size is identity)
+    fn wrapped_lowpass(field: &[f32], size: u32, cells: u32) -> Vec<f32> {
+        let (n, c) = (size as usize, cells as usize);
+        let mut sums = vec![0.0f32; c * c];
+        let mut counts = vec![0u32; c * c];
+        for y in 0..n {
+            for x in 0..n {
+                let cell = (y * c / n) * c + x * c / n;
+                sums[cell] += field[y * n + x];
+                counts[cell] += 1;
+            }
+        }
+        let means: Vec<f32> = sums
+            .iter()
+            .zip(&counts)
+            .map(|(s, &k)| s / k.max(1) as f32)
+            .collect();
+
+        let mut out = vec![0.0f32; n * n];
+        for y in 0..n {
+            for x in 0..n {
+                let fx = (x as f32 + 0.5) * c as f32 / n as f32 - 0.5;
+                let fy = (y as f32 + 0.5) * c as f32 / n as f32 - 0.5;
+                let (x0, y0) = (fx.floor(), fy.floor());
+                let (tx, ty) = (fx - x0, fy - y0);
+                let ix0 = (x0 as i64).rem_euclid(c as i64) as usize;
+                let iy0 = (y0 as i64).rem_euclid(c as i64) as usize;
+                let (ix1, iy1) = ((ix0 + 1) % c, (iy0 + 1) % c);
+
+                let top = means[iy0 * c + ix0] * (1.0 - tx) + means[iy0 * c + ix1] * tx;
+                let bottom = means[iy1 * c + ix0] * (1.0 - tx) + means[iy1 * c + ix1] * tx;
+                out[y * n + x] = top * (1.0 - ty) + bottom * ty;
+            }
+        }
+        out
+    }
+
+    // Internal: tileable noise using hash
+    fn tileable_noise(&self, x: u32, y: u32, size: u32) -> f32 {
+        let hash = self.hash2d(x % size, y % size);
+        hash as f32 / u32::MAX as f32
+    }
+
+    // Internal: rasterize an antialiased thick segment into an alpha buffer (max blend)
+    fn stamp_segment(alpha: &mut [f32], size: u32, a: [f32; 2], b: [f32; 2], thickness: f32) {
+        let radius = thickness * 0.5;
+        let min_x = (a[0].min(b[0]) - radius - 1.0).floor().max(0.0) as u32;
+        let min_y = (a[1].min(b[1]) - radius - 1.0).floor().max(0.0) as u32;
+        let max_x = ((a[0].max(b[0]) + radius + 1.0).ceil() as u32).min(size);
+        let max_y = ((a[1].max(b[1]) + radius + 1.0).ceil() as u32).min(size);
+
+        let (abx, aby) = (b[0] - a[0], b[1] - a[1]);
+        let len_sq = (abx * abx + aby * aby).max(1e-12);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5 - a[0], y as f32 + 0.5 - a[1]);
+                let t = ((px * abx + py * aby) / len_sq).clamp(0.0, 1.0);
+                let (dx, dy) = (px - abx * t, py - aby * t);
+                let coverage = (radius + 0.5 - (dx * dx + dy * dy).sqrt()).clamp(0.0, 1.0);
+                let idx = (y * size + x) as usize;
+                alpha[idx] = alpha[idx].max(coverage);
+            }
+        }
+    }
+
+    // Fast integer hash
+    fn hash2d(&self, x: u32, y: u32) -> u32 {
+        let mut h = self.seed;
+        h ^= x.wrapping_mul(0x45d9f3b);
+        h = h.wrapping_mul(0x45d9f3b);
+        h ^= y.wrapping_mul(0x119de1f3);
+        h = h.wrapping_mul(0x119de1f3);
+        h ^= h >> 16;
+        h
+    }
+}
+
+/// sRGB transfer function, display value -> linear light
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Inverse sRGB transfer function, linear light -> display value
+fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.max(0.0);
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Small deterministic xorshift RNG for seeded generators
+pub(crate) struct SeededRng {
+    state: u32,
+}
+
+impl SeededRng {
+    pub(crate) fn new(seed: u32) -> Self {
+        // Scramble the seed so nearby seeds diverge; xorshift state must be non-zero
+        let mut h = seed ^ 0x9e37_79b9;
+        h = (h ^ (h >> 16)).wrapping_mul(0x45d9f3b);
+        h = (h ^ (h >> 16)).wrapping_mul(0x45d9f3b);
+        h ^= h >> 16;
+        Self { state: h.max(1) }
+    }
+
+    pub(crate) fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Uniform float in [0, 1)
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Uniform float in [lo, hi)
+    pub(crate) fn range(&mut self, lo: f32, hi: f32) -> f32 {
+        lo + (hi - lo) * self.next_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise_generation() {
+        let gen = TextureGenerator::new(42);
+        let data = gen.generate_noise(64);
+        assert_eq!(data.len(), 64 * 64 * 4);
+    }
+
+    #[test]
+    fn test_lut_generation() {
+        let gen = TextureGenerator::new(42);
+        let data = gen.generate_color_lut(16, 1.1, 1.0);
+        assert_eq!(data.len(), 16 * 16 * 16 * 4);
+    }
+
+    #[test]
+    fn test_lut_layouts_consistent() {
+        let gen = TextureGenerator::new(42);
+        let size = 8usize;
+        let strip = gen.generate_color_lut_ex(8, 1.3, 0.7, LUT_LAYOUT_STRIP);
+        let unity = gen.generate_color_lut_ex(8, 1.3, 0.7, LUT_LAYOUT_STRIP_UNITY);
+        let volume = gen.generate_color_lut_ex(8, 1.3, 0.7, LUT_LAYOUT_3D);
+
+        // Legacy entry point is the strip layout
+        assert_eq!(strip, gen.generate_color_lut(8, 1.3, 0.7));
+
+        for &(r, g, b) in &[(0, 0, 0), (7, 0, 3), (2, 5, 7), (7, 7, 7), (1, 6, 4)] {
+            // Sample each layout by its documented 2D/3D addressing
+            let s = ((g * size * size) + b * size + r) * 4;
+            let u = ((size - 1 - g) * size * size + b * size + r) * 4;
+            let v = (r + g * size + b * size * size) * 4;
+            assert_eq!(strip[s..s + 4], unity[u..u + 4]);
+            assert_eq!(strip[s..s + 4], volume[v..v + 4]);
+        }
+
+        assert_eq!(
+            TextureGenerator::convert_lut_layout(&strip, 8, 0, 2),
+            volume
+        );
+        assert_eq!(
+            TextureGenerator::convert_lut_layout(&volume, 8, 2, 1),
+            unity
+        );
+        assert_eq!(TextureGenerator::convert_lut_layout(&unity, 8, 1, 0), strip);
+        assert!(TextureGenerator::convert_lut_layout(&strip, 9, 0, 2).is_empty());
+
+        // Unity's neutral strip: top-left is pure green, bottom-left black
+        let neutral = gen.generate_color_lut_ex(8, 1.0, 1.0, LUT_LAYOUT_STRIP_UNITY);
+        assert_eq!(neutral[..4], [0, 255, 0, 255]);
+        let bottom_left = 7 * size * size * 4;
+        assert_eq!(neutral[bottom_left..bottom_left + 4], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_chunked_generation_matches_single_call() {
+        let mut gen = TextureGenerator::new(42);
+        let stamp = dot_stamp(8);
+        let mask: Vec<u8> = (0..32 * 32).map(|i| (i * 13 % 256) as u8).collect();
+        gen.set_bombing_stamp(&stamp, 8, &[0.5, 2.0], &mask)
+            .unwrap();
+        let (master, red) = ([0.5, 0.6], [0.2, 0.1, 0.8, 0.9]);
+        gen.set_lut_curves(&master, &red, &[], &[0.5, 0.4], true)
+            .unwrap();
+
+        let cases: [(u8, Vec<f32>, Vec<u8>); 13] = [
+            (0, vec![33.0], gen.generate_noise(33)),
+            (1, vec![32.0], gen.generate_blue_noise(32)),
+            (2, vec![9.0, 1.2, 0.8], gen.generate_color_lut(9, 1.2, 0.8)),
+            (
+                2,
+                vec![9.0, 1.2, 0.8, 2.0],
+                gen.generate_color_lut_ex(9, 1.2, 0.8, 2),
+            ),
+            (
+                TEXTURE_KIND_SHAPED_NOISE,
+                vec![16.0, -6.0],
+                gen.generate_shaped_noise(16, -6.0),
+            ),
+            (
+                TEXTURE_KIND_SPLAT,
+                vec![16.0, 3.0, 5.0, 0.5, 1.5],
+                gen.generate_splat(16, 3, 5, 0.5, 1.5),
+            ),
+            (
+                TEXTURE_KIND_CRACK,
+                vec![32.0, 3.0, 4.0, 2.0],
+                gen.generate_crack(32, 3, 4, 2),
+            ),
+            (
+                TEXTURE_KIND_LENS_DISTORTION,
+                vec![16.0, 8.0, 0.1, 0.02, 0.05, 1.0],
+                gen.generate_lens_distortion_map(16, 8, 0.1, 0.02, 0.05, 1),
+            ),
+            (
+                TEXTURE_KIND_LENS_DISTORTION,
+                vec![33.0, 17.0, -0.3, 0.1, 0.0, 2.0],
+                gen.generate_lens_distortion_map(33, 17, -0.3, 0.1, 0.0, 2),
+            ),
+            (
+                TEXTURE_KIND_STARFIELD,
+                vec![16.0, 8.0, 20.0, 3.0, 1.0, 2.0],
+                gen.generate_starfield(16, 8, 20.0, 3, 1, 2),
+            ),
+            // Splats taller than the image wrap onto the same row more than once
+            (
+                TEXTURE_KIND_STARFIELD,
+                vec![7.0, 5.0, 200.0, 2.0, 1.0, 3.0],
+                gen.generate_starfield(7, 5, 200.0, 2, 1, 3),
+            ),
+            (
+                TEXTURE_KIND_BOMBING,
+                vec![32.0, 20.0, 5.0, 1.0, 1.0],
+                gen.generate_bombing_masked(32, &stamp, 8, 20, 5, &[0.5, 2.0], true, 1, &mask),
+            ),
+            (
+                TEXTURE_KIND_COLOR_LUT_CURVES,
+                vec![9.0],
+                gen.generate_color_lut_from_curves(9, &master, &red, &[], &[0.5, 0.4], true)
+                    .unwrap(),
+            ),
+        ];
+
+        for (kind, params, expected) in cases {
+            for rows in [1, 4, 7, 1000] {
+                gen.lens_offset_scale = 0.0;
+                gen.begin(kind, &params).unwrap();
+                let mut last = 0.0;
+                loop {
+                    let progress = gen.generate_chunk(rows);
+                    assert!(progress >= last);
+                    last = progress;
+                    if progress >= 1.0 {
+                        break;
+                    }
+                }
+                assert_eq!(gen.finish(), expected.len());
+                assert_eq!(gen.output, expected, "kind {kind}, {rows} rows per chunk");
+                if kind == TEXTURE_KIND_LENS_DISTORTION {
+                    let map = gen.generate_lens_distortion_map(
+                        params[0] as u32,
+                        params[1] as u32,
+                        params[2],
+                        params[3],
+                        params[4],
+                        params[5] as u8,
+                    );
+                    let chunked_scale = gen.lens_offset_scale;
+                    assert_eq!(map, expected);
+                    assert_eq!(chunked_scale, gen.get_lens_offset_scale());
+                }
+            }
+        }
+
+        // Lens maps scan every row before writing any: half the steps, no bytes yet
+        gen.begin(
+            TEXTURE_KIND_LENS_DISTORTION,
+            &[16.0, 8.0, 0.1, 0.02, 0.0, 0.0],
+        )
+        .unwrap();
+        assert_eq!(gen.generate_chunk(8), 0.5);
+        assert!(gen.output.iter().all(|&b| b == 0));
+        gen.finish();
+
+        // Bombing and curves LUTs need their setter first; a wrong-size mask is refused
+        let mut fresh = TextureGenerator::new(42);
+        for (kind, name) in [
+            (TEXTURE_KIND_BOMBING, "set_bombing_stamp"),
+            (TEXTURE_KIND_COLOR_LUT_CURVES, "set_lut_curves"),
+        ] {
+            let err = fresh.begin(kind, &[16.0]).unwrap_err();
+            assert!(err.contains(name), "{err}");
+        }
+        assert!(fresh.set_bombing_stamp(&stamp, 7, &[], &[]).is_err());
+        assert!(fresh.set_lut_curves(&[0.5], &[], &[], &[], false).is_err());
+        assert!(gen.begin(TEXTURE_KIND_BOMBING, &[16.0, 4.0]).is_err());
+        assert!(gen
+            .begin(TEXTURE_KIND_COLOR_LUT_CURVES + 1, &[16.0])
+            .is_err());
+        assert!(gen.begin(TEXTURE_KIND_STARFIELD, &[16.0]).is_err());
+        assert!(gen.begin(2, &[16.0]).is_err());
+        assert_eq!(gen.generate_chunk(1), 1.0);
+
+        gen.begin(0, &[16.0]).unwrap();
+        gen.dispose();
+        assert_eq!(gen.output.capacity(), 0);
+        assert!(gen.begin(0, &[16.0]).is_err());
+        assert_eq!(gen.get_output_len(), 0);
+    }
+
+    /// Ratio of low-frequency to high-frequency power, averaged over rows (naive DFT)
+    fn low_high_power_ratio(data: &[u8], size: usize) -> f32 {
+        let (mut low, mut high) = (0.0f32, 0.0f32);
+        for y in 0..size {
+            let row: Vec<f32> = (0..size).map(|x| data[(y * size + x) * 4] as f32).collect();
+            for k in 1..=size / 2 {
+                let (mut re, mut im) = (0.0f32, 0.0f32);
+                for (x, v) in row.iter().enumerate() {
+                    let angle = std::f32::consts::TAU * (k * x) as f32 / size as f32;
+                    re += v * angle.cos();
+                    im -= v * angle.sin();
+                }
+                if k <= size / 4 {
+                    low += re * re + im * im;
+                } else {
+                    high += re * re + im * im;
+                }
+            }
+        }
+        low / high
+    }
+
+    #[test]
+    fn test_shaped_noise_spectrum() {
+        let gen = TextureGenerator::new(42);
+        assert_eq!(gen.generate_shaped_noise(32, 0.0), gen.generate_noise(32));
+
+        let pink = gen.generate_shaped_noise(64, -6.0);
+        let white = gen.generate_shaped_noise(64, 0.0);
+        let blue = gen.generate_shaped_noise(64, 3.0);
+        assert_eq!(pink.len(), 64 * 64 * 4);
+
+        let (p, w, b) = (
+            low_high_power_ratio(&pink, 64),
+            low_high_power_ratio(&white, 64),
+            low_high_power_ratio(&blue, 64),
+        );
+        assert!(p > w && w > b, "ratios pink={p} white={w} blue={b}");
+
+        // Renormalized to the full range
+        for data in [&pink, &blue] {
+            assert_eq!(data.iter().step_by(4).min(), Some(&0));
+            assert_eq!(data.iter().step_by(4).max(), Some(&255));
+        }
+    }
+
+    fn border_alpha_is_zero(data: &[u8], size: usize) -> bool {
+        (0..size).all(|i| {
+            let edges = [(i, 0), (i, size - 1), (0, i), (size - 1, i)];
+            edges
+                .iter()
+                .all(|&(x, y)| data[(y * size + x) * 4 + 3] == 0)
+        })
+    }
+
+    #[test]
+    fn test_splat_generation() {
+        let gen = TextureGenerator::new(42);
+        let a = gen.generate_splat(64, 7, 5, 0.6, 1.5);
+        assert_eq!(a.len(), 64 * 64 * 4);
+        assert_eq!(a, gen.generate_splat(64, 7, 5, 0.6, 1.5));
+        assert_ne!(a, gen.generate_splat(64, 8, 5, 0.6, 1.5));
+        assert!(border_alpha_is_zero(&a, 64));
+        assert!(a[(32 * 64 + 32) * 4 + 3] > 200, "center should be opaque");
+    }
+
+    #[test]
+    fn test_crack_generation() {
+        let gen = TextureGenerator::new(42);
+        let a = gen.generate_crack(128, 3, 4, 2);
+        assert_eq!(a, gen.generate_crack(128, 3, 4, 2));
+        assert_ne!(a, gen.generate_crack(128, 4, 4, 2));
+        assert!(border_alpha_is_zero(&a, 128));
+        assert!(a.chunks(4).any(|px| px[3] == 255));
+    }
+
+    #[test]
+    fn test_lens_distortion_map() {
+        let mut gen = TextureGenerator::new(1);
+        let (w, h) = (33u32, 17u32);
+        let map = gen.generate_lens_distortion_map(w, h, -0.2, 0.05, 0.0, 0);
+        assert_eq!(map.len(), (w * h * 2) as usize);
+        let scale = gen.get_lens_offset_scale();
+        assert!(scale > 0.0);
+
+        let texel = |x: u32, y: u32| {
+            let i = ((y * w + x) * 2) as usize;
+            (map[i] as i32 - 128, map[i + 1] as i32 - 128)
+        };
+
+        // Center texel is exactly neutral
+        assert_eq!(texel(w / 2, h / 2), (0, 0));
+
+        // Mirrored texels have mirrored offsets
+        for y in 0..h {
+            for x in 0..w {
+                let (du, dv) = texel(x, y);
+                assert_eq!(texel(w - 1 - x, y), (-du, dv));
+                assert_eq!(texel(x, h - 1 - y), (du, -dv));
+            }
+        }
+
+        // Corners never sample outside [0, 1] without overscan
+        for (x, y) in [(0, 0), (w - 1, 0), (0, h - 1), (w - 1, h - 1)] {
+            let (du, dv) = texel(x, y);
+            let u = (x as f32 + 0.5) / w as f32 + du as f32 / 127.0 * scale;
+            let v = (y as f32 + 0.5) / h as f32 + dv as f32 / 127.0 * scale;
+            assert!((-1e-3..=1.0 + 1e-3).contains(&u), "{u}");
+            assert!((-1e-3..=1.0 + 1e-3).contains(&v), "{v}");
+        }
+    }
+
+    #[test]
+    fn test_lens_distortion_chromatic_layers() {
+        let mut gen = TextureGenerator::new(1);
+        let map = gen.generate_lens_distortion_map(16, 16, 0.1, 0.0, 0.02, LENS_MAP_CHROMATIC);
+        let layer = 16 * 16 * 2;
+        assert_eq!(map.len(), layer * 3);
+
+        // Red bends least, blue most, at the same (unclamped) texel
+        let texel_u = |l: usize| map[l * layer + (8 * 16 + 3) * 2] as i32 - 128;
+        assert!(texel_u(0).abs() < texel_u(1).abs());
+        assert!(texel_u(1).abs() < texel_u(2).abs());
+
+        // Overscan lets pincushion corners reach outside the texture
+        let map = gen.generate_lens_distortion_map(16, 16, 0.5, 0.0, 0.0, LENS_MAP_OVERSCAN);
+        let du = (map[0] as i32 - 128) as f32 / 127.0 * gen.get_lens_offset_scale();
+        assert!(0.5 / 16.0 + du < 0.0);
+    }
+
+    #[test]
+    fn test_starfield() {
+        let gen = TextureGenerator::new(1);
+
+        // 64x48 at 2.5 stars / kpx = 7.68 expected stars
+        for distribution in [STAR_SIZE_UNIFORM, STAR_SIZE_POWER_LAW] {
+            let stars = TextureGenerator::place_stars(64, 48, 2.5, 9, distribution);
+            assert!((stars.len() as f32 - 7.68).abs() <= 1.0);
+            assert!(stars
+                .iter()
+                .all(|s| (STAR_MIN_RADIUS..=STAR_MAX_RADIUS).contains(&s.radius)));
+        }
+
+        let sheet = gen.generate_starfield(64, 48, 2.5, 9, STAR_SIZE_POWER_LAW, 4);
+        assert_eq!(sheet.len(), 64 * 48 * 4 * 4);
+        assert_eq!(
+            sheet,
+            gen.generate_starfield(64, 48, 2.5, 9, STAR_SIZE_POWER_LAW, 4)
+        );
+        assert!(sheet.chunks(4).any(|t| t[3] > 0));
+        assert!(gen
+            .generate_starfield(64, 48, 0.0, 9, 0, 1)
+            .iter()
+            .all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_starfield_wraps_at_border() {
+        let star_at = |x: f32, y: f32| Star {
+            x,
+            y,
+            radius: STAR_MAX_RADIUS,
+            tint: [1.0; 3],
+            twinkle_cycles: 1,
+            twinkle_phase: 0.0,
+            twinkle_depth: 0.0,
+        };
+        let total = |light: &[[f32; 3]]| light.iter().map(|t| t[0]).sum::<f32>();
+
+        let field = Starfield {
+            width: 32,
+            height: 32,
+            frames: 1,
+            stars: Vec::new(),
+            row_stars: Vec::new(),
+        };
+        let splat = |star: &Star| -> Vec<[f32; 3]> {
+            (0..32)
+                .flat_map(|y| {
+                    let mut light = vec![[0.0f32; 3]; 32];
+                    field.splat_row(&mut light, star, 1.0, y);
+                    light
+                })
+                .collect()
+        };
+        let centered = splat(&star_at(16.5, 16.5));
+
+        // A star in the corner spills onto the opposite edges and loses no energy
+        let corner = splat(&star_at(31.5, 31.5));
+        assert!(corner[0][0] > 0.0);
+        assert!((total(&centered) - total(&corner)).abs() < 1e-4);
+    }
+
+    /// FNV-1a over a byte buffer, for locking generator output
+    fn fingerprint(data: &[u8]) -> u64 {
+        data.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+            (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// Soft round RGBA dot
+    fn dot_stamp(size: u32) -> Vec<u8> {
+        let half = size as f32 * 0.5;
+        (0..size * size)
+            .flat_map(|i| {
+                let dx = (i % size) as f32 + 0.5 - half;
+                let dy = (i / size) as f32 + 0.5 - half;
+                let a = (1.0 - (dx * dx + dy * dy).sqrt() / half).max(0.0);
+                [255, 255, 255, (a * 255.0) as u8]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bombing() {
+        let gen = TextureGenerator::new(1);
+        let stamp = dot_stamp(8);
+        let output = gen.generate_bombing(64, &stamp, 8, 40, 5, &[0.5, 2.0], true, BOMB_BLEND_OVER);
+        assert_eq!(output.len(), 64 * 64 * 4);
+        assert_eq!(fingerprint(&output), 13034374044342539961);
+        assert_eq!(
+            output,
+            gen.generate_bombing(64, &stamp, 8, 40, 5, &[0.5, 2.0], true, BOMB_BLEND_OVER)
+        );
+
+        // Malformed inputs
+        assert!(gen
+            .generate_bombing(64, &stamp[4..], 8, 4, 5, &[], false, 0)
+            .is_empty());
+        assert!(gen
+            .generate_bombing_masked(64, &stamp, 8, 4, 5, &[], false, 0, &[255; 10])
+            .is_empty());
+
+        // A zero mask never accepts a position
+        let masked = gen.generate_bombing_masked(64, &stamp, 8, 40, 5, &[], false, 0, &[0; 4096]);
+        assert!(masked.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_bombing_wraps_at_border() {
+        let stamp = dot_stamp(8);
+        let alpha_sum = |canvas: &[[f32; 4]]| canvas.iter().map(|t| t[3]).sum::<f32>();
+
+        let mut centered = vec![[0.0f32; 4]; 32 * 32];
+        TextureGenerator::stamp_wrapped(&mut centered, 32, &stamp, 8, [16.0, 16.0], 1.0, 0.3, 1);
+
+        // The same stamp on the corner is split over all four corners, unchanged in total
+        let mut corner = vec![[0.0f32; 4]; 32 * 32];
+        TextureGenerator::stamp_wrapped(&mut corner, 32, &stamp, 8, [0.0, 0.0], 1.0, 0.3, 1);
+        for (x, y) in [(0, 0), (31, 0), (0, 31), (31, 31)] {
+            assert!(corner[y * 32 + x][3] > 0.0);
+        }
+        assert!((alpha_sum(&centered) - alpha_sum(&corner)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_tone_lut_neutral() {
+        let gen = TextureGenerator::new(1);
+        for linearize in [false, true] {
+            assert_eq!(
+                gen.generate_color_lut_tone(9, 1.1, 0.9, 2, TONE_CURVE_NONE, 0.0, linearize),
+                gen.generate_color_lut_ex(9, 1.1, 0.9, 2)
+            );
+        }
+    }
+
+    #[test]
+    fn test_tone_lut_monotonic_gray_axis() {
+        let mut gen = TextureGenerator::new(1);
+        gen.set_hable_params(0.22, 0.3);
+        let size = 32usize;
+
+        for curve in [TONE_CURVE_REINHARD, TONE_CURVE_HABLE, TONE_CURVE_ACES] {
+            for (ev, linearize) in [(0.0, false), (2.0, true), (-1.0, true)] {
+                let lut = gen.generate_color_lut_tone(32, 1.0, 1.0, 2, curve, ev, linearize);
+                let mut last = [0u8; 3];
+                for i in 0..size {
+                    let t = ((i * size + i) * size + i) * 4;
+                    let texel = [lut[t], lut[t + 1], lut[t + 2]];
+                    for c in 0..3 {
+                        assert!(texel[c] >= last[c], "curve {curve}, ev {ev}, step {i}");
+                    }
+                    last = texel;
+                }
+                // With exposure lift, white lands in the bright shoulder
+                if ev > 0.0 {
+                    assert!(last[0] > 160, "curve {curve} too dark at white");
+                }
+            }
+        }
+
+        // The chunked path bakes the same grade
+        let mut chunked = TextureGenerator::new(1);
+        chunked.set_hable_params(0.22, 0.3);
+        chunked
+            .begin(2, &[9.0, 1.0, 1.0, 0.0, TONE_CURVE_HABLE as f32, 1.0, 1.0])
+            .unwrap();
+        chunked.generate_chunk(100);
+        chunked.finish();
+        assert_eq!(
+            chunked.output,
+            gen.generate_color_lut_tone(9, 1.0, 1.0, 0, TONE_CURVE_HABLE, 1.0, true)
+        );
+    }
+
+    #[test]
+    fn test_curves_lut() {
+        let gen = TextureGenerator::new(1);
+        let identity = gen.generate_color_lut(9, 1.0, 1.0);
+        for linear in [false, true] {
+            let lut = gen
+                .generate_color_lut_from_curves(9, &[], &[], &[], &[], linear)
+                .unwrap();
+            assert_eq!(lut, identity);
+        }
+
+        // Midpoint lift: points (0, 0), (0.5, 0.7), (1, 1); secants 1.4 and 0.6
+        let size = 33;
+        let lut = gen
+            .generate_color_lut_from_curves(size, &[0.5, 0.7], &[], &[], &[], false)
+            .unwrap();
+        let hermite = |t: f32, y0: f32, y1: f32, m0: f32, m1: f32, h: f32| {
+            let (t2, t3) = (t * t, t * t * t);
+            (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+                + (t3 - 2.0 * t2 + t) * h * m0
+                + (-2.0 * t3 + 3.0 * t2) * y1
+                + (t3 - t2) * h * m1
+        };
+        let mut previous = 0u8;
+        for r in 0..size as usize {
+            let x = r as f32 / (size - 1) as f32;
+            let expected = if x <= 0.5 {
+                hermite(x / 0.5, 0.0, 0.7, 1.4, 1.0, 0.5)
+            } else {
+                hermite((x - 0.5) / 0.5, 0.7, 1.0, 1.0, 0.6, 0.5)
+            };
+            // Red channel of texel (r, 0, 0) in the strip layout
+            let red = lut[r * 4];
+            assert!((red as f32 - expected * 255.0).abs() <= 1.0, "x = {x}");
+            assert!(red >= previous);
+            previous = red;
+        }
+
+        // Master then channel: a crushing red curve after the lift
+        let lut = gen
+            .generate_color_lut_from_curves(size, &[0.5, 0.7], &[0.7, 0.0], &[], &[], false)
+            .unwrap();
+        // Mid gray texel (16, 16, 16): strip index = g * size^2 + b * size + r
+        let gray = (16 * 33 * 33 + 16 * 33 + 16) * 4;
+        assert_eq!(lut[gray], 0);
+        assert_eq!(lut[gray + 1], (0.7f32 * 255.0) as u8);
+
+        let bad = [
+            vec![0.5, 0.5, 0.5, 0.6],
+            vec![0.2, 0.3, 1.5, 1.0],
+            vec![0.5],
+        ];
+        for curve in bad {
+            let err = gen
+                .generate_color_lut_from_curves(9, &[], &curve, &[], &[], false)
+                .unwrap_err();
+            assert!(err.starts_with("red curve"), "{err}");
+        }
     }
 }